@@ -1,4 +1,8 @@
 use crate::poseidon::get_poseidon_params;
+use crate::{
+    bytes_to_plaintext_chunks_sized, plaintext_chunks_to_bytes_sized, PlaintextPredicate,
+    PoseidonDigest,
+};
 use anyhow::anyhow;
 use ark_crypto_primitives::snark::NonNativeFieldInputVar;
 use ark_crypto_primitives::Error;
@@ -19,10 +23,14 @@ use ark_sponge::constraints::{AbsorbGadget, CryptographicSpongeVar};
 use ark_sponge::poseidon::constraints::PoseidonSpongeVar;
 use ark_sponge::poseidon::{PoseidonParameters, PoseidonSponge};
 use ark_sponge::{Absorb, CryptographicSponge, FieldBasedCryptographicSponge};
+use ark_std::cfg_iter;
 use ark_std::marker::PhantomData;
 use ark_std::rand::{CryptoRng, Rng, RngCore};
 use ark_std::vec::Vec;
 use ark_std::UniformRand;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
 use std::borrow::Borrow;
 use std::cmp::Ordering;
 use std::fmt::Debug;
@@ -39,6 +47,8 @@ where
     pk: PublicKey<C>,
     pub resulted_ciphertext: Ciphertext<C>,
     params: Parameters<C>,
+    /// Statements over the plaintext the circuit also proves, see [`EncryptCircuit::with_predicate`].
+    predicates: Vec<Box<dyn PlaintextPredicate<C::BaseField>>>,
     _curve_var: PhantomData<CV>,
 }
 
@@ -90,10 +100,32 @@ impl<C: ProjectiveCurve> UniformRand for Randomness<C> {
     }
 }
 
+/// Domain tag of the per-chunk randomness derivation.
+const CHUNK_RANDOMNESS_TAG: &[u8] = b"zkcp/chunk-randomness/v1";
+
+impl<C: ProjectiveCurve> Randomness<C> {
+    /// Derives randomness of the chunk at `index` from `master` randomness, so that no two chunks
+    /// can share it while it stays as unpredictable as `master` is.
+    pub fn derive(master: &Self, index: u64) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(CHUNK_RANDOMNESS_TAG);
+        hasher.update(to_bytes![&master.0].unwrap());
+        hasher.update(index.to_le_bytes());
+        Randomness(C::ScalarField::from_le_bytes_mod_order(&hasher.finalize()))
+    }
+}
+
 pub type Plaintext<C: ProjectiveCurve> = Vec<C::BaseField>;
 
 pub type Ciphertext<C: ProjectiveCurve> = (C, Vec<C::BaseField>);
 
+/// Re-derives public key of `sk` as `sk * generator`, the same one [`EncryptCircuit::keygen`] yields.
+pub fn public_key_from_secret<C: ProjectiveCurve>(sk: &SecretKey<C>) -> PublicKey<C> {
+    let mut public_key = C::prime_subgroup_generator();
+    public_key.mul_assign(sk.clone());
+    public_key
+}
+
 impl<C, CV> EncryptCircuit<C, CV>
 where
     C: ProjectiveCurve,
@@ -119,10 +151,48 @@ where
             pk,
             resulted_ciphertext: enc,
             params,
+            predicates: vec![],
             _curve_var: PhantomData,
         })
     }
 
+    /// Makes the circuit also prove `predicate` over the plaintext, with its public inputs following
+    /// the ciphertext ones and those of predicates added before, see [`Self::get_public_inputs_with`].
+    pub fn with_predicate<P: PlaintextPredicate<C::BaseField> + 'static>(
+        mut self,
+        predicate: P,
+    ) -> Self {
+        self.predicates.push(Box::new(predicate));
+        self
+    }
+
+    /// Makes the circuit also prove that plaintext hashes to its [`Self::plaintext_commitment`],
+    /// passed as the public input following the ciphertext, see [`Self::get_public_inputs_committed`].
+    pub fn with_plaintext_commitment(self) -> Self {
+        let digest = PoseidonDigest::new(self.params.poseidon.clone());
+        self.with_predicate(digest)
+    }
+
+    /// Poseidon digest of `msg` padded with zeros up to `params.n` chunks, as the circuit hashes it.
+    pub fn plaintext_commitment(msg: &Plaintext<C>, params: &Parameters<C>) -> C::BaseField {
+        PoseidonDigest::new(params.poseidon.clone()).digest(&Self::padded(msg, params))
+    }
+
+    /// Values of the public inputs of all predicates the circuit proves, in the order they were added.
+    pub fn predicate_inputs(&self) -> Vec<C::BaseField> {
+        let padded = Self::padded(&self.msg, &self.params);
+        self.predicates
+            .iter()
+            .flat_map(|predicate| predicate.public_inputs(&padded))
+            .collect()
+    }
+
+    fn padded(msg: &Plaintext<C>, params: &Parameters<C>) -> Vec<C::BaseField> {
+        (0..params.n)
+            .map(|i| msg.get(i).map_or(C::BaseField::zero(), |&m| m))
+            .collect()
+    }
+
     pub fn keygen<R: CryptoRng + RngCore>(
         mut rng: &mut R,
     ) -> anyhow::Result<(SecretKey<C>, PublicKey<C>)> {
@@ -130,8 +200,7 @@ where
         let secret_key = C::ScalarField::rand(rng);
 
         // compute secret_key*generator to derive the public key
-        let mut public_key = C::prime_subgroup_generator();
-        public_key.mul_assign(secret_key.clone());
+        let public_key = public_key_from_secret::<C>(&secret_key);
 
         Ok((secret_key, public_key))
     }
@@ -151,6 +220,38 @@ where
         c1_inputs.into_iter().chain(c2_inputs).collect()
     }
 
+    /// Public inputs of the circuit made with [`Self::with_plaintext_commitment`].
+    pub fn get_public_inputs_committed<E: PairingEngine>(
+        cipher: &Ciphertext<C>,
+        commitment: &C::BaseField,
+        params: &Parameters<C>,
+    ) -> Vec<E::Fr>
+    where
+        C::BaseField: ToConstraintField<E::Fr>,
+        C: ToConstraintField<E::Fr>,
+    {
+        Self::get_public_inputs_with::<E>(cipher, &[*commitment], params)
+    }
+
+    /// Public inputs of the circuit made with predicates exposing `predicate_inputs`, see [`Self::predicate_inputs`].
+    pub fn get_public_inputs_with<E: PairingEngine>(
+        cipher: &Ciphertext<C>,
+        predicate_inputs: &[C::BaseField],
+        params: &Parameters<C>,
+    ) -> Vec<E::Fr>
+    where
+        C::BaseField: ToConstraintField<E::Fr>,
+        C: ToConstraintField<E::Fr>,
+    {
+        let mut inputs = Self::get_public_inputs::<E>(cipher, params);
+        inputs.extend(
+            predicate_inputs
+                .iter()
+                .flat_map(|input| input.to_field_elements().unwrap()),
+        );
+        inputs
+    }
+
     pub fn encrypt(
         pk: &PublicKey<C>,
         msg: &Plaintext<C>,
@@ -171,6 +272,55 @@ where
         Ok((c1, c2))
     }
 
+    /// Encrypts every chunk of `msg` separately with randomness derived from `master` and chunk's index,
+    /// see [`Randomness::derive`]. Resulting ciphertexts aren't covered by the encryption circuit.
+    /// With `parallel` feature chunks are spread across the rayon thread pool,
+    /// yielding the same ciphertexts in the same order as [`Self::encrypt_separated_sequential`].
+    pub fn encrypt_separated(
+        pk: &PublicKey<C>,
+        msg: &Plaintext<C>,
+        master: &Randomness<C>,
+        params: &Parameters<C>,
+    ) -> Result<Vec<Ciphertext<C>>, Error> {
+        // boxed error isn't `Send`, so it crosses threads as a string.
+        cfg_iter!(msg)
+            .enumerate()
+            .map(|(i, m)| {
+                Self::encrypt(pk, &vec![*m], &Randomness::derive(master, i as u64), params)
+                    .map_err(|e| e.to_string())
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Error::from)
+    }
+
+    /// [`Self::encrypt_separated`] on the calling thread only.
+    pub fn encrypt_separated_sequential(
+        pk: &PublicKey<C>,
+        msg: &Plaintext<C>,
+        master: &Randomness<C>,
+        params: &Parameters<C>,
+    ) -> Result<Vec<Ciphertext<C>>, Error> {
+        msg.iter()
+            .enumerate()
+            .map(|(i, m)| {
+                Self::encrypt(pk, &vec![*m], &Randomness::derive(master, i as u64), params)
+            })
+            .collect()
+    }
+
+    /// Decrypts chunks encrypted with [`Self::encrypt_separated`].
+    pub fn decrypt_separated(
+        ciphers: Vec<Ciphertext<C>>,
+        sk: SecretKey<C>,
+        params: &Parameters<C>,
+    ) -> anyhow::Result<Plaintext<C>> {
+        ciphers
+            .into_iter()
+            .map(|cipher| Self::decrypt(cipher, sk, params))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map(|chunks| chunks.concat())
+    }
+
     pub fn decrypt(
         cipher: Ciphertext<C>,
         sk: SecretKey<C>,
@@ -216,6 +366,26 @@ where
         Ok(c2 - dh)
     }
 
+    /// Re-encrypts `cipher` to `pk` with its plaintext re-packed from `chunk_size` into `new_chunk_size` byte chunks.
+    /// Requires the secret key, since plaintext must be decrypted first; resulting ciphertext has
+    /// fresh randomness and isn't covered by any proof made for the original one.
+    pub fn rechunk<R: Rng>(
+        cipher: Ciphertext<C>,
+        sk: SecretKey<C>,
+        chunk_size: usize,
+        new_chunk_size: usize,
+        pk: &PublicKey<C>,
+        params: &Parameters<C>,
+        rng: &mut R,
+    ) -> anyhow::Result<Ciphertext<C>> {
+        let plaintext = Self::decrypt(cipher, sk, params)?;
+        let bytes = plaintext_chunks_to_bytes_sized::<C>(plaintext, chunk_size)?;
+        let msg = bytes_to_plaintext_chunks_sized::<C, _>(bytes, new_chunk_size)?;
+
+        Self::encrypt(pk, &msg, &Randomness::rand(rng), params)
+            .map_err(|e| anyhow!("error encrypting message: {e}"))
+    }
+
     pub(crate) fn verify_encryption(
         &self,
         cs: ConstraintSystemRef<C::BaseField>,
@@ -304,7 +474,7 @@ where
         self,
         cs: ConstraintSystemRef<C::BaseField>,
     ) -> Result<(), SynthesisError> {
-        let message = (0..self.params.n)
+        let message: Vec<_> = (0..self.params.n)
             .map(|i| {
                 FpVar::<C::BaseField>::new_witness(ns!(cs, "plaintext"), || {
                     Ok(self.msg.get(i).map_or(C::BaseField::zero(), |c| c.clone()))
@@ -313,14 +483,27 @@ where
             .collect::<Result<_, _>>()?;
         let ciphertext = self.ciphertext_var(cs.clone(), AllocationMode::Input)?;
 
+        let padded = Self::padded(&self.msg, &self.params);
+        for predicate in &self.predicates {
+            let inputs = predicate
+                .public_inputs(&padded)
+                .into_iter()
+                .map(|input| FpVar::new_input(ns!(cs, "predicate_input"), || Ok(input)))
+                .collect::<Result<Vec<_>, _>>()?;
+            predicate.enforce(cs.clone(), &message, &inputs)?;
+        }
+
         self.verify_encryption(cs.clone(), &message, &ciphertext)
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{ark_from_bytes, ark_to_bytes, EncryptCircuit};
-    use crate::{poseidon, Parameters};
+    use crate::PlaintextPredicate;
+    use crate::{ark_from_bytes, ark_to_bytes, bytes_to_plaintext_chunks_sized, EncryptCircuit};
+    use crate::{
+        plaintext_chunks_to_bytes_sized, poseidon, public_key_from_secret, Parameters, Randomness,
+    };
     use ark_bls12_381::{Bls12_381 as E, Fr};
     use ark_ec::ProjectiveCurve;
     use ark_ed_on_bls12_381::{
@@ -338,7 +521,9 @@ mod test {
     use ark_r1cs_std::fields::fp::FpVar;
     use ark_r1cs_std::prelude::{AllocVar, AllocationMode, Boolean, EqGadget, FieldVar};
     use ark_r1cs_std::{R1CSVar, ToBytesGadget, ToConstraintFieldGadget};
-    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+    use ark_relations::r1cs::{
+        ConstraintSynthesizer, ConstraintSystem, ConstraintSystemRef, SynthesisError,
+    };
     use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
     use ark_snark::{CircuitSpecificSetupSNARK, SNARK};
     use ark_sponge::constraints::AbsorbGadget;
@@ -368,6 +553,97 @@ mod test {
         assert_eq!(vec![msg], plaintext);
     }
 
+    #[test]
+    fn test_public_key_from_secret() {
+        let mut rng = test_rng();
+        for _ in 0..8 {
+            let (sk, pk) = TestEnc::keygen(&mut rng).unwrap();
+            assert_eq!(public_key_from_secret::<Curve>(&sk), pk);
+        }
+    }
+
+    #[test]
+    fn test_rechunk() {
+        let mut rng = test_rng();
+        let params = Parameters::<Curve>::default_multi(8);
+        // top byte of each 32-byte chunk must stay below the field modulus.
+        let data = (0..100).cycle().take(4 * 32).collect::<Vec<u8>>();
+        let (sk, pk) = TestEnc::keygen(&mut rng).unwrap();
+
+        let msg = bytes_to_plaintext_chunks_sized::<Curve, _>(&data, 32).unwrap();
+        let cipher = TestEnc::new(pk, msg, params.clone(), &mut rng)
+            .unwrap()
+            .resulted_ciphertext;
+
+        let halved = TestEnc::rechunk(cipher, sk, 32, 16, &pk, &params, &mut rng).unwrap();
+        assert_eq!(halved.1.len(), 8);
+        let restored = TestEnc::rechunk(halved, sk, 16, 32, &pk, &params, &mut rng).unwrap();
+        assert_eq!(restored.1.len(), 4);
+
+        let plaintext = TestEnc::decrypt(restored, sk, &params).unwrap();
+        assert_eq!(
+            plaintext_chunks_to_bytes_sized::<Curve>(plaintext, 32).unwrap(),
+            data
+        );
+    }
+
+    #[test]
+    fn test_chunk_randomness_separated() {
+        let mut rng = test_rng();
+        let params = Parameters::<Curve>::default_multi(64);
+        let msg = (0..64u64).map(Fq::from).collect::<Vec<_>>();
+        let (sk, pk) = TestEnc::keygen(&mut rng).unwrap();
+        let master = Randomness::<Curve>::rand(&mut rng);
+
+        let derive_all = |master: &Randomness<Curve>| {
+            (0..msg.len() as u64)
+                .map(|i| Randomness::derive(master, i).0)
+                .collect::<Vec<_>>()
+        };
+        let derived = derive_all(&master);
+        let distinct = derived.iter().collect::<std::collections::HashSet<_>>();
+        assert_eq!(distinct.len(), msg.len());
+        assert!(!distinct.contains(&master.0));
+        assert_eq!(derive_all(&Randomness(master.0)), derived);
+        assert_ne!(derive_all(&Randomness::rand(&mut rng)), derived);
+
+        let ciphers = TestEnc::encrypt_separated(&pk, &msg, &master, &params).unwrap();
+        let c1s = ciphers
+            .iter()
+            .map(|c| c.0)
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(c1s.len(), msg.len());
+        assert_eq!(
+            TestEnc::encrypt_separated(&pk, &msg, &Randomness(master.0), &params).unwrap(),
+            ciphers
+        );
+        assert_eq!(
+            TestEnc::decrypt_separated(ciphers, sk, &params).unwrap(),
+            msg
+        );
+    }
+
+    #[test]
+    fn test_parallel_encryption_matches_sequential() {
+        let mut rng = test_rng();
+        let params = Parameters::<Curve>::default();
+        let data = (0..31 * 300)
+            .map(|_| u8::rand(&mut rng))
+            .collect::<Vec<_>>();
+        let msg = bytes_to_plaintext_chunks_sized::<Curve, _>(&data, 31).unwrap();
+        let (_, pk) = TestEnc::keygen(&mut rng).unwrap();
+        let master = Randomness::<Curve>::rand(&mut rng);
+
+        let parallel = TestEnc::encrypt_separated(&pk, &msg, &master, &params).unwrap();
+        let sequential =
+            TestEnc::encrypt_separated_sequential(&pk, &msg, &master, &params).unwrap();
+        assert_eq!(parallel.len(), msg.len());
+        assert_eq!(
+            ark_to_bytes(parallel).unwrap(),
+            ark_to_bytes(sequential).unwrap()
+        );
+    }
+
     #[test]
     fn test_encryption_circuit() {
         pretty_env_logger::init();
@@ -411,6 +687,112 @@ mod test {
         assert!(valid_proof);
     }
 
+    #[test]
+    fn test_plaintext_commitment_circuit() {
+        let mut rng = test_rng();
+        let params = Parameters::<Curve>::default_multi(2);
+        let msg = vec![Fq::from(7u64)];
+        let (_, pub_key) = TestEnc::keygen(&mut rng).unwrap();
+        let commitment = TestEnc::plaintext_commitment(&msg, &params);
+        assert_eq!(
+            commitment,
+            TestEnc::plaintext_commitment(&vec![Fq::from(7u64), Fq::zero()], &params)
+        );
+        assert_ne!(
+            commitment,
+            TestEnc::plaintext_commitment(&vec![Fq::from(8u64)], &params)
+        );
+
+        let circuit = TestEnc::new(pub_key, msg.clone(), params.clone(), &mut rng)
+            .unwrap()
+            .with_plaintext_commitment();
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+
+        let setup = TestEnc::new(pub_key, msg.clone(), params.clone(), &mut rng).unwrap();
+        let (pk, vk) = Groth16::<E>::setup(setup.with_plaintext_commitment(), &mut rng).unwrap();
+        let circuit = TestEnc::new(pub_key, msg, params.clone(), &mut rng).unwrap();
+        let enc = circuit.resulted_ciphertext.clone();
+        let proof = Groth16::prove(&pk, circuit.with_plaintext_commitment(), &mut rng).unwrap();
+
+        let inputs = TestEnc::get_public_inputs_committed::<E>(&enc, &commitment, &params);
+        assert!(Groth16::<E>::verify(&vk, &inputs, &proof).unwrap());
+        let other = Fq::from(1u64);
+        let inputs = TestEnc::get_public_inputs_committed::<E>(&enc, &other, &params);
+        assert!(!Groth16::<E>::verify(&vk, &inputs, &proof).unwrap());
+    }
+
+    /// Proves the first plaintext chunk is the public input.
+    struct LeadingChunk;
+
+    impl PlaintextPredicate<Fq> for LeadingChunk {
+        fn public_inputs(&self, plaintext: &[Fq]) -> Vec<Fq> {
+            vec![plaintext[0]]
+        }
+
+        fn enforce(
+            &self,
+            _cs: ConstraintSystemRef<Fq>,
+            plaintext: &[FpVar<Fq>],
+            inputs: &[FpVar<Fq>],
+        ) -> Result<(), SynthesisError> {
+            plaintext[0].enforce_equal(&inputs[0])
+        }
+    }
+
+    #[test]
+    fn test_custom_predicate_circuit() {
+        let mut rng = test_rng();
+        let params = Parameters::<Curve>::default_multi(2);
+        let msg = vec![Fq::from(7u64), Fq::from(9u64)];
+        let (_, pub_key) = TestEnc::keygen(&mut rng).unwrap();
+        let circuit = |rng: &mut _| {
+            TestEnc::new(pub_key, msg.clone(), params.clone(), rng)
+                .unwrap()
+                .with_plaintext_commitment()
+                .with_predicate(LeadingChunk)
+        };
+
+        let (pk, vk) = Groth16::<E>::setup(circuit(&mut rng), &mut rng).unwrap();
+        let proved = circuit(&mut rng);
+        let enc = proved.resulted_ciphertext.clone();
+        let predicate_inputs = proved.predicate_inputs();
+        let commitment = TestEnc::plaintext_commitment(&msg, &params);
+        assert_eq!(predicate_inputs, vec![commitment, Fq::from(7u64)]);
+        let proof = Groth16::prove(&pk, proved, &mut rng).unwrap();
+
+        let inputs = TestEnc::get_public_inputs_with::<E>(&enc, &predicate_inputs, &params);
+        assert!(Groth16::<E>::verify(&vk, &inputs, &proof).unwrap());
+        let claimed = [commitment, Fq::from(9u64)];
+        let inputs = TestEnc::get_public_inputs_with::<E>(&enc, &claimed, &params);
+        assert!(!Groth16::<E>::verify(&vk, &inputs, &proof).unwrap());
+    }
+
+    #[test]
+    fn test_encryption_circuit_bn254() {
+        use ark_bn254::Bn254;
+        use ark_ed_on_bn254::{
+            constraints::EdwardsVar as BabyJubJubVar, EdwardsProjective as BabyJubJub, Fq as BnFr,
+        };
+        type BnEnc = EncryptCircuit<BabyJubJub, BabyJubJubVar>;
+
+        let mut rng = test_rng();
+        let params = Parameters::<BabyJubJub>::default_multi(2);
+        let msg = vec![BnFr::from(7u64), BnFr::from(11u64)];
+        let (sk, pub_key) = BnEnc::keygen(&mut rng).unwrap();
+
+        let setup = BnEnc::new(pub_key, msg.clone(), params.clone(), &mut rng).unwrap();
+        let (pk, vk) = Groth16::<Bn254>::setup(setup, &mut rng).unwrap();
+        let circuit = BnEnc::new(pub_key, msg.clone(), params.clone(), &mut rng).unwrap();
+        let enc = circuit.resulted_ciphertext.clone();
+        let proof = Groth16::prove(&pk, circuit, &mut rng).unwrap();
+
+        let public_inputs = BnEnc::get_public_inputs::<Bn254>(&enc, &params);
+        assert!(Groth16::<Bn254>::verify(&vk, &public_inputs, &proof).unwrap());
+        assert_eq!(BnEnc::decrypt(enc, sk, &params).unwrap(), msg);
+    }
+
     #[test]
     fn test_elgamal_keygen() {
         let mut rng = test_rng();
@@ -5,11 +5,13 @@ mod circom_wrapper;
 pub mod encryption;
 mod parameters;
 pub mod poseidon;
+mod predicate;
 mod sample_entries;
 mod utils;
 
 pub use crate::circom_wrapper::*;
 pub use crate::encryption::*;
+pub use crate::predicate::*;
 pub use crate::sample_entries::*;
 pub use crate::utils::*;
 pub use ark_bls12_377::{constraints::G1Var as Bls12377Var, G1Projective as Bls12377};
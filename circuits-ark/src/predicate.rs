@@ -0,0 +1,60 @@
+use ark_ff::PrimeField;
+use ark_r1cs_std::fields::fp::FpVar;
+use ark_r1cs_std::prelude::*;
+use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError};
+use ark_sponge::constraints::CryptographicSpongeVar;
+use ark_sponge::poseidon::constraints::PoseidonSpongeVar;
+use ark_sponge::poseidon::{PoseidonParameters, PoseidonSponge};
+use ark_sponge::{Absorb, CryptographicSponge};
+
+/// Statement over the plaintext the encryption circuit proves on top of the encryption itself,
+/// see [`crate::EncryptCircuit::with_predicate`].
+pub trait PlaintextPredicate<F: PrimeField>: Send + Sync {
+    /// Values of the public inputs the predicate exposes for `plaintext`, padded with zeros up to the circuit size.
+    /// They follow the ciphertext in the public inputs of the circuit.
+    fn public_inputs(&self, plaintext: &[F]) -> Vec<F>;
+
+    /// Enforces the predicate over `plaintext` variables, given its public `inputs`
+    /// allocated in the order of [`Self::public_inputs`].
+    fn enforce(
+        &self,
+        cs: ConstraintSystemRef<F>,
+        plaintext: &[FpVar<F>],
+        inputs: &[FpVar<F>],
+    ) -> Result<(), SynthesisError>;
+}
+
+/// Plaintext hashes with Poseidon to the commitment exposed as the only public input of the predicate.
+#[derive(Clone)]
+pub struct PoseidonDigest<F: PrimeField> {
+    params: PoseidonParameters<F>,
+}
+
+impl<F: PrimeField + Absorb> PoseidonDigest<F> {
+    pub fn new(params: PoseidonParameters<F>) -> Self {
+        Self { params }
+    }
+
+    pub fn digest(&self, plaintext: &[F]) -> F {
+        let mut sponge = PoseidonSponge::new(&self.params);
+        sponge.absorb(&plaintext.to_vec());
+        sponge.squeeze_field_elements::<F>(1).remove(0)
+    }
+}
+
+impl<F: PrimeField + Absorb> PlaintextPredicate<F> for PoseidonDigest<F> {
+    fn public_inputs(&self, plaintext: &[F]) -> Vec<F> {
+        vec![self.digest(plaintext)]
+    }
+
+    fn enforce(
+        &self,
+        cs: ConstraintSystemRef<F>,
+        plaintext: &[FpVar<F>],
+        inputs: &[FpVar<F>],
+    ) -> Result<(), SynthesisError> {
+        let mut poseidon = PoseidonSpongeVar::new(cs, &self.params);
+        poseidon.absorb(&plaintext.to_vec())?;
+        poseidon.squeeze_field_elements(1)?[0].enforce_equal(&inputs[0])
+    }
+}
@@ -6,44 +6,172 @@ use ark_ff::{to_bytes, Field, PrimeField, Zero};
 use ark_groth16::{Proof, ProvingKey, VerifyingKey};
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
 use std::fs;
-use std::io::{BufReader, BufWriter, Read, Write};
+use std::io::{self, Chain, Cursor, Read, Take};
+use std::marker::PhantomData;
 use std::path::Path;
 
+/// Size in bytes of the plaintext chunk packed into a single field element,
+/// one byte short of the element width so that every chunk stays below the ~255-bit modulus.
+pub const PLAINTEXT_CHUNK_SIZE: usize = 31;
+
+/// Bytes [`bytes_to_plaintext_chunks`] packs ahead of the data, its u64 little-endian length.
+pub const LENGTH_PREFIX_SIZE: usize = 8;
+
+/// Bytes [`bytes_to_plaintext_chunks_with_width`] packs ahead of the data, the width and length.
+pub const WIDTH_HEADER_SIZE: usize = 1 + LENGTH_PREFIX_SIZE;
+
+/// Widest plaintext chunk that always stays below the modulus of `C`'s base field,
+/// [`PLAINTEXT_CHUNK_SIZE`] for JubJub.
+pub fn plaintext_chunk_size<C: ProjectiveCurve>() -> usize {
+    (<C::BaseField as Field>::BasePrimeField::size_in_bits() - 1) / 8
+}
+
 pub fn ark_from_bytes<B: AsRef<[u8]>, O: CanonicalDeserialize>(
     bytes: B,
 ) -> Result<O, SerializationError> {
     O::deserialize(bytes.as_ref())
 }
 
+/// Encodes `f` with curve points compressed, about half the size of [`ark_to_bytes_uncompressed`].
 pub fn ark_to_bytes<I: CanonicalSerialize>(f: I) -> Result<Vec<u8>, SerializationError> {
     let mut buf = vec![];
     f.serialize(&mut buf)?;
     Ok(buf)
 }
 
+pub fn ark_from_bytes_uncompressed<B: AsRef<[u8]>, O: CanonicalDeserialize>(
+    bytes: B,
+) -> Result<O, SerializationError> {
+    O::deserialize_uncompressed(bytes.as_ref())
+}
+
+/// Encodes `f` with both coordinates of every curve point, sparing decompression on decoding.
+pub fn ark_to_bytes_uncompressed<I: CanonicalSerialize>(
+    f: I,
+) -> Result<Vec<u8>, SerializationError> {
+    let mut buf = vec![];
+    f.serialize_uncompressed(&mut buf)?;
+    Ok(buf)
+}
+
+/// Packs `bytes` prefixed with their u64 little-endian length into [`PLAINTEXT_CHUNK_SIZE`] wide chunks,
+/// so that zero padding of the last chunk can't be mistaken for data.
 pub fn bytes_to_plaintext_chunks<C: ProjectiveCurve, B: AsRef<[u8]>>(
     bytes: B,
 ) -> anyhow::Result<Plaintext<C>> {
-    let mut reader = BufReader::new(bytes.as_ref());
+    let bytes = bytes.as_ref();
+    PlaintextChunks::<C, _>::new(bytes, bytes.len() as u64)
+        .and_then(|chunks| chunks.collect())
+        .map_err(|e| anyhow!("failed to cast bytes to scalars: {e}"))
+}
 
-    let mut chunks = vec![];
-    loop {
-        let mut buf = [0; 32];
-        if !matches!(reader.read(&mut buf), Ok(n) if n != 0) {
-            break;
-        }
+/// Packs `bytes` into `chunk_size` wide chunks as [`bytes_to_plaintext_chunks`] does, but with the width
+/// recorded in the first byte ahead of the length prefix, see [`plaintext_chunks_to_bytes_with_width`].
+pub fn bytes_to_plaintext_chunks_with_width<C: ProjectiveCurve, B: AsRef<[u8]>>(
+    bytes: B,
+    chunk_size: usize,
+) -> anyhow::Result<Plaintext<C>> {
+    let bytes = bytes.as_ref();
+    PlaintextChunks::<C, _>::with_width(bytes, bytes.len() as u64, chunk_size)
+        .and_then(|chunks| chunks.collect())
+        .map_err(|e| anyhow!("failed to cast bytes to scalars: {e}"))
+}
+
+/// Packs `len` bytes read from `reader` as [`bytes_to_plaintext_chunks`] does, yielding a chunk
+/// per [`PLAINTEXT_CHUNK_SIZE`] bytes read, so that the data never has to be held in memory at once.
+pub struct PlaintextChunks<C: ProjectiveCurve, R: Read> {
+    reader: Chain<Cursor<Vec<u8>>, Take<R>>,
+    chunk_size: usize,
+    prefix_len: u64,
+    expected: u64,
+    read: u64,
+    index: usize,
+    done: bool,
+    _curve: PhantomData<C>,
+}
 
-        chunks.push(buf);
+impl<C: ProjectiveCurve, R: Read> PlaintextChunks<C, R> {
+    pub fn new(reader: R, len: u64) -> anyhow::Result<Self> {
+        Self::framed(
+            reader,
+            len,
+            PLAINTEXT_CHUNK_SIZE,
+            len.to_le_bytes().to_vec(),
+        )
     }
 
-    let plaintext_chunks: Option<Vec<_>> = chunks
-        .into_iter()
-        .map(|chunk| C::BaseField::from_random_bytes(&chunk))
-        .collect();
+    /// Chunks `chunk_size` wide framed as [`bytes_to_plaintext_chunks_with_width`] does.
+    pub fn with_width(reader: R, len: u64, chunk_size: usize) -> anyhow::Result<Self> {
+        let width = u8::try_from(chunk_size)
+            .map_err(|_| anyhow!("chunk size {chunk_size} doesn't fit into width header"))?;
+        let prefix = [&[width][..], &len.to_le_bytes()].concat();
+        Self::framed(reader, len, chunk_size, prefix)
+    }
+
+    fn framed(reader: R, len: u64, chunk_size: usize, prefix: Vec<u8>) -> anyhow::Result<Self> {
+        ensure_chunk_fits::<C>(chunk_size)?;
+        let prefix_len = prefix.len() as u64;
+        Ok(Self {
+            reader: Cursor::new(prefix).chain(reader.take(len)),
+            chunk_size,
+            prefix_len,
+            expected: prefix_len + len,
+            read: 0,
+            index: 0,
+            done: false,
+            _curve: PhantomData,
+        })
+    }
 
-    match plaintext_chunks {
-        Some(res) => Ok(res),
-        None => Err(anyhow!("failed to cast bytes to scalars")),
+    fn fill(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.reader.read(&mut buf[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(filled)
+    }
+}
+
+impl<C: ProjectiveCurve, R: Read> Iterator for PlaintextChunks<C, R> {
+    type Item = anyhow::Result<C::BaseField>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let mut buf = vec![0; self.chunk_size];
+        let filled = match self.fill(&mut buf) {
+            Ok(filled) => filled,
+            Err(e) => {
+                self.done = true;
+                return Some(Err(anyhow!("error reading plaintext: {e}")));
+            }
+        };
+        self.read += filled as u64;
+
+        if filled < buf.len() {
+            self.done = true;
+            if self.read < self.expected {
+                return Some(Err(anyhow!(
+                    "plaintext ended after {} of {} bytes",
+                    self.read.saturating_sub(self.prefix_len),
+                    self.expected - self.prefix_len
+                )));
+            }
+            if filled == 0 {
+                return None;
+            }
+        }
+
+        let element = chunk_to_element::<C>(&buf[..filled], self.index);
+        self.index += 1;
+        Some(element)
     }
 }
 
@@ -71,44 +199,158 @@ pub fn bytes_to_plaintext_chunks_direct<C: ProjectiveCurve, B: AsRef<[u8]>>(
         .collect())
 }
 
+/// Unpacks bytes framed by [`bytes_to_plaintext_chunks`], truncated to exactly their recorded length.
 pub fn plaintext_chunks_to_bytes<C: ProjectiveCurve>(
     chunks: Plaintext<C>,
 ) -> anyhow::Result<Vec<u8>> {
-    let mut buf = vec![0; chunks.len() * 32];
-    let mut writer = BufWriter::new(&mut *buf);
+    let buf = plaintext_chunks_to_bytes_sized::<C>(chunks, PLAINTEXT_CHUNK_SIZE)?;
+    strip_length_prefix(&buf)
+}
 
-    for chunk in chunks {
-        if let Ok(bytes) = to_bytes!(chunk) {
-            let mut bytes = bytes
-                .into_iter()
-                .rev()
-                .skip_while(|&b| b == 0)
-                .collect::<Vec<_>>();
-            bytes.reverse();
-            writer
-                .write(&bytes)
-                .map_err(|e| anyhow!("error filling buffer: {e}"))?;
+/// Unpacks bytes framed by [`bytes_to_plaintext_chunks_with_width`], failing unless they were
+/// chunked `chunk_size` wide, as the width recorded in the first byte tells.
+pub fn plaintext_chunks_to_bytes_with_width<C: ProjectiveCurve>(
+    chunks: Plaintext<C>,
+    chunk_size: usize,
+) -> anyhow::Result<Vec<u8>> {
+    let width = chunks
+        .first()
+        .and_then(|chunk| to_bytes!(chunk).ok())
+        .map(|bytes| bytes[0] as usize)
+        .ok_or(anyhow!("plaintext is missing its width header"))?;
+    if width != chunk_size {
+        return Err(anyhow!(
+            "plaintext was chunked {width} bytes wide, but {chunk_size} is expected"
+        ));
+    }
+
+    let buf = plaintext_chunks_to_bytes_sized::<C>(chunks, chunk_size)?;
+    strip_length_prefix(
+        buf.get(WIDTH_HEADER_SIZE - LENGTH_PREFIX_SIZE..)
+            .unwrap_or_default(),
+    )
+}
+
+/// Truncates `buf` to the length recorded in its u64 little-endian prefix.
+/// Lengths past `usize` are rejected rather than wrapped around on 32-bit targets.
+fn strip_length_prefix(buf: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let len = buf
+        .get(..LENGTH_PREFIX_SIZE)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        .ok_or(anyhow!("plaintext is missing its length prefix"))?;
+    let len = usize::try_from(len)
+        .map_err(|_| anyhow!("plaintext length {len} doesn't fit into memory"))?;
+
+    buf.get(LENGTH_PREFIX_SIZE..)
+        .filter(|data| data.len() >= len)
+        .map(|data| data[..len].to_vec())
+        .ok_or(anyhow!(
+            "plaintext length {len} exceeds {} unpacked bytes",
+            buf.len() - LENGTH_PREFIX_SIZE
+        ))
+}
+
+/// Packs `bytes` into field elements of `chunk_size` little-endian bytes each,
+/// the last chunk is padded with zeros.
+pub fn bytes_to_plaintext_chunks_sized<C: ProjectiveCurve, B: AsRef<[u8]>>(
+    bytes: B,
+    chunk_size: usize,
+) -> anyhow::Result<Plaintext<C>> {
+    ensure_chunk_fits::<C>(chunk_size)?;
+
+    bytes
+        .as_ref()
+        .chunks(chunk_size)
+        .enumerate()
+        .map(|(i, chunk)| chunk_to_element::<C>(chunk, i))
+        .collect()
+}
+
+fn ensure_chunk_fits<C: ProjectiveCurve>(chunk_size: usize) -> anyhow::Result<()> {
+    let capacity = (<C::BaseField as Field>::BasePrimeField::size_in_bits() + 7) / 8;
+    if chunk_size == 0 || chunk_size > capacity {
+        return Err(anyhow!(
+            "chunk size {chunk_size} doesn't fit into field element of {capacity} bytes"
+        ));
+    }
+    Ok(())
+}
+
+/// Casts little-endian `chunk` into the field element.
+fn chunk_to_element<C: ProjectiveCurve>(chunk: &[u8], i: usize) -> anyhow::Result<C::BaseField> {
+    let element = chunk.iter().rev().fold(C::BaseField::zero(), |acc, b| {
+        acc * C::BaseField::from(256u128) + C::BaseField::from(*b as u128)
+    });
+    // chunks as wide as the field element may exceed its modulus and wrap around.
+    match to_bytes!(element) {
+        Ok(bytes) if bytes.starts_with(chunk) => Ok(element),
+        _ => Err(anyhow!("chunk {i} doesn't fit into field element")),
+    }
+}
+
+/// Unpacks exactly `chunk_size` little-endian bytes from each field element.
+/// Unlike [`plaintext_chunks_to_bytes`] keeps zero bytes, so data with inner zeros is restored as is.
+pub fn plaintext_chunks_to_bytes_sized<C: ProjectiveCurve>(
+    chunks: Plaintext<C>,
+    chunk_size: usize,
+) -> anyhow::Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(chunks.len() * chunk_size);
+
+    for (i, chunk) in chunks.into_iter().enumerate() {
+        let mut bytes = to_bytes!(chunk).map_err(|e| anyhow!("error casting chunk {i}: {e}"))?;
+        if bytes.len() < chunk_size {
+            bytes.resize(chunk_size, 0);
         }
+        if bytes[chunk_size..].iter().any(|&b| b != 0) {
+            return Err(anyhow!("chunk {i} exceeds {chunk_size} bytes"));
+        }
+        buf.extend_from_slice(&bytes[..chunk_size]);
     }
 
-    Ok(writer.buffer().to_vec())
+    Ok(buf)
+}
+
+/// Maps byte `offset` in the original data to the index of chunk holding it and offset within
+/// that chunk, skipping `header_len` bytes packed ahead of the data: [`LENGTH_PREFIX_SIZE`] for
+/// [`bytes_to_plaintext_chunks`], [`WIDTH_HEADER_SIZE`] for
+/// [`bytes_to_plaintext_chunks_with_width`] and `0` with `chunk_size` of `1` for plaintext cast
+/// byte by byte.
+pub fn chunk_for_offset(offset: usize, chunk_size: usize, header_len: usize) -> (usize, usize) {
+    let packed = header_len + offset;
+    (packed / chunk_size, packed % chunk_size)
+}
+
+/// Inverse of [`chunk_for_offset`], none if the position falls into the header.
+pub fn offset_for_chunk(
+    chunk_index: usize,
+    inner_offset: usize,
+    chunk_size: usize,
+    header_len: usize,
+) -> Option<usize> {
+    (chunk_index * chunk_size + inner_offset).checked_sub(header_len)
 }
 
 #[cfg(test)]
 mod test {
     use crate::{
-        ark_from_bytes, ark_to_bytes, bytes_to_plaintext_chunks, plaintext_chunks_to_bytes,
-        Ciphertext, JubJub,
+        ark_from_bytes, ark_from_bytes_uncompressed, ark_to_bytes, ark_to_bytes_uncompressed,
+        bytes_to_plaintext_chunks, bytes_to_plaintext_chunks_direct,
+        bytes_to_plaintext_chunks_sized, bytes_to_plaintext_chunks_with_width, chunk_for_offset,
+        offset_for_chunk, plaintext_chunk_size, plaintext_chunks_to_bytes,
+        plaintext_chunks_to_bytes_sized, plaintext_chunks_to_bytes_with_width, Ciphertext, JubJub,
+        PlaintextChunks, LENGTH_PREFIX_SIZE, PLAINTEXT_CHUNK_SIZE, WIDTH_HEADER_SIZE,
     };
     use ark_bls12_381::Fr;
     use ark_crypto_primitives::encryption::elgamal::{Plaintext, PublicKey};
     use ark_ec::twisted_edwards_extended::GroupProjective;
     use ark_ec::{AffineCurve, ProjectiveCurve};
-    use ark_ed_on_bls12_381::Fq;
-    use ark_ff::Field;
+    use ark_ed_on_bls12_381::{Fq, Fr as Scalar};
+    use ark_ff::{to_bytes, Field, PrimeField};
     use ark_std::rand::RngCore;
     use ark_std::test_rng;
-    use std::io::{BufReader, Read};
+    use ark_std::UniformRand;
+    use proptest::prelude::*;
+    use std::io::{BufReader, Cursor, Read};
     use std::ops::Add;
 
     const ALICE_SK: &str = "be3f1cca6354c294cf64c098dea22d04009e94b7dbfb6bf46e783b7e4fd4dd0a";
@@ -120,6 +362,23 @@ mod test {
         let pk: JubJub = ark_from_bytes(&bytes).unwrap();
     }
 
+    #[test]
+    fn test_point_compression_round_trip() {
+        let mut rng = test_rng();
+        let points = (0..4).map(|_| JubJub::rand(&mut rng)).collect::<Vec<_>>();
+
+        let compressed = ark_to_bytes(points.clone()).unwrap();
+        let uncompressed = ark_to_bytes_uncompressed(points.clone()).unwrap();
+        assert_eq!(8 + 4 * 32, compressed.len());
+        assert_eq!(8 + 4 * 64, uncompressed.len());
+
+        let decoded: Vec<JubJub> = ark_from_bytes(&compressed).unwrap();
+        assert_eq!(decoded, points);
+        let decoded: Vec<JubJub> = ark_from_bytes_uncompressed(&uncompressed).unwrap();
+        assert_eq!(decoded, points);
+        assert!(ark_from_bytes_uncompressed::<_, Vec<JubJub>>(&compressed).is_err());
+    }
+
     #[test]
     fn test_secret_key_decode() {
         let bytes = hex::decode(ALICE_SK).unwrap();
@@ -146,6 +405,295 @@ mod test {
         assert_eq!(bytes, res)
     }
 
+    #[test]
+    fn test_plaintext_trailing_zeros_kept() {
+        for len in [1, 2, 5] {
+            let mut bytes = vec![1; 10];
+            bytes.extend(vec![0; len]);
+            let chunks = bytes_to_plaintext_chunks::<JubJub, _>(&bytes).unwrap();
+            assert_eq!(plaintext_chunks_to_bytes::<JubJub>(chunks).unwrap(), bytes);
+        }
+
+        let zeros = vec![0; 40];
+        let chunks = bytes_to_plaintext_chunks::<JubJub, _>(&zeros).unwrap();
+        assert_eq!(plaintext_chunks_to_bytes::<JubJub>(chunks).unwrap(), zeros);
+
+        let empty = bytes_to_plaintext_chunks::<JubJub, _>([]).unwrap();
+        assert!(plaintext_chunks_to_bytes::<JubJub>(empty)
+            .unwrap()
+            .is_empty());
+        assert!(plaintext_chunks_to_bytes::<JubJub>(vec![]).is_err());
+    }
+
+    #[test]
+    fn test_plaintext_exact_chunk_multiple() {
+        // prefix and data together fill the chunks up to the brim.
+        for chunks in 1..4 {
+            let mut bytes = vec![0xab; chunks * PLAINTEXT_CHUNK_SIZE - 8];
+            *bytes.last_mut().unwrap() = 0;
+            let plaintext = bytes_to_plaintext_chunks::<JubJub, _>(&bytes).unwrap();
+            assert_eq!(plaintext.len(), chunks);
+            assert_eq!(
+                plaintext_chunks_to_bytes::<JubJub>(plaintext).unwrap(),
+                bytes
+            );
+        }
+
+        // data alone a multiple of the chunk, so the prefix spills into one more.
+        let bytes = vec![0; 2 * PLAINTEXT_CHUNK_SIZE];
+        let plaintext = bytes_to_plaintext_chunks::<JubJub, _>(&bytes).unwrap();
+        assert_eq!(plaintext.len(), 3);
+        assert_eq!(
+            plaintext_chunks_to_bytes::<JubJub>(plaintext).unwrap(),
+            bytes
+        );
+    }
+
+    #[test]
+    fn test_streamed_plaintext_chunks() {
+        let mut rng = test_rng();
+        let mut bytes = vec![0; 300 * 1024 + 17];
+        rng.fill_bytes(&mut bytes);
+
+        let framed = [&(bytes.len() as u64).to_le_bytes()[..], &bytes].concat();
+        let expected =
+            bytes_to_plaintext_chunks_sized::<JubJub, _>(&framed, PLAINTEXT_CHUNK_SIZE).unwrap();
+        let streamed = PlaintextChunks::<JubJub, _>::new(Cursor::new(&bytes), bytes.len() as u64)
+            .unwrap()
+            .collect::<anyhow::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(streamed, expected);
+        assert_eq!(
+            bytes_to_plaintext_chunks::<JubJub, _>(&bytes).unwrap(),
+            expected
+        );
+
+        // reader running dry before the announced length is an error, not a shorter plaintext.
+        let short = PlaintextChunks::<JubJub, _>::new(Cursor::new(&bytes[..1000]), 2000)
+            .unwrap()
+            .collect::<anyhow::Result<Vec<_>>>();
+        assert!(short.is_err());
+    }
+
+    #[test]
+    fn test_random_plaintext_round_trip() {
+        let mut rng = test_rng();
+        for fill in [None, Some(0xff)] {
+            for _ in 0..16 {
+                let mut bytes = vec![0; 1024];
+                match fill {
+                    Some(b) => bytes.iter_mut().for_each(|x| *x = b),
+                    None => rng.fill_bytes(&mut bytes),
+                }
+
+                let chunks = bytes_to_plaintext_chunks::<JubJub, _>(&bytes).unwrap();
+                assert_eq!(
+                    chunks.len(),
+                    (8 + 1024 + PLAINTEXT_CHUNK_SIZE - 1) / PLAINTEXT_CHUNK_SIZE
+                );
+                assert_eq!(plaintext_chunks_to_bytes::<JubJub>(chunks).unwrap(), bytes);
+            }
+        }
+    }
+
+    #[test]
+    fn test_plaintext_width_round_trip() {
+        assert_eq!(plaintext_chunk_size::<JubJub>(), PLAINTEXT_CHUNK_SIZE);
+        // BLS12-381 G1 has 381-bit base field.
+        assert_eq!(plaintext_chunk_size::<ark_bls12_381::G1Projective>(), 47);
+
+        let mut rng = test_rng();
+        let mut bytes = vec![0; 300];
+        rng.fill_bytes(&mut bytes);
+        bytes.extend([0; 5]);
+
+        for chunk_size in [1, 8, 16, PLAINTEXT_CHUNK_SIZE] {
+            let chunks =
+                bytes_to_plaintext_chunks_with_width::<JubJub, _>(&bytes, chunk_size).unwrap();
+            assert_eq!(
+                chunks.len(),
+                (9 + bytes.len() + chunk_size - 1) / chunk_size
+            );
+            assert_eq!(
+                plaintext_chunks_to_bytes_with_width::<JubJub>(chunks, chunk_size).unwrap(),
+                bytes
+            );
+        }
+
+        // decoding with any other width fails on the header rather than garbling the data.
+        let chunks = bytes_to_plaintext_chunks_with_width::<JubJub, _>(&bytes, 16).unwrap();
+        for chunk_size in [8, PLAINTEXT_CHUNK_SIZE] {
+            let err = plaintext_chunks_to_bytes_with_width::<JubJub>(chunks.clone(), chunk_size)
+                .unwrap_err();
+            assert!(err.to_string().contains("chunked 16 bytes wide"), "{err}");
+        }
+        assert!(plaintext_chunks_to_bytes_with_width::<JubJub>(vec![], 16).is_err());
+        assert!(
+            plaintext_chunks_to_bytes_with_width::<JubJub>(vec![Fq::from(0u64); 2], 0).is_err()
+        );
+        assert!(bytes_to_plaintext_chunks_with_width::<JubJub, _>(&bytes, 33).is_err());
+    }
+
+    #[test]
+    fn test_sized_plaintext_decode() {
+        let bytes = vec![0, 1, 0, 2, 0, 0, 3, 0, 255, 0];
+
+        for chunk_size in [1, 4, 7, 31, 32] {
+            let chunks = bytes_to_plaintext_chunks_sized::<JubJub, _>(&bytes, chunk_size).unwrap();
+            assert_eq!(chunks.len(), (bytes.len() + chunk_size - 1) / chunk_size);
+
+            let mut res = plaintext_chunks_to_bytes_sized::<JubJub>(chunks, chunk_size).unwrap();
+            res.truncate(bytes.len());
+            assert_eq!(bytes, res);
+        }
+
+        assert!(bytes_to_plaintext_chunks_sized::<JubJub, _>(&bytes, 33).is_err());
+        assert!(bytes_to_plaintext_chunks_sized::<JubJub, _>([0xff; 32], 32).is_err());
+        let wide = bytes_to_plaintext_chunks_sized::<JubJub, _>(&bytes, 8).unwrap();
+        assert!(plaintext_chunks_to_bytes_sized::<JubJub>(wide, 4).is_err());
+    }
+
+    /// Longest plaintext exercised by the round-trip property, a couple of circuit batches worth of chunks.
+    const MAX_PLAINTEXT_LEN: usize = 4096;
+
+    fn sized_round_trip(bytes: &[u8], chunk_size: usize) -> Vec<u8> {
+        let chunks = bytes_to_plaintext_chunks_sized::<JubJub, _>(bytes, chunk_size).unwrap();
+        let mut res = plaintext_chunks_to_bytes_sized::<JubJub>(chunks, chunk_size).unwrap();
+        res.truncate(bytes.len());
+        res
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(256))]
+
+        #[test]
+        fn prop_sized_plaintext_round_trip(
+            bytes in prop::collection::vec(any::<u8>(), 0..=MAX_PLAINTEXT_LEN),
+            chunk_size in 1..PLAINTEXT_CHUNK_SIZE,
+        ) {
+            prop_assert_eq!(sized_round_trip(&bytes, chunk_size), bytes);
+        }
+    }
+
+    /// Arbitrary plaintext, often mostly zeros so that it's easily confused with zero padding.
+    fn plaintext_bytes() -> impl Strategy<Value = Vec<u8>> {
+        prop_oneof![
+            prop::collection::vec(any::<u8>(), 0..=MAX_PLAINTEXT_LEN),
+            prop::collection::vec(
+                prop_oneof![7 => Just(0u8), 1 => any::<u8>()],
+                0..=MAX_PLAINTEXT_LEN
+            ),
+        ]
+    }
+
+    fn field_elements(max: usize) -> impl Strategy<Value = Vec<Fq>> {
+        prop::collection::vec(any::<[u8; 32]>(), 0..max).prop_map(|elements| {
+            elements
+                .iter()
+                .map(|bytes| Fq::from_le_bytes_mod_order(bytes))
+                .collect()
+        })
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(256))]
+
+        #[test]
+        fn prop_plaintext_round_trip(bytes in plaintext_bytes()) {
+            let chunks = bytes_to_plaintext_chunks::<JubJub, _>(&bytes).unwrap();
+            prop_assert_eq!(
+                chunks.len(),
+                (8 + bytes.len() + PLAINTEXT_CHUNK_SIZE - 1) / PLAINTEXT_CHUNK_SIZE
+            );
+            prop_assert_eq!(plaintext_chunks_to_bytes::<JubJub>(chunks).unwrap(), bytes);
+        }
+
+        #[test]
+        fn prop_plaintext_width_round_trip(
+            bytes in plaintext_bytes(),
+            chunk_size in 1..=PLAINTEXT_CHUNK_SIZE,
+        ) {
+            let chunks =
+                bytes_to_plaintext_chunks_with_width::<JubJub, _>(&bytes, chunk_size).unwrap();
+            prop_assert_eq!(
+                plaintext_chunks_to_bytes_with_width::<JubJub>(chunks, chunk_size).unwrap(),
+                bytes
+            );
+        }
+
+        #[test]
+        fn prop_plaintext_overstated_length_rejected(
+            bytes in plaintext_bytes(),
+            extra in (PLAINTEXT_CHUNK_SIZE as u64)..=u64::MAX / 2,
+        ) {
+            // length past the last chunk, so zero padding can't make up for the missing data.
+            let len = bytes.len() as u64 + extra;
+            let framed = [&len.to_le_bytes()[..], &bytes].concat();
+            let chunks = bytes_to_plaintext_chunks_sized::<JubJub, _>(&framed, PLAINTEXT_CHUNK_SIZE)
+                .unwrap();
+            prop_assert!(plaintext_chunks_to_bytes::<JubJub>(chunks).is_err());
+        }
+
+        #[test]
+        fn prop_arbitrary_plaintext_chunks_decode(
+            chunks in field_elements(64),
+            chunk_size in 0..=40usize,
+        ) {
+            // whatever decryption with a wrong key yields is rejected or decoded, never a panic.
+            if let Ok(bytes) = plaintext_chunks_to_bytes::<JubJub>(chunks.clone()) {
+                prop_assert!(bytes.len() + 8 <= chunks.len() * PLAINTEXT_CHUNK_SIZE);
+            }
+            let _ = plaintext_chunks_to_bytes_with_width::<JubJub>(chunks.clone(), chunk_size);
+            let _ = plaintext_chunks_to_bytes_sized::<JubJub>(chunks, chunk_size);
+        }
+
+        #[test]
+        fn prop_ciphertext_round_trip(seed in any::<[u8; 32]>(), c2 in field_elements(16)) {
+            let c1 = JubJub::prime_subgroup_generator()
+                .mul(Scalar::from_le_bytes_mod_order(&seed).into_repr());
+            let ciphertext: Ciphertext<JubJub> = (c1, c2);
+
+            let compressed = ark_to_bytes(ciphertext.clone()).unwrap();
+            prop_assert_eq!(compressed.len(), 32 + 8 + 32 * ciphertext.1.len());
+            prop_assert_eq!(
+                ark_from_bytes::<_, Ciphertext<JubJub>>(&compressed).unwrap(),
+                ciphertext.clone()
+            );
+            let uncompressed = ark_to_bytes_uncompressed(ciphertext.clone()).unwrap();
+            prop_assert_eq!(
+                ark_from_bytes_uncompressed::<_, Ciphertext<JubJub>>(&uncompressed).unwrap(),
+                ciphertext
+            );
+        }
+
+        #[test]
+        fn prop_key_round_trip(seed in any::<[u8; 32]>()) {
+            let sk = Scalar::from_le_bytes_mod_order(&seed);
+            let pk = JubJub::prime_subgroup_generator().mul(sk.into_repr()).into_affine();
+
+            let sk_bytes = ark_to_bytes(sk).unwrap();
+            prop_assert_eq!(sk_bytes.len(), 32);
+            prop_assert_eq!(ark_from_bytes::<_, Scalar>(&sk_bytes).unwrap(), sk);
+            let pk_bytes = ark_to_bytes(pk).unwrap();
+            prop_assert_eq!(pk_bytes.len(), 32);
+            let decoded: <JubJub as ProjectiveCurve>::Affine = ark_from_bytes(&pk_bytes).unwrap();
+            prop_assert_eq!(decoded, pk);
+        }
+    }
+
+    #[test]
+    fn test_sized_plaintext_round_trip_edge_cases() {
+        let max = (0..=255)
+            .cycle()
+            .take(MAX_PLAINTEXT_LEN)
+            .collect::<Vec<u8>>();
+        for bytes in [vec![], vec![0; MAX_PLAINTEXT_LEN], vec![0xff; 31], max] {
+            for chunk_size in 1..PLAINTEXT_CHUNK_SIZE {
+                assert_eq!(sized_round_trip(&bytes, chunk_size), bytes);
+            }
+        }
+    }
+
     #[test]
     fn test_ciphertext_decode() {
         let mut rng = test_rng();
@@ -159,4 +707,54 @@ mod test {
 
         assert_eq!(ciphertext, decoded)
     }
+
+    #[test]
+    fn test_chunk_for_offset() {
+        let size = PLAINTEXT_CHUNK_SIZE;
+        // distinct bytes, so that landing on a neighbour of the byte looked up is noticed.
+        let data = (1..=3 * size as u8).collect::<Vec<u8>>();
+        let packed = bytes_to_plaintext_chunks::<JubJub, _>(&data).unwrap();
+        let framed = bytes_to_plaintext_chunks_with_width::<JubJub, _>(&data, size).unwrap();
+        let direct = bytes_to_plaintext_chunks_direct::<JubJub, _>(&data, data.len()).unwrap();
+        let layouts = [
+            (packed.as_slice(), size, LENGTH_PREFIX_SIZE),
+            (framed.as_slice(), size, WIDTH_HEADER_SIZE),
+            (direct.as_slice(), 1, 0),
+        ];
+
+        for offset in [
+            0,
+            size - WIDTH_HEADER_SIZE,
+            size - LENGTH_PREFIX_SIZE,
+            size,
+            data.len() - 1,
+        ] {
+            for (chunks, chunk_size, header_len) in layouts {
+                let (chunk, inner) = chunk_for_offset(offset, chunk_size, header_len);
+                let bytes = to_bytes!(chunks[chunk]).unwrap();
+                assert_eq!(
+                    bytes[inner], data[offset],
+                    "offset {offset} after {header_len}"
+                );
+                assert_eq!(
+                    offset_for_chunk(chunk, inner, chunk_size, header_len),
+                    Some(offset)
+                );
+            }
+        }
+
+        assert_eq!(
+            chunk_for_offset(0, size, LENGTH_PREFIX_SIZE),
+            (0, LENGTH_PREFIX_SIZE)
+        );
+        assert_eq!(
+            chunk_for_offset(size - LENGTH_PREFIX_SIZE, size, LENGTH_PREFIX_SIZE),
+            (1, 0)
+        );
+        // the header holds no data.
+        assert_eq!(
+            offset_for_chunk(0, LENGTH_PREFIX_SIZE - 1, size, LENGTH_PREFIX_SIZE),
+            None
+        );
+    }
 }
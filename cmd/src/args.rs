@@ -1,8 +1,28 @@
 use gumdrop::Options;
+use scriptless_zkcp::zk::{Predicate, ProofBackend};
+use scriptless_zkcp::{CatalogItem, ChainId, CurveTag, EventHook, SegmentRange};
+use std::net::IpAddr;
 
 #[derive(Debug, Options, Clone)]
 pub struct CLIArgs {
     help: bool,
+
+    #[options(help = "print errors as JSON objects for scripts to parse")]
+    pub json_errors: bool,
+
+    #[options(
+        no_short,
+        help = "print results of the command as JSON object and logs as JSON lines, progress goes to stderr"
+    )]
+    pub json: bool,
+
+    #[options(
+        no_short,
+        help = "path to TOML profile with options of each command in its own section",
+        meta = "PATH"
+    )]
+    pub config: Option<String>,
+
     #[options(command)]
     pub command: Option<Command>,
 }
@@ -17,21 +37,70 @@ pub enum Command {
     Buy(BuyArgs),
     #[options(help = "Compile circuits")]
     Compile(CompileArgs),
+    #[options(help = "Audit public bundle of the sale")]
+    Audit(AuditArgs),
+    #[options(help = "Print wallet public key and its fingerprint")]
+    Pubkey(PubkeyArgs),
+    #[options(help = "Verify proof of encryption against the ciphertext")]
+    Verify(VerifyArgs),
+    #[options(help = "Decrypt ciphertext of the bought data with its secret key")]
+    Decrypt(DecryptArgs),
+    #[options(help = "Print sale manifest in readable form")]
+    Manifest(ManifestArgs),
+    #[options(help = "Run trusted setup ceremony over compiled circuit keys")]
+    Ceremony(CeremonyArgs),
+    #[options(help = "Refund payment the seller didn't settle, revoking its authorization")]
+    Refund(RefundArgs),
+    #[options(help = "Manage profiles passed with --config")]
+    Config(ConfigArgs),
+    #[options(help = "Check receipts of completed trades in the audit log")]
+    Receipts(ReceiptsArgs),
 }
 
 #[derive(Debug, Options, Clone)]
 pub struct SetupArgs {
     help: bool,
 
-    #[options(help = "path to keystore location", default = "./keys")]
+    #[options(
+        help = "path to keystore location, `:memory:` to hold wallets in memory only",
+        default = "./keys"
+    )]
     pub keystore_dir: String,
+
+    #[options(help = "BIP-39 mnemonic phrase to create or restore wallet from")]
+    pub mnemonic: Option<String>,
+
+    #[options(
+        help = "BIP-32 path of the key imported from mnemonic or read from Ledger",
+        default = "m/44'/60'/0'/0/0"
+    )]
+    pub derivation_path: String,
+
+    #[options(
+        help = "read account of the connected Ledger to receive payments to, keeping its key on the device"
+    )]
+    pub ledger: bool,
+
+    #[options(help = "change password of the existing wallet instead of creating one")]
+    pub rotate_password: bool,
+
+    #[options(help = "wallet name")]
+    pub wallet_name: Option<String>,
+
+    #[options(help = "wallet password, CONTANGLE_WALLET_PASSWORD env var or prompt if not set")]
+    pub password: Option<String>,
+
+    #[options(
+        help = "password --rotate-password sets, CONTANGLE_NEW_WALLET_PASSWORD env var or prompt if not set"
+    )]
+    pub new_password: Option<String>,
 }
 
 #[derive(Debug, Options, Clone)]
 pub struct SellArgs {
     help: bool,
 
-    #[options(help = "path to the data file being sold")]
+    #[options(help = "path to the data file being sold, `-` or none to read from piped stdin")]
     pub data_path: Option<String>,
 
     #[options(
@@ -43,61 +112,347 @@ pub struct SellArgs {
     #[options(help = "price of the data")]
     pub price: Option<String>,
 
+    #[options(help = "time after which sale offer expires (RFC 3339)")]
+    pub expires_at: Option<String>,
+
+    #[options(help = "abort if proving takes longer than this many seconds")]
+    pub max_prove_secs: Option<u64>,
+
+    #[options(help = "pause proving while less than this many MiB of memory are free")]
+    pub min_free_mem: Option<u64>,
+
+    #[options(
+        help = "prove this many Step 1 responses of each sale ahead, for buyers without handshake"
+    )]
+    pub precompute_step1: Option<usize>,
+
+    #[options(help = "turn away new buyers while this many payments are being settled")]
+    pub max_sessions: Option<usize>,
+
+    #[options(
+        no_short,
+        help = "seconds one-time key stays locked for buyer who hasn't paid, a day by default"
+    )]
+    pub lock_ttl_secs: Option<u64>,
+
+    #[options(help = "limit each buyer IP to this many purchase requests per minute")]
+    pub rate_limit: Option<u32>,
+
+    #[options(
+        no_short,
+        help = "reverse proxy whose X-Real-IP header --rate-limit counts buyers by, may be repeated"
+    )]
+    pub trusted_proxy: Vec<IpAddr>,
+
+    #[options(no_short, help = "serve over HTTPS with this PEM certificate chain")]
+    pub tls_cert: Option<String>,
+
+    #[options(no_short, help = "PEM private key of the --tls-cert certificate")]
+    pub tls_key: Option<String>,
+
     #[options(help = "chain RPC address", default = "http://localhost:8545")]
     pub rpc_address: String,
 
-    #[options(help = "chain id", default = "http://localhost:8545")]
-    pub chain_id: String,
+    #[options(help = "chain id, decimal or 0x-prefixed hex", default = "31337")]
+    pub chain_id: ChainId,
+
+    #[options(help = "address of the ERC-20 token prices are paid in, instead of ether")]
+    pub token: Option<String>,
+
+    #[options(help = "decimals of the --token amounts", default = "18")]
+    pub decimals: u8,
 
-    #[options(help = "path to keystore location", default = "./keys")]
+    #[options(help = "address payments are sent to, wallet's own by default")]
+    pub payout_address: Option<String>,
+
+    #[options(
+        help = "path to keystore location, `:memory:` to hold wallets in memory only",
+        default = "./keys"
+    )]
     pub keystore_dir: String,
 
     #[options(help = "wallet name")]
     pub wallet_name: Option<String>,
 
-    #[options(help = "wallet password")]
+    #[options(help = "wallet password, CONTANGLE_WALLET_PASSWORD env var or prompt if not set")]
     pub password: Option<String>,
 
+    #[options(help = "abort unless wallet key has this fingerprint")]
+    pub expected_key_fingerprint: Option<String>,
+
     #[options(
         help = "path for the key used to prove encryption",
         default = "./circuit.pk"
     )]
     pub encryption_proving_key_path: String,
+
+    #[options(help = "compress hosted ciphertext and proofs with zstd")]
+    pub compress_bundle: bool,
+
+    #[options(help = "strip UTF-8 BOM from the data file before encryption")]
+    pub strip_bom: bool,
+
+    #[options(help = "path to export public audit bundle of the sale to")]
+    pub audit_bundle: Option<String>,
+
+    #[options(help = "path to .sale file to host instead of encrypting data")]
+    pub sale_path: Option<String>,
+
+    #[options(help = "path to export sale as .sale file to")]
+    pub export_sale: Option<String>,
+
+    #[options(help = "path to export proofs of property to as a single proof set file")]
+    pub export_proofs: Option<String>,
+
+    #[options(help = "path to write JSON summary of timings and resource stats to")]
+    pub summary_json: Option<String>,
+
+    #[options(help = "encrypt and prove, then exit without serving the sale")]
+    pub prove_only: bool,
+
+    #[options(help = "stream sale container to stdout, requires --prove-only")]
+    pub stdout: bool,
+
+    #[options(help = "serve restored sales without keystore access, refusing purchases")]
+    pub observer: bool,
+
+    // gumdrop can't hide options from the usage, so it warns instead.
+    #[options(
+        no_short,
+        help = "seed encryption randomness for reproducible tests, exposes the decryption key"
+    )]
+    pub deterministic_seed: Option<u64>,
+
+    #[options(
+        help = "serve another sale as <id>=<cache dir of its earlier run>[=<price>], may be repeated",
+        meta = "ID=DIR"
+    )]
+    pub listing: Vec<CatalogItem>,
+
+    #[options(
+        no_short,
+        help = "stream the sale: split data into this many segments, each listed as segment-<n> and paid for apart"
+    )]
+    pub segments: Option<usize>,
+
+    #[options(
+        help = "path to JSON list of further sales to serve, each with id, cache_dir and optional price"
+    )]
+    pub catalog: Option<String>,
+
+    #[options(
+        help = "webhook URL to POST, or shell command to run, once buyer's payment lands on-chain, may be repeated",
+        meta = "URL|CMD"
+    )]
+    pub on_funded: Vec<EventHook>,
+
+    #[options(
+        help = "webhook URL to POST, or shell command to run, once payment is final, may be repeated",
+        meta = "URL|CMD"
+    )]
+    pub on_settled: Vec<EventHook>,
+
+    #[options(help = "confirmations payment needs to be final", default = "3")]
+    pub confirmations: u64,
+
+    #[options(
+        help = "WebSocket address of the Ethereum node to follow new blocks with, polled over RPC otherwise"
+    )]
+    pub ws_address: Option<String>,
 }
 
 #[derive(Debug, Options, Clone)]
 pub struct BuyArgs {
     help: bool,
 
-    #[options(help = "seller server address")]
+    #[options(help = "seller server address, or comma-separated list of addresses tried in order")]
     pub seller_address: String,
 
+    #[options(help = "give up each attempt to reach the seller after this many seconds")]
+    pub connect_timeout: Option<u64>,
+
+    #[options(
+        help = "attempts to reach the seller after the first one fails, with exponential backoff",
+        default = "0"
+    )]
+    pub connect_retries: u32,
+
     #[options(help = "path where bought data will be placed")]
     pub data_path: Option<String>,
 
+    #[options(help = "octal permissions of the created data file, e.g. 600 (Unix only)")]
+    pub output_mode: Option<String>,
+
+    #[options(help = "hex SHA-256 the decrypted file must match before it is saved")]
+    pub verify_hash: Option<String>,
+
+    #[options(
+        help = "path to the directory where cache is stored",
+        default = "./cache"
+    )]
+    pub cache_dir: String,
+
     #[options(help = "chain RPC address", default = "http://localhost:8545")]
     pub rpc_address: String,
 
-    #[options(help = "chain id", default = "31337")]
-    pub chain_id: String,
+    #[options(help = "chain id, decimal or 0x-prefixed hex", default = "31337")]
+    pub chain_id: ChainId,
+
+    #[options(help = "address of the ERC-20 token prices are paid in, instead of ether")]
+    pub token: Option<String>,
 
-    #[options(help = "path to keystore location", default = "./keys")]
+    #[options(help = "decimals of the --token amounts", default = "18")]
+    pub decimals: u8,
+
+    #[options(
+        help = "path to keystore location, `:memory:` to hold wallets in memory only",
+        default = "./keys"
+    )]
     pub keystore_dir: String,
 
     #[options(help = "wallet name")]
     pub wallet_name: Option<String>,
 
-    #[options(help = "wallet password")]
+    #[options(help = "wallet password, CONTANGLE_WALLET_PASSWORD env var or prompt if not set")]
     pub password: Option<String>,
 
+    #[options(help = "abort unless wallet key has this fingerprint")]
+    pub expected_key_fingerprint: Option<String>,
+
     #[options(
         help = "path for the key used to verify proof of encryption",
         default = "./circuit.vk"
     )]
     pub encryption_verifying_key_path: String,
 
+    #[options(help = "address of the registry contract the verifying key hash is anchored in")]
+    pub vk_registry: Option<String>,
+
+    #[options(
+        help = "abort unless the sale proves plaintext hashes to this hex-encoded commitment, needs circuits compiled with `--predicate digest`"
+    )]
+    pub expect_commitment: Option<String>,
+
+    #[options(
+        help = "abort unless sale announcement and Step 1 message are signed by the wallet with this address"
+    )]
+    pub expected_seller: Option<String>,
+
+    #[options(help = "path to seller's reputation attestation to show before paying")]
+    pub attestation_path: Option<String>,
+
+    #[options(help = "hex public key of the party trusted to sign reputation attestations")]
+    pub attester_key: Option<String>,
+
+    #[options(help = "skip confirms", default = "false")]
+    pub non_interactive: bool,
+
+    #[options(help = "report why verification of the seller's proofs failed")]
+    pub explain: bool,
+
+    #[options(help = "path to .sale file to use instead of downloading ciphertext")]
+    pub sale_path: Option<String>,
+
+    #[options(help = "path to proof set file to verify instead of proofs sent by seller")]
+    pub proofs_path: Option<String>,
+
+    #[options(help = "restore UTF-8 BOM stripped by seller, requires sale file")]
+    pub restore_bom: bool,
+
+    #[options(help = "have seller encrypt the key to buyer's fresh public key during handshake")]
+    pub handshake: bool,
+
+    #[options(
+        help = "pay the price seller signs a time-limited quote of, rather than the announced one"
+    )]
+    pub quote: bool,
+
+    #[options(help = "id of the sale to buy from seller serving several")]
+    pub sale_id: Option<String>,
+
+    #[options(
+        no_short,
+        help = "buy segments of streamed sale, e.g. 1..5, skipping those paid for earlier",
+        meta = "FIRST..LAST"
+    )]
+    pub segments: Option<SegmentRange>,
+
+    #[options(help = "print sales the seller serves besides the default one and exit")]
+    pub list: bool,
+
+    #[options(
+        help = "print details of the sale with this id and exit, without paying",
+        meta = "ID"
+    )]
+    pub inspect: Option<String>,
+
+    #[options(
+        help = "resume interrupted purchase saved under this session id",
+        meta = "ID"
+    )]
+    pub resume: Option<String>,
+
+    #[options(help = "path to write JSON summary of timings and resource stats to")]
+    pub summary_json: Option<String>,
+
+    #[options(help = "threads to verify proofs on, as many as there are CPUs by default")]
+    pub verify_threads: Option<usize>,
+
+    #[options(help = "refund payment if seller doesn't settle it within this many blocks")]
+    pub timeout_blocks: Option<u64>,
+
+    #[options(help = "cap on the EIP-1559 max fee of refund transactions, in gwei")]
+    pub max_fee_gwei: Option<f64>,
+
+    #[options(help = "re-send stuck refund with bumped fees after this many blocks")]
+    pub bump_after_blocks: Option<u64>,
+}
+
+#[derive(Debug, Options, Clone)]
+pub struct RefundArgs {
+    help: bool,
+
+    #[options(
+        help = "path to the directory where cache is stored",
+        default = "./cache"
+    )]
+    pub cache_dir: String,
+
+    #[options(help = "session id of the purchase to refund", meta = "ID")]
+    pub session: Option<String>,
+
+    #[options(help = "chain RPC address", default = "http://localhost:8545")]
+    pub rpc_address: String,
+
+    #[options(help = "chain id, decimal or 0x-prefixed hex", default = "31337")]
+    pub chain_id: ChainId,
+
+    #[options(
+        help = "path to keystore location, `:memory:` to hold wallets in memory only",
+        default = "./keys"
+    )]
+    pub keystore_dir: String,
+
+    #[options(help = "wallet name")]
+    pub wallet_name: Option<String>,
+
+    #[options(help = "wallet password, CONTANGLE_WALLET_PASSWORD env var or prompt if not set")]
+    pub password: Option<String>,
+
     #[options(help = "skip confirms", default = "false")]
     pub non_interactive: bool,
+
+    #[options(
+        help = "seconds since payment was locked the seller is given to settle it before refund",
+        default = "600"
+    )]
+    pub grace_secs: u64,
+
+    #[options(help = "cap on the EIP-1559 max fee of refund transactions, in gwei")]
+    pub max_fee_gwei: Option<f64>,
+
+    #[options(help = "re-send stuck refund with bumped fees after this many blocks")]
+    pub bump_after_blocks: Option<u64>,
 }
 
 #[derive(Debug, Options, Clone)]
@@ -117,7 +472,7 @@ pub struct CompileArgs {
     pub build_dir: String,
 
     #[options(
-        help = "limit of arbitrary size possible to encrypt with ZK circuit (n * 32b)",
+        help = "limit of arbitrary size possible to encrypt with ZK circuit (n * 31b)",
         default = "100"
     )]
     pub limit_data_enc_dir: usize,
@@ -135,4 +490,208 @@ pub struct CompileArgs {
         default = "plaintext"
     )]
     pub plaintext_field_name: String,
+
+    #[options(help = "replace circuit keys existing in build directory")]
+    pub overwrite_keys: bool,
+
+    #[options(
+        help = "directory to write Solidity verifiers of the circuits to, needs BN254 curve",
+        meta = "PATH"
+    )]
+    pub export_solidity: Option<String>,
+
+    #[options(
+        help = "proving backend to compile circuits for, only groth16 is supported",
+        default = "groth16"
+    )]
+    pub backend: ProofBackend,
+
+    #[options(
+        help = "curve to compile circuits over, bls12_381 or bn254 (needs build with `bn254` feature)"
+    )]
+    pub curve: Option<CurveTag>,
+
+    #[options(
+        help = "statement data encryption circuit also proves over the plaintext: digest (Poseidon commitment)",
+        meta = "PREDICATE"
+    )]
+    pub predicate: Option<Predicate>,
+}
+
+#[derive(Debug, Options, Clone)]
+pub struct AuditArgs {
+    help: bool,
+
+    #[options(help = "path to the audit bundle", default = "./audit.json")]
+    pub bundle_path: String,
+}
+
+#[derive(Debug, Options, Clone)]
+pub struct VerifyArgs {
+    help: bool,
+
+    #[options(help = "path to the verifying key of the encryption circuit")]
+    pub verifying_key: String,
+
+    #[options(help = "path to the encoded ciphertext")]
+    pub ciphertext: String,
+
+    #[options(help = "hex-encoded ElGamal public key the ciphertext is encrypted to")]
+    pub pubkey: String,
+
+    #[options(help = "path to the proof of encryption")]
+    pub proof: String,
+
+    #[options(
+        help = "number of plaintext chunks the encryption circuit is compiled for",
+        default = "1"
+    )]
+    pub chunks: usize,
+
+    #[options(help = "threads to verify proof on, as many as there are CPUs by default")]
+    pub verify_threads: Option<usize>,
+
+    #[options(help = "report why verification of the proof failed")]
+    pub explain: bool,
+
+    #[options(
+        help = "hex-encoded hash of the verifying key seller announced, to detect swapped keys",
+        meta = "HASH"
+    )]
+    pub vk_hash: Option<String>,
+}
+
+#[derive(Debug, Options, Clone)]
+pub struct DecryptArgs {
+    help: bool,
+
+    #[options(help = "path to the encoded ciphertext")]
+    pub ciphertext: String,
+
+    #[options(help = "hex-encoded ElGamal secret key the ciphertext decrypts with")]
+    pub sk: String,
+
+    #[options(help = "path where decrypted data will be placed")]
+    pub out: String,
+
+    #[options(help = "octal permissions of the created data file, e.g. 600 (Unix only)")]
+    pub output_mode: Option<String>,
+}
+
+#[derive(Debug, Options, Clone)]
+pub struct PubkeyArgs {
+    help: bool,
+
+    #[options(
+        help = "path to keystore location, `:memory:` to hold wallets in memory only",
+        default = "./keys"
+    )]
+    pub keystore_dir: String,
+
+    #[options(help = "wallet name")]
+    pub wallet_name: Option<String>,
+
+    #[options(help = "wallet password, CONTANGLE_WALLET_PASSWORD env var or prompt if not set")]
+    pub password: Option<String>,
+}
+
+#[derive(Debug, Options, Clone)]
+pub struct ManifestArgs {
+    help: bool,
+
+    #[options(help = "path to the .sale file or its standalone manifest")]
+    pub sale_path: String,
+
+    #[options(help = "path to the verifying key of the encryption circuit, to print its hash")]
+    pub verifying_key: Option<String>,
+
+    #[options(help = "print manifest as JSON, as global --json does")]
+    pub json: bool,
+}
+
+#[derive(Debug, Options, Clone)]
+pub struct CeremonyArgs {
+    help: bool,
+
+    #[options(command)]
+    pub command: Option<CeremonyCommand>,
+}
+
+#[derive(Debug, Options, Clone)]
+pub enum CeremonyCommand {
+    #[options(help = "Re-randomize circuit keys with a secret contribution")]
+    Contribute(CeremonyDirArgs),
+    #[options(help = "Check every contribution in the ceremony transcript")]
+    VerifyTranscript(CeremonyDirArgs),
+    #[options(help = "Close ceremony with a public beacon contribution")]
+    Finalize(FinalizeArgs),
+}
+
+#[derive(Debug, Options, Clone)]
+pub struct CeremonyDirArgs {
+    help: bool,
+
+    #[options(help = "directory with keys of the circuit, e.g. ./build/data_encryption")]
+    pub circuit_dir: String,
+}
+
+#[derive(Debug, Options, Clone)]
+pub struct FinalizeArgs {
+    help: bool,
+
+    #[options(help = "directory with keys of the circuit, e.g. ./build/data_encryption")]
+    pub circuit_dir: String,
+
+    #[options(help = "public randomness nobody could predict, e.g. hash of a future block")]
+    pub beacon: String,
+}
+
+#[derive(Debug, Options, Clone)]
+pub struct ConfigArgs {
+    help: bool,
+
+    #[options(command)]
+    pub command: Option<ConfigCommand>,
+}
+
+#[derive(Debug, Options, Clone)]
+pub enum ConfigCommand {
+    #[options(help = "Write commented profile template to fill in")]
+    Init(ConfigInitArgs),
+}
+
+#[derive(Debug, Options, Clone)]
+pub struct ConfigInitArgs {
+    help: bool,
+
+    #[options(help = "path the template is written to", default = "./contangle.toml")]
+    pub path: String,
+
+    #[options(help = "replace file existing at the path")]
+    pub force: bool,
+}
+
+#[derive(Debug, Options, Clone)]
+pub struct ReceiptsArgs {
+    help: bool,
+
+    #[options(command)]
+    pub command: Option<ReceiptsCommand>,
+}
+
+#[derive(Debug, Options, Clone)]
+pub enum ReceiptsCommand {
+    #[options(help = "Verify hash chain and signatures of the audit log offline")]
+    Verify(ReceiptsVerifyArgs),
+}
+
+#[derive(Debug, Options, Clone)]
+pub struct ReceiptsVerifyArgs {
+    help: bool,
+
+    #[options(help = "path to the audit log", default = "./cache/receipts.jsonl")]
+    pub log: String,
+
+    #[options(help = "path to .sale file to check receipts of its sale against")]
+    pub sale_path: Option<String>,
 }
@@ -0,0 +1,239 @@
+use anyhow::anyhow;
+use std::fs;
+use toml::value::Table;
+use toml::Value;
+
+/// Commented profile written by `config init`, with options of the commands run most often.
+pub const CONFIG_TEMPLATE: &str = r#"# Contangle profile, used with `cmd --config <path> <command>`.
+#
+# Each section holds options of the command it's named after, with dashes of the flag
+# names written as underscores, e.g. `rpc_address` for `--rpc-address`. Subcommands have
+# sections of their own, e.g. `[ceremony.contribute]`.
+#
+# Options given on the command line override the ones set here, except that flags set
+# to true can't be turned off and repeated options, such as `listing`, add up.
+# Keep wallet passwords out of this file, use CONTANGLE_WALLET_PASSWORD instead.
+
+[setup]
+# keystore_dir = "./keys"
+# wallet_name = "seller"
+
+[sell]
+# data_path = "./data.txt"
+# cache_dir = "./cache"
+# price = "0.1"
+# rpc_address = "http://localhost:8545"
+# chain_id = "31337"
+# token = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"
+# decimals = 6
+# keystore_dir = "./keys"
+# wallet_name = "seller"
+# expected_key_fingerprint = "0123abcd"
+# encryption_proving_key_path = "./circuit.pk"
+# max_prove_secs = 600
+# compress_bundle = true
+# listing = ["photos=./cache/photos=2.5"]
+# on_settled = ["https://shop.example/hooks/zkcp", "./fulfill.sh"]
+# confirmations = 3
+# precompute_step1 = 4
+# max_sessions = 16
+# lock_ttl_secs = 86400
+# rate_limit = 30
+# trusted_proxy = ["127.0.0.1"]
+# tls_cert = "./tls/cert.pem"
+# tls_key = "./tls/key.pem"
+# segments = 10
+
+[buy]
+# seller_address = "http://localhost:8000"
+# connect_retries = 3
+# cache_dir = "./cache"
+# rpc_address = "http://localhost:8545"
+# chain_id = "31337"
+# token = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"
+# decimals = 6
+# keystore_dir = "./keys"
+# wallet_name = "buyer"
+# encryption_verifying_key_path = "./circuit.vk"
+# timeout_blocks = 100
+# max_fee_gwei = 50
+
+[refund]
+# rpc_address = "http://localhost:8545"
+# chain_id = "31337"
+# keystore_dir = "./keys"
+# wallet_name = "buyer"
+
+[ceremony.contribute]
+# circuit_dir = "./build/data_encryption"
+"#;
+
+/// Path given with `--config` among the options preceding the command, if any.
+pub fn config_path(args: &[String]) -> Option<String> {
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return args.next().cloned();
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(path.to_string());
+        }
+        if !arg.starts_with('-') {
+            break;
+        }
+    }
+
+    None
+}
+
+/// Inserts options of the matching profile sections right after each command in `args`,
+/// so that options given on the command line come after and override them.
+pub fn apply_profile(args: Vec<String>) -> anyhow::Result<Vec<String>> {
+    let path = match config_path(&args) {
+        Some(path) => path,
+        None => return Ok(args),
+    };
+    let profile =
+        fs::read_to_string(&path).map_err(|e| anyhow!("error reading config {path}: {e}"))?;
+    let profile: Table =
+        toml::from_str(&profile).map_err(|e| anyhow!("error parsing config {path}: {e}"))?;
+
+    let mut args = args;
+    let mut at = 1;
+    while at < args.len() {
+        match args[at].as_str() {
+            "--config" => at += 2,
+            arg if arg.starts_with('-') => at += 1,
+            _ => break,
+        }
+    }
+    insert_section(&mut args, at, &profile)?;
+
+    Ok(args)
+}
+
+fn insert_section(args: &mut Vec<String>, at: usize, sections: &Table) -> anyhow::Result<()> {
+    let section = match args.get(at).and_then(|command| sections.get(command)) {
+        Some(Value::Table(section)) => section,
+        Some(_) => return Err(anyhow!("config section {} must be a table", args[at])),
+        None => return Ok(()),
+    };
+
+    // subcommand directly follows its parent, so its options go in first to keep its index.
+    insert_section(args, at + 1, section)?;
+    let options = section_args(section)?;
+    args.splice(at + 1..at + 1, options);
+
+    Ok(())
+}
+
+/// Options of the profile `section` as they'd be given on the command line.
+pub fn section_args(section: &Table) -> anyhow::Result<Vec<String>> {
+    let mut args = vec![];
+    for (key, value) in section {
+        let flag = format!("--{}", key.replace('_', "-"));
+        match value {
+            Value::Table(_) | Value::Boolean(false) => {}
+            Value::Boolean(true) => args.push(flag),
+            Value::Array(items) => {
+                for item in items {
+                    args.push(flag.clone());
+                    args.push(scalar(key, item)?);
+                }
+            }
+            value => {
+                args.push(flag);
+                args.push(scalar(key, value)?);
+            }
+        }
+    }
+
+    Ok(args)
+}
+
+fn scalar(key: &str, value: &Value) -> anyhow::Result<String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Integer(i) => Ok(i.to_string()),
+        Value::Float(f) => Ok(f.to_string()),
+        _ => Err(anyhow!(
+            "config value of {key} must be a string, number, boolean or list of these"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::args::{CLIArgs, Command};
+    use crate::config::{apply_profile, config_path, CONFIG_TEMPLATE};
+    use gumdrop::Options;
+    use std::fs;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_template_options_exist() {
+        let uncommented = CONFIG_TEMPLATE
+            .lines()
+            .map(|line| {
+                line.strip_prefix("# ")
+                    .filter(|l| l.contains(" = "))
+                    .unwrap_or(line)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let path = std::env::temp_dir().join("zkcp_test_config_template.toml");
+        fs::write(&path, uncommented).unwrap();
+        let path = path.to_str().unwrap();
+
+        for command in [
+            vec!["setup"],
+            vec!["sell"],
+            vec!["buy"],
+            vec!["refund"],
+            vec!["ceremony", "contribute"],
+        ] {
+            let argv =
+                apply_profile(args(&[&["cmd", "--config", path], &command[..]].concat())).unwrap();
+            assert!(argv.len() > command.len() + 3, "no options for {command:?}");
+            CLIArgs::parse_args_default(&argv[1..])
+                .unwrap_or_else(|e| panic!("template of {command:?} doesn't parse: {e}"));
+        }
+    }
+
+    #[test]
+    fn test_cli_overrides_profile() {
+        let path = std::env::temp_dir().join("zkcp_test_config_override.toml");
+        fs::write(
+            &path,
+            "[sell]\nrpc_address = \"http://file:8545\"\nprice = \"1\"\ncompress_bundle = true\n",
+        )
+        .unwrap();
+        let path = path.to_str().unwrap();
+
+        let argv = args(&[
+            "cmd",
+            "--json-errors",
+            "--config",
+            path,
+            "sell",
+            "--price",
+            "2",
+        ]);
+        assert_eq!(config_path(&argv).as_deref(), Some(path));
+        let argv = apply_profile(argv).unwrap();
+        let parsed = CLIArgs::parse_args_default(&argv[1..]).unwrap();
+
+        match parsed.command {
+            Some(Command::Sell(sell)) => {
+                assert_eq!(sell.rpc_address, "http://file:8545");
+                assert_eq!(sell.price.as_deref(), Some("2"));
+                assert!(sell.compress_bundle);
+            }
+            _ => panic!("expected sell command"),
+        }
+        assert_eq!(config_path(&args(&["cmd", "sell", "--config", path])), None);
+    }
+}
@@ -0,0 +1,114 @@
+use scriptless_zkcp::zk::{ProveTimeExceeded, PublicInputCountMismatch};
+use scriptless_zkcp::{SettlementTimedOut, WrongArtifactType};
+use serde::Serialize;
+
+/// Process exit codes, kept stable so that scripts wrapping the CLI may rely on them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExitCode {
+    Failure = 1,
+    Usage = 2,
+    WrongArtifactType = 3,
+    ProveTimeExceeded = 4,
+    PublicInputCountMismatch = 5,
+    SettlementTimedOut = 6,
+}
+
+/// Machine-readable error printed to stderr in `--json-errors` mode.
+#[derive(Clone, Debug, Serialize)]
+pub struct JsonError {
+    pub code: i32,
+    pub kind: &'static str,
+    pub message: String,
+    /// Underlying causes, outermost first.
+    pub context: Vec<String>,
+}
+
+impl JsonError {
+    pub fn new(err: &anyhow::Error) -> Self {
+        let (code, kind) = classify(err);
+        Self {
+            code: code as i32,
+            kind,
+            message: err.to_string(),
+            context: err.chain().skip(1).map(|e| e.to_string()).collect(),
+        }
+    }
+
+    pub fn usage<M: Into<String>>(message: M) -> Self {
+        Self {
+            code: ExitCode::Usage as i32,
+            kind: "usage",
+            message: message.into(),
+            context: vec![],
+        }
+    }
+}
+
+/// Maps typed error found anywhere in the chain of `err` to its exit code and kind.
+pub fn classify(err: &anyhow::Error) -> (ExitCode, &'static str) {
+    for cause in err.chain() {
+        if cause.is::<WrongArtifactType>() {
+            return (ExitCode::WrongArtifactType, "wrong_artifact_type");
+        }
+        if cause.is::<ProveTimeExceeded>() {
+            return (ExitCode::ProveTimeExceeded, "prove_time_exceeded");
+        }
+        if cause.is::<PublicInputCountMismatch>() {
+            return (
+                ExitCode::PublicInputCountMismatch,
+                "public_input_count_mismatch",
+            );
+        }
+        if cause.is::<SettlementTimedOut>() {
+            return (ExitCode::SettlementTimedOut, "settlement_timed_out");
+        }
+    }
+
+    (ExitCode::Failure, "failure")
+}
+
+/// Prints `err` to stderr, as JSON object if `json` is set, and exits with the matching code.
+pub fn exit_with_error(err: anyhow::Error, json: bool) -> ! {
+    let report = JsonError::new(&err);
+    match json {
+        true => eprintln!(
+            "{}",
+            serde_json::to_string(&report).expect("expected error to marshal to json")
+        ),
+        false => eprintln!("Error: {err:?}"),
+    }
+
+    std::process::exit(report.code)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::errors::{ExitCode, JsonError};
+    use anyhow::Context;
+    use scriptless_zkcp::{read_verifying_key, PairingEngine};
+    use std::fs;
+
+    #[test]
+    fn test_json_error_structure() {
+        let path = std::env::temp_dir().join("zkcp_test_json_errors");
+        fs::write(&path, b"ZKPK").unwrap();
+
+        let err = read_verifying_key::<_, PairingEngine>(&path)
+            .context("error loading verifier")
+            .unwrap_err();
+        let json = serde_json::to_value(JsonError::new(&err)).unwrap();
+
+        assert_eq!(json["code"], ExitCode::WrongArtifactType as i32);
+        assert_eq!(json["kind"], "wrong_artifact_type");
+        assert_eq!(json["message"], "error loading verifier");
+        assert_eq!(
+            json["context"],
+            serde_json::json!(["expected verifying key, found proving key"])
+        );
+
+        let err = anyhow::anyhow!("error connecting to seller");
+        let json = serde_json::to_value(JsonError::new(&err)).unwrap();
+        assert_eq!(json["code"], ExitCode::Failure as i32);
+        assert_eq!(json["context"], serde_json::json!([]));
+    }
+}
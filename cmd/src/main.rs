@@ -1,96 +1,488 @@
 #![feature(async_closure)]
 
 mod args;
-use crate::args::{BuyArgs, CLIArgs, Command, CompileArgs, SellArgs, SetupArgs};
-use anyhow::anyhow;
+mod config;
+mod errors;
+mod output;
+use crate::args::{
+    AuditArgs, BuyArgs, CLIArgs, CeremonyArgs, CeremonyCommand, Command, CompileArgs, ConfigArgs,
+    ConfigCommand, DecryptArgs, ManifestArgs, PubkeyArgs, ReceiptsArgs, ReceiptsCommand,
+    RefundArgs, SellArgs, SetupArgs, VerifyArgs,
+};
+use crate::config::{apply_profile, CONFIG_TEMPLATE};
+use crate::errors::{exit_with_error, ExitCode, JsonError};
+use crate::output::{
+    init_logging, AuditResult, BuyResult, CeremonyResult, ConfigResult, InspectResult, Output,
+    ReceiptsResult, SellResult,
+};
+use anyhow::{anyhow, Context};
 use chrono;
 use circuits::encryption;
 use futures_util::TryFutureExt;
 use gumdrop::Options;
 use inquire::{Confirm, Password, Select, Text};
 use rocket::http::hyper::body::HttpBody;
-use scriptless_zkcp::zk::{CircomParams, ZkEncryption, ZkSampleEntries, ZkVerifiableEncryption};
+use scriptless_zkcp::zk::{
+    public_key_from_hex, read_ceremony, verification_pool, write_ceremony, CeremonyTranscript,
+    CircomParams, VerifiableEncryption, ZkEncryption, ZkSampleEntries, ZkVerifiableEncryption,
+    DATA_CHUNK_SIZE,
+};
 use scriptless_zkcp::{
-    cipher_host, keypair_from_bip39, keypair_from_hex, keypair_gen, write_to_keystore, BuyerConfig,
-    CipherDownloader, CipherHost, Ethereum, LocalWallet, Seller, SellerConfig, Step1Msg, ZkConfig,
+    attester_from_hex, check_expiry, cipher_host, decimal_units, describe_sale,
+    ensure_artifacts_writable, ensure_build_curve, ensure_content_hash, ensure_evm_pairing,
+    ensure_readable_file, ensure_unique_items, find_session, key_fingerprint,
+    keypair_from_bip39_path, keypair_from_hex, keypair_gen, open_keystore, parse_file_mode,
+    permissive_key_files, read_catalog, read_proofs, read_sale, read_session, read_verifying_key,
+    refund_unsettled, remove_session, rotate_keystore_password, sale_wire_size, segment_id,
+    split_segments, strip_bom, verify_receipts, verifying_key_hash, write_proofs, write_sale_to,
+    write_sale_with_options, write_session, write_solidity_verifier, write_with_mode, AuditBundle,
+    BlockSource, BuyerConfig, BuyerSession, ChunkOffsets, CipherDownloader, CipherHost,
+    ConfirmationSource, DataFingerprint, Erc20Token, Ethereum, EventHooks, EventWatcher, FeePolicy,
+    LocalWallet, PairingEngine, ProjectiveCurve, Receipt, ReceiptEntry, ReputationAttestation,
+    RunSummary, SaleOptions, Seller, SellerConfig, SessionPhase, Step1Msg, StreamProgress,
+    VerifyingKeyJson, ZkConfig, DATA_FINGERPRINT_FILE, LOCK_TTL, SETTLEMENT_POLL_INTERVAL,
+    VERIFYING_KEY_FILE, VERIFYING_KEY_JSON_FILE,
 };
 use scriptless_zkcp::{Buyer, ChainProvider};
+use serde_json::json;
 use server::client;
+use std::collections::HashMap;
 use std::fs;
+use std::io;
+use std::io::{IsTerminal, Read};
 use std::path::Path;
 use std::path::PathBuf;
 use std::process;
+use std::time::{Duration, Instant};
 use tokio::spawn;
 use url::Url;
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    // pretty_env_logger::init();
-
-    let args: CLIArgs = CLIArgs::parse_args_default_or_exit();
+async fn main() {
+    let argv = apply_profile(std::env::args().collect())
+        .unwrap_or_else(|e| exit_with_error(e, std::env::args().any(|arg| arg == "--json-errors")));
+    let args = parse_args_or_exit(&argv);
     let command = args.command.unwrap_or_else(|| {
-        eprintln!("[command] is required");
-        eprintln!("{}", CLIArgs::usage());
-        process::exit(2)
+        match args.json_errors {
+            true => eprintln!(
+                "{}",
+                serde_json::to_string(&JsonError::usage("[command] is required"))
+                    .expect("expected error to marshal to json")
+            ),
+            false => {
+                eprintln!("[command] is required");
+                eprintln!("{}", CLIArgs::usage());
+            }
+        }
+        process::exit(ExitCode::Usage as i32)
     });
+    init_logging(args.json);
+    let output = Output::new(args.json);
 
-    match command {
-        Command::Setup(args) => setup(args).await?,
-        Command::Sell(args) => sell(args).await?,
-        Command::Buy(args) => buy(args).await?,
-        Command::Compile(args) => compile(args).await?,
+    let res = match command {
+        Command::Setup(args) => setup(args, output).await,
+        Command::Sell(args) => sell(args, output).await,
+        Command::Buy(args) if args.segments.is_some() => buy_segments(args, output).await,
+        Command::Buy(args) => buy(args, output).await.map(|_| ()),
+        Command::Compile(args) => compile(args, output).await,
+        Command::Audit(args) => audit(args, output).await,
+        Command::Pubkey(args) => pubkey(args, output).await,
+        Command::Verify(args) => verify(args, output).await,
+        Command::Decrypt(args) => decrypt(args, output).await,
+        Command::Manifest(args) => manifest(args, output).await,
+        Command::Ceremony(args) => ceremony(args, output).await,
+        Command::Refund(args) => refund(args, output).await,
+        Command::Config(args) => config(args, output).await,
+        Command::Receipts(args) => receipts(args, output).await,
+    };
+
+    if let Err(e) = res {
+        exit_with_error(e, args.json_errors)
     }
+}
 
-    Ok(())
+/// Parses `argv` with options of the profile already in, as `parse_args_default_or_exit` does
+/// with the arguments of the process.
+fn parse_args_or_exit(argv: &[String]) -> CLIArgs {
+    let args = CLIArgs::parse_args_default(&argv[1..]).unwrap_or_else(|e| {
+        eprintln!("{}: {e}", argv[0]);
+        process::exit(ExitCode::Usage as i32)
+    });
+
+    if args.help_requested() {
+        let mut command = &args as &dyn Options;
+        let mut command_str = String::new();
+        while let Some(subcommand) = command.command() {
+            command = subcommand;
+            if let Some(name) = subcommand.command_name() {
+                command_str.push(' ');
+                command_str.push_str(name);
+            }
+        }
+
+        eprintln!("Usage: {}{command_str} [OPTIONS]", argv[0]);
+        eprintln!();
+        eprintln!("{}", command.self_usage());
+        if let Some(commands) = command.self_command_list() {
+            eprintln!();
+            eprintln!("Available commands:");
+            eprintln!("{commands}");
+        }
+        process::exit(0)
+    }
+
+    args
 }
 
-async fn setup(args: SetupArgs) -> anyhow::Result<()> {
-    let options = vec![
-        "Generate new",
-        "Recover from hex",
-        "Recover from BIP39 mnemonic",
-    ];
-    let picked = Select::new("Wallet source?", options.clone())
+/// Environment variable wallet password is taken from when `--password` isn't given.
+const PASSWORD_ENV: &str = "CONTANGLE_WALLET_PASSWORD";
+
+/// Environment variable new wallet password is taken from when `--new-password` isn't given.
+const NEW_PASSWORD_ENV: &str = "CONTANGLE_NEW_WALLET_PASSWORD";
+
+/// Resolves wallet password from `--password`, then [`PASSWORD_ENV`], then a no-echo prompt
+/// if stdin is a terminal, failing rather than waiting for input that can't come.
+fn resolve_password(flag: Option<String>, non_interactive: bool) -> anyhow::Result<String> {
+    resolve_secret(
+        flag,
+        "--password",
+        PASSWORD_ENV,
+        "Password:",
+        non_interactive,
+    )
+}
+
+/// Resolves secret from `flag` named `flag_name`, then `env` var, then a no-echo prompt
+/// showing `message`, as [`resolve_password`] does.
+fn resolve_secret(
+    flag: Option<String>,
+    flag_name: &str,
+    env: &str,
+    message: &str,
+    non_interactive: bool,
+) -> anyhow::Result<String> {
+    if let Some(secret) = flag.or_else(|| std::env::var(env).ok()) {
+        return Ok(secret);
+    }
+    if non_interactive || !io::stdin().is_terminal() {
+        return Err(anyhow!(
+            "wallet password is required: pass {flag_name} or set {env}"
+        ));
+    }
+    Password::new(message)
         .prompt()
-        .unwrap();
-    let sk = match options
-        .iter()
-        .position(|e| *e == picked)
-        .expect("unexpected option")
-    {
-        0 => keypair_gen().0,
-        1 => keypair_from_hex(&Text::new("Paste hex here:").prompt().unwrap())?.0,
-        2 => keypair_from_bip39(&Text::new("Mnemonic phrase:").prompt().unwrap())?.0,
-        _ => panic!("unexpected option"),
+        .context("error reading password")
+}
+
+/// Takes wallet name from `--wallet-name`, prompting for it only if stdin is a terminal.
+fn resolve_wallet_name(flag: Option<String>, non_interactive: bool) -> anyhow::Result<String> {
+    match flag {
+        Some(name) => Ok(name),
+        None if non_interactive => Err(anyhow!("wallet name is required: pass --wallet-name")),
+        None => prompt_text("Wallet name:", None, "pass --wallet-name"),
+    }
+}
+
+/// Asks `message` on the terminal, failing rather than panicking when there's none or the prompt
+/// is cancelled, with `hint` on how to give the answer otherwise.
+fn prompt_text(message: &str, default: Option<&str>, hint: &str) -> anyhow::Result<String> {
+    if !io::stdin().is_terminal() {
+        return Err(anyhow!("stdin isn't a terminal to prompt on, {hint}"));
+    }
+    let mut prompt = Text::new(message);
+    if let Some(default) = default {
+        prompt = prompt.with_default(default);
+    }
+    prompt
+        .prompt()
+        .with_context(|| format!("error reading answer to `{message}`"))
+}
+
+/// Asks to confirm `message` on the terminal, as [`prompt_text`] does.
+fn confirm(message: &str) -> anyhow::Result<bool> {
+    if !io::stdin().is_terminal() {
+        return Err(anyhow!("stdin isn't a terminal to confirm on"));
+    }
+    Confirm::new(message)
+        .prompt()
+        .context("error reading confirmation")
+}
+
+/// Data path meaning data to be sold is piped through stdin.
+const STDIN_PATH: &str = "-";
+
+/// Reads data to be sold from `path`, or from stdin if it's `-` or not given while stdin isn't a terminal.
+fn read_sale_data(path: Option<String>) -> anyhow::Result<Vec<u8>> {
+    let path = match path {
+        Some(path) => path,
+        None if !io::stdin().is_terminal() => STDIN_PATH.to_string(),
+        None => prompt_text("File to be sold:", None, "pass --data-path")?,
     };
+    if path != STDIN_PATH {
+        return fs::read(path).map_err(|e| anyhow!("error reading data: {e}"));
+    }
 
-    let name = Text::new("Wallet name:").prompt().unwrap();
-    let password = Password::new("Password:").prompt().unwrap();
+    let mut data = vec![];
+    io::stdin()
+        .lock()
+        .read_to_end(&mut data)
+        .map_err(|e| anyhow!("error reading data from stdin: {e}"))?;
+    // empty sale would encrypt fine, only to be rejected by buyers' verification later.
+    if data.is_empty() {
+        return Err(anyhow!("no data to sell was piped through stdin"));
+    }
+    Ok(data)
+}
+
+/// Reads data to be sold with [`read_sale_data`] and parses it as JSON list of entries,
+/// telling whether UTF-8 BOM was stripped off it first.
+fn read_sale_entries(path: Option<String>, strip: bool) -> anyhow::Result<(Vec<u8>, bool)> {
+    let data = read_sale_data(path)?;
+    let (data, stripped) = match strip {
+        true => strip_bom(&data),
+        false => (&data[..], false),
+    };
+    let data: Vec<String> = serde_json::from_slice(data).unwrap();
+    Ok((
+        data.into_iter().map(|e| e.parse().unwrap()).collect(),
+        stripped,
+    ))
+}
 
-    write_to_keystore(sk, args.keystore_dir, name, password)
+/// Warns about key files in `keystore_dir` that other users may read.
+fn warn_permissive_keys(keystore_dir: &str) {
+    for path in permissive_key_files(keystore_dir) {
+        eprintln!(
+            "warning: key file {} is accessible by other users, restrict it with `chmod 600`",
+            path.display()
+        );
+    }
 }
 
-async fn sell(args: SellArgs) -> anyhow::Result<()> {
-    let name = args
-        .wallet_name
-        .unwrap_or_else(|| Text::new("Wallet name:").prompt().unwrap());
-    let password = args
-        .password
-        .unwrap_or_else(|| Password::new("Password:").prompt().unwrap());
-    let keystore = Path::new(&args.keystore_dir).join(name);
-    let wallet = LocalWallet::from_keystore(keystore, password)?;
+async fn setup(args: SetupArgs, output: Output) -> anyhow::Result<()> {
+    warn_permissive_keys(&args.keystore_dir);
+    if args.rotate_password {
+        let name = resolve_wallet_name(args.wallet_name, false)?;
+        let old_password = resolve_secret(
+            args.password,
+            "--password",
+            PASSWORD_ENV,
+            "Current password:",
+            false,
+        )?;
+        let new_password = resolve_secret(
+            args.new_password,
+            "--new-password",
+            NEW_PASSWORD_ENV,
+            "New password:",
+            false,
+        )?;
 
-    let rpc_url = Url::parse(&args.rpc_address).map_err(|e| anyhow!("bad rpc address: {e}"))?;
-    let eth_provider = Ethereum::new(rpc_url).await?;
+        return rotate_keystore_password(
+            Path::new(&args.keystore_dir).join(name),
+            old_password,
+            new_password,
+        );
+    }
+    if args.ledger {
+        return setup_ledger(&args.derivation_path, output).await;
+    }
+
+    let (sk, pk) = match args.mnemonic {
+        Some(phrase) => keypair_from_bip39_path(&phrase, &args.derivation_path)?,
+        None => {
+            let options = vec![
+                "Generate new",
+                "Recover from hex",
+                "Recover from BIP39 mnemonic",
+            ];
+            if !io::stdin().is_terminal() {
+                return Err(anyhow!(
+                    "stdin isn't a terminal to prompt on, pass --mnemonic to restore wallet"
+                ));
+            }
+            let picked = Select::new("Wallet source?", options.clone())
+                .prompt()
+                .context("error reading wallet source")?;
+            match options
+                .iter()
+                .position(|e| *e == picked)
+                .expect("unexpected option")
+            {
+                0 => keypair_gen(),
+                1 => keypair_from_hex(&prompt_text(
+                    "Paste hex here:",
+                    None,
+                    "run setup in a terminal",
+                )?)?,
+                2 => keypair_from_bip39_path(
+                    &prompt_text("Mnemonic phrase:", None, "pass --mnemonic")?,
+                    &args.derivation_path,
+                )?,
+                _ => panic!("unexpected option"),
+            }
+        }
+    };
+
+    let name = resolve_wallet_name(args.wallet_name, false)?;
+    let password = resolve_password(args.password, false)?;
 
-    let price_str = args
-        .price
-        .unwrap_or_else(|| Text::new("Price (ETH):").prompt().unwrap());
+    open_keystore(&args.keystore_dir).write(sk, &name, password.as_bytes())?;
+    let fingerprint = key_fingerprint(&pk);
+    output.result(
+        &json!({ "wallet_name": name, "fingerprint": fingerprint }),
+        format!("key fingerprint: {fingerprint}"),
+    );
+
+    Ok(())
+}
+
+/// Prints address of the Ledger account sales may be paid to, there's no key to keep in the keystore.
+#[cfg(feature = "ledger")]
+async fn setup_ledger(derivation_path: &str, output: Output) -> anyhow::Result<()> {
+    let address = scriptless_zkcp::ledger_address(derivation_path).await?;
+    output.result(
+        &json!({ "address": format!("{address:?}") }),
+        format!(
+            "ledger address: {address:?}\npass it to sell with --payout-address to receive payments there"
+        ),
+    );
+
+    Ok(())
+}
+
+#[cfg(not(feature = "ledger"))]
+async fn setup_ledger(_derivation_path: &str, _output: Output) -> anyhow::Result<()> {
+    Err(anyhow!(
+        "built without Ledger support, rebuild with --features ledger"
+    ))
+}
+
+/// Makes payments on Ethereum in the ERC-20 `token` with `decimals`, if one is given.
+fn with_payment_token(
+    eth: Ethereum,
+    token: &Option<String>,
+    decimals: u8,
+) -> anyhow::Result<Ethereum> {
+    match token {
+        None => Ok(eth),
+        Some(token) => Ok(eth.with_token(Erc20Token::from_hex(token, decimals)?)),
+    }
+}
+
+/// Fees of the transactions buyer signs in full, capped at `max_fee_gwei` if given.
+fn fee_policy(
+    max_fee_gwei: Option<f64>,
+    bump_after_blocks: Option<u64>,
+) -> anyhow::Result<FeePolicy> {
+    let default = FeePolicy::default();
+    Ok(FeePolicy {
+        max_fee: max_fee_gwei
+            .map(|gwei| decimal_units(gwei, 9))
+            .transpose()
+            .map_err(|e| anyhow!("bad --max-fee-gwei: {e}"))?,
+        bump_after_blocks: bump_after_blocks.unwrap_or(default.bump_after_blocks),
+        ..default
+    })
+}
+
+/// Decodes hex-encoded hash of the verifying key, as the seller announces it.
+fn decode_vk_hash(hash: &str) -> anyhow::Result<[u8; 32]> {
+    hex::decode(hash.trim_start_matches("0x"))
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(anyhow!("error decoding verifying key hash"))
+}
+
+async fn sell(args: SellArgs, output: Output) -> anyhow::Result<()> {
+    if !args.observer {
+        warn_permissive_keys(&args.keystore_dir);
+    }
+    if let Some(data_path) = args.data_path.as_ref().filter(|p| *p != STDIN_PATH) {
+        ensure_readable_file(data_path)?;
+    }
+    if args.stdout && !args.prove_only {
+        return Err(anyhow!("streaming sale to stdout requires --prove-only"));
+    }
+    let tls = match (args.tls_cert.clone(), args.tls_key.clone()) {
+        (Some(certs), Some(key)) => Some(server::TlsPaths { certs, key }),
+        (None, None) => None,
+        _ => {
+            return Err(anyhow!(
+                "serving over HTTPS requires both --tls-cert and --tls-key"
+            ))
+        }
+    };
+    if args.segments.is_some() && (args.stdout || args.prove_only || args.export_sale.is_some()) {
+        return Err(anyhow!(
+            "streamed sale is proven segment by segment as it's listed, it can't be exported"
+        ));
+    }
+    if args.stdout && output.json {
+        return Err(anyhow!(
+            "--json prints results to stdout, which --stdout streams the sale to"
+        ));
+    }
+    // stdout carries the sale container then, so progress goes to stderr.
+    let to_stdout = args.stdout;
+    let report = |msg: String| match to_stdout {
+        true => eprintln!("{msg}"),
+        false => output.progress(msg),
+    };
+    // default sale is the first segment of streamed sale, the others are listed after it.
+    let segments = match args.segments {
+        Some(_) if args.observer || args.sale_path.is_some() => {
+            return Err(anyhow!("streamed sale is split from --data-path only"))
+        }
+        Some(count) => {
+            let (data, stripped) = read_sale_entries(args.data_path.clone(), args.strip_bom)?;
+            Some((split_segments(&data, count)?, stripped))
+        }
+        None => None,
+    };
+    let read_entries = |path: Option<String>| match &segments {
+        Some((segments, stripped)) => Ok((segments[0].clone(), *stripped)),
+        None => read_sale_entries(path, args.strip_bom),
+    };
+    let mut summary = RunSummary::default();
+    let mut result = SellResult {
+        cache_dir: args.cache_dir.clone(),
+        ..Default::default()
+    };
+    let started = Instant::now();
+
+    // observer never signs, so it runs with a throwaway key instead of the keystore.
+    let wallet = match args.observer {
+        true => LocalWallet::new()?,
+        false => {
+            let name = resolve_wallet_name(args.wallet_name, false)?;
+            let password = resolve_password(args.password, false)?;
+            let keystore = open_keystore(&args.keystore_dir);
+            let wallet = LocalWallet::from_store(&*keystore, &name, password)?;
+            if let Some(expected) = args.expected_key_fingerprint {
+                wallet.ensure_fingerprint(&expected)?;
+            }
+            wallet
+        }
+    };
+
+    let rpc_url = Url::parse(&args.rpc_address).map_err(|e| anyhow!("bad rpc address: {e}"))?;
+    let eth_provider = Ethereum::new(rpc_url.clone(), args.chain_id).await?;
+    let eth_provider = with_payment_token(eth_provider, &args.token, args.decimals)?;
+    let price_str = match args.price {
+        Some(price) => price,
+        None => {
+            let unit = args.token.as_deref().unwrap_or("ETH");
+            prompt_text(&format!("Price ({unit}):"), None, "pass --price")?
+        }
+    };
     let price: f64 = price_str
         .parse()
         .map_err(|e| anyhow!("error parsing price: {e}"))?;
 
-    let cipher_host = cipher_host::LocalHost::new(&args.cache_dir);
+    let cache_dir = PathBuf::from(&args.cache_dir);
+    let cipher_host =
+        cipher_host::LocalHost::new(&cache_dir).with_compression(args.compress_bundle);
 
     if !Path::new("zk-config.json").exists() {
         return Err(anyhow!(
@@ -98,18 +490,61 @@ async fn sell(args: SellArgs) -> anyhow::Result<()> {
         ));
     }
 
+    let expires_at = args
+        .expires_at
+        .map(|t| chrono::DateTime::parse_from_rfc3339(&t))
+        .transpose()
+        .map_err(|e| anyhow!("error parsing expiry: {e}"))?
+        .map(|t| t.with_timezone(&chrono::Utc));
+
     let cfg = SellerConfig {
         price,
-        cache_dir: PathBuf::from(args.cache_dir),
+        expires_at,
+        max_prove_time: args.max_prove_secs.map(Duration::from_secs),
+        min_free_memory: args.min_free_mem.map(|mib| mib * 1024 * 1024),
+        cache_dir: cache_dir.clone(),
         zk: serde_json::from_slice(
             &*fs::read("zk-config.json").expect("expect zk-config.json to exist"),
         )
         .map_err(|_e| anyhow!("error unmarshalling zk-config.json"))?,
+        observer: args.observer,
+        deterministic_seed: args.deterministic_seed,
+        chain_id: args.chain_id.0,
+        precomputed_key_encryptions: args.precompute_step1.unwrap_or(0),
+        max_sessions: args.max_sessions,
+        lock_ttl: args.lock_ttl_secs.map_or(LOCK_TTL, Duration::from_secs),
     };
+    let zk = cfg.zk.clone();
     let property_verifier = ZkSampleEntries::new(
         cfg.zk.prop_verifier_dir.clone(),
         cfg.zk.data_encryption_limit,
     );
+    // observer's throwaway key can't vouch for the seller, so its announcements go unsigned.
+    let signing_key = (!args.observer).then(|| wallet.sec_key().clone());
+    let watcher = match args.observer {
+        true => None,
+        false => {
+            let hooks = EventHooks {
+                on_funded: args.on_funded,
+                on_settled: args.on_settled,
+            };
+            let blocks = match &args.ws_address {
+                Some(ws_address) => BlockSource::Subscription(
+                    Url::parse(ws_address).map_err(|e| anyhow!("bad ws address: {e}"))?,
+                ),
+                None => BlockSource::Polling(SETTLEMENT_POLL_INTERVAL),
+            };
+            let chain: Box<dyn ConfirmationSource + Send + Sync> =
+                Box::new(Ethereum::lazy(rpc_url, args.chain_id));
+            Some(EventWatcher::new(
+                chain,
+                cache_dir.clone(),
+                hooks,
+                args.confirmations,
+                blocks,
+            )?)
+        }
+    };
     let (mut seller, to_runtime) = Seller::new(
         cfg,
         eth_provider,
@@ -117,53 +552,403 @@ async fn sell(args: SellArgs) -> anyhow::Result<()> {
         property_verifier,
         wallet,
     )?;
+    let watcher = match watcher {
+        Some((watcher, events)) => {
+            seller = seller.with_events(events);
+            Some(watcher)
+        }
+        None => None,
+    };
+    if let Some(address) = &args.payout_address {
+        let address = address
+            .parse()
+            .map_err(|e| anyhow!("bad payout address: {e}"))?;
+        seller = seller.with_payout_address(address);
+    }
 
-    if !cipher_host.is_hosted().await? {
-        println!("encrypting data and generation proof of encryption...");
-        let data_path = args
-            .data_path
-            .unwrap_or_else(|| Text::new("File to be sold:").prompt().unwrap());
+    summary.record_phase("setup", started);
 
-        let data = fs::read(data_path).map_err(|e| anyhow!("error reading data: {e}"))?;
-        let data: Vec<String> = serde_json::from_slice(&*data).unwrap();
-        let data = data.into_iter().map(|e| e.parse().unwrap()).collect();
+    // whether BOM was stripped is only known for sales encrypted or restored from file in this run.
+    let started = Instant::now();
+    let mut bom_stripped = false;
+    // cached sale is keyed by the content of its data, so it isn't served anymore once the data changes.
+    let changed_data = match (cipher_host.is_hosted().await?, &args.data_path) {
+        (true, Some(_)) if !args.observer => {
+            let (data, stripped) = read_entries(args.data_path.clone())?;
+            let fingerprint = DataFingerprint::new(&data, DATA_CHUNK_SIZE);
+            match DataFingerprint::read(cache_dir.join(DATA_FINGERPRINT_FILE)) {
+                Ok(cached) if cached == fingerprint => None,
+                Ok(cached) => {
+                    report(format!(
+                        "data changed in chunks {:?} since the sale was cached.",
+                        cached.changed_ranges(&fingerprint)
+                    ));
+                    Some((data, stripped))
+                }
+                Err(_) => {
+                    report("cached sale has no fingerprint of its data.".to_string());
+                    Some((data, stripped))
+                }
+            }
+        }
+        _ => None,
+    };
+
+    if let Some((data, stripped)) = changed_data {
+        report("encrypting data and generation proof of encryption...".to_string());
+        bom_stripped = stripped;
         seller.step0_setup(data).await?;
+    } else if cipher_host.is_hosted().await? {
+        report("encrypted data was restored from cache.".to_string());
+    } else if let Some(sale_path) = args.sale_path {
+        let (manifest, sale) = read_sale(sale_path)?;
+        if manifest.data_encryption_limit != zk.data_encryption_limit {
+            return Err(anyhow!("sale file was made for different circuits"));
+        }
+        bom_stripped = manifest.bom_stripped;
+        seller.host_sale(sale).await?;
+        report("encrypted data was restored from sale file.".to_string());
+    } else if args.observer {
+        return Err(anyhow!(
+            "observer mode serves existing sales only, restore one from cache or --sale-path"
+        ));
     } else {
-        println!("encrypted data was restored from cache.");
+        report("encrypting data and generation proof of encryption...".to_string());
+        let (data, stripped) = read_entries(args.data_path)?;
+        bom_stripped = stripped;
+        seller.step0_setup(data).await?;
+    }
+
+    let sale = cipher_host.read().await?;
+    summary.record_phase("encrypt", started);
+    let sale_hash = sale.hash();
+    let plaintext_commitment = sale.plaintext_commitment.clone();
+    result.sale_hash = hex::encode(sale_hash);
+    result.plaintext_commitment = plaintext_commitment.as_ref().map(hex::encode);
+    if let Some(commitment) = &plaintext_commitment {
+        report(format!("plaintext commitment: {}", hex::encode(commitment)));
+    }
+    let wire_size = |sale: &VerifiableEncryption| {
+        sale_wire_size::<ProjectiveCurve, PairingEngine>(
+            ChunkOffsets::new(sale.ciphertext.len()).chunks,
+            sale.proofs_of_property.len(),
+            true,
+        )
+    };
+    let default_wire_size = wire_size(&sale);
+    summary.chunk_count = ChunkOffsets::new(sale.ciphertext.len()).chunks;
+    summary.proof_count = 1 + sale.proofs_of_property.len();
+
+    let started = Instant::now();
+    if let Some(sale_path) = args.export_sale {
+        let options = SaleOptions {
+            bom_stripped,
+            ..Default::default()
+        };
+        write_sale_with_options(&sale_path, &sale, zk.data_encryption_limit, options)?;
+        report(format!("sale exported to {sale_path}"));
+        result.sale_path = Some(sale_path);
+    }
+
+    if let Some(proofs_path) = args.export_proofs {
+        write_proofs(&proofs_path, &sale.proofs_of_property)?;
+        report(format!("proofs exported to {proofs_path}"));
+        result.proofs_path = Some(proofs_path);
+    }
+
+    if args.stdout {
+        let options = SaleOptions {
+            bom_stripped,
+            ..Default::default()
+        };
+        write_sale_to(
+            io::stdout().lock(),
+            &sale,
+            zk.data_encryption_limit,
+            options,
+        )?;
+    }
+
+    if let Some(bundle_path) = args.audit_bundle {
+        let bundle = AuditBundle::new(
+            sale,
+            zk.data_encryption_limit,
+            &read_verifying_key(zk.data_encryption_dir.join(VERIFYING_KEY_FILE))?,
+            &read_verifying_key(zk.prop_verifier_dir.join(VERIFYING_KEY_FILE))?,
+        )?;
+        fs::write(
+            &bundle_path,
+            serde_json::to_vec(&bundle).expect("expected audit bundle to marshal to json"),
+        )
+        .map_err(|e| anyhow!("error writing audit bundle: {e}"))?;
+        report(format!("audit bundle exported to {bundle_path}"));
+        result.audit_bundle = Some(bundle_path);
+    }
+    summary.record_phase("export", started);
+
+    if args.prove_only {
+        if let Some(summary_path) = args.summary_json {
+            summary.write(summary_path)?;
+        }
+        let text = format!("sale hash: {}", result.sale_hash);
+        match to_stdout {
+            true => eprintln!("{text}"),
+            false => output.result(&result, text),
+        }
+        return Ok(());
+    }
+
+    let mut items = args.listing;
+    if let Some(catalog_path) = &args.catalog {
+        items.extend(read_catalog(catalog_path)?);
+    }
+    ensure_unique_items(&items)?;
+
+    let mut listings = HashMap::new();
+    for item in items {
+        let (id, dir) = (item.id, item.cache_dir);
+        let price = item.price.unwrap_or(price);
+        let host = cipher_host::LocalHost::new(&dir);
+        let sale = host.read().await?;
+        let decryption_key = match args.observer {
+            true => None,
+            false => Some(
+                fs::read(dir.join("decryption_key"))
+                    .map_err(|e| anyhow!("error reading decryption key of sale {id}: {e}"))?,
+            ),
+        };
+        let _ = listings.insert(
+            id.clone(),
+            server::ListingInfo {
+                price,
+                sale_hash: sale.hash(),
+                wire_size: Some(wire_size(&sale)),
+                plaintext_commitment: sale.plaintext_commitment.clone(),
+            },
+        );
+        seller
+            .list_sale(id.clone(), host, sale, decryption_key, price)
+            .await?;
+        report(format!(
+            "listed sale {id} from {} at {price}",
+            dir.display()
+        ));
+        result.listings.push(id);
     }
 
+    if let Some((segments, _)) = &segments {
+        let key = fs::read(cache_dir.join("decryption_key"))
+            .map_err(|e| anyhow!("error reading decryption key of the sale: {e}"))?;
+        let first = cipher_host.read().await?;
+        seller
+            .list_sale(segment_id(1), cipher_host.clone(), first, Some(key), price)
+            .await?;
+        for (index, data) in segments.iter().enumerate().skip(1).map(|(i, d)| (i + 1, d)) {
+            let (id, dir) = (segment_id(index), cache_dir.join(segment_id(index)));
+            let host = cipher_host::LocalHost::new(&dir).with_compression(args.compress_bundle);
+            let fingerprint = DataFingerprint::new(data, DATA_CHUNK_SIZE);
+            // segment is proven again once its data changes, as the default sale is.
+            let cached = host.is_hosted().await?
+                && matches!(
+                    DataFingerprint::read(dir.join(DATA_FINGERPRINT_FILE)),
+                    Ok(cached) if cached == fingerprint
+                );
+            let sale = match cached {
+                true => {
+                    let sale = host.read().await?;
+                    let key = fs::read(dir.join("decryption_key")).map_err(|e| {
+                        anyhow!("error reading decryption key of segment {index}: {e}")
+                    })?;
+                    seller
+                        .list_sale(id.clone(), host, sale.clone(), Some(key), price)
+                        .await?;
+                    sale
+                }
+                false => {
+                    report(format!(
+                        "encrypting segment {index} of {}...",
+                        segments.len()
+                    ));
+                    seller
+                        .list_new_sale(id.clone(), host, &dir, data.clone(), price)
+                        .await?
+                }
+            };
+            let _ = listings.insert(
+                id.clone(),
+                server::ListingInfo {
+                    price,
+                    sale_hash: sale.hash(),
+                    wire_size: Some(wire_size(&sale)),
+                    plaintext_commitment: sale.plaintext_commitment.clone(),
+                },
+            );
+            result.listings.push(id);
+        }
+        report(format!(
+            "streaming sale in {} segments at {price} each",
+            segments.len()
+        ));
+    }
+
+    // daemon serves until killed, so summary covers preparing the sale, before anything is transferred.
+    if let Some(summary_path) = args.summary_json {
+        summary.write(summary_path)?;
+    }
+    output.result(&result, format!("sale hash: {}", result.sale_hash));
+
+    let vk_hash = verifying_key_hash(
+        &fs::read(zk.data_encryption_dir.join(VERIFYING_KEY_FILE))
+            .map_err(|e| anyhow!("error reading verifying key: {e}"))?,
+    )?;
+
+    if let Some(watcher) = watcher {
+        spawn(watcher.run());
+    }
+    let metrics = seller.metrics();
     spawn(async {
         seller.run().await;
     });
 
-    server::serve(to_runtime, price).await;
+    let cfg = server::ServeConfig {
+        price,
+        sale_hash,
+        wire_size: Some(default_wire_size),
+        plaintext_commitment,
+        expires_at,
+        listings,
+        signing_key,
+        verifying_key_hash: Some(vk_hash),
+        rate_limit: args.rate_limit.map(|limit| {
+            server::RateLimiter::new(limit, Duration::from_secs(60))
+                .with_trusted_proxies(args.trusted_proxy.clone())
+        }),
+        metrics: Some(metrics),
+        tls,
+    };
+    server::serve(to_runtime, cfg).await;
 
     Ok(())
 }
 
-async fn buy(args: BuyArgs) -> anyhow::Result<()> {
+/// Buys the sale, none bought if buyer declines it or only lists or inspects the sales.
+async fn buy(args: BuyArgs, output: Output) -> anyhow::Result<Option<BuyResult>> {
+    warn_permissive_keys(&args.keystore_dir);
+    if args.list {
+        let client = client::SellerClient::new(&args.seller_address)?;
+        let catalog = client.catalog().await?;
+        let text = catalog
+            .iter()
+            .map(|entry| {
+                format!(
+                    "{}: price {}, sale hash {}",
+                    entry.id, entry.price, entry.sale_hash
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        output.result(&catalog, text);
+        return Ok(None);
+    }
+    if let Some(sale_id) = &args.inspect {
+        return inspect_sale(&args.seller_address, sale_id, output)
+            .await
+            .map(|_| None);
+    }
+    let mut summary = RunSummary::default();
     let rpc_url = Url::parse(&args.rpc_address).map_err(|e| anyhow!("bad rpc address: {e}"))?;
-    let eth_provider = Ethereum::new(rpc_url).await?;
+    let eth_provider = Ethereum::new(rpc_url, args.chain_id).await?;
+    let eth_provider = with_payment_token(eth_provider, &args.token, args.decimals)?
+        .with_fee_policy(fee_policy(args.max_fee_gwei, args.bump_after_blocks)?);
+    // parsed before paying, so that typo doesn't cost the purchase.
+    let output_mode = args
+        .output_mode
+        .as_deref()
+        .map(parse_file_mode)
+        .transpose()?;
+    let attestation = match (&args.attestation_path, &args.attester_key) {
+        (Some(path), Some(attester)) => Some((
+            ReputationAttestation::from_file(path)?,
+            attester_from_hex(attester)?,
+        )),
+        (Some(_), None) => return Err(anyhow!("verifying attestation requires attester key")),
+        (None, _) => None,
+    };
+
+    let mut client = match args.sale_id {
+        Some(sale_id) => client::SellerClient::new(args.seller_address)?.with_sale(sale_id),
+        None => client::SellerClient::new(args.seller_address)?,
+    };
+    if let Some(seller) = args.expected_seller {
+        let seller = seller
+            .parse()
+            .map_err(|e| anyhow!("bad expected seller address: {e}"))?;
+        client = client.with_expected_seller(seller);
+    }
+    let policy = client::ConnectPolicy {
+        timeout: args.connect_timeout.map(Duration::from_secs),
+        retries: args.connect_retries,
+        ..Default::default()
+    };
+    let price = client
+        .connect(policy, |attempt, e, delay| {
+            eprintln!(
+                "attempt {attempt} to reach seller failed: {e}, retrying in {}s...",
+                delay.as_secs_f64()
+            )
+        })
+        .await?;
+    check_expiry(client.expires_at(), chrono::Utc::now())?;
 
-    let client = client::SellerClient::new(args.seller_address)?;
-    let price = client.price().await?;
+    // quoted price is the one buyer pays and seller settles, replacing the announced one.
+    let quote = match args.quote && args.resume.is_none() {
+        true => {
+            let quote = client.quote().await?;
+            // quote has to be signed by the key that announced the sale, for the sale announced.
+            let seller_pk = client.seller_pk().ok_or(anyhow!(
+                "seller announces sale unsigned, its quote can't be trusted"
+            ))?;
+            quote.verify(&seller_pk)?;
+            let sale_hash = client.sale_hash().ok_or(anyhow!(
+                "seller announced no sale hash to check quote against"
+            ))?;
+            quote.quote.ensure_terms(
+                client.sale_id(),
+                &sale_hash,
+                args.chain_id.0,
+                eth_provider.payment_token(),
+            )?;
+            quote.quote.ensure_unexpired(chrono::Utc::now())?;
+            Some(quote)
+        }
+        false => None,
+    };
+    let price = quote.as_ref().map_or(price, |q| q.quote.price);
 
+    let unit = args.token.as_deref().unwrap_or("ETH");
+    let validity = quote
+        .as_ref()
+        .map(|q| format!(", quoted until {}", q.quote.expires_at))
+        .unwrap_or_default();
     if !args.non_interactive
-        && !Confirm::new(&format!("Price is {price} ETH. Continue? (y/N): "))
-            .prompt()
-            .unwrap()
+        && args.resume.is_none()
+        && !confirm(&match client.wire_size() {
+            Some(size) => {
+                format!("Price is {price} {unit}{validity} for {size} bytes. Continue? (y/N): ")
+            }
+            None => format!("Price is {price} {unit}{validity}. Continue? (y/N): "),
+        })?
     {
-        return Ok(());
+        return Ok(None);
     }
 
-    let name = args
-        .wallet_name
-        .unwrap_or_else(|| Text::new("Wallet name:").prompt().unwrap());
-    let password = args
-        .password
-        .unwrap_or_else(|| Password::new("Password:").prompt().unwrap());
-    let keystore = Path::new(&args.keystore_dir).join(name);
-    let wallet = LocalWallet::from_keystore(keystore, password)?;
+    let name = resolve_wallet_name(args.wallet_name, args.non_interactive)?;
+    let password = resolve_password(args.password, args.non_interactive)?;
+    let keystore = open_keystore(&args.keystore_dir);
+    let wallet = LocalWallet::from_store(&*keystore, &name, password)?;
+    if let Some(expected) = args.expected_key_fingerprint {
+        wallet.ensure_fingerprint(&expected)?;
+    }
     let address = eth_provider.address_from_pk(wallet.pub_key());
     let pub_key = wallet.pub_key().clone();
 
@@ -173,86 +958,352 @@ async fn buy(args: BuyArgs) -> anyhow::Result<()> {
         ));
     }
 
+    let cache_dir = PathBuf::from(&args.cache_dir);
     let cfg = BuyerConfig {
+        cache_dir: cache_dir.clone(),
         zk: serde_json::from_slice(
             &*fs::read("zk-config.json").expect("expect zk-config.json to exist"),
         )
         .map_err(|_e| anyhow!("error unmarshalling zk-config.json"))?,
+        verify_threads: args.verify_threads.unwrap_or(0),
+    };
+    if args.expect_commitment.is_some() && cfg.zk.predicate.is_none() {
+        return Err(anyhow!(
+            "--expect-commitment needs circuits compiled with `--predicate digest`"
+        ));
+    }
+    // proof only shows plaintext hashes to some commitment, so it has to be known before paying.
+    let announced_commitment = match cfg.zk.predicate {
+        Some(_) => Some(client.plaintext_commitment().ok_or(anyhow!(
+            "seller doesn't announce the plaintext commitment, refusing to buy data that can't be checked"
+        ))?),
+        None => None,
     };
 
     let property_verifier = ZkSampleEntries::new(
         cfg.zk.prop_verifier_dir.clone(),
         cfg.zk.data_encryption_limit,
     );
+    let vk_registry = match args.vk_registry {
+        Some(address) => {
+            let address = address
+                .parse()
+                .map_err(|e| anyhow!("bad vk registry address: {e}"))?;
+            Some(eth_provider.vk_registry(address))
+        }
+        None => None,
+    };
     let mut buyer = Buyer::new(cfg, eth_provider, property_verifier, wallet);
 
-    println!("downloading encrypted data...");
-    let encrypted_data = client.download().await?;
-    if !buyer.step0_verify(&encrypted_data)? {
-        return Err(anyhow!("seller sent invalid proof of data encryption"));
-    }
-    println!("proof of encryption is valid");
-
-    let Step1Msg {
-        ciphertext,
-        proof_of_encryption,
-        data_pk,
-        seller_address,
-    } = client.step1(address).await?;
-
-    // todo: cache ciphertext and data_pk.
-    let enc_sig = buyer
-        .step2(
-            &ciphertext,
-            proof_of_encryption,
-            &data_pk,
-            seller_address,
-            price,
-        )
-        .await?;
+    // payment made before purchase got interrupted is picked up at key recovery, not made twice.
+    let prior_payment = match (&args.resume, client.sale_hash()) {
+        (None, Some(sale_hash)) => buyer.prior_payment(&sale_hash, price).await?,
+        _ => None,
+    };
+    let resume = match (args.resume.clone(), &prior_payment) {
+        (Some(session_id), _) => Some(session_id),
+        // sessions are removed once data is recovered, so the sale was bought in full then.
+        (None, Some((tx_hash, session_id))) => {
+            if find_session::<_, BuyerSession>(&cache_dir, session_id)?.is_none() {
+                return Err(anyhow!(
+                    "payment for this sale was already made in tx {tx_hash:?}, refusing to pay twice"
+                ));
+            }
+            Some(session_id.clone())
+        }
+        (None, None) => None,
+    };
 
-    if !args.non_interactive
-        && !Confirm::new(&format!(
-        "Encrypted one-time key received. Sign transfer transaction to address 0x{address}? (y/N): "
-    ))
-        .prompt()
-        .unwrap()
-    {
-        return Ok(());
-    }
+    let mut manifest = None;
+    let (mut session, session_id, enc_sig, started) = match resume {
+        Some(session_id) => {
+            let mut session: BuyerSession = read_session(&cache_dir, &session_id)?;
+            let enc_sig = buyer.resume(&session)?;
+            if let Some((tx_hash, _)) = prior_payment {
+                session.phase = SessionPhase::Paid;
+                session.tx_hash = Some(tx_hash);
+                write_session(&cache_dir, &session_id, &session)?;
+                output.progress(format!("payment was already made in tx {tx_hash:?}"));
+            }
+            output.progress(format!("resuming purchase session {session_id}"));
+            (session, session_id, enc_sig, Instant::now())
+        }
+        None => {
+            let started = Instant::now();
+            let mut encrypted_data = match args.sale_path {
+                Some(sale_path) => {
+                    let (sale_manifest, sale) = read_sale(sale_path)?;
+                    manifest = Some(sale_manifest);
+                    if client.sale_hash() != Some(sale.hash()) {
+                        return Err(anyhow!(
+                            "sale file doesn't match the sale announced by seller"
+                        ));
+                    }
+                    sale
+                }
+                None => {
+                    output.progress("downloading encrypted data...");
+                    let sale = client.download().await?;
+                    summary.bytes_transferred += sale_wire_size::<ProjectiveCurve, PairingEngine>(
+                        ChunkOffsets::new(sale.ciphertext.len()).chunks,
+                        sale.proofs_of_property.len(),
+                        true,
+                    ) as u64;
+                    sale
+                }
+            };
+            summary.record_phase("download", started);
+            summary.chunk_count = ChunkOffsets::new(encrypted_data.ciphertext.len()).chunks;
 
-    let tx_hash = client.step3(pub_key, enc_sig).await?;
+            let started = Instant::now();
+            if let Some(proofs_path) = args.proofs_path {
+                encrypted_data.proofs_of_property = read_proofs(proofs_path)?;
+            }
+            if let Some(manifest) = &manifest {
+                manifest.ensure_complete_proof_set(&encrypted_data)?;
+            }
+            summary.proof_count = 1 + encrypted_data.proofs_of_property.len();
+            if let Some(registry) = &vk_registry {
+                buyer
+                    .step0_check_anchored_vk(registry, &encrypted_data)
+                    .await?;
+                output.progress("verifying key matches the one anchored on-chain");
+            }
+            if !buyer.step0_verify(&encrypted_data)? {
+                if args.explain {
+                    let announced_vk = client
+                        .inspect()
+                        .await
+                        .ok()
+                        .and_then(|sale| sale.verifying_key_hash);
+                    let announced_vk = announced_vk.as_deref().map(decode_vk_hash).transpose()?;
+                    output.progress(buyer.step0_explain(&encrypted_data, announced_vk.as_ref())?);
+                }
+                return Err(anyhow!("seller sent invalid proof of data encryption"));
+            }
+            output.progress("proof of encryption is valid");
+            if let Some(announced) = &announced_commitment {
+                encrypted_data.ensure_plaintext_commitment(&hex::encode(announced))?;
+                output.progress("proof is bound to the plaintext commitment announced by seller");
+            }
+            if let Some(expected) = &args.expect_commitment {
+                encrypted_data.ensure_plaintext_commitment(expected)?;
+                output.progress("plaintext matches the expected commitment");
+            }
+            summary.record_phase("verify", started);
 
-    let data = buyer.step4(tx_hash, encrypted_data.ciphertext).await?;
-    let data = serde_json::to_vec(&data).unwrap();
+            // proofs verification takes a while, so offer may have expired since.
+            check_expiry(client.expires_at(), chrono::Utc::now())?;
+            if let Some(quote) = &quote {
+                quote.quote.ensure_unexpired(chrono::Utc::now())?;
+            }
+            let started = Instant::now();
+            let Step1Msg {
+                ciphertext,
+                proof_of_encryption,
+                data_pk,
+                seller_address,
+            } = match args.handshake {
+                true => client.handshake(address, &buyer.step1_handshake()?).await?,
+                false => client.step1(address).await?,
+            };
 
-    let data_path = args.data_path.unwrap_or_else(|| {
-        Text::new("File decrypted! Where to save the result?:")
-            .with_default(&format!("purchase_{}", chrono::Local::today().to_string()))
-            .prompt()
-            .unwrap()
-    });
+            summary.bytes_transferred += (ciphertext.len() + proof_of_encryption.len()) as u64;
+
+            // todo: cache ciphertext and data_pk.
+            let enc_sig = buyer
+                .step2(
+                    &ciphertext,
+                    proof_of_encryption,
+                    &data_pk,
+                    seller_address,
+                    price,
+                )
+                .await?;
+            let session_id = buyer.session_id(&encrypted_data, price);
+            let session = buyer.session(encrypted_data, price, quote.clone())?;
+
+            let reputation = match &attestation {
+                Some((attestation, attester)) => {
+                    attestation.verify(attester, seller_address)?;
+                    output.progress(format!("seller reputation attested: {attestation}"));
+                    format!(" Seller has {attestation}.")
+                }
+                None => String::new(),
+            };
+
+            if !args.non_interactive
+                && !confirm(&format!(
+                    "Encrypted one-time key received.{reputation} Sign transfer transaction to address 0x{address}? (y/N): "
+                ))?
+            {
+                return Ok(None);
+            }
+
+            write_session(&cache_dir, &session_id, &session)?;
+            output.progress(format!(
+                "purchase saved as session {session_id}, pass it to --resume if interrupted"
+            ));
+            (session, session_id, enc_sig, started)
+        }
+    };
+
+    let mut result = BuyResult::default();
+    let tx_hash = match session.tx_hash {
+        Some(tx_hash) => tx_hash,
+        None => {
+            let tx_params = buyer
+                .tx_params()
+                .ok_or(anyhow!("payment wasn't signed at Step 2"))?;
+            let settle = client.step3(pub_key, enc_sig, &tx_params, session.quote.as_ref());
+            let tx_hash = match args.timeout_blocks {
+                Some(timeout_blocks) => buyer.step3_with_timeout(settle, timeout_blocks).await?,
+                None => settle.await?,
+            };
+            buyer.record_payment(&session.sale, session.price, tx_hash)?;
+            session.phase = SessionPhase::Paid;
+            session.tx_hash = Some(tx_hash);
+            write_session(&cache_dir, &session_id, &session)?;
+            tx_hash
+        }
+    };
+    summary.record_phase("exchange", started);
+
+    let started = Instant::now();
+    let data = buyer.step4(tx_hash, &session.sale).await?;
+    result.sale_hash = hex::encode(session.sale.hash());
+    result.price = session.price;
+    result.tx_hashes = vec![hex::encode(tx_hash.as_bytes())];
+    let own = buyer.receipt(
+        client.sale_id().map(Into::into),
+        &session.sale,
+        session.price,
+        args.chain_id.0,
+        result.tx_hashes.clone(),
+    )?;
+    record_receipt(&buyer, own, client.receipt(address).await, output);
+    let mut data = serde_json::to_vec(&data).unwrap();
+    if args.restore_bom {
+        data = manifest
+            .ok_or(anyhow!("restoring BOM requires sale file"))?
+            .restore_bom(data);
+    }
+    if let Some(expected) = &args.verify_hash {
+        ensure_content_hash(&data, expected)?;
+        output.progress("decrypted data matches the expected hash");
+    }
+    summary.record_phase("decrypt", started);
+
+    let data_path = match args.data_path {
+        Some(path) => path,
+        None => {
+            let default = format!("purchase_{}", chrono::Local::today());
+            prompt_text(
+                "File decrypted! Where to save the result?:",
+                Some(&default),
+                "pass --data-path",
+            )?
+        }
+    };
 
     let data_path = Path::new(&data_path);
     let _ = fs::create_dir_all(data_path.parent().unwrap());
-    fs::write(data_path, data).map_err(|e| anyhow!("error writing decrypted data: {e}"))?;
+    write_with_mode(data_path, data, output_mode)
+        .map_err(|e| anyhow!("error writing decrypted data: {e}"))?;
+    if let Some(summary_path) = args.summary_json {
+        summary.write(summary_path)?;
+    }
 
-    println!(
-        "find your purchased data at {}",
-        data_path.to_str().unwrap()
-    );
+    remove_session(&cache_dir, &session_id)?;
+    result.session_id = session_id;
+
+    result.data_path = data_path.to_str().unwrap().to_string();
+    let text = format!("find your purchased data at {}", result.data_path);
+    output.result(&result, text);
+
+    Ok(Some(result))
+}
+
+/// Buys `--segments` of streamed sale one after another, each saved next to `--data-path`
+/// with its number appended, skipping those paid for earlier and stopping once buyer declines one.
+async fn buy_segments(args: BuyArgs, output: Output) -> anyhow::Result<()> {
+    let range = args
+        .segments
+        .clone()
+        .expect("expected segments to be given")
+        .0;
+    if args.sale_id.is_some() || args.resume.is_some() {
+        return Err(anyhow!(
+            "segments are bought as sales of their own, without --sale-id or --resume"
+        ));
+    }
+    let data_path = args.data_path.clone().ok_or(anyhow!(
+        "buying segments requires --data-path to save them at"
+    ))?;
+
+    let mut progress = StreamProgress::read(&args.cache_dir, &args.seller_address)?;
+    for index in range {
+        if progress.is_settled(index) {
+            output.progress(format!("segment {index} was paid for earlier, skipping"));
+            continue;
+        }
+        output.progress(format!("buying segment {index}..."));
+        let segment = BuyArgs {
+            sale_id: Some(segment_id(index)),
+            data_path: Some(format!("{data_path}.{index}")),
+            segments: None,
+            ..args.clone()
+        };
+        match buy(segment, output).await? {
+            Some(bought) => {
+                let _ = progress.settled.insert(index, bought.tx_hashes);
+                progress.write(&args.cache_dir, &args.seller_address)?;
+            }
+            None => {
+                output.progress(format!("stopped before segment {index}"));
+                break;
+            }
+        }
+    }
 
     Ok(())
 }
 
-async fn compile(args: CompileArgs) -> anyhow::Result<()> {
+/// Countersigns seller's receipt of the completed trade into the audit log, recording buyer's `own`
+/// receipt instead if seller gave none or one that doesn't match. Purchase is done by now,
+/// so failures are only reported.
+fn record_receipt(
+    buyer: &Buyer<Ethereum, ZkSampleEntries>,
+    own: Receipt,
+    from_seller: anyhow::Result<Option<ReceiptEntry>>,
+    output: Output,
+) {
+    let countersigned = match from_seller {
+        Ok(Some(entry)) => buyer.countersign_receipt(&own, entry),
+        Ok(None) => Err(anyhow!("seller has no receipt of the trade")),
+        Err(e) => Err(e),
+    };
+    let recorded = match countersigned {
+        Ok(_) => {
+            output.progress("receipt countersigned with seller");
+            return;
+        }
+        Err(e) => {
+            eprintln!("warning: seller's receipt not countersigned: {e}");
+            buyer.record_receipt(own)
+        }
+    };
+    if let Err(e) = recorded {
+        eprintln!("warning: receipt not recorded: {e}");
+    }
+}
+
+async fn compile(args: CompileArgs, output: Output) -> anyhow::Result<()> {
     let cipher_host = cipher_host::LocalHost::new(&args.cache_dir);
 
-    if cipher_host.is_hosted().await.unwrap() && !Confirm::new(&format!(
+    if cipher_host.is_hosted().await.unwrap() && !confirm(
         "Proof of encryption cache found. Recompiling circuit will cause it becoming broken. Continue? (y/N): "
-    ))
-        .prompt()
-        .unwrap()
+    )?
     {
         return Ok(())
     }
@@ -268,28 +1319,411 @@ async fn compile(args: CompileArgs) -> anyhow::Result<()> {
             wasm_path: PathBuf::from(args.wasm_path),
             r1cs_path: PathBuf::from(args.r1cs_path),
         },
+        backend: args.backend,
+        predicate: args.predicate,
     };
 
+    for dir in [
+        &cfg.prop_verifier_dir,
+        &cfg.data_encryption_dir,
+        &cfg.key_encryption_dir,
+    ] {
+        ensure_artifacts_writable(dir, args.overwrite_keys)?;
+    }
+    if let Some(curve) = args.curve {
+        ensure_build_curve(curve)?;
+    }
+    if args.export_solidity.is_some() {
+        ensure_evm_pairing::<PairingEngine>()?;
+    }
+
     fs::write(
         "zk-config.json",
         serde_json::to_vec(&cfg).expect("expected zk config to marshal to json"),
     )
     .map_err(|e| anyhow!("error saving zk config: {e}"))?;
 
-    println!("compiling data encryption circuit...");
+    output.progress("compiling data encryption circuit...");
     let property_verifier =
         ZkSampleEntries::new(cfg.prop_verifier_dir.clone(), cfg.data_encryption_limit);
     let prop_verification = ZkVerifiableEncryption::new(
         &cfg.data_encryption_dir,
         property_verifier,
         encryption::Parameters::default_multi(cfg.data_encryption_limit),
-    );
+    )
+    .with_predicate(cfg.predicate);
     let _ = prop_verification.compile(&mut rand::thread_rng())?;
 
-    println!("compiling key encryption circuit...");
+    output.progress("compiling key encryption circuit...");
     let key_encryption = ZkEncryption::new(&cfg.key_encryption_dir, Default::default());
     let _ = key_encryption.compile(&mut rand::thread_rng())?;
 
-    println!("done!");
+    // prover only needs the binary keys, JSON is for tooling that embeds the verifying key.
+    for dir in [
+        &cfg.prop_verifier_dir,
+        &cfg.data_encryption_dir,
+        &cfg.key_encryption_dir,
+    ] {
+        let vk = read_verifying_key::<_, PairingEngine>(dir.join(VERIFYING_KEY_FILE))?;
+        VerifyingKeyJson::new(&vk)?.write(dir.join(VERIFYING_KEY_JSON_FILE))?;
+
+        if let Some(solidity_dir) = &args.export_solidity {
+            fs::create_dir_all(solidity_dir)
+                .map_err(|e| anyhow!("error creating solidity directory: {e}"))?;
+            let name = dir.file_name().unwrap().to_string_lossy();
+            write_solidity_verifier(&vk, Path::new(solidity_dir).join(format!("{name}.sol")))?;
+        }
+    }
+
+    output.result(&cfg, "done!");
+    Ok(())
+}
+
+/// Prints details of the sale for buyer to check before paying, and whether its proofs fit local circuits.
+async fn inspect_sale(seller_address: &str, sale_id: &str, output: Output) -> anyhow::Result<()> {
+    let client = client::SellerClient::new(seller_address)?.with_sale(sale_id);
+    let sale = client.inspect().await?;
+
+    let mut text = vec![
+        format!("sale: {sale_id}"),
+        format!("price: {}", sale.price),
+        format!("sale hash: {}", sale.sale_hash),
+    ];
+    if let Some(wire_size) = sale.wire_size {
+        text.push(format!("download size: {wire_size} bytes"));
+    }
+    if let Some(expires_at) = &sale.expires_at {
+        text.push(format!("expires at: {expires_at}"));
+    }
+    text.push(format!(
+        "seller address: {}",
+        sale.seller_address
+            .as_deref()
+            .map_or("unknown".to_string(), |a| format!("0x{a}"))
+    ));
+    text.push(format!(
+        "verifying key hash: {}",
+        sale.verifying_key_hash.as_deref().unwrap_or("unknown")
+    ));
+
+    let mut proofs_checkable = None;
+    if let Some(vk_hash) = &sale.verifying_key_hash {
+        if Path::new("zk-config.json").exists() {
+            let zk: ZkConfig = serde_json::from_slice(
+                &fs::read("zk-config.json")
+                    .map_err(|e| anyhow!("error reading zk-config.json: {e}"))?,
+            )
+            .map_err(|_e| anyhow!("error unmarshalling zk-config.json"))?;
+            let local = verifying_key_hash(
+                &fs::read(zk.data_encryption_dir.join(VERIFYING_KEY_FILE))
+                    .map_err(|e| anyhow!("error reading verifying key: {e}"))?,
+            )?;
+            let checkable = hex::encode(local) == *vk_hash;
+            let verdict = match checkable {
+                true => "proofs are checkable with local circuits",
+                false => "local circuits differ from the seller's, proofs can't be checked",
+            };
+            text.push(verdict.to_string());
+            proofs_checkable = Some(checkable);
+        }
+    }
+
+    output.result(
+        &InspectResult {
+            sale,
+            proofs_checkable,
+        },
+        text.join("\n"),
+    );
+    Ok(())
+}
+
+async fn config(args: ConfigArgs, output: Output) -> anyhow::Result<()> {
+    let command = args
+        .command
+        .ok_or_else(|| anyhow!("config command is required: init"))?;
+
+    match command {
+        ConfigCommand::Init(args) => {
+            if Path::new(&args.path).exists() && !args.force {
+                return Err(anyhow!(
+                    "config {} already exists, pass --force to replace it",
+                    args.path
+                ));
+            }
+            fs::write(&args.path, CONFIG_TEMPLATE)
+                .map_err(|e| anyhow!("error writing config {}: {e}", args.path))?;
+            output.result(
+                &ConfigResult {
+                    path: args.path.clone(),
+                },
+                format!("config template written to {}", args.path),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn receipts(args: ReceiptsArgs, output: Output) -> anyhow::Result<()> {
+    let command = args
+        .command
+        .ok_or_else(|| anyhow!("receipts command is required: verify"))?;
+
+    match command {
+        ReceiptsCommand::Verify(args) => {
+            let entries = verify_receipts(&args.log)?;
+            let mut text = vec![];
+            let mut result = ReceiptsResult::default();
+            if let Some(sale_path) = args.sale_path {
+                let (_, sale) = read_sale(sale_path)?;
+                let sale_hash = hex::encode(sale.hash());
+                let mut matched = 0;
+                for (i, entry) in entries.iter().enumerate() {
+                    if entry.receipt.sale_hash != sale_hash {
+                        continue;
+                    }
+                    entry
+                        .receipt
+                        .ensure_sale(&sale)
+                        .map_err(|e| anyhow!("receipt #{i}: {e}"))?;
+                    matched += 1;
+                }
+                if matched == 0 {
+                    return Err(anyhow!("no receipts of the sale in the audit log"));
+                }
+                text.push(format!("{matched} receipts match the sale"));
+                result.matched = Some(matched);
+            }
+
+            for (i, entry) in entries.iter().enumerate() {
+                let receipt = &entry.receipt;
+                let signers = match (&entry.seller_signature, &entry.buyer_signature) {
+                    (Some(_), Some(_)) => "seller and buyer",
+                    (Some(_), None) => "seller",
+                    _ => "buyer",
+                };
+                text.push(format!(
+                    "#{i} {}: price {}, sale hash {}, txs {}, signed by {signers}, completed at {}",
+                    receipt.sale_id.as_deref().unwrap_or("default sale"),
+                    receipt.price,
+                    receipt.sale_hash,
+                    receipt.tx_ids.join(", "),
+                    receipt.completed_at
+                ));
+            }
+            text.push(format!("{} receipts verified", entries.len()));
+            result.receipts = entries;
+            output.result(&result, text.join("\n"));
+        }
+    }
+
+    Ok(())
+}
+
+async fn ceremony(args: CeremonyArgs, output: Output) -> anyhow::Result<()> {
+    let command = args.command.ok_or_else(|| {
+        anyhow!("ceremony command is required: contribute, verify-transcript or finalize")
+    })?;
+    let mut rng = rand::thread_rng();
+    let hashes = |transcript: &CeremonyTranscript<PairingEngine>| CeremonyResult {
+        contributions: transcript
+            .contributions
+            .iter()
+            .map(|contribution| hex::encode(contribution.hash()))
+            .collect(),
+    };
+
+    match command {
+        CeremonyCommand::Contribute(args) => {
+            let dir = Path::new(&args.circuit_dir);
+            let (mut transcript, mut pk) = read_ceremony::<_, PairingEngine>(dir)?;
+            transcript.verify(&pk, &mut rng)?;
+            let contribution = transcript.contribute(&mut pk, &mut rng);
+            write_ceremony(dir, &transcript, &pk)?;
+            output.result(
+                &hashes(&transcript),
+                format!(
+                    "contribution #{}: {}",
+                    transcript.contributions.len() - 1,
+                    hex::encode(contribution.hash())
+                ),
+            );
+        }
+        CeremonyCommand::VerifyTranscript(args) => {
+            let (transcript, pk) = read_ceremony::<_, PairingEngine>(&args.circuit_dir)?;
+            transcript.verify(&pk, &mut rng)?;
+            let result = hashes(&transcript);
+            let mut text = result
+                .contributions
+                .iter()
+                .enumerate()
+                .map(|(i, hash)| format!("contribution #{i}: {hash}"))
+                .collect::<Vec<_>>();
+            text.push("transcript is valid".to_string());
+            output.result(&result, text.join("\n"));
+        }
+        CeremonyCommand::Finalize(args) => {
+            let dir = Path::new(&args.circuit_dir);
+            let (mut transcript, mut pk) = read_ceremony::<_, PairingEngine>(dir)?;
+            transcript.verify(&pk, &mut rng)?;
+            transcript.contribute_beacon(&mut pk, args.beacon.as_bytes());
+            transcript.verify(&pk, &mut rng)?;
+            write_ceremony(dir, &transcript, &pk)?;
+            VerifyingKeyJson::new(&pk.vk)?.write(dir.join(VERIFYING_KEY_JSON_FILE))?;
+            output.result(
+                &hashes(&transcript),
+                format!(
+                    "ceremony finalized after {} contributions",
+                    transcript.contributions.len()
+                ),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn pubkey(args: PubkeyArgs, output: Output) -> anyhow::Result<()> {
+    warn_permissive_keys(&args.keystore_dir);
+    let name = resolve_wallet_name(args.wallet_name, false)?;
+    let password = resolve_password(args.password, false)?;
+    let wallet = LocalWallet::from_store(&*open_keystore(&args.keystore_dir), &name, password)?;
+
+    let (public_key, fingerprint) = (wallet.pub_key().to_string(), wallet.fingerprint());
+    output.result(
+        &json!({ "public_key": public_key, "fingerprint": fingerprint }),
+        format!("public key: {public_key}\nkey fingerprint: {fingerprint}"),
+    );
+    Ok(())
+}
+
+async fn refund(args: RefundArgs, output: Output) -> anyhow::Result<()> {
+    warn_permissive_keys(&args.keystore_dir);
+    let session_id = args
+        .session
+        .as_ref()
+        .ok_or(anyhow!("--session of the purchase to refund is required"))?;
+    let session: BuyerSession = read_session(&args.cache_dir, session_id)?;
+    // refund has to spend the nonce of the payment, any later one leaves it claimable.
+    let payment = session.tx_params.ok_or(anyhow!(
+        "session {session_id} was saved without the nonce its payment is pinned to"
+    ))?;
+    if session.tx_hash.is_some() {
+        return Err(anyhow!(
+            "payment of session {session_id} was already settled"
+        ));
+    }
+    let locked_at = session.locked_at.as_deref().ok_or(anyhow!(
+        "session {session_id} was saved without the time its payment was locked"
+    ))?;
+    let locked_at = chrono::DateTime::parse_from_rfc3339(locked_at)
+        .map_err(|e| anyhow!("error decoding payment lock time: {e}"))?
+        .with_timezone(&chrono::Utc);
+    let rpc_url = Url::parse(&args.rpc_address).map_err(|e| anyhow!("bad rpc address: {e}"))?;
+    let eth_provider = Ethereum::new(rpc_url, args.chain_id)
+        .await?
+        .with_fee_policy(fee_policy(args.max_fee_gwei, args.bump_after_blocks)?);
+    let name = resolve_wallet_name(args.wallet_name, args.non_interactive)?;
+    let password = resolve_password(args.password, args.non_interactive)?;
+    let wallet = LocalWallet::from_store(&*open_keystore(&args.keystore_dir), &name, password)?;
+
+    if !args.non_interactive && !confirm("Revoke payment authorized to the seller? (y/N): ")? {
+        return Ok(());
+    }
+
+    let grace = Duration::from_secs(args.grace_secs);
+    let tx_hash = refund_unsettled(&eth_provider, &wallet, &payment, locked_at, grace).await?;
+    output.result(
+        &json!({ "tx_hash": format!("{tx_hash:?}") }),
+        format!("payment refunded in tx {tx_hash:?}"),
+    );
+    Ok(())
+}
+
+async fn audit(args: AuditArgs, output: Output) -> anyhow::Result<()> {
+    let bundle: AuditBundle = serde_json::from_slice(
+        &*fs::read(&args.bundle_path).map_err(|e| anyhow!("error reading audit bundle: {e}"))?,
+    )
+    .map_err(|e| anyhow!("error unmarshalling audit bundle: {e}"))?;
+
+    let report = bundle.audit();
+    output.result(&AuditResult::from(&report), &report);
+
+    if !report.passed() {
+        process::exit(ExitCode::Failure as i32);
+    }
+
+    Ok(())
+}
+
+async fn verify(args: VerifyArgs, output: Output) -> anyhow::Result<()> {
+    let verifying_key = read_verifying_key::<_, PairingEngine>(&args.verifying_key)?;
+    let ciphertext =
+        fs::read(&args.ciphertext).map_err(|e| anyhow!("error reading ciphertext: {e}"))?;
+    let proof = fs::read(&args.proof).map_err(|e| anyhow!("error reading proof: {e}"))?;
+    // public key stays a witness of the circuit, so it's only checked to be a valid point.
+    let _ = public_key_from_hex(&args.pubkey)?;
+
+    let verifier = ZkEncryption::from_verifying_key(
+        verifying_key,
+        encryption::Parameters::default_multi(args.chunks),
+    );
+    let pool = verification_pool(args.verify_threads.unwrap_or(0))?;
+    if !pool.install(|| verifier.verify_proof(&proof, &ciphertext))? {
+        if args.explain {
+            let vk_hash = args.vk_hash.as_deref().map(decode_vk_hash).transpose()?;
+            if let Some(failure) = verifier.explain_proof(&proof, &ciphertext, vk_hash.as_ref())? {
+                output.progress(format!("proof of encryption: {failure}"));
+            }
+        }
+        output.result(&json!({ "valid": false }), "FAIL");
+        process::exit(ExitCode::Failure as i32);
+    }
+
+    output.result(&json!({ "valid": true }), "OK");
+    Ok(())
+}
+
+async fn decrypt(args: DecryptArgs, output: Output) -> anyhow::Result<()> {
+    let output_mode = args
+        .output_mode
+        .as_deref()
+        .map(parse_file_mode)
+        .transpose()?;
+    let sk = hex::decode(args.sk.trim()).map_err(|e| anyhow!("error decoding secret key: {e}"))?;
+    let ciphertext =
+        fs::read(&args.ciphertext).map_err(|e| anyhow!("error reading ciphertext: {e}"))?;
+
+    // chunk count only matters for proving, decryption goes over whatever ciphertext has.
+    let decryptor = ZkEncryption::new_decryptor(Default::default());
+    let data = decryptor.decrypt(sk, ciphertext)?;
+    write_with_mode(&args.out, &data, output_mode)?;
+
+    output.result(
+        &json!({ "bytes": data.len(), "path": args.out }),
+        format!("decrypted {} bytes to {}", data.len(), args.out),
+    );
+    Ok(())
+}
+
+async fn manifest(args: ManifestArgs, output: Output) -> anyhow::Result<()> {
+    let sale = fs::read(&args.sale_path).map_err(|e| anyhow!("error reading sale file: {e}"))?;
+    let mut summary = describe_sale(sale)?;
+    if let Some(vk_path) = args.verifying_key {
+        let vk = fs::read(vk_path).map_err(|e| anyhow!("error reading verifying key: {e}"))?;
+        summary = summary.with_verifying_key(&vk)?;
+    }
+
+    match (output.json, args.json) {
+        // global --json prints it as results of other commands are, on a single line.
+        (true, _) => output.result(&summary, ""),
+        (false, true) => println!(
+            "{}",
+            serde_json::to_string_pretty(&summary)
+                .expect("expected sale summary to marshal to json")
+        ),
+        (false, false) => print!("{summary}"),
+    }
     Ok(())
 }
@@ -0,0 +1,190 @@
+use scriptless_zkcp::{AuditReport, ReceiptEntry};
+use serde::Serialize;
+use server::SaleMetadata;
+use std::fmt::Display;
+use std::io;
+use tracing_subscriber::EnvFilter;
+
+/// Where commands print to: progress and results as text on stdout, or in `--json` mode
+/// progress on stderr and results as a single JSON object on stdout, for scripts to parse.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Output {
+    pub json: bool,
+}
+
+impl Output {
+    pub fn new(json: bool) -> Self {
+        Self { json }
+    }
+
+    /// Prints progress `msg` where it doesn't mix with the results.
+    pub fn progress<D: Display>(&self, msg: D) {
+        match self.json {
+            true => eprintln!("{msg}"),
+            false => println!("{msg}"),
+        }
+    }
+
+    /// Prints `result` as JSON object in `--json` mode, as `text` otherwise.
+    pub fn result<T: Serialize, D: Display>(&self, result: &T, text: D) {
+        match self.json {
+            true => println!("{}", render(result)),
+            false => println!("{text}"),
+        }
+    }
+}
+
+fn render<T: Serialize>(result: &T) -> String {
+    serde_json::to_string(result).expect("expected result to marshal to json")
+}
+
+/// Installs subscriber writing logs to stderr, as JSON lines in `--json` mode,
+/// filtered with `RUST_LOG`, info and above by default.
+pub fn init_logging(json: bool) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr);
+    // set as global default only, so that logs of rocket keep going through its own logger.
+    let _ = match json {
+        true => tracing::subscriber::set_global_default(builder.json().finish()),
+        false => tracing::subscriber::set_global_default(builder.finish()),
+    };
+}
+
+/// Result of `buy`, once the data is decrypted and saved.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BuyResult {
+    pub sale_hash: String,
+    pub price: f64,
+    /// Session the purchase was saved as.
+    pub session_id: String,
+    /// Hashes of the payment transaction.
+    pub tx_hashes: Vec<String>,
+    pub data_path: String,
+}
+
+/// Result of `sell`, once the sale is proven and exported, before the daemon starts serving it.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SellResult {
+    pub sale_hash: String,
+    pub cache_dir: String,
+    pub plaintext_commitment: Option<String>,
+    /// Files the sale was written to with `--export-sale`, `--export-proofs` and `--audit-bundle`.
+    pub sale_path: Option<String>,
+    pub proofs_path: Option<String>,
+    pub audit_bundle: Option<String>,
+    /// Ids of the sales listed alongside.
+    pub listings: Vec<String>,
+}
+
+/// Result of `buy --inspect`, the sale as its seller describes it.
+#[derive(Clone, Debug, Serialize)]
+pub struct InspectResult {
+    #[serde(flatten)]
+    pub sale: SaleMetadata,
+    /// Whether the sale's proofs fit local circuits, none without `zk-config.json` or the seller's
+    /// verifying key hash to compare.
+    pub proofs_checkable: Option<bool>,
+}
+
+/// Result of `audit`, each check of the bundle with the reason it failed, if it did.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct AuditResult {
+    pub passed: bool,
+    pub checks: Vec<AuditCheck>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct AuditCheck {
+    pub check: String,
+    pub failure: Option<String>,
+}
+
+impl From<&AuditReport> for AuditResult {
+    fn from(report: &AuditReport) -> Self {
+        Self {
+            passed: report.passed(),
+            checks: report
+                .checks
+                .iter()
+                .map(|(check, failure)| AuditCheck {
+                    check: check.clone(),
+                    failure: failure.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Result of `config init`.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ConfigResult {
+    pub path: String,
+}
+
+/// Result of `receipts verify`, once the chain and signatures of the log check out.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ReceiptsResult {
+    /// Receipts of the sale passed with `--sale-path`, none without it.
+    pub matched: Option<usize>,
+    pub receipts: Vec<ReceiptEntry>,
+}
+
+/// Result of `ceremony` commands.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CeremonyResult {
+    /// Hashes of the transcript's contributions, the one just made or the beacon last.
+    pub contributions: Vec<String>,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::output::{render, AuditResult, BuyResult, SellResult};
+    use scriptless_zkcp::AuditReport;
+
+    #[test]
+    fn test_results_render_as_json() {
+        let bought = BuyResult {
+            sale_hash: hex::encode([1; 32]),
+            price: 0.1,
+            session_id: "session".to_string(),
+            tx_hashes: vec![hex::encode([2; 32])],
+            data_path: "./purchase".to_string(),
+        };
+        let json: serde_json::Value = serde_json::from_str(&render(&bought)).unwrap();
+        assert_eq!(json["session_id"], "session");
+        assert_eq!(json["tx_hashes"][0], hex::encode([2; 32]));
+        assert_eq!(json["data_path"], "./purchase");
+
+        let sold = SellResult {
+            sale_hash: hex::encode([1; 32]),
+            sale_path: Some("./data.sale".to_string()),
+            ..Default::default()
+        };
+        let rendered = render(&sold);
+        assert!(!rendered.contains('\n'), "result must fit a single line");
+        let json: serde_json::Value = serde_json::from_str(&rendered).unwrap();
+        assert_eq!(json["sale_path"], "./data.sale");
+        assert!(json["proofs_path"].is_null());
+    }
+
+    #[test]
+    fn test_audit_result_renders_failures() {
+        let report = AuditReport {
+            checks: vec![
+                ("sale commitment".to_string(), None),
+                (
+                    "proof of encryption".to_string(),
+                    Some("bad proof".to_string()),
+                ),
+            ],
+        };
+        let json: serde_json::Value =
+            serde_json::from_str(&render(&AuditResult::from(&report))).unwrap();
+        assert_eq!(json["passed"], false);
+        assert!(json["checks"][0]["failure"].is_null());
+        assert_eq!(json["checks"][1]["check"], "proof of encryption");
+        assert_eq!(json["checks"][1]["failure"], "bad proof");
+    }
+}
@@ -0,0 +1,126 @@
+use crate::eth_address;
+use anyhow::anyhow;
+use ecdsa_fun::{Signature, ECDSA};
+use ethers::types::Address;
+use secp256kfun::nonce::Deterministic;
+use secp256kfun::{Point, Scalar};
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+
+/// Domain tag of the announcement digest, so that the signature can't be replayed as anything else.
+const ANNOUNCEMENT_TAG: &[u8] = b"zkcp/sale-announcement/v1";
+
+/// Terms of the sale the seller announces, signed with its wallet key so that buyer doesn't take
+/// terms of whoever relays or impersonates the daemon for the seller's.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SaleAnnouncement {
+    pub schema_version: u32,
+    pub price: f64,
+    pub sale_hash: [u8; 32],
+    pub wire_size: Option<usize>,
+    /// RFC 3339 expiry, as announced.
+    pub expires_at: Option<String>,
+    /// Commitment to the plaintext proof of encryption is bound to, see [`crate::zk::VerifiableEncryption::plaintext_commitment`].
+    pub plaintext_commitment: Option<Vec<u8>>,
+}
+
+impl SaleAnnouncement {
+    /// Digest of the announced terms the signature is made over.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(ANNOUNCEMENT_TAG);
+        hasher.update(self.schema_version.to_le_bytes());
+        hasher.update(self.price.to_le_bytes());
+        hasher.update(self.sale_hash);
+        hasher.update(
+            self.wire_size
+                .map_or(u64::MAX, |size| size as u64)
+                .to_le_bytes(),
+        );
+        hasher.update(self.expires_at.as_deref().unwrap_or_default().as_bytes());
+        // absent from digests of announcements made before sales were committed to, keeping them valid.
+        if let Some(commitment) = &self.plaintext_commitment {
+            hasher.update(commitment);
+        }
+        hasher.finalize().into()
+    }
+
+    /// Hex-encoded ECDSA signature of `sk` over [`Self::digest`].
+    pub fn sign(&self, sk: &Scalar) -> String {
+        let ecdsa = ECDSA::new(Deterministic::<Sha256>::default());
+        hex::encode(ecdsa.sign(sk, &self.digest()).to_bytes())
+    }
+
+    /// Fails unless `signature` over the announcement is made by `seller_pk`,
+    /// whose address is `expected_seller`.
+    pub fn verify(
+        &self,
+        seller_pk: &str,
+        signature: &str,
+        expected_seller: Address,
+    ) -> anyhow::Result<()> {
+        let seller_pk =
+            Point::from_str(seller_pk).map_err(|e| anyhow!("bad seller public key: {e}"))?;
+        let signature = hex::decode(signature)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .and_then(Signature::from_bytes)
+            .ok_or(anyhow!("error decoding announcement signature"))?;
+
+        if !ECDSA::verify_only().verify(&seller_pk, &self.digest(), &signature) {
+            return Err(anyhow!("announcement signature is invalid"));
+        }
+        let seller = eth_address(&seller_pk);
+        if seller != expected_seller {
+            return Err(anyhow!(
+                "announcement is signed by {seller:?}, not expected seller {expected_seller:?}"
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{eth_address, keypair_gen, SaleAnnouncement};
+
+    #[test]
+    fn test_verify_announcement() {
+        let (seller_sk, seller_pk) = keypair_gen();
+        let seller = eth_address(&seller_pk);
+        let announcement = SaleAnnouncement {
+            schema_version: 1,
+            price: 0.1,
+            sale_hash: [7; 32],
+            wire_size: Some(4096),
+            expires_at: None,
+            plaintext_commitment: Some(vec![9; 32]),
+        };
+        let signature = announcement.sign(&seller_sk);
+        announcement
+            .verify(&seller_pk.to_string(), &signature, seller)
+            .unwrap();
+
+        let mut tampered = announcement.clone();
+        tampered.price = 0.01;
+        assert!(tampered
+            .verify(&seller_pk.to_string(), &signature, seller)
+            .is_err());
+        let mut tampered = announcement.clone();
+        tampered.plaintext_commitment = Some(vec![0; 32]);
+        assert!(tampered
+            .verify(&seller_pk.to_string(), &signature, seller)
+            .is_err());
+
+        // validly signed by another key than the expected seller's.
+        let (other_sk, other_pk) = keypair_gen();
+        let forged = announcement.sign(&other_sk);
+        assert!(announcement
+            .verify(&other_pk.to_string(), &forged, seller)
+            .is_err());
+        assert!(announcement
+            .verify(&seller_pk.to_string(), &forged, seller)
+            .is_err());
+    }
+}
@@ -0,0 +1,356 @@
+use crate::cipher_host::ZSTD_MAGIC;
+use crate::{PairingEngine, SALE_MAGIC};
+use anyhow::anyhow;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Magic bytes prefixing proving keys written by [`crate::write_circuit_artifacts`].
+pub const PROVING_KEY_MAGIC: [u8; 4] = *b"ZKPK";
+/// Magic bytes prefixing verifying keys written by [`crate::write_circuit_artifacts`].
+pub const VERIFYING_KEY_MAGIC: [u8; 4] = *b"ZKVK";
+/// Magic bytes of the header tagging artifact with the pairing curve it's made for, placed before its own magic.
+pub const CURVE_TAG_MAGIC: [u8; 4] = *b"ZKCT";
+/// Format version of keys written by [`crate::write_circuit_artifacts`], placed right after their curve tag.
+/// Keys tagged before the version was introduced are read as version 0.
+/// Version 2 follows it with [`KeyFlags`] and the hash of the circuit the key is made for.
+pub const KEY_FORMAT_VERSION: u8 = 2;
+/// Flag of the key written with curve points compressed, as [`crate::write_circuit_artifacts`] does.
+pub const KEY_COMPRESSED_FLAG: u8 = 1;
+
+/// Pairing curve the artifact is made for, as recorded in its header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CurveTag {
+    Bls12_381,
+    Bn254,
+}
+
+impl CurveTag {
+    fn id(self) -> u8 {
+        match self {
+            CurveTag::Bls12_381 => 1,
+            CurveTag::Bn254 => 2,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(CurveTag::Bls12_381),
+            2 => Some(CurveTag::Bn254),
+            _ => None,
+        }
+    }
+}
+
+impl Display for CurveTag {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                CurveTag::Bls12_381 => "BLS12-381",
+                CurveTag::Bn254 => "BN254",
+            }
+        )
+    }
+}
+
+impl FromStr for CurveTag {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().replace('-', "_").as_str() {
+            "bls12_381" => Ok(CurveTag::Bls12_381),
+            "bn254" => Ok(CurveTag::Bn254),
+            curve => Err(anyhow!(
+                "unknown curve '{curve}', expected bls12_381 or bn254"
+            )),
+        }
+    }
+}
+
+/// Pairing engine artifacts made for which are tagged with [`CurveTag`].
+pub trait TaggedCurve {
+    const CURVE_TAG: CurveTag;
+}
+
+impl TaggedCurve for ark_bls12_381::Bls12_381 {
+    const CURVE_TAG: CurveTag = CurveTag::Bls12_381;
+}
+
+impl TaggedCurve for ark_bn254::Bn254 {
+    const CURVE_TAG: CurveTag = CurveTag::Bn254;
+}
+
+/// Fails unless circuits of this build are made over `curve`, which is picked by `bn254` feature.
+pub fn ensure_build_curve(curve: CurveTag) -> anyhow::Result<()> {
+    let built = PairingEngine::CURVE_TAG;
+    if curve == built {
+        return Ok(());
+    }
+    let hint = match curve {
+        CurveTag::Bn254 => "with `bn254` feature",
+        CurveTag::Bls12_381 => "without `bn254` feature",
+    };
+    Err(anyhow!(
+        "this build makes circuits over {built} curve, rebuild {hint} for {curve}"
+    ))
+}
+
+/// Prefixes `bytes` with the header tagging them with `curve`.
+pub fn tag_curve(curve: CurveTag, bytes: &[u8]) -> Vec<u8> {
+    [&CURVE_TAG_MAGIC[..], &[curve.id()], bytes].concat()
+}
+
+/// Splits the curve tag off `bytes`, artifacts without one are passed as is.
+pub fn split_curve_tag(bytes: &[u8]) -> (Option<CurveTag>, &[u8]) {
+    match bytes.strip_prefix(&CURVE_TAG_MAGIC) {
+        Some([id, rest @ ..]) => match CurveTag::from_id(*id) {
+            Some(curve) => (Some(curve), rest),
+            None => (None, bytes),
+        },
+        _ => (None, bytes),
+    }
+}
+
+/// Header of the key artifact: curve it's built for and version of its format,
+/// followed since version 2 by its encoding flags and hash of the circuit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyHeader {
+    pub curve: CurveTag,
+    pub version: u8,
+    /// Whether curve points are compressed, as they are in keys written before the flag was introduced.
+    pub compressed: bool,
+    /// Hash of the compressed verifying key of the circuit, see [`crate::circuit_hash`].
+    pub circuit_hash: Option<[u8; 32]>,
+}
+
+impl KeyHeader {
+    fn legacy(curve: CurveTag, version: u8) -> Self {
+        Self {
+            curve,
+            version,
+            compressed: true,
+            circuit_hash: None,
+        }
+    }
+}
+
+/// Prefixes compressed key `bytes`, magic included, with the header tagging them with `curve`,
+/// [`KEY_FORMAT_VERSION`] and `circuit_hash`.
+pub fn tag_key(curve: CurveTag, circuit_hash: &[u8; 32], bytes: &[u8]) -> Vec<u8> {
+    let header = [KEY_FORMAT_VERSION, KEY_COMPRESSED_FLAG];
+    tag_curve(curve, &[&header[..], circuit_hash, bytes].concat())
+}
+
+/// Splits the key header off `bytes`, keys without one are passed as is.
+pub fn split_key_header(bytes: &[u8]) -> (Option<KeyHeader>, &[u8]) {
+    match split_curve_tag(bytes) {
+        (Some(curve), rest)
+            if rest.starts_with(&PROVING_KEY_MAGIC) || rest.starts_with(&VERIFYING_KEY_MAGIC) =>
+        {
+            (Some(KeyHeader::legacy(curve, 0)), rest)
+        }
+        (Some(curve), [version, flags, rest @ ..]) if *version >= 2 && rest.len() >= 32 => {
+            let (circuit_hash, rest) = rest.split_at(32);
+            let header = KeyHeader {
+                curve,
+                version: *version,
+                compressed: flags & KEY_COMPRESSED_FLAG != 0,
+                circuit_hash: circuit_hash.try_into().ok(),
+            };
+            (Some(header), rest)
+        }
+        (Some(curve), [version, rest @ ..]) => (Some(KeyHeader::legacy(curve, *version)), rest),
+        _ => (None, bytes),
+    }
+}
+
+/// Key is built for another curve than the one it's read for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WrongKeyCurve {
+    pub expected: CurveTag,
+    pub found: CurveTag,
+}
+
+impl Display for WrongKeyCurve {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "key was built for {} curve, expected {}",
+            self.found, self.expected
+        )
+    }
+}
+
+impl std::error::Error for WrongKeyCurve {}
+
+/// Key is written in a format newer than this build reads.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnsupportedKeyVersion {
+    pub version: u8,
+}
+
+impl Display for UnsupportedKeyVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "key format version {} is not supported, expected at most {KEY_FORMAT_VERSION}",
+            self.version
+        )
+    }
+}
+
+impl std::error::Error for UnsupportedKeyVersion {}
+
+/// Fails if the key in `bytes` has a header of another curve than `E`'s or of the unsupported format version,
+/// otherwise returns the header. Keys without header are let through, since they may have been written
+/// before it was introduced.
+pub(crate) fn ensure_key_header<E: TaggedCurve>(bytes: &[u8]) -> anyhow::Result<Option<KeyHeader>> {
+    match split_key_header(bytes).0 {
+        Some(header) if header.version > KEY_FORMAT_VERSION => Err(UnsupportedKeyVersion {
+            version: header.version,
+        }
+        .into()),
+        Some(header) if header.curve != E::CURVE_TAG => Err(WrongKeyCurve {
+            expected: E::CURVE_TAG,
+            found: header.curve,
+        }
+        .into()),
+        header => Ok(header),
+    }
+}
+
+/// Key doesn't belong to the circuit recorded in its header, eg. it was spliced from artifacts of another build.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CircuitMismatch {
+    pub expected: [u8; 32],
+    pub found: [u8; 32],
+}
+
+impl Display for CircuitMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "key is made for circuit {}, but its header records circuit {}",
+            hex::encode(self.found),
+            hex::encode(self.expected)
+        )
+    }
+}
+
+impl std::error::Error for CircuitMismatch {}
+
+/// Fails if `header` records the hash of another circuit than `found`.
+pub fn ensure_same_circuit(header: Option<&KeyHeader>, found: [u8; 32]) -> anyhow::Result<()> {
+    match header.and_then(|header| header.circuit_hash) {
+        Some(expected) if expected != found => Err(CircuitMismatch { expected, found }.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Proof is made for another curve than the verifying key it's checked against.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CurveMismatch {
+    pub proof: CurveTag,
+    pub verifying_key: CurveTag,
+}
+
+impl Display for CurveMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "proof is made for {} curve, but verifying key is for {}",
+            self.proof, self.verifying_key
+        )
+    }
+}
+
+impl std::error::Error for CurveMismatch {}
+
+/// Fails if both proof and verifying key are tagged and their curves differ.
+pub fn ensure_same_curve(
+    proof: Option<CurveTag>,
+    verifying_key: Option<CurveTag>,
+) -> Result<(), CurveMismatch> {
+    match (proof, verifying_key) {
+        (Some(proof), Some(verifying_key)) if proof != verifying_key => Err(CurveMismatch {
+            proof,
+            verifying_key,
+        }),
+        _ => Ok(()),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArtifactType {
+    ProvingKey,
+    VerifyingKey,
+    Params,
+    Ciphertext,
+    Unknown,
+}
+
+impl Display for ArtifactType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ArtifactType::ProvingKey => "proving key",
+                ArtifactType::VerifyingKey => "verifying key",
+                ArtifactType::Params => "zk config",
+                ArtifactType::Ciphertext => "ciphertext",
+                ArtifactType::Unknown => "unrelated data",
+            }
+        )
+    }
+}
+
+/// File passed to an artifact reader holds an artifact of another type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WrongArtifactType {
+    pub expected: ArtifactType,
+    pub found: ArtifactType,
+}
+
+impl Display for WrongArtifactType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "expected {}, found {}", self.expected, self.found)
+    }
+}
+
+impl std::error::Error for WrongArtifactType {}
+
+/// Detects type of the artifact by its leading bytes.
+pub fn sniff_artifact(bytes: &[u8]) -> ArtifactType {
+    let (_, bytes) = split_key_header(bytes);
+    if bytes.starts_with(&PROVING_KEY_MAGIC) {
+        return ArtifactType::ProvingKey;
+    }
+    if bytes.starts_with(&VERIFYING_KEY_MAGIC) {
+        return ArtifactType::VerifyingKey;
+    }
+    if bytes.starts_with(&SALE_MAGIC) || bytes.starts_with(&ZSTD_MAGIC) {
+        return ArtifactType::Ciphertext;
+    }
+
+    match serde_json::from_slice::<serde_json::Value>(bytes) {
+        Ok(json) if json.get("ciphertext").is_some() => ArtifactType::Ciphertext,
+        Ok(json) if json.get("data_encryption_limit").is_some() => ArtifactType::Params,
+        _ => ArtifactType::Unknown,
+    }
+}
+
+/// Strips key header and magic of the `expected` artifact type from `bytes`.
+/// Artifacts of the unknown type are passed as is, since they may have been written before magic was introduced.
+pub(crate) fn strip_artifact_magic(
+    bytes: &[u8],
+    expected: ArtifactType,
+) -> Result<&[u8], WrongArtifactType> {
+    let (_, bytes) = split_key_header(bytes);
+    match sniff_artifact(bytes) {
+        ArtifactType::Unknown => Ok(bytes),
+        found if found == expected => Ok(&bytes[4..]),
+        found => Err(WrongArtifactType { expected, found }),
+    }
+}
@@ -0,0 +1,115 @@
+use anyhow::anyhow;
+use ecdsa_fun::{Signature, ECDSA};
+use ethers::types::Address;
+use secp256kfun::Point;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Domain tag of the attestation digest, so that the signature can't be replayed as anything else.
+const ATTESTATION_TAG: &[u8] = b"zkcp/reputation-attestation/v1";
+
+/// Seller's track record as signed by a party the buyer trusts, who issues it out of band.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReputationAttestation {
+    pub seller_address: Address,
+    pub successful_sales: u64,
+    pub disputes: u64,
+    /// Hex-encoded ECDSA signature of the attester over [`Self::digest`].
+    pub signature: String,
+}
+
+impl ReputationAttestation {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let buf = fs::read(path).map_err(|e| anyhow!("error reading attestation: {e}"))?;
+        serde_json::from_slice(&buf).map_err(|e| anyhow!("error decoding attestation: {e}"))
+    }
+
+    /// Digest of the attested stats the signature is made over.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(ATTESTATION_TAG);
+        hasher.update(self.seller_address.as_bytes());
+        hasher.update(self.successful_sales.to_le_bytes());
+        hasher.update(self.disputes.to_le_bytes());
+        hasher.finalize().into()
+    }
+
+    /// Fails unless attestation is signed by `attester` and is about `seller_address`.
+    pub fn verify(&self, attester: &Point, seller_address: Address) -> anyhow::Result<()> {
+        let signature = hex::decode(&self.signature)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .and_then(Signature::from_bytes)
+            .ok_or(anyhow!("error decoding attestation signature"))?;
+
+        if !ECDSA::verify_only().verify(attester, &self.digest(), &signature) {
+            return Err(anyhow!("attestation signature is invalid"));
+        }
+        if self.seller_address != seller_address {
+            return Err(anyhow!(
+                "attestation is about {:?}, not seller {seller_address:?}",
+                self.seller_address
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for ReputationAttestation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} successful sales, {} disputes",
+            self.successful_sales, self.disputes
+        )
+    }
+}
+
+/// Parses hex-encoded public key of the attester.
+pub fn attester_from_hex(hex: &str) -> anyhow::Result<Point> {
+    Point::from_str(hex).map_err(|e| anyhow!("bad attester public key: {e}"))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{keypair_gen, ReputationAttestation};
+    use ecdsa_fun::ECDSA;
+    use ethers::types::Address;
+    use secp256kfun::nonce::Deterministic;
+    use sha2::Sha256;
+
+    #[test]
+    fn test_verify_attestation() {
+        let (attester_sk, attester_pk) = keypair_gen();
+        let seller = Address::random();
+        let mut attestation = ReputationAttestation {
+            seller_address: seller,
+            successful_sales: 42,
+            disputes: 1,
+            signature: String::new(),
+        };
+        let ecdsa = ECDSA::new(Deterministic::<Sha256>::default());
+        attestation.signature =
+            hex::encode(ecdsa.sign(&attester_sk, &attestation.digest()).to_bytes());
+
+        attestation.verify(&attester_pk, seller).unwrap();
+        assert_eq!(attestation.to_string(), "42 successful sales, 1 disputes");
+
+        let encoded = serde_json::to_vec(&attestation).unwrap();
+        let decoded: ReputationAttestation = serde_json::from_slice(&encoded).unwrap();
+        assert_eq!(decoded, attestation);
+
+        let mut tampered = attestation.clone();
+        tampered.disputes = 0;
+        assert!(tampered.verify(&attester_pk, seller).is_err());
+
+        let (_, other_pk) = keypair_gen();
+        assert!(attestation.verify(&other_pk, seller).is_err());
+        assert!(attestation.verify(&attester_pk, Address::random()).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use crate::zk::{
+    explain_verification_failure, PropertyVerifier, VerifiableEncryption, ZkEncryption,
+    ZkSampleEntries,
+};
+use crate::PairingEngine;
+use anyhow::anyhow;
+use ark_groth16::VerifyingKey;
+use circuits::{ark_to_bytes, encryption};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// Public bundle letting a third party confirm that sale ciphertext is backed by valid proofs.
+/// Contains no secret keys, so can be shared freely.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AuditBundle {
+    pub sale: VerifiableEncryption,
+    /// Commitment to the sale announced by the seller, see [`VerifiableEncryption::hash`].
+    pub sale_hash: String,
+    pub data_encryption_limit: usize,
+    pub encryption_vk: Vec<u8>,
+    pub property_vk: Vec<u8>,
+    pub encryption_inputs: Vec<Vec<u8>>,
+    /// Inputs shared by all proofs of property, each is prefixed with the proof's own arguments.
+    pub property_inputs: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct AuditReport {
+    /// Name of each performed check and the reason it failed, if it did.
+    pub checks: Vec<(String, Option<String>)>,
+}
+
+impl AuditBundle {
+    pub fn new(
+        sale: VerifiableEncryption,
+        data_encryption_limit: usize,
+        encryption_vk: &VerifyingKey<PairingEngine>,
+        property_vk: &VerifyingKey<PairingEngine>,
+    ) -> anyhow::Result<Self> {
+        let encryption_inputs = encryption_inputs(&sale, data_encryption_limit)?;
+        let property_inputs = property_inputs(&sale, data_encryption_limit)?;
+
+        Ok(Self {
+            sale_hash: hex::encode(sale.hash()),
+            sale,
+            data_encryption_limit,
+            encryption_vk: ark_to_bytes(encryption_vk.clone())
+                .map_err(|e| anyhow!("error encoding verifying key: {e}"))?,
+            property_vk: ark_to_bytes(property_vk.clone())
+                .map_err(|e| anyhow!("error encoding verifying key: {e}"))?,
+            encryption_inputs,
+            property_inputs,
+        })
+    }
+
+    /// Verifies sale commitment, derivation of public inputs, and all proofs in the bundle.
+    pub fn audit(&self) -> AuditReport {
+        let n = self.data_encryption_limit;
+        let mut checks = vec![];
+
+        let sale_hash = hex::encode(self.sale.hash());
+        checks.push((
+            "sale commitment".to_string(),
+            (sale_hash != self.sale_hash)
+                .then(|| format!("announced {}, computed {sale_hash}", self.sale_hash)),
+        ));
+
+        checks.push((
+            "encryption public inputs".to_string(),
+            check_inputs(encryption_inputs(&self.sale, n), &self.encryption_inputs),
+        ));
+        checks.push((
+            "proof of encryption".to_string(),
+            explain_verification_failure::<PairingEngine>(
+                &self.encryption_vk,
+                None,
+                &self.sale.proof_of_encryption,
+                &self.encryption_inputs,
+            )
+            .map(|failure| failure.to_string()),
+        ));
+
+        checks.push((
+            "property public inputs".to_string(),
+            check_inputs(property_inputs(&self.sale, n), &self.property_inputs),
+        ));
+        for (i, proof) in self.sale.proofs_of_property.iter().enumerate() {
+            let public_inputs = proof
+                .arguments
+                .iter()
+                .map(|(_, arg)| arg.clone())
+                .chain(self.property_inputs.iter().cloned())
+                .collect::<Vec<_>>();
+            checks.push((
+                format!("proof of property #{i}"),
+                explain_verification_failure::<PairingEngine>(
+                    &self.property_vk,
+                    None,
+                    &proof.proof,
+                    &public_inputs,
+                )
+                .map(|failure| failure.to_string()),
+            ));
+        }
+
+        AuditReport { checks }
+    }
+}
+
+impl AuditReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|(_, failure)| failure.is_none())
+    }
+}
+
+impl Display for AuditReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (check, failure) in &self.checks {
+            match failure {
+                None => writeln!(f, "[pass] {check}")?,
+                Some(reason) => writeln!(f, "[fail] {check}: {reason}")?,
+            }
+        }
+
+        write!(f, "{}", if self.passed() { "PASS" } else { "FAIL" })
+    }
+}
+
+fn encryption_inputs(sale: &VerifiableEncryption, n: usize) -> anyhow::Result<Vec<Vec<u8>>> {
+    ZkEncryption::new_inner(encryption::Parameters::default_multi(n))
+        .public_inputs(&sale.ciphertext)?
+        .into_iter()
+        .map(|input| ark_to_bytes(input).map_err(|e| anyhow!("error encoding public input: {e}")))
+        .collect()
+}
+
+fn property_inputs(sale: &VerifiableEncryption, n: usize) -> anyhow::Result<Vec<Vec<u8>>> {
+    ZkSampleEntries::new_inner(n)
+        .prepare_public_inputs(&sale.ciphertext)?
+        .into_iter()
+        .map(|input| ark_to_bytes(input).map_err(|e| anyhow!("error encoding public input: {e}")))
+        .collect()
+}
+
+fn check_inputs(derived: anyhow::Result<Vec<Vec<u8>>>, bundled: &[Vec<u8>]) -> Option<String> {
+    match derived {
+        Ok(derived) if derived == bundled => None,
+        Ok(_) => Some("bundled inputs don't match those derived from ciphertext".to_string()),
+        Err(e) => Some(format!("error deriving inputs from ciphertext: {e}")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::zk::{VerifiableEncryption, ZkEncryption};
+    use crate::AuditBundle;
+    use circuits::encryption;
+
+    #[test]
+    fn test_audit_bundle() {
+        let mut rng = rand::thread_rng();
+        let n = 2;
+        let build_dir = std::env::temp_dir().join("zkcp_test_audit");
+        let params = || encryption::Parameters::default_multi(n);
+
+        let (_, vk) = ZkEncryption::new(&build_dir, params())
+            .compile(&mut rng)
+            .unwrap();
+        let zk = ZkEncryption::new(&build_dir, params());
+        let (_, pk) = zk.keygen(&mut rng).unwrap();
+        let (ciphertext, proof_of_encryption) = zk.encrypt(vec![7; 64], pk, &mut rng).unwrap();
+        let sale = VerifiableEncryption {
+            ciphertext,
+            proof_of_encryption,
+            proofs_of_property: vec![],
+            plaintext_commitment: None,
+        };
+
+        let bundle = AuditBundle::new(sale.clone(), n, &vk, &vk).unwrap();
+        let report = bundle.audit();
+        assert!(report.passed(), "{report}");
+
+        // verifying key of independent setup of the same circuit.
+        let (_, other_vk) = ZkEncryption::new(&build_dir, params())
+            .compile(&mut rng)
+            .unwrap();
+        let swapped = AuditBundle::new(sale, n, &other_vk, &vk).unwrap();
+        let report = swapped.audit();
+        assert!(!report.passed());
+        assert_eq!(
+            report.checks[2],
+            (
+                "proof of encryption".to_string(),
+                Some("pairing check failed".to_string())
+            )
+        );
+    }
+}
@@ -0,0 +1,518 @@
+use crate::{CurveVar, Fq, ProjectiveCurve};
+use anyhow::anyhow;
+use ark_ff::Zero;
+use ark_serialize::CanonicalSerialize;
+use circuits::{
+    ark_from_bytes, encryption, plaintext_chunks_to_bytes_sized, Ciphertext, EncryptCircuit,
+    SecretKey,
+};
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+/// Layout of the encoded ciphertext blob: `c1` point, u64 number of chunks, then the chunks of equal size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChunkOffsets {
+    pub header_len: u64,
+    pub chunk_len: u64,
+    pub chunks: usize,
+}
+
+impl ChunkOffsets {
+    /// Layout of the blob of `ciphertext_len` bytes, as recorded in [`crate::SaleManifest`].
+    pub fn new(ciphertext_len: usize) -> Self {
+        let point_len = ProjectiveCurve::zero().serialized_size() as u64;
+        let header_len = point_len + 8;
+        let chunk_len = Fq::zero().serialized_size() as u64;
+
+        Self {
+            header_len,
+            chunk_len,
+            chunks: (ciphertext_len as u64).saturating_sub(header_len) as usize
+                / chunk_len as usize,
+        }
+    }
+
+    pub fn offset(&self, index: usize) -> Option<u64> {
+        (index < self.chunks).then(|| self.header_len + index as u64 * self.chunk_len)
+    }
+}
+
+/// Reads ciphertext chunks from the blob on demand instead of loading it into memory as a whole.
+pub struct BlobReader<R> {
+    inner: R,
+    offsets: ChunkOffsets,
+}
+
+impl BlobReader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P, offsets: ChunkOffsets) -> anyhow::Result<Self> {
+        let file = File::open(path).map_err(|e| anyhow!("error opening ciphertext blob: {e}"))?;
+        Ok(Self::new(BufReader::new(file), offsets))
+    }
+}
+
+impl<R: Read + Seek> BlobReader<R> {
+    pub fn new(inner: R, offsets: ChunkOffsets) -> Self {
+        Self { inner, offsets }
+    }
+
+    /// Reads encoded chunk at `index`.
+    pub fn read_chunk(&mut self, index: usize) -> anyhow::Result<Vec<u8>> {
+        let offset = self
+            .offsets
+            .offset(index)
+            .ok_or(anyhow!("chunk {index} is out of the blob bounds"))?;
+        self.read_at(offset, self.offsets.chunk_len as usize)
+    }
+
+    /// Reads chunks in `range` together with the `c1` point, so that result can be passed
+    /// to [`circuits::EncryptCircuit::decrypt`] to recover just that part of the plaintext.
+    pub fn read_range(
+        &mut self,
+        range: Range<usize>,
+    ) -> anyhow::Result<Ciphertext<ProjectiveCurve>> {
+        if range.end > self.offsets.chunks {
+            return Err(anyhow!(
+                "range {range:?} is out of the blob bounds of {} chunks",
+                self.offsets.chunks
+            ));
+        }
+
+        let c1 = self.read_c1()?;
+        let c2 = range
+            .map(|i| self.read_chunk_field(i))
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok((c1, c2))
+    }
+
+    fn read_c1(&mut self) -> anyhow::Result<ProjectiveCurve> {
+        let c1 = self.read_at(0, self.offsets.header_len as usize - 8)?;
+        ark_from_bytes(c1).map_err(|e| anyhow!("error casting ciphertext: {e}"))
+    }
+
+    fn read_chunk_field(&mut self, index: usize) -> anyhow::Result<Fq> {
+        let chunk = self.read_chunk(index)?;
+        ark_from_bytes(chunk).map_err(|e| anyhow!("error casting chunk {index}: {e}"))
+    }
+
+    fn read_at(&mut self, offset: u64, len: usize) -> anyhow::Result<Vec<u8>> {
+        self.inner
+            .seek(SeekFrom::Start(offset))
+            .map_err(|e| anyhow!("error seeking ciphertext blob: {e}"))?;
+        let mut buf = vec![0; len];
+        self.inner
+            .read_exact(&mut buf)
+            .map_err(|e| anyhow!("error reading ciphertext blob: {e}"))?;
+        Ok(buf)
+    }
+}
+
+/// Tells whether `sk` decrypts `blob` without decrypting all of it: the first chunk alone is decrypted
+/// and must unpack into `chunk_size` bytes. A wrong key passes that only by chance,
+/// which is the smaller the narrower chunks are against the field element.
+pub fn can_decrypt_blob<R: Read + Seek>(
+    blob: &mut BlobReader<R>,
+    sk: SecretKey<ProjectiveCurve>,
+    params: &encryption::Parameters<ProjectiveCurve>,
+    chunk_size: usize,
+) -> anyhow::Result<bool> {
+    if blob.offsets.chunks == 0 {
+        return Ok(true);
+    }
+
+    let ciphertext = blob.read_range(0..1)?;
+    let plaintext = EncryptCircuit::<ProjectiveCurve, CurveVar>::decrypt(ciphertext, sk, params)?;
+    Ok(plaintext_chunks_to_bytes_sized::<ProjectiveCurve>(plaintext, chunk_size).is_ok())
+}
+
+/// Number of chunks decrypted at once by [`DecryptingReader`].
+const DECRYPT_BATCH: usize = 64;
+
+/// Where [`DecryptingReader`] takes ciphertext chunks from.
+enum ChunkSource<R> {
+    Blob(BlobReader<R>),
+    /// Chunks read ahead on a background thread, see [`DecryptingReader::with_prefetch`].
+    Prefetched {
+        c1: ProjectiveCurve,
+        chunks: Receiver<anyhow::Result<Fq>>,
+    },
+}
+
+/// Yields plaintext of the blob as [`Read`] source, decrypting chunks in batches as they are consumed.
+pub struct DecryptingReader<R> {
+    source: ChunkSource<R>,
+    chunks: usize,
+    sk: SecretKey<ProjectiveCurve>,
+    params: encryption::Parameters<ProjectiveCurve>,
+    chunk_size: usize,
+    /// Plaintext bytes left to yield, padding of the final chunk is trimmed once it reaches zero.
+    remaining: usize,
+    next_chunk: usize,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: Read + Seek> DecryptingReader<R> {
+    /// Reader over the whole blob, `chunk_size` being the one recorded in [`crate::SaleManifest`].
+    pub fn new(
+        blob: BlobReader<R>,
+        sk: SecretKey<ProjectiveCurve>,
+        params: encryption::Parameters<ProjectiveCurve>,
+        chunk_size: usize,
+    ) -> Self {
+        let chunks = blob.offsets.chunks;
+        Self {
+            source: ChunkSource::Blob(blob),
+            chunks,
+            sk,
+            params,
+            chunk_size,
+            remaining: chunks * chunk_size,
+            next_chunk: 0,
+            buf: vec![],
+            pos: 0,
+        }
+    }
+
+    /// Trims the output to `len` bytes, dropping zero padding of the final chunk.
+    pub fn with_len(mut self, len: usize) -> Self {
+        self.remaining = self.remaining.min(len);
+        self
+    }
+
+    /// Reads up to `chunks` ciphertext chunks ahead on a background thread while the current batch
+    /// is being decrypted, overlapping reads from a slow source with decryption. Zero disables it.
+    pub fn with_prefetch(mut self, chunks: usize) -> anyhow::Result<Self>
+    where
+        R: Send + 'static,
+    {
+        let mut blob = match self.source {
+            ChunkSource::Blob(blob) if chunks > 0 => blob,
+            source => {
+                self.source = source;
+                return Ok(self);
+            }
+        };
+
+        let c1 = blob.read_c1()?;
+        let (tx, rx) = mpsc::sync_channel(chunks);
+        let (start, end) = (self.next_chunk, self.chunks);
+        thread::spawn(move || {
+            for i in start..end {
+                let chunk = blob.read_chunk_field(i);
+                let failed = chunk.is_err();
+                // receiver is gone once the reader is dropped.
+                if tx.send(chunk).is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        self.source = ChunkSource::Prefetched { c1, chunks: rx };
+        Ok(self)
+    }
+
+    fn decrypt_batch(&mut self) -> anyhow::Result<()> {
+        let end = (self.next_chunk + DECRYPT_BATCH).min(self.chunks);
+        let ciphertext = match &mut self.source {
+            ChunkSource::Blob(blob) => blob.read_range(self.next_chunk..end)?,
+            ChunkSource::Prefetched { c1, chunks } => {
+                let c2 = (self.next_chunk..end)
+                    .map(|i| {
+                        chunks
+                            .recv()
+                            .map_err(|_| anyhow!("prefetching stopped before chunk {i}"))?
+                    })
+                    .collect::<anyhow::Result<_>>()?;
+                (*c1, c2)
+            }
+        };
+        let plaintext = EncryptCircuit::<ProjectiveCurve, CurveVar>::decrypt(
+            ciphertext,
+            self.sk,
+            &self.params,
+        )?;
+
+        self.buf = plaintext_chunks_to_bytes_sized::<ProjectiveCurve>(plaintext, self.chunk_size)?;
+        self.pos = 0;
+        self.next_chunk = end;
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Read for DecryptingReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 || out.is_empty() {
+            return Ok(0);
+        }
+        if self.pos == self.buf.len() {
+            self.decrypt_batch()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        }
+
+        let n = out.len().min(self.buf.len() - self.pos).min(self.remaining);
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        self.remaining -= n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        can_decrypt_blob, ensure_content_hash, BlobReader, ChunkOffsets, CurveVar,
+        DecryptingReader, ProjectiveCurve,
+    };
+    use circuits::{ark_to_bytes, bytes_to_plaintext_chunks_sized, encryption, EncryptCircuit};
+    use sha2::{Digest, Sha256};
+    use std::fs::{self, File};
+    use std::io::{Read, Seek, SeekFrom};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    /// Records every seek and number of bytes read through it.
+    struct TracingReader {
+        inner: File,
+        seeks: Vec<u64>,
+        read: usize,
+    }
+
+    impl Read for TracingReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.inner.read(buf)?;
+            self.read += n;
+            Ok(n)
+        }
+    }
+
+    impl Seek for TracingReader {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            let offset = self.inner.seek(pos)?;
+            self.seeks.push(offset);
+            Ok(offset)
+        }
+    }
+
+    #[test]
+    fn test_read_single_chunk() {
+        let offsets = ChunkOffsets::new(40 + 32 * 10_000);
+        let blob = (0..offsets.chunks)
+            .flat_map(|i| (i as u32).to_le_bytes().repeat(8))
+            .collect::<Vec<_>>();
+        let path = std::env::temp_dir().join("zkcp_test_blob");
+        fs::write(&path, [vec![0; 40], blob].concat()).unwrap();
+
+        let mut reader = BlobReader::new(
+            TracingReader {
+                inner: File::open(&path).unwrap(),
+                seeks: vec![],
+                read: 0,
+            },
+            offsets,
+        );
+        let chunk = reader.read_chunk(5000).unwrap();
+
+        assert_eq!(chunk, 5000u32.to_le_bytes().repeat(8));
+        assert_eq!(reader.inner.seeks, vec![offsets.offset(5000).unwrap()]);
+        assert_eq!(reader.inner.read, 32);
+        assert!(reader.read_chunk(offsets.chunks).is_err());
+    }
+
+    #[test]
+    fn test_read_range_decrypts() {
+        type Enc = EncryptCircuit<ProjectiveCurve, CurveVar>;
+        let mut rng = rand::thread_rng();
+        let params = encryption::Parameters::<ProjectiveCurve>::default_multi(16);
+        let msg = (0..16u64).map(Into::into).collect::<Vec<_>>();
+        let (sk, pk) = Enc::keygen(&mut rng).unwrap();
+        let cipher = Enc::new(pk, msg.clone(), params.clone(), &mut rng)
+            .unwrap()
+            .resulted_ciphertext;
+
+        let encoded = ark_to_bytes(cipher).unwrap();
+        let path = std::env::temp_dir().join("zkcp_test_blob_range");
+        fs::write(&path, &encoded).unwrap();
+
+        let mut reader = BlobReader::open(&path, ChunkOffsets::new(encoded.len())).unwrap();
+        let range = reader.read_range(4..7).unwrap();
+        assert_eq!(Enc::decrypt(range, sk, &params).unwrap(), msg[4..7]);
+        assert!(reader.read_range(10..17).is_err());
+    }
+
+    #[test]
+    fn test_decrypting_reader() {
+        type Enc = EncryptCircuit<ProjectiveCurve, CurveVar>;
+        let mut rng = rand::thread_rng();
+        let chunk_size = 31;
+        let data = (0..=255)
+            .cycle()
+            .take(200 * chunk_size + 7)
+            .collect::<Vec<u8>>();
+        let msg = bytes_to_plaintext_chunks_sized::<ProjectiveCurve, _>(&data, chunk_size).unwrap();
+        let params = encryption::Parameters::<ProjectiveCurve>::default_multi(msg.len());
+        let (sk, pk) = Enc::keygen(&mut rng).unwrap();
+        let cipher = Enc::new(pk, msg, params.clone(), &mut rng)
+            .unwrap()
+            .resulted_ciphertext;
+
+        let encoded = ark_to_bytes(cipher).unwrap();
+        let path = std::env::temp_dir().join("zkcp_test_blob_decrypting");
+        fs::write(&path, &encoded).unwrap();
+
+        let blob = BlobReader::open(&path, ChunkOffsets::new(encoded.len())).unwrap();
+        let mut reader = DecryptingReader::new(blob, sk, params, chunk_size).with_len(data.len());
+        let mut plaintext = vec![];
+        std::io::copy(&mut reader, &mut plaintext).unwrap();
+
+        assert_eq!(plaintext, data);
+    }
+
+    /// Sleeps on every read as a slow source would, counting seeks shared with the test.
+    struct SlowReader {
+        inner: File,
+        seeks: Arc<AtomicUsize>,
+    }
+
+    impl Read for SlowReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            std::thread::sleep(Duration::from_millis(1));
+            self.inner.read(buf)
+        }
+    }
+
+    impl Seek for SlowReader {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.seeks.fetch_add(1, Ordering::SeqCst);
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_decrypting_reader_prefetch() {
+        type Enc = EncryptCircuit<ProjectiveCurve, CurveVar>;
+        let mut rng = rand::thread_rng();
+        let chunk_size = 31;
+        let data = (0..=255)
+            .cycle()
+            .take(200 * chunk_size)
+            .collect::<Vec<u8>>();
+        let msg = bytes_to_plaintext_chunks_sized::<ProjectiveCurve, _>(&data, chunk_size).unwrap();
+        let params = encryption::Parameters::<ProjectiveCurve>::default_multi(msg.len());
+        let (sk, pk) = Enc::keygen(&mut rng).unwrap();
+        let cipher = Enc::new(pk, msg, params.clone(), &mut rng)
+            .unwrap()
+            .resulted_ciphertext;
+
+        let encoded = ark_to_bytes(cipher).unwrap();
+        let path = std::env::temp_dir().join("zkcp_test_blob_prefetch");
+        fs::write(&path, &encoded).unwrap();
+        let reader = |prefetch: usize| {
+            let seeks = Arc::new(AtomicUsize::new(0));
+            let blob = BlobReader::new(
+                SlowReader {
+                    inner: File::open(&path).unwrap(),
+                    seeks: seeks.clone(),
+                },
+                ChunkOffsets::new(encoded.len()),
+            );
+            let reader = DecryptingReader::new(blob, sk, params.clone(), chunk_size)
+                .with_prefetch(prefetch)
+                .unwrap();
+            (reader, seeks)
+        };
+
+        // one seek for c1 and one per chunk of the first decrypted batch of 64.
+        let (mut unbuffered, seeks) = reader(0);
+        unbuffered.read_exact(&mut [0; 1]).unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(seeks.load(Ordering::SeqCst), 1 + 64);
+
+        // chunks keep being read while the consumer is busy with the first batch.
+        let prefetch = 16;
+        let (mut prefetched, seeks) = reader(prefetch);
+        let mut plaintext = vec![0; 1];
+        prefetched.read_exact(&mut plaintext).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while seeks.load(Ordering::SeqCst) < 1 + 64 + prefetch && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        std::thread::sleep(Duration::from_millis(50));
+        // buffered chunks plus the one blocked on sending.
+        let read_ahead = seeks.load(Ordering::SeqCst) - 1 - 64;
+        assert!(
+            (prefetch..=prefetch + 1).contains(&read_ahead),
+            "{read_ahead} chunks read ahead"
+        );
+
+        prefetched.read_to_end(&mut plaintext).unwrap();
+        assert_eq!(plaintext, data);
+        let mut rest = vec![];
+        unbuffered.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, data[1..]);
+    }
+
+    #[test]
+    fn test_decrypt_verifies_content_hash() {
+        type Enc = EncryptCircuit<ProjectiveCurve, CurveVar>;
+        let mut rng = rand::thread_rng();
+        let chunk_size = 31;
+        let data = b"content whose hash was announced".repeat(4);
+        let msg = bytes_to_plaintext_chunks_sized::<ProjectiveCurve, _>(&data, chunk_size).unwrap();
+        let params = encryption::Parameters::<ProjectiveCurve>::default_multi(msg.len());
+        let (sk, pk) = Enc::keygen(&mut rng).unwrap();
+        let cipher = Enc::new(pk, msg, params.clone(), &mut rng)
+            .unwrap()
+            .resulted_ciphertext;
+
+        let encoded = ark_to_bytes(cipher).unwrap();
+        let path = std::env::temp_dir().join("zkcp_test_blob_hash");
+        fs::write(&path, &encoded).unwrap();
+
+        let blob = BlobReader::open(&path, ChunkOffsets::new(encoded.len())).unwrap();
+        let mut reader = DecryptingReader::new(blob, sk, params, chunk_size).with_len(data.len());
+        let mut plaintext = vec![];
+        std::io::copy(&mut reader, &mut plaintext).unwrap();
+
+        let expected = hex::encode(Sha256::digest(&data));
+        ensure_content_hash(&plaintext, &expected).unwrap();
+        ensure_content_hash(&plaintext, &format!("0x{expected}")).unwrap();
+
+        let wrong = hex::encode(Sha256::digest(b"other content"));
+        let err = ensure_content_hash(&plaintext, &wrong)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("content hash mismatch"));
+        assert!(err.contains(&wrong) && err.contains(&expected));
+        assert!(ensure_content_hash(&plaintext, "not hex").is_err());
+    }
+
+    #[test]
+    fn test_can_decrypt_blob() {
+        type Enc = EncryptCircuit<ProjectiveCurve, CurveVar>;
+        let mut rng = rand::thread_rng();
+        // narrow chunks leave enough zero bytes for a wrong key to be told apart reliably.
+        let chunk_size = 8;
+        let data = b"blob about to be archived".repeat(8);
+        let msg = bytes_to_plaintext_chunks_sized::<ProjectiveCurve, _>(&data, chunk_size).unwrap();
+        let params = encryption::Parameters::<ProjectiveCurve>::default_multi(msg.len());
+        let (sk, pk) = Enc::keygen(&mut rng).unwrap();
+        let cipher = Enc::new(pk, msg, params.clone(), &mut rng)
+            .unwrap()
+            .resulted_ciphertext;
+
+        let encoded = ark_to_bytes(cipher).unwrap();
+        let path = std::env::temp_dir().join("zkcp_test_blob_can_decrypt");
+        fs::write(&path, &encoded).unwrap();
+        let mut blob = BlobReader::open(&path, ChunkOffsets::new(encoded.len())).unwrap();
+
+        assert!(can_decrypt_blob(&mut blob, sk, &params, chunk_size).unwrap());
+        let (wrong_sk, _) = Enc::keygen(&mut rng).unwrap();
+        assert!(!can_decrypt_blob(&mut blob, wrong_sk, &params, chunk_size).unwrap());
+    }
+}
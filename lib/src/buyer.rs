@@ -1,15 +1,28 @@
 use crate::traits::ChainProvider;
-use crate::zk::{PropertyVerifier, VerifiableEncryption, ZkEncryption, ZkVerifiableEncryption};
-use crate::ZkConfig;
+use crate::zk::{
+    joint_secret_key, verification_pool, PropertyVerifier, VerifiableEncryption, ZkEncryption,
+    ZkVerifiableEncryption,
+};
+use crate::{
+    append_receipt, ensure_anchored_vk, find_payment, payment_idempotency_key, session_id,
+    settle_or_refund, write_pending_payment, BuyerSession, Dispute, ProjectiveCurve, Receipt,
+    ReceiptEntry, SaleId, SessionPhase, SignedQuote, TxParams, VkRegistry, ZkConfig, RECEIPTS_FILE,
+    SETTLEMENT_POLL_INTERVAL, VERIFYING_KEY_FILE,
+};
 use anyhow::anyhow;
+use ark_ec::ProjectiveCurve as _;
 use backoff::ExponentialBackoff;
-use circuits::encryption;
+use chrono::Utc;
+use circuits::{ark_from_bytes, ark_to_bytes, encryption, SecretKey};
 use ecdsa_fun::adaptor::{Adaptor, EncryptedSignature, HashTranscript};
 use ethers::prelude::{Address, H256};
 use rand_chacha::ChaCha20Rng;
 use secp256kfun::nonce::Deterministic;
-use secp256kfun::Point;
+use secp256kfun::{Point, Scalar};
 use sha2::Sha256;
+use std::future::Future;
+use std::path::PathBuf;
+use std::str::FromStr;
 
 pub struct Buyer<TChainProvider, TPropVerifier: PropertyVerifier> {
     chain: TChainProvider,
@@ -17,14 +30,23 @@ pub struct Buyer<TChainProvider, TPropVerifier: PropertyVerifier> {
     adaptor: Adaptor<HashTranscript<Sha256, ChaCha20Rng>, Deterministic<Sha256>>,
     encrypted_key: Option<Vec<u8>>,
     one_time_pk: Option<Point>,
+    handshake_sk: Option<SecretKey<ProjectiveCurve>>,
     encrypted_sig: Option<EncryptedSignature>,
+    /// Nonce and gas the payment signed at Step 2 is pinned to.
+    tx_params: Option<TxParams>,
     data_encryption: ZkVerifiableEncryption<TPropVerifier>,
     key_encryption: ZkEncryption,
+    data_encryption_vk_path: PathBuf,
+    cache_dir: PathBuf,
+    verify_threads: usize,
 }
 
 #[derive(Clone, Debug)]
 pub struct BuyerConfig {
+    pub cache_dir: PathBuf,
     pub zk: ZkConfig,
+    /// Threads to verify proofs on, as many as there are CPUs if zero.
+    pub verify_threads: usize,
 }
 
 impl<TChainProvider: ChainProvider, TPropVerifier: PropertyVerifier>
@@ -42,9 +64,11 @@ impl<TChainProvider: ChainProvider, TPropVerifier: PropertyVerifier>
             &cfg.zk.data_encryption_dir,
             property_verifier,
             encryption::Parameters::default_multi(cfg.zk.data_encryption_limit),
-        );
+        )
+        .with_predicate(cfg.zk.predicate);
         let key_encryption =
             ZkEncryption::new_verifier(&cfg.zk.key_encryption_dir, Default::default());
+        let data_encryption_vk_path = cfg.zk.data_encryption_dir.join(VERIFYING_KEY_FILE);
 
         Self {
             chain,
@@ -52,15 +76,226 @@ impl<TChainProvider: ChainProvider, TPropVerifier: PropertyVerifier>
             adaptor,
             encrypted_key: None,
             one_time_pk: None,
+            handshake_sk: None,
             encrypted_sig: None,
+            tx_params: None,
             data_encryption,
             key_encryption,
+            data_encryption_vk_path,
+            cache_dir: cfg.cache_dir,
+            verify_threads: cfg.verify_threads,
         }
     }
 
-    /// Step 0: Bob verifies data ciphertext.
-    pub fn step0_verify(&self, proof: &VerifiableEncryption) -> anyhow::Result<bool> {
-        self.data_encryption.verify_proof(proof)
+    /// Returns hash of the payment this buyer already made for the sale with `sale_hash`, if any,
+    /// along with id of the session it was made in, see [`Self::session_id`].
+    pub async fn prior_payment(
+        &self,
+        sale_hash: &[u8; 32],
+        price: f64,
+    ) -> anyhow::Result<Option<(H256, String)>> {
+        let key = self.payment_key(sale_hash, price);
+        let tx_hash = find_payment(&self.chain, &self.cache_dir, &key).await?;
+        Ok(tx_hash.map(|tx_hash| (tx_hash, session_id(&key))))
+    }
+
+    /// Persists hash of the payment made for `sale`, so that re-running purchase won't pay twice.
+    pub fn record_payment(
+        &self,
+        sale: &VerifiableEncryption,
+        price: f64,
+        tx_hash: H256,
+    ) -> anyhow::Result<()> {
+        write_pending_payment(
+            &self.cache_dir,
+            &self.payment_key(&sale.hash(), price),
+            tx_hash,
+        )
+    }
+
+    fn payment_key(&self, sale_hash: &[u8; 32], price: f64) -> [u8; 32] {
+        let address = self.chain.address_from_pk(self.wallet.pub_key());
+        payment_idempotency_key(address, sale_hash, price)
+    }
+
+    /// Id of the session purchasing `sale` for `price`, the same across retries of the purchase.
+    pub fn session_id(&self, sale: &VerifiableEncryption, price: f64) -> String {
+        session_id(&self.payment_key(&sale.hash(), price))
+    }
+
+    /// Captures state of the purchase made after Step 2 for `price` agreed on with `quote`, if any,
+    /// so that it can be resumed with [`Self::resume`].
+    pub fn session(
+        &self,
+        sale: VerifiableEncryption,
+        price: f64,
+        quote: Option<SignedQuote>,
+    ) -> anyhow::Result<BuyerSession> {
+        let (encrypted_key, one_time_pk, encrypted_sig, tx_params) = match (
+            &self.encrypted_key,
+            &self.one_time_pk,
+            &self.encrypted_sig,
+            self.tx_params,
+        ) {
+            (Some(key), Some(pk), Some(sig), Some(params)) => {
+                (hex::encode(key), pk.to_string(), sig.to_string(), params)
+            }
+            _ => return Err(anyhow!("purchase session can only be saved after Step 2")),
+        };
+        let handshake_sk = self
+            .handshake_sk
+            .map(|sk| ark_to_bytes(sk).map(hex::encode))
+            .transpose()
+            .map_err(|e| anyhow!("error encoding handshake key: {e}"))?;
+
+        Ok(BuyerSession {
+            phase: SessionPhase::Locked,
+            sale,
+            price,
+            encrypted_key,
+            one_time_pk,
+            encrypted_sig,
+            handshake_sk,
+            quote,
+            tx_params: Some(tx_params),
+            locked_at: Some(Utc::now().to_rfc3339()),
+            tx_hash: None,
+        })
+    }
+
+    /// Buyer's own receipt of the trade of `sale` settled in `tx_ids`, once the one-time key is known.
+    pub fn receipt(
+        &self,
+        sale_id: Option<SaleId>,
+        sale: &VerifiableEncryption,
+        price: f64,
+        chain_id: u64,
+        tx_ids: Vec<String>,
+    ) -> anyhow::Result<Receipt> {
+        let one_time_pk = self
+            .one_time_pk
+            .as_ref()
+            .ok_or(anyhow!("receipt can only be made after Step 2"))?;
+        Ok(Receipt::new(
+            sale_id,
+            sale,
+            price,
+            chain_id,
+            tx_ids,
+            one_time_pk,
+            self.wallet.pub_key(),
+        ))
+    }
+
+    /// Countersigns receipt `from_seller` and records it to the audit log in cache, once it's checked
+    /// to be signed by the seller for the same trade as `own` receipt.
+    pub fn countersign_receipt(
+        &self,
+        own: &Receipt,
+        from_seller: ReceiptEntry,
+    ) -> anyhow::Result<ReceiptEntry> {
+        let receipt = from_seller.receipt;
+        let seller_pk = receipt
+            .seller_pk
+            .as_deref()
+            .ok_or(anyhow!("seller's receipt has no seller public key"))?;
+        let seller_signature = from_seller
+            .seller_signature
+            .ok_or(anyhow!("seller's receipt isn't signed"))?;
+        receipt.verify(seller_pk, &seller_signature)?;
+        receipt.ensure_same_trade(own)?;
+
+        let buyer_signature = receipt.sign(self.wallet.sec_key());
+        append_receipt(
+            self.cache_dir.join(RECEIPTS_FILE),
+            receipt,
+            Some(seller_signature),
+            Some(buyer_signature),
+        )
+    }
+
+    /// Records `own` receipt signed by buyer alone to the audit log in cache, eg. if seller gave none.
+    pub fn record_receipt(&self, own: Receipt) -> anyhow::Result<ReceiptEntry> {
+        let buyer_signature = own.sign(self.wallet.sec_key());
+        append_receipt(
+            self.cache_dir.join(RECEIPTS_FILE),
+            own,
+            None,
+            Some(buyer_signature),
+        )
+    }
+
+    /// Restores state of the purchase interrupted after Step 2, so that it continues at Step 3.
+    pub fn resume(&mut self, session: &BuyerSession) -> anyhow::Result<EncryptedSignature> {
+        let encrypted_key = hex::decode(&session.encrypted_key)
+            .map_err(|e| anyhow!("error decoding encrypted key: {e}"))?;
+        let one_time_pk = Point::from_str(&session.one_time_pk)
+            .map_err(|e| anyhow!("error decoding one-time public key: {e}"))?;
+        let encrypted_sig = EncryptedSignature::from_str(&session.encrypted_sig)
+            .map_err(|e| anyhow!("error decoding encrypted signature: {e}"))?;
+        let handshake_sk = session
+            .handshake_sk
+            .as_ref()
+            .map(|sk| {
+                hex::decode(sk)
+                    .map_err(|e| anyhow!("{e}"))
+                    .and_then(|sk| ark_from_bytes(sk).map_err(|e| anyhow!("{e}")))
+            })
+            .transpose()
+            .map_err(|e| anyhow!("error decoding handshake key: {e}"))?;
+
+        self.encrypted_key = Some(encrypted_key);
+        self.one_time_pk = Some(one_time_pk);
+        self.encrypted_sig = Some(encrypted_sig.clone());
+        self.handshake_sk = handshake_sk;
+        self.tx_params = Some(session.tx_params.ok_or(anyhow!(
+            "session was saved without the nonce its payment is pinned to, it can't be resumed"
+        ))?);
+        Ok(encrypted_sig)
+    }
+
+    /// Step 0: Bob verifies data ciphertext, checking proofs on a pool of [`BuyerConfig::verify_threads`].
+    pub fn step0_verify(&self, proof: &VerifiableEncryption) -> anyhow::Result<bool>
+    where
+        TPropVerifier: Sync,
+    {
+        let pool = verification_pool(self.verify_threads)?;
+        self.data_encryption.verify_proof_on(proof, &pool)
+    }
+
+    /// Step 0, before verifying proofs: Bob confirms that the loaded verifying key is the one anchored on-chain.
+    pub async fn step0_check_anchored_vk<R: VkRegistry>(
+        &self,
+        registry: &R,
+        proof: &VerifiableEncryption,
+    ) -> anyhow::Result<()> {
+        ensure_anchored_vk(registry, &proof.hash(), &self.data_encryption_vk_path).await
+    }
+
+    /// Explains why data ciphertext was rejected at Step 0, given hash of the verifying key
+    /// the seller announced, if known.
+    pub fn step0_explain(
+        &self,
+        proof: &VerifiableEncryption,
+        expected_vk_hash: Option<&[u8; 32]>,
+    ) -> anyhow::Result<String> {
+        Ok(
+            match self
+                .data_encryption
+                .explain_proof(proof, expected_vk_hash)?
+            {
+                Some(failure) => format!("proof of encryption: {failure}"),
+                None => "proof of encryption is valid, proof of property was rejected".to_string(),
+            },
+        )
+    }
+
+    /// Step 1 for interactive sales: Bob generates ElGamal key pair and sends its public key to Alice,
+    /// who encrypts data decryption key to it, see [`crate::SellerMsg::Handshake`].
+    pub fn step1_handshake(&mut self) -> anyhow::Result<Vec<u8>> {
+        let (sk, pk) = self.key_encryption.keygen(&mut rand::thread_rng())?;
+        let _ = self.handshake_sk.insert(sk);
+        ark_to_bytes(pk.into_affine()).map_err(|e| anyhow!("error encoding public key: {e}"))
     }
 
     /// Step 2: Bob signs a transaction to transfer coins to Alice address
@@ -79,11 +314,10 @@ impl<TChainProvider: ChainProvider, TPropVerifier: PropertyVerifier>
 
         let _ = self.encrypted_key.insert(encrypted_key.as_ref().to_vec());
         let _ = self.one_time_pk.insert(one_time_pk.clone());
-        let (_, tx_hash) = self.chain.compose_tx(
-            self.chain.address_from_pk(self.wallet.pub_key()),
-            addr_to,
-            amount,
-        )?;
+        let from = self.chain.address_from_pk(self.wallet.pub_key());
+        let tx_params = self.chain.tx_params(from, addr_to, amount).await?;
+        let (_, tx_hash) = self.chain.compose_tx(from, addr_to, amount, &tx_params)?;
+        let _ = self.tx_params.insert(tx_params);
 
         let encrypted_sig = self.adaptor.encrypted_sign(
             self.wallet.sec_key(),
@@ -96,12 +330,40 @@ impl<TChainProvider: ChainProvider, TPropVerifier: PropertyVerifier>
         return Ok(encrypted_sig);
     }
 
+    /// Nonce and gas of the payment signed at Step 2, sent to Alice along with it at Step 3.
+    pub fn tx_params(&self) -> Option<TxParams> {
+        self.tx_params
+    }
+
+    /// Step 3: Bob awaits Alice to `settle` payment, refunding it if she doesn't within `timeout_blocks`.
+    pub async fn step3_with_timeout<Fut>(
+        &self,
+        settle: Fut,
+        timeout_blocks: u64,
+    ) -> anyhow::Result<H256>
+    where
+        Fut: Future<Output = anyhow::Result<H256>>,
+    {
+        let tx_params = self
+            .tx_params
+            .ok_or(anyhow!("payment can only be settled after Step 2"))?;
+        settle_or_refund(
+            &self.chain,
+            &self.wallet,
+            &tx_params,
+            settle,
+            timeout_blocks,
+            SETTLEMENT_POLL_INTERVAL,
+        )
+        .await
+    }
+
     /// Step 4: Bob observes signature on-chain and use it to recover `data_sk`
-    /// and decrypt the data file from the ciphertext given to him by Alice.
-    pub async fn step4<B: AsRef<[u8]>>(
+    /// and decrypt the data file from the `sale` given to him by Alice, see [`Self::decrypt_with`].
+    pub async fn step4(
         &mut self,
         tx_hash: H256,
-        encrypted_data: B,
+        sale: &VerifiableEncryption,
     ) -> anyhow::Result<Vec<u8>> {
         let signature = backoff::future::retry(ExponentialBackoff::default(), || async {
             match self.chain.get_signature(tx_hash).await {
@@ -121,9 +383,43 @@ impl<TChainProvider: ChainProvider, TPropVerifier: PropertyVerifier>
             )
             .unwrap();
 
+        self.decrypt_with(recovered_sk, sale)
+    }
+
+    /// Decrypts `sale` with the key recovered from `recovered_sk`. Plaintext of the sale bound to
+    /// a commitment is checked to hash to it, writing a [`Dispute`] to cache if it doesn't.
+    fn decrypt_with(
+        &mut self,
+        recovered_sk: Scalar,
+        sale: &VerifiableEncryption,
+    ) -> anyhow::Result<Vec<u8>> {
+        let key_sk = match self.handshake_sk.take() {
+            Some(buyer_sk) => joint_secret_key(&recovered_sk, &buyer_sk)?,
+            None => recovered_sk.to_bytes().to_vec(),
+        };
         let decryption_key = self
             .key_encryption
-            .decrypt(recovered_sk.to_bytes(), self.encrypted_key.take().unwrap())?;
-        self.data_encryption.decrypt(decryption_key, encrypted_data)
+            .decrypt(key_sk, self.encrypted_key.take().unwrap())?;
+        let committed = match &sale.plaintext_commitment {
+            Some(committed) => committed,
+            None => {
+                return self
+                    .data_encryption
+                    .decrypt(decryption_key, &sale.ciphertext)
+            }
+        };
+
+        let (data, decrypted) = self
+            .data_encryption
+            .decrypt_committed(&decryption_key, &sale.ciphertext)?;
+        if &decrypted != committed {
+            let path = Dispute::new(sale, &decryption_key, &decrypted).write(&self.cache_dir)?;
+            return Err(anyhow!(
+                "decrypted data doesn't match the plaintext commitment of the sale, dispute evidence is written to {}",
+                path.display()
+            ));
+        }
+
+        Ok(data)
     }
 }
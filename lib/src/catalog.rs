@@ -0,0 +1,103 @@
+use crate::SaleId;
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Sale the daemon serves alongside the default one, restored from the cache of its earlier run.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CatalogItem {
+    pub id: SaleId,
+    pub cache_dir: PathBuf,
+    /// Price of the item, the one of the default sale if missing.
+    #[serde(default)]
+    pub price: Option<f64>,
+}
+
+/// Parses `<id>=<cache dir>[=<price>]`, the form items are given in on the command line.
+impl FromStr for CatalogItem {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, '=');
+        let (id, cache_dir) = match (parts.next(), parts.next()) {
+            (Some(id), Some(dir)) if !id.is_empty() && !dir.is_empty() => (id, dir),
+            _ => return Err(anyhow!("listing must be given as <id>=<dir>, got {s}")),
+        };
+        let price = parts
+            .next()
+            .map(|price| {
+                price
+                    .parse::<f64>()
+                    .map_err(|e| anyhow!("error parsing price of sale {id}: {e}"))
+            })
+            .transpose()?;
+
+        Ok(Self {
+            id: id.to_string(),
+            cache_dir: PathBuf::from(cache_dir),
+            price,
+        })
+    }
+}
+
+/// Reads JSON array of [`CatalogItem`]s the daemon serves.
+pub fn read_catalog<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<CatalogItem>> {
+    let buf = fs::read(path).map_err(|e| anyhow!("error reading catalog: {e}"))?;
+    serde_json::from_slice(&buf).map_err(|e| anyhow!("error decoding catalog: {e}"))
+}
+
+/// Fails if two items share an id, since buyers couldn't tell which one they're buying.
+pub fn ensure_unique_items(items: &[CatalogItem]) -> anyhow::Result<()> {
+    let mut seen = HashSet::new();
+    match items.iter().find(|item| !seen.insert(&item.id)) {
+        Some(item) => Err(anyhow!("sale {} is listed more than once", item.id)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ensure_unique_items, read_catalog, CatalogItem};
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_catalog_items() {
+        let item = "photos=./cache/photos=2.5".parse::<CatalogItem>().unwrap();
+        assert_eq!(
+            item,
+            CatalogItem {
+                id: "photos".to_string(),
+                cache_dir: PathBuf::from("./cache/photos"),
+                price: Some(2.5),
+            }
+        );
+        assert_eq!(
+            "docs=./cache/docs".parse::<CatalogItem>().unwrap().price,
+            None
+        );
+        assert!("docs".parse::<CatalogItem>().is_err());
+        assert!("=./cache".parse::<CatalogItem>().is_err());
+        assert!("docs=./cache=cheap".parse::<CatalogItem>().is_err());
+
+        let path = std::env::temp_dir().join("zkcp_test_catalog.json");
+        fs::write(
+            &path,
+            r#"[{"id": "photos", "cache_dir": "./cache/photos", "price": 2.5},
+                {"id": "docs", "cache_dir": "./cache/docs"}]"#,
+        )
+        .unwrap();
+        let mut items = read_catalog(&path).unwrap();
+        assert_eq!(items[0], item);
+        assert_eq!(items[1].price, None);
+        assert!(ensure_unique_items(&items).is_ok());
+
+        items.push(item);
+        let err = ensure_unique_items(&items).unwrap_err();
+        assert_eq!(err.to_string(), "sale photos is listed more than once");
+        let _ = fs::remove_file(path);
+    }
+}
@@ -1,9 +1,11 @@
-use crate::zk::CircomParams;
+use crate::zk::{CircomParams, Predicate, ProofBackend};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
 pub const PROVING_KEY_FILE: &str = "circuit.zkey";
 pub const VERIFYING_KEY_FILE: &str = "verification.key";
+/// Verifying key in [`crate::VerifyingKeyJson`] form, written by `compile` next to the binary one.
+pub const VERIFYING_KEY_JSON_FILE: &str = "verification.key.json";
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ZkConfig {
@@ -12,6 +14,12 @@ pub struct ZkConfig {
     pub data_encryption_limit: usize,
     pub key_encryption_dir: PathBuf,
     pub circom_params: CircomParams,
+    /// Missing in configs predating the choice, those were all compiled with Groth16.
+    #[serde(default)]
+    pub backend: ProofBackend,
+    /// Statement data encryption circuit also proves over the plaintext, if any.
+    #[serde(default)]
+    pub predicate: Option<Predicate>,
 }
 
 // impl Default for ZkConfig {
@@ -0,0 +1,92 @@
+use crate::zk::VerifiableEncryption;
+use anyhow::anyhow;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory in buyer's cache disputes are written to, one file per sale.
+pub const DISPUTES_DIR: &str = "disputes";
+
+/// Evidence that the key seller revealed for `sale` doesn't decrypt it to the plaintext its proof is
+/// bound to: anyone can decrypt the ciphertext with `decryption_key` and hash the result.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Dispute {
+    /// Hex-encoded [`VerifiableEncryption::hash`] of the sale.
+    pub sale_hash: String,
+    /// Hex-encoded commitment of the sale and the one the decrypted plaintext hashes to instead.
+    pub committed: String,
+    pub decrypted: String,
+    /// Hex-encoded data decryption key recovered after paying for the sale.
+    pub decryption_key: String,
+    pub sale: VerifiableEncryption,
+    /// RFC 3339 time the mismatch was found at.
+    pub created_at: String,
+}
+
+impl Dispute {
+    pub fn new(sale: &VerifiableEncryption, decryption_key: &[u8], decrypted: &[u8]) -> Self {
+        Self {
+            sale_hash: hex::encode(sale.hash()),
+            committed: sale
+                .plaintext_commitment
+                .as_deref()
+                .map(hex::encode)
+                .unwrap_or_default(),
+            decrypted: hex::encode(decrypted),
+            decryption_key: hex::encode(decryption_key),
+            sale: sale.clone(),
+            created_at: Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Writes dispute to [`DISPUTES_DIR`] of `cache_dir`, returning its path.
+    pub fn write<P: AsRef<Path>>(&self, cache_dir: P) -> anyhow::Result<PathBuf> {
+        let dir = cache_dir.as_ref().join(DISPUTES_DIR);
+        fs::create_dir_all(&dir).map_err(|e| anyhow!("error creating disputes dir: {e}"))?;
+        let path = dir.join(format!("{}.json", self.sale_hash));
+        let json =
+            serde_json::to_vec_pretty(self).map_err(|e| anyhow!("error encoding dispute: {e}"))?;
+        fs::write(&path, json).map_err(|e| anyhow!("error writing dispute: {e}"))?;
+        Ok(path)
+    }
+}
+
+/// Reads dispute written with [`Dispute::write`].
+pub fn read_dispute<P: AsRef<Path>>(path: P) -> anyhow::Result<Dispute> {
+    let json = fs::read(path).map_err(|e| anyhow!("error reading dispute: {e}"))?;
+    serde_json::from_slice(&json).map_err(|e| anyhow!("error decoding dispute: {e}"))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::zk::VerifiableEncryption;
+    use crate::{read_dispute, Dispute, DISPUTES_DIR};
+
+    #[test]
+    fn test_write_dispute() {
+        let cache_dir = std::env::temp_dir().join("zkcp_test_dispute");
+        let _ = std::fs::remove_dir_all(&cache_dir);
+        let sale = VerifiableEncryption {
+            ciphertext: vec![1, 2, 3],
+            proof_of_encryption: vec![4, 5, 6],
+            proofs_of_property: vec![],
+            plaintext_commitment: Some(vec![7; 32]),
+        };
+
+        let dispute = Dispute::new(&sale, &[9; 32], &[8; 32]);
+        let path = dispute.write(&cache_dir).unwrap();
+        assert_eq!(
+            path,
+            cache_dir
+                .join(DISPUTES_DIR)
+                .join(format!("{}.json", hex::encode(sale.hash())))
+        );
+
+        let read = read_dispute(&path).unwrap();
+        assert_eq!(read.committed, hex::encode([7; 32]));
+        assert_eq!(read.decrypted, hex::encode([8; 32]));
+        assert_eq!(read.decryption_key, dispute.decryption_key);
+        assert_eq!(read.sale.hash(), sale.hash());
+    }
+}
@@ -1,49 +1,345 @@
-use crate::traits::ChainProvider;
+use crate::traits::{ChainProvider, ConfirmationSource, VkRegistry};
+use crate::{LocalWallet, TxParams, SETTLEMENT_POLL_INTERVAL};
 use anyhow::anyhow;
 use async_trait::async_trait;
+use ecdsa_fun::ECDSA;
+use ethers::abi::{encode, Token};
 use ethers::prelude::*;
+use ethers::types::transaction::eip2718::TypedTransaction;
 pub use ethers::utils::WEI_IN_ETHER;
-use ethers::utils::{keccak256, parse_ether};
+use ethers::utils::{id, keccak256, parse_ether};
 use k256::elliptic_curve::sec1::ToEncodedPoint;
 use k256::PublicKey;
+use secp256kfun::nonce::Deterministic;
 use secp256kfun::{marker::*, Point, Scalar};
+use sha2::Sha256;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 use url::Url;
 
+/// EIP-155 chain id, parsed either from decimal or `0x`-prefixed hex.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ChainId(pub u64);
+
+impl ChainId {
+    pub const MAINNET: ChainId = ChainId(1);
+    pub const GOERLI: ChainId = ChainId(5);
+    pub const ANVIL: ChainId = ChainId(31337);
+}
+
+impl FromStr for ChainId {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            Some(hex) => u64::from_str_radix(hex, 16),
+            None => s.parse(),
+        }
+        .map(ChainId)
+        .map_err(|e| anyhow!("error parsing chain id '{s}': {e}"))
+    }
+}
+
+impl Display for ChainId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// ERC-20 token payments are made in instead of ether, see [`Ethereum::with_token`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Erc20Token {
+    pub address: Address,
+    pub decimals: u8,
+}
+
+/// `amount` in units of `10^-decimals`, failing if it's more precise than that.
+pub fn decimal_units(amount: f64, decimals: u8) -> anyhow::Result<U256> {
+    if !amount.is_finite() || amount < 0.0 {
+        return Err(anyhow!(
+            "amount must be a non-negative number, got {amount}"
+        ));
+    }
+    let amount = amount.to_string();
+    let (whole, fraction) = amount.split_once('.').unwrap_or((&amount, ""));
+    let decimals = decimals as usize;
+    if fraction.len() > decimals {
+        return Err(anyhow!(
+            "amount {amount} is more precise than {decimals} decimals"
+        ));
+    }
+    U256::from_dec_str(&format!("{whole}{fraction:0<decimals$}"))
+        .map_err(|e| anyhow!("error converting amount {amount}: {e}"))
+}
+
+/// Least percentage nodes accept replacing transaction with raised fees by.
+const MIN_BUMP_PERCENT: u64 = 10;
+
+/// How transactions signed in full by the local wallet are priced and re-priced while stuck,
+/// see [`ChainProvider::send_own`]. Payments signed with adaptor signatures are priced by the buyer
+/// who signs them, while the seller broadcasting them can't re-sign them with higher fees.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeePolicy {
+    /// Cap on `maxFeePerGas` in wei, estimated fees are lowered to it and never bumped past it.
+    pub max_fee: Option<U256>,
+    /// Blocks transaction may stay unmined for before it's sent again with bumped fees.
+    pub bump_after_blocks: u64,
+    /// Percentage both fees are raised by on each bump, nodes replace transactions raised by at least 10%.
+    pub bump_percent: u64,
+    pub max_bumps: u32,
+}
+
+impl Default for FeePolicy {
+    fn default() -> Self {
+        Self {
+            max_fee: None,
+            bump_after_blocks: 3,
+            bump_percent: 15,
+            max_bumps: 5,
+        }
+    }
+}
+
+impl FeePolicy {
+    /// Lowers estimated EIP-1559 fees to the cap, keeping priority fee within the max fee.
+    pub fn cap(&self, max_fee: U256, priority_fee: U256) -> (U256, U256) {
+        let max_fee = self.max_fee.map_or(max_fee, |cap| max_fee.min(cap));
+        (max_fee, priority_fee.min(max_fee))
+    }
+
+    /// Fees of the transaction re-sent after `max_fee` and `priority_fee` have left it stuck,
+    /// both raised by at least 10%, failing if the cap leaves no room for that.
+    pub fn bump(&self, max_fee: U256, priority_fee: U256) -> anyhow::Result<(U256, U256)> {
+        // rounded up, so that even fees too low to be raised by the percentage go up.
+        let raise = |fee: U256, percent: u64| fee + ((fee * percent + 99) / 100).max(U256::one());
+        let percent = self.bump_percent.max(MIN_BUMP_PERCENT);
+        let (bumped_max_fee, bumped_priority_fee) =
+            self.cap(raise(max_fee, percent), raise(priority_fee, percent));
+
+        if bumped_max_fee < raise(max_fee, MIN_BUMP_PERCENT)
+            || bumped_priority_fee < raise(priority_fee, MIN_BUMP_PERCENT)
+        {
+            return Err(anyhow!(
+                "transaction is stuck with fees too close to the cap of {} wei per gas to bump",
+                self.max_fee.unwrap_or(max_fee)
+            ));
+        }
+        Ok((bumped_max_fee, bumped_priority_fee))
+    }
+}
+
+impl Erc20Token {
+    /// Token deployed at hex-encoded `address`, with `decimals` its amounts are given in.
+    pub fn from_hex(address: &str, decimals: u8) -> anyhow::Result<Self> {
+        let address =
+            Address::from_str(address).map_err(|e| anyhow!("bad token address {address}: {e}"))?;
+        Ok(Self { address, decimals })
+    }
+
+    /// Smallest units of the token `amount` is worth, failing if it's more precise than the token.
+    pub fn units(&self, amount: f64) -> anyhow::Result<U256> {
+        decimal_units(amount, self.decimals)
+            .map_err(|e| anyhow!("error converting token amount: {e}"))
+    }
+
+    /// Calldata of `transfer(address,uint256)` moving `amount` of the token to `to`.
+    pub fn transfer_calldata(&self, to: Address, amount: f64) -> anyhow::Result<Vec<u8>> {
+        let args = encode(&[Token::Address(to), Token::Uint(self.units(amount)?)]);
+        Ok([id("transfer(address,uint256)").to_vec(), args].concat())
+    }
+}
+
 pub struct Ethereum {
     provider: Provider<Http>,
-    chain_id: u64,
+    chain_id: ChainId,
+    token: Option<Erc20Token>,
+    fee_policy: FeePolicy,
 }
 
 impl Ethereum {
-    pub async fn new(url: impl Into<Url>) -> anyhow::Result<Self> {
+    /// Connects to the RPC node at `url`, which is expected to serve chain with `chain_id`.
+    pub async fn new(url: impl Into<Url>, chain_id: ChainId) -> anyhow::Result<Self> {
         let provider = Provider::new(Http::new(url));
-        let chain_id = provider
+        let rpc_chain_id = provider
             .get_chainid()
             .await
             .map_err(|_e| anyhow!("error making request to the specified Ethereum RPC address"))?;
 
+        if rpc_chain_id.as_u64() != chain_id.0 {
+            return Err(anyhow!(
+                "RPC node serves chain {rpc_chain_id}, while chain {chain_id} was expected"
+            ));
+        }
+
         Ok(Self {
             provider,
-            chain_id: chain_id.as_u64(),
+            chain_id,
+            token: None,
+            fee_policy: FeePolicy::default(),
+        })
+    }
+
+    /// Provider that doesn't reach the RPC node until used, e.g. by the event watcher.
+    pub fn lazy(url: impl Into<Url>, chain_id: ChainId) -> Self {
+        Self {
+            provider: Provider::new(Http::new(url)),
+            chain_id,
+            token: None,
+            fee_policy: FeePolicy::default(),
+        }
+    }
+
+    /// Pays in ERC-20 `token` instead of ether: payment becomes the token's `transfer` call
+    /// signed by the buyer, so it's settled with the adaptor signature as is, with no approval needed.
+    pub fn with_token(mut self, token: Erc20Token) -> Self {
+        self.token = Some(token);
+        self
+    }
+
+    pub fn with_fee_policy(mut self, fee_policy: FeePolicy) -> Self {
+        self.fee_policy = fee_policy;
+        self
+    }
+
+    /// Signature of the transaction with `sighash` made by `from`, with recovery id `v` set.
+    fn recoverable_signature(
+        &self,
+        sighash: H256,
+        from: Address,
+        sig: &ecdsa_fun::Signature,
+    ) -> anyhow::Result<Signature> {
+        let r = U256::from_big_endian(&sig.R_x.to_bytes());
+        let s = U256::from_big_endian(&sig.s.to_bytes());
+        let v = {
+            let v = to_eip155_v(1, self.chain_id.0);
+            let recid = Signature { r, s, v }.verify(sighash, from).is_ok() as u8;
+            to_eip155_v(recid, self.chain_id.0)
+        };
+
+        let sig = Signature { r, s, v };
+        sig.verify(sighash, from)
+            .map_err(|e| anyhow!("verification error: {e}"))?;
+        Ok(sig)
+    }
+
+    /// Transfer of `amount` from `from` to `to`, in the payment token if one is set.
+    fn transfer_request(
+        &self,
+        from: Address,
+        to: Address,
+        amount: f64,
+    ) -> anyhow::Result<TransactionRequest> {
+        let tx = TransactionRequest::new()
+            .chain_id(self.chain_id.0)
+            .from(from);
+        Ok(match &self.token {
+            Some(token) => tx
+                .to(token.address)
+                .data(token.transfer_calldata(to, amount)?),
+            None => tx
+                .to(to)
+                .value(parse_ether(amount).map_err(|e| anyhow!("error parsing ether: {e}"))?),
         })
     }
+
+    /// Nonce the next transaction of `from` is sent with, counting the ones still pending.
+    async fn pending_nonce(&self, from: Address) -> anyhow::Result<U256> {
+        self.provider
+            .get_transaction_count(from, Some(BlockNumber::Pending.into()))
+            .await
+            .map_err(|e| anyhow!("error getting nonce: {e}"))
+    }
+
+    /// First of `tx_hashes` that's mined, if any.
+    async fn mined(&self, tx_hashes: &[H256]) -> anyhow::Result<Option<H256>> {
+        for tx_hash in tx_hashes {
+            let receipt = self
+                .provider
+                .get_transaction_receipt(*tx_hash)
+                .await
+                .map_err(|e| anyhow!("error getting tx receipt: {e}"))?;
+            if receipt.map_or(false, |r| r.block_number.is_some()) {
+                return Ok(Some(*tx_hash));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl Ethereum {
+    /// Registry contract deployed at `address`, sharing the RPC connection.
+    pub fn vk_registry(&self, address: Address) -> EthereumVkRegistry {
+        EthereumVkRegistry {
+            provider: self.provider.clone(),
+            address,
+        }
+    }
+}
+
+/// Registry contract exposing `vkHash(bytes32 saleHash) returns (bytes32)`, zero meaning nothing is anchored.
+pub struct EthereumVkRegistry {
+    provider: Provider<Http>,
+    address: Address,
+}
+
+#[async_trait]
+impl VkRegistry for EthereumVkRegistry {
+    async fn anchored_vk_hash(&self, sale_hash: &[u8; 32]) -> anyhow::Result<Option<[u8; 32]>> {
+        let calldata = [id("vkHash(bytes32)").to_vec(), sale_hash.to_vec()].concat();
+        let tx = TransactionRequest::new().to(self.address).data(calldata);
+        let res = self
+            .provider
+            .call(&tx.into(), None)
+            .await
+            .map_err(|e| anyhow!("error calling vk registry: {e}"))?;
+
+        let vk_hash: [u8; 32] = res
+            .as_ref()
+            .try_into()
+            .map_err(|_e| anyhow!("vk registry returned {} bytes instead of 32", res.len()))?;
+        Ok((vk_hash != [0; 32]).then_some(vk_hash))
+    }
 }
 
 #[async_trait]
 impl ChainProvider for Ethereum {
     type Tx = TransactionRequest;
 
+    async fn tx_params(&self, from: Address, to: Address, amount: f64) -> anyhow::Result<TxParams> {
+        let nonce = self.pending_nonce(from).await?;
+        let gas = self
+            .provider
+            .estimate_gas(&self.transfer_request(from, to, amount)?.into())
+            .await
+            .map_err(|e| anyhow!("error estimating gas: {e}"))?;
+        let gas_price = self
+            .provider
+            .get_gas_price()
+            .await
+            .map_err(|e| anyhow!("error getting gas price: {e}"))?;
+        let (gas_price, _) = self.fee_policy.cap(gas_price, gas_price);
+
+        Ok(TxParams {
+            nonce,
+            gas,
+            gas_price,
+        })
+    }
+
     fn compose_tx(
         &self,
         from: Address,
         to: Address,
         amount: f64,
+        params: &TxParams,
     ) -> anyhow::Result<(Self::Tx, H256)> {
-        let tx = TransactionRequest::new()
-            .chain_id(self.chain_id)
-            .from(from)
-            .to(to)
-            .value(parse_ether(amount).map_err(|e| anyhow!("error parsing ether: {e}"))?);
+        let tx = self
+            .transfer_request(from, to, amount)?
+            .nonce(params.nonce)
+            .gas(params.gas)
+            .gas_price(params.gas_price);
 
         let tx_hash = tx.sighash();
 
@@ -52,20 +348,8 @@ impl ChainProvider for Ethereum {
 
     async fn sent_signed(&self, tx: Self::Tx, sig: &ecdsa_fun::Signature) -> anyhow::Result<H256> {
         let from = tx.from.unwrap();
-        let m = tx.sighash();
-        let r = U256::from_big_endian(&sig.R_x.to_bytes());
-        let s = U256::from_big_endian(&sig.s.to_bytes());
-        let v = {
-            let v = to_eip155_v(1, self.chain_id);
-            let recid = Signature { r, s, v }.verify(m, from).is_ok() as u8;
-            to_eip155_v(recid, self.chain_id)
-        };
-
-        let encoded_tx = tx.rlp_signed(&Signature { r, s, v });
-
-        Signature { r, s, v }
-            .verify(m, from)
-            .map_err(|e| anyhow!("verification error: {e}"))?;
+        let sig = self.recoverable_signature(tx.sighash(), from, sig)?;
+        let encoded_tx = tx.rlp_signed(&sig);
 
         let pending = self
             .provider
@@ -111,12 +395,277 @@ impl ChainProvider for Ethereum {
             })
     }
 
+    async fn block_number(&self) -> anyhow::Result<u64> {
+        self.provider
+            .get_block_number()
+            .await
+            .map(|n| n.as_u64())
+            .map_err(|e| anyhow!("error getting block number: {e}"))
+    }
+
     fn address_from_pk(&self, pk: &Point) -> Address {
-        let public_key = PublicKey::from_sec1_bytes(pk.to_bytes().as_slice()).unwrap();
-        let public_key = public_key.to_encoded_point(false);
-        let public_key = public_key.as_bytes();
-        debug_assert_eq!(public_key[0], 0x04);
-        let hash = keccak256(&public_key[1..]);
-        Address::from_slice(&hash[12..])
+        eth_address(pk)
+    }
+
+    fn payment_token(&self) -> Option<Address> {
+        self.token.map(|token| token.address)
+    }
+
+    async fn nonce_mined(&self, from: Address, nonce: U256) -> anyhow::Result<bool> {
+        let mined = self
+            .provider
+            .get_transaction_count(from, Some(BlockNumber::Latest.into()))
+            .await
+            .map_err(|e| anyhow!("error getting nonce: {e}"))?;
+        Ok(mined > nonce)
+    }
+
+    /// Sends EIP-1559 transaction with estimated gas and fees capped by the [`FeePolicy`],
+    /// re-signing it with bumped fees each time it stays unmined for `bump_after_blocks`.
+    async fn send_own(
+        &self,
+        wallet: &LocalWallet,
+        to: Address,
+        amount: f64,
+        replacing: Option<&TxParams>,
+    ) -> anyhow::Result<H256> {
+        let from = eth_address(wallet.pub_key());
+        let nonce = match replacing {
+            Some(replaced) => replaced.nonce,
+            None => self.pending_nonce(from).await?,
+        };
+        let tx = Eip1559TransactionRequest::new()
+            .chain_id(self.chain_id.0)
+            .from(from)
+            .nonce(nonce);
+        let tx = match &self.token {
+            Some(token) => tx
+                .to(token.address)
+                .data(token.transfer_calldata(to, amount)?),
+            None => tx
+                .to(to)
+                .value(parse_ether(amount).map_err(|e| anyhow!("error parsing ether: {e}"))?),
+        };
+        let gas = self
+            .provider
+            .estimate_gas(&tx.clone().into())
+            .await
+            .map_err(|e| anyhow!("error estimating gas: {e}"))?;
+        let (max_fee, priority_fee) = self
+            .provider
+            .estimate_eip1559_fees(None)
+            .await
+            .map_err(|e| anyhow!("error estimating fees: {e}"))?;
+        let (mut max_fee, mut priority_fee) = self.fee_policy.cap(max_fee, priority_fee);
+        // nodes only replace pending transaction with one paying more on both fees.
+        if let Some(replaced) = replacing {
+            let (min_fee, min_priority_fee) = self
+                .fee_policy
+                .bump(replaced.gas_price, replaced.gas_price)?;
+            max_fee = max_fee.max(min_fee);
+            priority_fee = priority_fee.max(min_priority_fee);
+        }
+
+        let ecdsa = ECDSA::new(Deterministic::<Sha256>::default());
+        let mut sent = vec![];
+        // nonce and gas are fixed above, so that each bump replaces the transaction sent before it.
+        loop {
+            let priced: TypedTransaction = tx
+                .clone()
+                .gas(gas)
+                .max_fee_per_gas(max_fee)
+                .max_priority_fee_per_gas(priority_fee)
+                .into();
+            let sighash = priced.sighash();
+            let sig = ecdsa.sign(wallet.sec_key(), sighash.as_fixed_bytes());
+            let encoded_tx = priced.rlp_signed(&self.recoverable_signature(sighash, from, &sig)?);
+            match self.provider.send_raw_transaction(encoded_tx).await {
+                Ok(pending) => sent.push(*pending),
+                // replacement is refused once the transaction it replaces is mined.
+                Err(e) => {
+                    return match self.mined(&sent).await? {
+                        Some(tx_hash) => Ok(tx_hash),
+                        None => Err(anyhow!("error sending transaction: {e}")),
+                    }
+                }
+            }
+
+            let deadline = self.block_number().await? + self.fee_policy.bump_after_blocks;
+            loop {
+                if let Some(tx_hash) = self.mined(&sent).await? {
+                    return Ok(tx_hash);
+                }
+                if self.block_number().await? >= deadline {
+                    break;
+                }
+                tokio::time::sleep(SETTLEMENT_POLL_INTERVAL).await;
+            }
+            if sent.len() > self.fee_policy.max_bumps as usize {
+                return Err(anyhow!(
+                    "transaction is still stuck after {} fee bumps",
+                    self.fee_policy.max_bumps
+                ));
+            }
+            (max_fee, priority_fee) = self.fee_policy.bump(max_fee, priority_fee)?;
+        }
+    }
+}
+
+#[async_trait]
+impl ConfirmationSource for Ethereum {
+    async fn tx_height(&self, tx_id: &str) -> anyhow::Result<Option<u64>> {
+        let hash = H256::from_str(tx_id).map_err(|e| anyhow!("bad tx hash {tx_id}: {e}"))?;
+        self.provider
+            .get_transaction_receipt(hash)
+            .await
+            .map(|receipt| receipt.and_then(|r| r.block_number).map(|n| n.as_u64()))
+            .map_err(|e| anyhow!("error getting tx receipt: {e}"))
+    }
+
+    async fn tip_height(&self) -> anyhow::Result<u64> {
+        self.block_number().await
+    }
+}
+
+/// Ethereum address of the account with public key `pk`.
+pub fn eth_address(pk: &Point) -> Address {
+    let public_key = PublicKey::from_sec1_bytes(pk.to_bytes().as_slice()).unwrap();
+    let public_key = public_key.to_encoded_point(false);
+    let public_key = public_key.as_bytes();
+    debug_assert_eq!(public_key[0], 0x04);
+    let hash = keccak256(&public_key[1..]);
+    Address::from_slice(&hash[12..])
+}
+
+#[cfg(test)]
+mod test {
+    use crate::traits::ChainProvider;
+    use crate::{ChainId, Erc20Token, Ethereum, FeePolicy, TxParams};
+    use ethers::prelude::{Address, NameOrAddress, U256};
+    use std::str::FromStr;
+    use url::Url;
+
+    #[test]
+    fn test_parse_decimal_chain_id() {
+        assert_eq!(ChainId::from_str("31337").unwrap(), ChainId::ANVIL);
+        assert_eq!(ChainId::from_str("1").unwrap(), ChainId::MAINNET);
+    }
+
+    #[test]
+    fn test_parse_hex_chain_id() {
+        assert_eq!(ChainId::from_str("0x7a69").unwrap(), ChainId::ANVIL);
+        assert_eq!(ChainId::from_str("0x1").unwrap(), ChainId::MAINNET);
+    }
+
+    #[test]
+    fn test_parse_invalid_chain_id() {
+        assert!(ChainId::from_str("http://localhost:8545").is_err());
+        assert!(ChainId::from_str("0x").is_err());
+        assert!(ChainId::from_str("-1").is_err());
+        assert!(ChainId::from_str("").is_err());
+    }
+
+    #[test]
+    fn test_display_chain_id() {
+        assert_eq!(ChainId::ANVIL.to_string(), "31337");
+        assert_eq!(
+            ChainId::from_str(&ChainId::ANVIL.to_string()).unwrap(),
+            ChainId::ANVIL
+        );
+    }
+
+    #[test]
+    fn test_token_units() {
+        let usdc = Erc20Token {
+            address: Address::repeat_byte(1),
+            decimals: 6,
+        };
+        assert_eq!(usdc.units(2.5).unwrap(), U256::from(2_500_000));
+        assert_eq!(usdc.units(1.0).unwrap(), U256::from(1_000_000));
+        assert_eq!(usdc.units(0.000001).unwrap(), U256::from(1));
+        assert!(usdc.units(0.0000001).is_err());
+        assert!(usdc.units(-1.0).is_err());
+        assert!(usdc.units(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_compose_token_transfer() {
+        let token = Erc20Token::from_hex(&"01".repeat(20), 18).unwrap();
+        assert_eq!(token.address, Address::repeat_byte(1));
+        assert!(Erc20Token::from_hex("0x01", 18).is_err());
+        let url = Url::parse("http://localhost:8545").unwrap();
+        let eth = Ethereum::lazy(url, ChainId::ANVIL).with_token(token);
+        let (from, to) = (Address::repeat_byte(2), Address::repeat_byte(3));
+
+        let params = TxParams {
+            nonce: U256::from(7),
+            gas: U256::from(60_000),
+            gas_price: U256::exp10(9),
+        };
+        let (tx, tx_hash) = eth.compose_tx(from, to, 0.1, &params).unwrap();
+        assert_eq!(tx.nonce, Some(params.nonce));
+        assert_eq!(tx.gas, Some(params.gas));
+        assert_eq!(tx.to, Some(NameOrAddress::Address(token.address)));
+        assert_eq!(tx.value, None);
+        let data = tx.data.unwrap();
+        assert_eq!(data[..4], [0xa9, 0x05, 0x9c, 0xbb]);
+        assert_eq!(data[16..36], to.to_fixed_bytes());
+        assert_eq!(
+            U256::from_big_endian(&data[36..]),
+            token.units(0.1).unwrap()
+        );
+        assert_eq!(eth.payment_token(), Some(token.address));
+
+        // paying the same amount in ether is another transaction.
+        let url = Url::parse("http://localhost:8545").unwrap();
+        let (_, ether_hash) = Ethereum::lazy(url, ChainId::ANVIL)
+            .compose_tx(from, to, 0.1, &params)
+            .unwrap();
+        assert_ne!(tx_hash, ether_hash);
+        // transfer pinned to another nonce is signed anew.
+        let next = TxParams {
+            nonce: U256::from(8),
+            ..params
+        };
+        assert_ne!(eth.compose_tx(from, to, 0.1, &next).unwrap().1, tx_hash);
+    }
+
+    #[test]
+    fn test_fee_policy() {
+        let gwei = |n: u64| U256::from(n) * U256::exp10(9);
+        let policy = FeePolicy {
+            max_fee: Some(gwei(100)),
+            ..Default::default()
+        };
+
+        assert_eq!(policy.cap(gwei(30), gwei(2)), (gwei(30), gwei(2)));
+        assert_eq!(policy.cap(gwei(150), gwei(120)), (gwei(100), gwei(100)));
+        assert_eq!(
+            policy.bump(gwei(20), gwei(2)).unwrap(),
+            (gwei(23), U256::from(2_300_000_000u64))
+        );
+        // fees are bumped up to the cap, but not past it.
+        assert_eq!(policy.bump(gwei(90), gwei(2)).unwrap().0, gwei(100));
+        assert!(policy.bump(gwei(100), gwei(2)).is_err());
+        // capped bump under 10% would be refused as replacement.
+        assert!(policy.bump(gwei(95), gwei(2)).is_err());
+        assert!(policy.bump(gwei(50), gwei(50)).is_ok());
+
+        // both fees rise by 10% however low the percentage is set.
+        let timid = FeePolicy {
+            bump_percent: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            timid.bump(gwei(20), gwei(2)).unwrap(),
+            (gwei(22), U256::from(2_200_000_000u64))
+        );
+
+        // even fees too low to be raised by the percentage go up.
+        let uncapped = FeePolicy::default();
+        assert_eq!(
+            uncapped.bump(U256::one(), U256::zero()).unwrap(),
+            (2.into(), 1.into())
+        );
     }
 }
@@ -0,0 +1,445 @@
+use crate::traits::ConfirmationSource;
+use crate::{
+    find_session, read_sessions, seller_session_id, write_session, SaleId, SellerSession,
+    SessionPhase, SETTLEMENT_POLL_INTERVAL,
+};
+use anyhow::anyhow;
+use ethers::prelude::Address;
+use ethers::providers::{Middleware, Provider, Ws};
+use futures::channel::mpsc;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::process::Command;
+use std::str::FromStr;
+use std::time::Duration;
+use url::Url;
+
+/// Environment variables hook commands get the event in, by name and as JSON.
+pub const EVENT_NAME_VAR: &str = "CONTANGLE_EVENT";
+pub const EVENT_JSON_VAR: &str = "CONTANGLE_EVENT_JSON";
+
+/// What happened on-chain to a trade of the seller, as passed to [`EventHook`]s.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SellerEvent {
+    /// Buyer's payment landed on-chain: the adaptor-signed transfer was mined in `claim_tx`.
+    Funded {
+        #[serde(default)]
+        sale_id: Option<SaleId>,
+        buyer: Address,
+        price: f64,
+        funding_tx: String,
+        claim_tx: String,
+    },
+    /// Seller's claim of the payment is confirmed deep enough to be final.
+    Settled {
+        #[serde(default)]
+        sale_id: Option<SaleId>,
+        buyer: Address,
+        claim_tx: String,
+        height: u64,
+        confirmations: u64,
+    },
+}
+
+impl SellerEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SellerEvent::Funded { .. } => "funded",
+            SellerEvent::Settled { .. } => "settled",
+        }
+    }
+}
+
+/// Where the event is delivered: JSON POSTed to the webhook URL, or the shell command run with it
+/// in [`EVENT_NAME_VAR`] and [`EVENT_JSON_VAR`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EventHook {
+    Webhook(Url),
+    Command(String),
+}
+
+impl FromStr for EventHook {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Err(anyhow!("event hook can't be empty"));
+        }
+        match Url::parse(s) {
+            Ok(url) if url.scheme() == "http" || url.scheme() == "https" => Ok(Self::Webhook(url)),
+            _ => Ok(Self::Command(s.to_string())),
+        }
+    }
+}
+
+impl EventHook {
+    pub async fn call(&self, event: &SellerEvent) -> anyhow::Result<()> {
+        let json = serde_json::to_string(event).expect("expected event to marshal to json");
+        match self {
+            EventHook::Webhook(url) => {
+                let resp = surf::post(url.as_str())
+                    .content_type("application/json")
+                    .body(json)
+                    .await
+                    .map_err(|e| anyhow!("error calling webhook {url}: {e}"))?;
+                match resp.status().is_success() {
+                    true => Ok(()),
+                    false => Err(anyhow!("webhook {url} responded with {}", resp.status())),
+                }
+            }
+            EventHook::Command(command) => {
+                let (command, name) = (command.clone(), event.name());
+                let status = tokio::task::spawn_blocking(move || {
+                    Command::new("sh")
+                        .arg("-c")
+                        .arg(&command)
+                        .env(EVENT_NAME_VAR, name)
+                        .env(EVENT_JSON_VAR, json)
+                        .status()
+                        .map_err(|e| anyhow!("error running hook `{command}`: {e}"))
+                })
+                .await
+                .map_err(|e| anyhow!("error awaiting hook: {e}"))??;
+                match status.success() {
+                    true => Ok(()),
+                    false => Err(anyhow!("hook exited with {status}")),
+                }
+            }
+        }
+    }
+}
+
+/// Hooks called on each kind of [`SellerEvent`], e.g. to plug seller into its fulfillment system.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EventHooks {
+    pub on_funded: Vec<EventHook>,
+    pub on_settled: Vec<EventHook>,
+}
+
+impl EventHooks {
+    /// Calls every hook of the `event` in order, all of them even if some fail.
+    pub async fn notify(&self, event: &SellerEvent) -> anyhow::Result<()> {
+        let hooks = match event {
+            SellerEvent::Funded { .. } => &self.on_funded,
+            SellerEvent::Settled { .. } => &self.on_settled,
+        };
+        let mut errors = vec![];
+        for hook in hooks {
+            if let Err(e) = hook.call(event).await {
+                errors.push(e.to_string());
+            }
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(anyhow!(
+                "{} hooks failed: {}",
+                event.name(),
+                errors.join("; ")
+            )),
+        }
+    }
+}
+
+/// How the watcher learns of new blocks: pushed over `eth_subscribe` by the WebSocket node at the URL,
+/// or polled every interval, which is also what subscription falls back to once it drops.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BlockSource {
+    Subscription(Url),
+    Polling(Duration),
+}
+
+/// Claim awaiting confirmations before the trade is settled.
+#[derive(Clone, Debug, PartialEq)]
+struct PendingClaim {
+    sale_id: Option<SaleId>,
+    buyer: Address,
+    claim_tx: String,
+}
+
+/// Follows seller's trades on-chain: calls hooks on [`SellerEvent::Funded`] events sent by the seller,
+/// see [`crate::Seller::with_events`], and settles their sessions once claims have `confirmations`.
+pub struct EventWatcher {
+    chain: Box<dyn ConfirmationSource + Send + Sync>,
+    cache_dir: PathBuf,
+    hooks: EventHooks,
+    confirmations: u64,
+    blocks: BlockSource,
+    from_seller: mpsc::UnboundedReceiver<SellerEvent>,
+    pending: Vec<PendingClaim>,
+}
+
+impl EventWatcher {
+    /// Watcher resuming claims of the sessions paid before restart, with channel seller sends events to.
+    pub fn new(
+        chain: Box<dyn ConfirmationSource + Send + Sync>,
+        cache_dir: PathBuf,
+        hooks: EventHooks,
+        confirmations: u64,
+        blocks: BlockSource,
+    ) -> anyhow::Result<(Self, mpsc::UnboundedSender<SellerEvent>)> {
+        let pending = read_sessions::<_, SellerSession>(&cache_dir)?
+            .into_iter()
+            .filter(|(_, session)| session.phase == SessionPhase::Paid)
+            .filter_map(|(_, session)| {
+                session.tx_hash.map(|hash| PendingClaim {
+                    sale_id: session.sale_id,
+                    buyer: session.buyer,
+                    claim_tx: hex::encode(hash.as_bytes()),
+                })
+            })
+            .collect();
+        let (to_watcher, from_seller) = mpsc::unbounded();
+
+        Ok((
+            Self {
+                chain,
+                cache_dir,
+                hooks,
+                confirmations: confirmations.max(1),
+                blocks,
+                from_seller,
+                pending,
+            },
+            to_watcher,
+        ))
+    }
+
+    pub async fn run(mut self) {
+        let interval = match self.blocks.clone() {
+            BlockSource::Subscription(url) => {
+                if let Err(e) = self.follow_subscription(&url).await {
+                    tracing::warn!(
+                        "block subscription failed: {e}, polling every {}s instead",
+                        SETTLEMENT_POLL_INTERVAL.as_secs()
+                    );
+                }
+                SETTLEMENT_POLL_INTERVAL
+            }
+            BlockSource::Polling(interval) => interval,
+        };
+
+        loop {
+            tokio::select! {
+                event = self.from_seller.next() => match event {
+                    Some(event) => self.handle(event).await,
+                    None => return,
+                },
+                _ = tokio::time::sleep(interval) => report(self.check_pending().await),
+            }
+        }
+    }
+
+    /// Checks pending claims on every block pushed by the node, until subscription drops.
+    async fn follow_subscription(&mut self, url: &Url) -> anyhow::Result<()> {
+        let provider = Provider::<Ws>::connect(url.as_str())
+            .await
+            .map_err(|e| anyhow!("error connecting to {url}: {e}"))?;
+        let mut blocks = provider
+            .subscribe_blocks()
+            .await
+            .map_err(|e| anyhow!("error subscribing to blocks: {e}"))?;
+
+        loop {
+            tokio::select! {
+                event = self.from_seller.next() => match event {
+                    Some(event) => self.handle(event).await,
+                    None => return Ok(()),
+                },
+                block = blocks.next() => match block {
+                    Some(_) => report(self.check_pending().await),
+                    None => return Err(anyhow!("subscription closed by the node")),
+                },
+            }
+        }
+    }
+
+    async fn handle(&mut self, event: SellerEvent) {
+        if let SellerEvent::Funded {
+            sale_id,
+            buyer,
+            claim_tx,
+            ..
+        } = &event
+        {
+            self.pending.push(PendingClaim {
+                sale_id: sale_id.clone(),
+                buyer: *buyer,
+                claim_tx: claim_tx.clone(),
+            });
+        }
+        report(self.hooks.notify(&event).await);
+    }
+
+    /// Settles sessions of the claims confirmed deep enough by now, calling hooks of each.
+    async fn check_pending(&mut self) -> anyhow::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let tip = self.chain.tip_height().await?;
+
+        let mut settled = vec![];
+        for (i, claim) in self.pending.iter().enumerate() {
+            // unknown or not yet mined claims are checked again on the next block.
+            let height = match self.chain.tx_height(&claim.claim_tx).await {
+                Ok(Some(height)) => height,
+                _ => continue,
+            };
+            let confirmations = (tip + 1).saturating_sub(height);
+            if confirmations >= self.confirmations {
+                settled.push((i, height, confirmations));
+            }
+        }
+
+        let mut errors = vec![];
+        for (i, height, confirmations) in settled.into_iter().rev() {
+            let claim = self.pending.remove(i);
+            if let Err(e) = self.settle_session(&claim) {
+                errors.push(e.to_string());
+            }
+            let event = SellerEvent::Settled {
+                sale_id: claim.sale_id,
+                buyer: claim.buyer,
+                claim_tx: claim.claim_tx,
+                height,
+                confirmations,
+            };
+            if let Err(e) = self.hooks.notify(&event).await {
+                errors.push(e.to_string());
+            }
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(anyhow!("{}", errors.join("; "))),
+        }
+    }
+
+    fn settle_session(&self, claim: &PendingClaim) -> anyhow::Result<()> {
+        let id = seller_session_id(claim.sale_id.as_deref(), claim.buyer);
+        if let Some(mut session) = find_session::<_, SellerSession>(&self.cache_dir, &id)? {
+            session.phase = SessionPhase::Settled;
+            write_session(&self.cache_dir, &id, &session)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Daemon has no one to return errors to, so the watcher reports and keeps going.
+fn report(res: anyhow::Result<()>) {
+    if let Err(e) = res {
+        tracing::error!("event watcher: {e}");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::traits::ConfirmationSource;
+    use crate::{
+        find_session, seller_session_id, write_session, BlockSource, EventHook, EventHooks,
+        EventWatcher, SellerEvent, SellerSession, SessionPhase,
+    };
+    use async_trait::async_trait;
+    use ethers::prelude::{Address, H256};
+    use std::fs;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    struct MockChain {
+        tip: Arc<AtomicU64>,
+    }
+
+    #[async_trait]
+    impl ConfirmationSource for MockChain {
+        async fn tx_height(&self, tx_id: &str) -> anyhow::Result<Option<u64>> {
+            Ok((tx_id == hex::encode([2u8; 32])).then_some(10))
+        }
+
+        async fn tip_height(&self) -> anyhow::Result<u64> {
+            Ok(self.tip.load(Ordering::SeqCst))
+        }
+    }
+
+    #[test]
+    fn test_parse_hook() {
+        assert_eq!(
+            "https://shop.example/hooks/zkcp"
+                .parse::<EventHook>()
+                .unwrap(),
+            EventHook::Webhook("https://shop.example/hooks/zkcp".parse().unwrap())
+        );
+        assert_eq!(
+            "./fulfill.sh --notify".parse::<EventHook>().unwrap(),
+            EventHook::Command("./fulfill.sh --notify".to_string())
+        );
+        assert!("".parse::<EventHook>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_settle_confirmed_claims() {
+        let cache_dir = std::env::temp_dir().join("zkcp_test_event_watcher");
+        let _ = fs::remove_dir_all(&cache_dir);
+        fs::create_dir_all(&cache_dir).unwrap();
+        let out = cache_dir.join("events.log");
+        let buyer = Address::repeat_byte(1);
+        let session = SellerSession {
+            phase: SessionPhase::Paid,
+            sale_id: None,
+            buyer,
+            one_time_sk: "00".repeat(32),
+            trade_nonce: None,
+            tx_hash: Some(H256::repeat_byte(2)),
+            locked_at: None,
+            key_encryption: None,
+        };
+        let id = seller_session_id(None, buyer);
+        write_session(&cache_dir, &id, &session).unwrap();
+
+        let hooks = EventHooks {
+            on_funded: vec![],
+            on_settled: vec![EventHook::Command(format!(
+                "echo \"$CONTANGLE_EVENT_JSON\" >> {}",
+                out.display()
+            ))],
+        };
+        let tip = Arc::new(AtomicU64::new(10));
+        let chain = MockChain { tip: tip.clone() };
+        let (mut watcher, _) = EventWatcher::new(
+            Box::new(chain),
+            cache_dir.clone(),
+            hooks,
+            3,
+            BlockSource::Polling(Duration::from_secs(1)),
+        )
+        .unwrap();
+        assert_eq!(watcher.pending.len(), 1);
+
+        // mined at the tip, a single confirmation of the three required.
+        watcher.check_pending().await.unwrap();
+        assert_eq!(watcher.pending.len(), 1);
+        assert!(!out.exists());
+
+        tip.store(12, Ordering::SeqCst);
+        watcher.check_pending().await.unwrap();
+        assert!(watcher.pending.is_empty());
+        let settled: SellerSession = find_session(&cache_dir, &id).unwrap().unwrap();
+        assert_eq!(settled.phase, SessionPhase::Settled);
+
+        let event: SellerEvent =
+            serde_json::from_str(fs::read_to_string(&out).unwrap().trim()).unwrap();
+        assert_eq!(
+            event,
+            SellerEvent::Settled {
+                sale_id: None,
+                buyer,
+                claim_tx: hex::encode([2u8; 32]),
+                height: 10,
+                confirmations: 3,
+            }
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use crate::ciphertext_merkle_root;
+use crate::merkle::hash_leaf;
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+/// File in the cache dir with the fingerprint of the data cached sale was encrypted from.
+pub const DATA_FINGERPRINT_FILE: &str = "data_fingerprint.json";
+
+/// Content fingerprint of the data sale is encrypted from, keying the cached sale by content
+/// rather than by the data's path, so that the sale isn't served anymore once the data changes.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DataFingerprint {
+    /// Hex-encoded Merkle root over the chunks, see [`ciphertext_merkle_root`].
+    pub root: String,
+    /// Hex-encoded hash of every chunk, locating the changes.
+    pub chunks: Vec<String>,
+}
+
+impl DataFingerprint {
+    pub fn new(data: &[u8], chunk_size: usize) -> Self {
+        let chunks = data.chunks(chunk_size).collect::<Vec<_>>();
+        Self {
+            root: hex::encode(ciphertext_merkle_root(&chunks)),
+            chunks: chunks
+                .iter()
+                .map(|chunk| hex::encode(hash_leaf(chunk)))
+                .collect(),
+        }
+    }
+
+    /// Ranges of chunks differing from `other`'s, chunks only one of them has included.
+    pub fn changed_ranges(&self, other: &DataFingerprint) -> Vec<Range<usize>> {
+        if self.root == other.root {
+            return vec![];
+        }
+
+        let len = self.chunks.len().max(other.chunks.len());
+        let mut ranges: Vec<Range<usize>> = vec![];
+        for i in (0..len).filter(|&i| self.chunks.get(i) != other.chunks.get(i)) {
+            match ranges.last_mut() {
+                Some(range) if range.end == i => range.end += 1,
+                _ => ranges.push(i..i + 1),
+            }
+        }
+        ranges
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let json = serde_json::to_vec(self).expect("expected data fingerprint to marshal to json");
+        fs::write(path, json).map_err(|e| anyhow!("error writing data fingerprint: {e}"))
+    }
+
+    pub fn read<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let json = fs::read(path).map_err(|e| anyhow!("error reading data fingerprint: {e}"))?;
+        serde_json::from_slice(&json).map_err(|e| anyhow!("error decoding data fingerprint: {e}"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::DataFingerprint;
+
+    #[test]
+    fn test_changed_chunk_ranges() {
+        let data = (0..100u8).cycle().take(31 * 64).collect::<Vec<_>>();
+        let fingerprint = DataFingerprint::new(&data, 31);
+        assert_eq!(fingerprint.chunks.len(), 64);
+        assert!(fingerprint
+            .changed_ranges(&DataFingerprint::new(&data, 31))
+            .is_empty());
+
+        // bytes 40 * 31 - 1 ..= 42 * 31 + 2 span chunks 39 to 42.
+        let mut changed = data.clone();
+        changed[40 * 31 - 1..=42 * 31 + 2]
+            .iter_mut()
+            .for_each(|b| *b ^= 0xff);
+        let changed = DataFingerprint::new(&changed, 31);
+        assert_ne!(changed.root, fingerprint.root);
+        assert_eq!(fingerprint.changed_ranges(&changed), vec![39..43]);
+
+        // chunks around the change keep their hashes.
+        let unchanged = (0..64)
+            .filter(|i| fingerprint.chunks[*i] == changed.chunks[*i])
+            .collect::<Vec<_>>();
+        assert_eq!(unchanged, (0..39).chain(43..64).collect::<Vec<_>>());
+
+        let appended = [&data[..], &[1, 2, 3]].concat();
+        assert_eq!(
+            fingerprint.changed_ranges(&DataFingerprint::new(&appended, 31)),
+            vec![64..65]
+        );
+    }
+}
@@ -0,0 +1,76 @@
+//! Golden artifacts guarding the wire format against incompatible changes.
+//! Regenerate with `ZKCP_REGENERATE_GOLDEN=1 cargo test test_golden_artifacts`, only when breaking it deliberately.
+
+use crate::zk::ciphertext_from_bytes;
+use crate::{
+    read_verifying_key, Fq, PairingEngine, ProjectiveCurve, VERIFYING_KEY_FILE, VERIFYING_KEY_MAGIC,
+};
+use ark_bls12_381::Fr;
+use ark_ff::UniformRand;
+use ark_groth16::{Groth16, Proof};
+use ark_relations::lc;
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use ark_snark::{CircuitSpecificSetupSNARK, SNARK};
+use ark_std::test_rng;
+use circuits::{ark_from_bytes, ark_to_bytes};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const PROOF_FILE: &str = "proof.bin";
+const CIPHERTEXT_FILE: &str = "ciphertext.bin";
+
+/// Proves knowledge of `a` and `b` such that `a * b = c` for public `c`.
+struct MulCircuit {
+    a: Fr,
+    b: Fr,
+}
+
+impl ConstraintSynthesizer<Fr> for MulCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        let a = cs.new_witness_variable(|| Ok(self.a))?;
+        let b = cs.new_witness_variable(|| Ok(self.b))?;
+        let c = cs.new_input_variable(|| Ok(self.a * self.b))?;
+        cs.enforce_constraint(lc!() + a, lc!() + b, lc!() + c)
+    }
+}
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+/// Writes goldens from the fixed seed, so that unchanged format yields identical files.
+fn regenerate(dir: &Path) {
+    let mut rng = test_rng();
+    let (a, b) = (Fr::from(3u64), Fr::from(5u64));
+    let (pk, vk) = Groth16::<PairingEngine>::setup(MulCircuit { a, b }, &mut rng).unwrap();
+    let proof = Groth16::<PairingEngine>::prove(&pk, MulCircuit { a, b }, &mut rng).unwrap();
+    let ciphertext = (
+        ProjectiveCurve::rand(&mut rng),
+        (0..4).map(|_| Fq::rand(&mut rng)).collect::<Vec<_>>(),
+    );
+
+    fs::create_dir_all(dir).unwrap();
+    fs::write(
+        dir.join(VERIFYING_KEY_FILE),
+        [VERIFYING_KEY_MAGIC.to_vec(), ark_to_bytes(vk).unwrap()].concat(),
+    )
+    .unwrap();
+    fs::write(dir.join(PROOF_FILE), ark_to_bytes(proof).unwrap()).unwrap();
+    fs::write(dir.join(CIPHERTEXT_FILE), ark_to_bytes(ciphertext).unwrap()).unwrap();
+}
+
+#[test]
+fn test_golden_artifacts() {
+    let dir = golden_dir();
+    if std::env::var_os("ZKCP_REGENERATE_GOLDEN").is_some() {
+        regenerate(&dir);
+    }
+
+    let vk = read_verifying_key::<_, PairingEngine>(dir.join(VERIFYING_KEY_FILE)).unwrap();
+    let proof: Proof<PairingEngine> =
+        ark_from_bytes(fs::read(dir.join(PROOF_FILE)).unwrap()).unwrap();
+    assert!(Groth16::<PairingEngine>::verify(&vk, &[Fr::from(15u64)], &proof).unwrap());
+
+    let ciphertext = ciphertext_from_bytes(fs::read(dir.join(CIPHERTEXT_FILE)).unwrap()).unwrap();
+    assert_eq!(ciphertext.1.len(), 4);
+}
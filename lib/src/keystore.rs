@@ -0,0 +1,96 @@
+use crate::traits::Keystore;
+use crate::{read_from_keystore, write_to_keystore};
+use anyhow::anyhow;
+use secp256kfun::marker::{Mark, Normal};
+use secp256kfun::{g, Point, Scalar, G};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Keystore dir selecting [`InMemoryKeystore`] instead of the directory, see [`open_keystore`].
+pub const IN_MEMORY_KEYSTORE: &str = ":memory:";
+
+/// Keystore of encrypted key files in `dir`, as written by [`write_to_keystore`].
+pub struct FileKeystore {
+    dir: PathBuf,
+}
+
+impl FileKeystore {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+impl Keystore for FileKeystore {
+    fn write(&mut self, sk: Scalar, name: &str, password: &[u8]) -> anyhow::Result<()> {
+        write_to_keystore(sk, &self.dir, name, password)
+    }
+
+    fn read(&self, name: &str, password: &[u8]) -> anyhow::Result<(Scalar, Point)> {
+        read_from_keystore(self.dir.join(name), password)
+    }
+}
+
+/// Keystore holding wallets in process memory only, for tests and short-lived processes.
+/// Keys are kept in the clear and lost on exit, password is only checked against its digest.
+#[derive(Default)]
+pub struct InMemoryKeystore {
+    wallets: HashMap<String, ([u8; 32], Scalar)>,
+}
+
+impl InMemoryKeystore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Keystore for InMemoryKeystore {
+    fn write(&mut self, sk: Scalar, name: &str, password: &[u8]) -> anyhow::Result<()> {
+        let _ = self
+            .wallets
+            .insert(name.to_string(), (Sha256::digest(password).into(), sk));
+        Ok(())
+    }
+
+    fn read(&self, name: &str, password: &[u8]) -> anyhow::Result<(Scalar, Point)> {
+        match self.wallets.get(name) {
+            Some((digest, sk)) if digest[..] == Sha256::digest(password)[..] => {
+                let sk = sk.clone();
+                let pk = g!(sk * G).mark::<Normal>();
+                Ok((sk, pk))
+            }
+            Some(_) => Err(anyhow!("error decrypting key: wrong password")),
+            None => Err(anyhow!("no wallet named {name} in memory keystore")),
+        }
+    }
+}
+
+/// Opens [`InMemoryKeystore`] if `dir` is [`IN_MEMORY_KEYSTORE`], or [`FileKeystore`] in `dir` otherwise.
+pub fn open_keystore(dir: &str) -> Box<dyn Keystore> {
+    match dir {
+        IN_MEMORY_KEYSTORE => Box::new(InMemoryKeystore::new()),
+        dir => Box::new(FileKeystore::new(dir)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{keypair_gen, open_keystore, LocalWallet, IN_MEMORY_KEYSTORE};
+    use std::path::Path;
+
+    #[test]
+    fn test_in_memory_keystore() {
+        let (sk, pk) = keypair_gen();
+        let mut keystore = open_keystore(IN_MEMORY_KEYSTORE);
+        keystore.write(sk, "wallet", b"password").unwrap();
+
+        let wallet = LocalWallet::from_store(&*keystore, "wallet", "password").unwrap();
+        assert_eq!(wallet.pub_key(), &pk);
+        assert!(keystore.read("wallet", b"wrong").is_err());
+        assert!(keystore.read("other", b"password").is_err());
+
+        // sentinel isn't taken for a directory to write into.
+        assert!(!Path::new(IN_MEMORY_KEYSTORE).exists());
+        assert!(!Path::new("wallet").exists());
+    }
+}
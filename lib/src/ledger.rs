@@ -0,0 +1,13 @@
+use anyhow::anyhow;
+use ethers::signers::{HDPath, Ledger, Signer};
+use ethers::types::Address;
+
+/// Address of the account at BIP-32 `derivation_path` of the connected Ledger device,
+/// which payments may be sent to without its key ever leaving the device.
+pub async fn ledger_address(derivation_path: &str) -> anyhow::Result<Address> {
+    // chain id only matters for signing transactions, which isn't done with the device.
+    let ledger = Ledger::new(HDPath::Other(derivation_path.to_string()), 1)
+        .await
+        .map_err(|e| anyhow!("error connecting to ledger: {e}"))?;
+    Ok(ledger.address())
+}
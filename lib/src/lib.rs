@@ -1,24 +1,89 @@
 #![feature(async_closure)]
 
+mod announcement;
+mod artifact;
+mod attestation;
+mod audit;
+mod blob;
 mod buyer;
+mod catalog;
 pub mod cipher_host;
 mod config;
+mod dispute;
 mod ethereum;
+mod events;
+mod fingerprint;
+#[cfg(test)]
+mod golden;
+mod keystore;
+#[cfg(feature = "ledger")]
+mod ledger;
+mod merkle;
+mod metrics;
+mod offer;
+mod payment;
+mod proofs;
+mod quote;
+mod receipt;
+mod registry;
+mod sale;
 mod seller;
+mod session;
+mod solidity;
+mod stream;
+mod summary;
 mod traits;
 mod utils;
+mod vk_json;
 mod wallet;
 pub mod zk;
 
+pub use announcement::*;
+pub use artifact::*;
+pub use attestation::*;
+pub use audit::*;
+pub use blob::*;
 pub use buyer::*;
+pub use catalog::*;
 pub use config::*;
+pub use dispute::*;
 pub use ethereum::*;
+pub use events::*;
+pub use fingerprint::*;
+pub use keystore::*;
+#[cfg(feature = "ledger")]
+pub use ledger::*;
+pub use merkle::*;
+pub use metrics::*;
+pub use payment::*;
+pub use proofs::*;
+pub use quote::*;
+pub use receipt::*;
+pub use registry::*;
+pub use sale::*;
 pub use seller::*;
+pub use session::*;
+pub use solidity::*;
+pub use stream::*;
+pub use summary::*;
 pub use traits::*;
 pub use utils::*;
+pub use vk_json::*;
 pub use wallet::*;
 
+// BLS12-381 with JubJub unless built with `bn254` feature, which is checkable by the EVM precompiles.
+#[cfg(not(feature = "bn254"))]
 pub use ark_bls12_381::Bls12_381 as PairingEngine;
+#[cfg(not(feature = "bn254"))]
 pub use ark_ed_on_bls12_381::{
-    constraints::EdwardsVar as CurveVar, EdwardsProjective as ProjectiveCurve, Fq, Fr,
+    constraints::EdwardsVar as CurveVar, EdwardsAffine as CurveAffine,
+    EdwardsParameters as CurveParameters, EdwardsProjective as ProjectiveCurve, Fq, Fr,
+};
+
+#[cfg(feature = "bn254")]
+pub use ark_bn254::Bn254 as PairingEngine;
+#[cfg(feature = "bn254")]
+pub use ark_ed_on_bn254::{
+    constraints::EdwardsVar as CurveVar, EdwardsAffine as CurveAffine,
+    EdwardsParameters as CurveParameters, EdwardsProjective as ProjectiveCurve, Fq, Fr,
 };
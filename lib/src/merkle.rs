@@ -0,0 +1,124 @@
+use sha2::{Digest, Sha256};
+
+/// Hash standing for the missing leaves when their count isn't a power of two.
+const EMPTY_LEAF: [u8; 32] = [0; 32];
+
+/// Leaf is `SHA-256(0x00 || chunk)`; domain separation prevents passing inner node off as a leaf.
+pub(crate) fn hash_leaf(chunk: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([0]);
+    hasher.update(chunk);
+    hasher.finalize().into()
+}
+
+/// Inner node is `SHA-256(0x01 || left || right)`.
+fn hash_node(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([1]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Builds all tree levels bottom-up, leaves are padded with [`EMPTY_LEAF`] up to the power of two.
+fn build_levels<C: AsRef<[u8]>>(ciphertexts: &[C]) -> Vec<Vec<[u8; 32]>> {
+    let mut leaves: Vec<_> = ciphertexts.iter().map(|c| hash_leaf(c.as_ref())).collect();
+    leaves.resize(leaves.len().next_power_of_two(), EMPTY_LEAF);
+
+    let mut levels = vec![leaves];
+    while levels.last().unwrap().len() > 1 {
+        let level = levels
+            .last()
+            .unwrap()
+            .chunks(2)
+            .map(|pair| hash_node(&pair[0], &pair[1]))
+            .collect();
+        levels.push(level);
+    }
+
+    levels
+}
+
+/// SHA-256 Merkle root over ciphertext chunks, suitable for anchoring on-chain
+/// so that individual chunks can be checked against it with [`verify_merkle_proof`].
+pub fn ciphertext_merkle_root<C: AsRef<[u8]>>(ciphertexts: &[C]) -> [u8; 32] {
+    build_levels(ciphertexts).last().unwrap()[0]
+}
+
+/// Sibling hashes on the path from chunk at `index` to the root, bottom-up.
+pub fn merkle_proof<C: AsRef<[u8]>>(ciphertexts: &[C], index: usize) -> Option<Vec<[u8; 32]>> {
+    if index >= ciphertexts.len() {
+        return None;
+    }
+
+    let levels = build_levels(ciphertexts);
+    Some(
+        levels[..levels.len() - 1]
+            .iter()
+            .enumerate()
+            .map(|(depth, level)| level[(index >> depth) ^ 1])
+            .collect(),
+    )
+}
+
+pub fn verify_merkle_proof(
+    root: &[u8; 32],
+    ciphertext: &[u8],
+    index: usize,
+    proof: &[[u8; 32]],
+) -> bool {
+    let mut node = hash_leaf(ciphertext);
+    for (depth, sibling) in proof.iter().enumerate() {
+        node = match (index >> depth) & 1 {
+            0 => hash_node(&node, sibling),
+            _ => hash_node(sibling, &node),
+        };
+    }
+
+    index.checked_shr(proof.len() as u32).unwrap_or(0) == 0 && node == *root
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ciphertext_merkle_root, merkle_proof, verify_merkle_proof};
+    use rand::Rng;
+
+    fn chunks(n: usize) -> Vec<Vec<u8>> {
+        (0..n).map(|i| vec![i as u8; 32]).collect()
+    }
+
+    #[test]
+    fn test_merkle_root_stability() {
+        let root = ciphertext_merkle_root(&chunks(5));
+
+        assert_eq!(root, ciphertext_merkle_root(&chunks(5)));
+        assert_ne!(root, ciphertext_merkle_root(&chunks(6)));
+        assert_eq!(
+            hex::encode(ciphertext_merkle_root(&[[1u8; 32]])),
+            hex::encode(super::hash_leaf(&[1; 32]))
+        );
+    }
+
+    #[test]
+    fn test_merkle_proof_verification() {
+        let mut rng = rand::thread_rng();
+        for n in [1, 2, 7, 16, 33] {
+            let chunks = chunks(n);
+            let root = ciphertext_merkle_root(&chunks);
+
+            for _ in 0..8 {
+                let index = rng.gen_range(0..n);
+                let proof = merkle_proof(&chunks, index).unwrap();
+
+                assert!(verify_merkle_proof(&root, &chunks[index], index, &proof));
+                assert!(!verify_merkle_proof(&root, &[0xff; 32], index, &proof));
+                if n > 1 {
+                    let other = (index + 1) % n;
+                    assert!(!verify_merkle_proof(&root, &chunks[index], other, &proof));
+                }
+            }
+
+            assert!(merkle_proof(&chunks, n).is_none());
+        }
+    }
+}
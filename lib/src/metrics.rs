@@ -0,0 +1,109 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bounds in seconds of the buckets proving times are counted in.
+pub const PROVING_TIME_BUCKETS: [f64; 9] = [0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0];
+
+/// Counters of the seller daemon, rendered in Prometheus text format for `/metrics` to serve.
+#[derive(Debug, Default)]
+pub struct SellerMetrics {
+    proofs_generated: AtomicU64,
+    swaps_completed: AtomicU64,
+    proving_time: Mutex<Histogram>,
+}
+
+#[derive(Debug, Default)]
+struct Histogram {
+    /// Observations at or below each of [`PROVING_TIME_BUCKETS`], not cumulated yet.
+    buckets: [u64; PROVING_TIME_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl SellerMetrics {
+    /// Counts proof of encryption generated in `elapsed`, be it of the sale or of a key at Step 1.
+    pub fn record_proof(&self, elapsed: Duration) {
+        let _ = self.proofs_generated.fetch_add(1, Ordering::Relaxed);
+        let secs = elapsed.as_secs_f64();
+        let mut histogram = self.proving_time.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(i) = PROVING_TIME_BUCKETS.iter().position(|le| secs <= *le) {
+            histogram.buckets[i] += 1;
+        }
+        histogram.sum += secs;
+        histogram.count += 1;
+    }
+
+    /// Counts swap settled on-chain by adaptor-signed transfer.
+    pub fn record_swap(&self) {
+        let _ = self.swaps_completed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let counters = [
+            (
+                "contangle_proofs_generated_total",
+                "Proofs of encryption generated by the seller.",
+                &self.proofs_generated,
+            ),
+            (
+                "contangle_swaps_completed_total",
+                "Swaps the seller got paid for.",
+                &self.swaps_completed,
+            ),
+        ];
+        for (name, help, value) in counters {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} counter");
+            let _ = writeln!(out, "{name} {}", value.load(Ordering::Relaxed));
+        }
+
+        let histogram = self.proving_time.lock().unwrap_or_else(|e| e.into_inner());
+        let name = "contangle_proving_seconds";
+        let _ = writeln!(out, "# HELP {name} Time spent proving encryption.");
+        let _ = writeln!(out, "# TYPE {name} histogram");
+        let mut cumulative = 0;
+        for (le, count) in PROVING_TIME_BUCKETS.iter().zip(histogram.buckets) {
+            cumulative += count;
+            let _ = writeln!(out, "{name}_bucket{{le=\"{le}\"}} {cumulative}");
+        }
+        let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {}", histogram.count);
+        let _ = writeln!(out, "{name}_sum {}", histogram.sum);
+        let _ = writeln!(out, "{name}_count {}", histogram.count);
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::SellerMetrics;
+    use std::time::Duration;
+
+    #[test]
+    fn test_render_metrics() {
+        let metrics = SellerMetrics::default();
+        metrics.record_proof(Duration::from_millis(800));
+        metrics.record_proof(Duration::from_secs(20));
+        metrics.record_proof(Duration::from_secs(600));
+        metrics.record_swap();
+
+        let rendered = metrics.render();
+        let lines = rendered.lines().collect::<Vec<_>>();
+        for line in [
+            "contangle_proofs_generated_total 3",
+            "contangle_swaps_completed_total 1",
+            "# TYPE contangle_proving_seconds histogram",
+            "contangle_proving_seconds_bucket{le=\"0.5\"} 0",
+            "contangle_proving_seconds_bucket{le=\"1\"} 1",
+            "contangle_proving_seconds_bucket{le=\"30\"} 2",
+            "contangle_proving_seconds_bucket{le=\"300\"} 2",
+            "contangle_proving_seconds_bucket{le=\"+Inf\"} 3",
+            "contangle_proving_seconds_sum 620.8",
+            "contangle_proving_seconds_count 3",
+        ] {
+            assert!(lines.contains(&line), "missing {line} in:\n{rendered}");
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use crate::{eth_address, Step1Msg};
+use anyhow::anyhow;
+use ecdsa_fun::{Signature, ECDSA};
+use ethers::types::Address;
+use secp256kfun::nonce::Deterministic;
+use secp256kfun::{Point, Scalar};
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+
+/// Domain tag of the Step 1 digest, so that the signature can't be replayed as anything else.
+const OFFER_TAG: &[u8] = b"zkcp/key-offer/v1";
+
+impl Step1Msg {
+    /// Digest of the encrypted key and payment address offered to `buyer` for the sale `sale_id`,
+    /// the seller signs so that relays can't swap the address buyer pays to or the key it pays for.
+    pub fn digest(&self, sale_id: Option<&str>, buyer: Address) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(OFFER_TAG);
+        hasher.update(sale_id.unwrap_or_default().as_bytes());
+        hasher.update([0]);
+        hasher.update(buyer.as_bytes());
+        hasher.update((self.ciphertext.len() as u64).to_le_bytes());
+        hasher.update(&self.ciphertext);
+        hasher.update((self.proof_of_encryption.len() as u64).to_le_bytes());
+        hasher.update(&self.proof_of_encryption);
+        hasher.update(self.data_pk.to_bytes());
+        hasher.update(self.seller_address.as_bytes());
+        hasher.finalize().into()
+    }
+
+    /// Hex-encoded ECDSA signature of `sk` over [`Self::digest`].
+    pub fn sign(&self, sale_id: Option<&str>, buyer: Address, sk: &Scalar) -> String {
+        let ecdsa = ECDSA::new(Deterministic::<Sha256>::default());
+        hex::encode(ecdsa.sign(sk, &self.digest(sale_id, buyer)).to_bytes())
+    }
+
+    /// Fails unless `signature` over the message offered to `buyer` is made by `seller_pk`,
+    /// whose address is `expected_seller`.
+    pub fn verify(
+        &self,
+        sale_id: Option<&str>,
+        buyer: Address,
+        seller_pk: &str,
+        signature: &str,
+        expected_seller: Address,
+    ) -> anyhow::Result<()> {
+        let seller_pk =
+            Point::from_str(seller_pk).map_err(|e| anyhow!("bad seller public key: {e}"))?;
+        let signature = hex::decode(signature)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .and_then(Signature::from_bytes)
+            .ok_or(anyhow!("error decoding step1 signature"))?;
+
+        let digest = self.digest(sale_id, buyer);
+        if !ECDSA::verify_only().verify(&seller_pk, &digest, &signature) {
+            return Err(anyhow!("step1 signature is invalid"));
+        }
+        let seller = eth_address(&seller_pk);
+        if seller != expected_seller {
+            return Err(anyhow!(
+                "step1 message is signed by {seller:?}, not expected seller {expected_seller:?}"
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{eth_address, keypair_gen, Step1Msg};
+    use ethers::types::Address;
+
+    #[test]
+    fn test_verify_step1_offer() {
+        let (seller_sk, seller_pk) = keypair_gen();
+        let (_, data_pk) = keypair_gen();
+        let seller = eth_address(&seller_pk);
+        let buyer = Address::repeat_byte(1);
+        let msg = Step1Msg {
+            ciphertext: vec![1, 2, 3],
+            proof_of_encryption: vec![4, 5, 6],
+            data_pk,
+            seller_address: Address::repeat_byte(2),
+        };
+        let pk = seller_pk.to_string();
+        let signature = msg.sign(Some("sale"), buyer, &seller_sk);
+        msg.verify(Some("sale"), buyer, &pk, &signature, seller)
+            .unwrap();
+
+        // relay paying itself instead of the seller.
+        let mut tampered = msg.clone();
+        tampered.seller_address = Address::repeat_byte(3);
+        assert!(tampered
+            .verify(Some("sale"), buyer, &pk, &signature, seller)
+            .is_err());
+        // offers made to another buyer or for another sale aren't replayable.
+        assert!(msg
+            .verify(
+                Some("sale"),
+                Address::repeat_byte(4),
+                &pk,
+                &signature,
+                seller
+            )
+            .is_err());
+        assert!(msg.verify(None, buyer, &pk, &signature, seller).is_err());
+
+        // validly signed by another key than the expected seller's.
+        let (other_sk, other_pk) = keypair_gen();
+        let forged = msg.sign(Some("sale"), buyer, &other_sk);
+        let err = msg
+            .verify(Some("sale"), buyer, &other_pk.to_string(), &forged, seller)
+            .unwrap_err();
+        assert!(err.to_string().contains("not expected seller"), "{err}");
+    }
+}
@@ -0,0 +1,407 @@
+use crate::traits::ChainProvider;
+use crate::LocalWallet;
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use ethers::prelude::{Address, H256, U256};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::future::Future;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+const PAYMENTS_DIR: &str = "payments";
+
+/// Deterministic key identifying payment of `price` made by `buyer` for the sale with `sale_hash`.
+pub fn payment_idempotency_key(buyer: Address, sale_hash: &[u8; 32], price: f64) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(buyer.as_bytes());
+    hasher.update(sale_hash);
+    hasher.update(price.to_le_bytes());
+    hasher.finalize().into()
+}
+
+pub fn write_pending_payment<P: AsRef<Path>>(
+    cache_dir: P,
+    key: &[u8; 32],
+    tx_hash: H256,
+) -> anyhow::Result<()> {
+    let dir = cache_dir.as_ref().join(PAYMENTS_DIR);
+    fs::create_dir_all(&dir).map_err(|e| anyhow!("error creating payments dir: {e}"))?;
+    fs::write(dir.join(hex::encode(key)), hex::encode(tx_hash.as_bytes()))
+        .map_err(|e| anyhow!("error persisting payment: {e}"))
+}
+
+pub fn read_pending_payment<P: AsRef<Path>>(cache_dir: P, key: &[u8; 32]) -> Option<H256> {
+    let tx_hash =
+        fs::read_to_string(cache_dir.as_ref().join(PAYMENTS_DIR).join(hex::encode(key))).ok()?;
+    H256::from_str(tx_hash.trim()).ok()
+}
+
+/// Looks up persisted payment identified by `key` and confirms it is known to the chain.
+pub async fn find_payment<TChainProvider: ChainProvider, P: AsRef<Path>>(
+    chain: &TChainProvider,
+    cache_dir: P,
+    key: &[u8; 32],
+) -> anyhow::Result<Option<H256>> {
+    match read_pending_payment(cache_dir, key) {
+        Some(tx_hash) => Ok(chain.get_signature(tx_hash).await?.map(|_| tx_hash)),
+        None => Ok(None),
+    }
+}
+
+/// Makes payment identified by `key` with `broadcast`, unless it was already made,
+/// in which case hash of the existing transaction is returned, so retries never pay twice.
+pub async fn pay_once<TChainProvider, P, F, Fut>(
+    chain: &TChainProvider,
+    cache_dir: P,
+    key: &[u8; 32],
+    broadcast: F,
+) -> anyhow::Result<H256>
+where
+    TChainProvider: ChainProvider,
+    P: AsRef<Path>,
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = anyhow::Result<H256>>,
+{
+    if let Some(tx_hash) = find_payment(chain, &cache_dir, key).await? {
+        return Ok(tx_hash);
+    }
+
+    let tx_hash = broadcast().await?;
+    write_pending_payment(cache_dir, key, tx_hash)?;
+    Ok(tx_hash)
+}
+
+/// Nonce and gas buyer pins the payment authorized with adaptor signature to at Step 2, sent along
+/// at Step 3 for the seller to compose the same transaction, see [`ChainProvider::compose_tx`].
+/// Refund spends the same nonce, so that it can only be mined in place of the payment.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxParams {
+    pub nonce: U256,
+    pub gas: U256,
+    /// Gas price in wei, replacing transaction has to pay at least 10% more.
+    pub gas_price: U256,
+}
+
+/// How often the chain is polled while waiting for the seller to settle payment.
+pub const SETTLEMENT_POLL_INTERVAL: Duration = Duration::from_secs(4);
+
+/// Seller didn't settle payment in time, so the buyer revoked its authorization.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SettlementTimedOut {
+    pub timeout_blocks: u64,
+    /// Transaction that spent the nonce of the authorized payment.
+    pub refund_tx: H256,
+}
+
+impl Display for SettlementTimedOut {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "seller didn't settle payment within {} blocks, it was refunded in tx {:?}",
+            self.timeout_blocks, self.refund_tx
+        )
+    }
+}
+
+impl std::error::Error for SettlementTimedOut {}
+
+/// Refunds `payment` authorized to the seller with adaptor signature, by spending its nonce
+/// on a zero-value transfer to the buyer itself. Once mined, seller can no longer claim it.
+pub async fn revoke_payment<TChainProvider: ChainProvider>(
+    chain: &TChainProvider,
+    wallet: &LocalWallet,
+    payment: &TxParams,
+) -> anyhow::Result<H256> {
+    let address = chain.address_from_pk(wallet.pub_key());
+    chain
+        .send_own(wallet, address, 0.0, Some(payment))
+        .await
+        .map_err(|e| anyhow!("error refunding payment: {e}"))
+}
+
+/// Refunds `payment` locked at `locked_at` but never settled by seller, with [`revoke_payment`].
+/// Fails until `grace` has passed since the lock, so that seller still broadcasting it isn't raced,
+/// and once the payment is mined, since its signature has revealed the key by then.
+pub async fn refund_unsettled<TChainProvider: ChainProvider>(
+    chain: &TChainProvider,
+    wallet: &LocalWallet,
+    payment: &TxParams,
+    locked_at: DateTime<Utc>,
+    grace: Duration,
+) -> anyhow::Result<H256> {
+    let grace = chrono::Duration::from_std(grace)
+        .map_err(|e| anyhow!("grace period is out of range: {e}"))?;
+    let grace_ends = locked_at + grace;
+    if Utc::now() < grace_ends {
+        return Err(anyhow!(
+            "payment can only be refunded once its grace period ends at {}",
+            grace_ends.to_rfc3339()
+        ));
+    }
+    let buyer = chain.address_from_pk(wallet.pub_key());
+    if chain.nonce_mined(buyer, payment.nonce).await? {
+        return Err(anyhow!(
+            "payment was already made, the key is revealed by its signature"
+        ));
+    }
+
+    revoke_payment(chain, wallet, payment).await
+}
+
+/// Awaits seller to `settle` payment for at most `timeout_blocks` blocks, polling chain every `poll_interval`.
+/// Once timeout expires, `payment` is revoked with [`revoke_payment`] and [`SettlementTimedOut`]
+/// is returned, unless it was mined meanwhile, in which case seller's answer is still awaited.
+pub async fn settle_or_refund<TChainProvider, Fut>(
+    chain: &TChainProvider,
+    wallet: &LocalWallet,
+    payment: &TxParams,
+    settle: Fut,
+    timeout_blocks: u64,
+    poll_interval: Duration,
+) -> anyhow::Result<H256>
+where
+    TChainProvider: ChainProvider,
+    Fut: Future<Output = anyhow::Result<H256>>,
+{
+    let deadline = chain.block_number().await? + timeout_blocks;
+    tokio::pin!(settle);
+
+    loop {
+        tokio::select! {
+            res = &mut settle => return res,
+            _ = tokio::time::sleep(poll_interval) => {
+                if chain.block_number().await? >= deadline {
+                    break;
+                }
+            }
+        }
+    }
+
+    // seller broadcast payment, but didn't answer in time: it's paid and can't be refunded.
+    let buyer = chain.address_from_pk(wallet.pub_key());
+    if chain.nonce_mined(buyer, payment.nonce).await? {
+        return settle.await;
+    }
+    let refund_tx = revoke_payment(chain, wallet, payment).await?;
+    Err(SettlementTimedOut {
+        timeout_blocks,
+        refund_tx,
+    }
+    .into())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        pay_once, payment_idempotency_key, refund_unsettled, settle_or_refund, ChainProvider,
+        LocalWallet, SettlementTimedOut, TxParams,
+    };
+    use async_trait::async_trait;
+    use chrono::Utc;
+    use ecdsa_fun::Signature;
+    use ethers::prelude::{Address, H256, U256};
+    use secp256kfun::Point;
+    use std::fs;
+    use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct MockChain {
+        broadcasts: AtomicUsize,
+        blocks: AtomicU64,
+        /// Whether the payment was mined, spending its nonce.
+        paid: AtomicBool,
+        replaced: Mutex<Option<TxParams>>,
+    }
+
+    #[async_trait]
+    impl ChainProvider for MockChain {
+        type Tx = ();
+
+        async fn tx_params(&self, _: Address, _: Address, _: f64) -> anyhow::Result<TxParams> {
+            Ok(TxParams::default())
+        }
+
+        fn compose_tx(
+            &self,
+            _: Address,
+            _: Address,
+            _: f64,
+            _: &TxParams,
+        ) -> anyhow::Result<((), H256)> {
+            Ok(((), H256::zero()))
+        }
+
+        async fn sent_signed(&self, _tx: (), _sig: &Signature) -> anyhow::Result<H256> {
+            self.broadcasts.fetch_add(1, Ordering::SeqCst);
+            Ok(H256::repeat_byte(1))
+        }
+
+        async fn get_signature(&self, hash: H256) -> anyhow::Result<Option<Signature>> {
+            let is_mined =
+                self.broadcasts.load(Ordering::SeqCst) != 0 && hash == H256::repeat_byte(1);
+            Ok(is_mined.then(|| Signature::from_bytes([1; 64]).unwrap()))
+        }
+
+        async fn block_number(&self) -> anyhow::Result<u64> {
+            Ok(self.blocks.fetch_add(1, Ordering::SeqCst))
+        }
+
+        fn address_from_pk(&self, _pk: &Point) -> Address {
+            Address::zero()
+        }
+
+        async fn nonce_mined(&self, _: Address, _: U256) -> anyhow::Result<bool> {
+            Ok(self.paid.load(Ordering::SeqCst))
+        }
+
+        async fn send_own(
+            &self,
+            _: &LocalWallet,
+            _: Address,
+            _: f64,
+            replacing: Option<&TxParams>,
+        ) -> anyhow::Result<H256> {
+            *self.replaced.lock().unwrap() = replacing.copied();
+            self.sent_signed((), &Signature::from_bytes([1; 64]).unwrap())
+                .await
+        }
+    }
+
+    #[test]
+    fn test_idempotency_key_is_deterministic() {
+        let key = payment_idempotency_key(Address::zero(), &[7; 32], 0.1);
+
+        assert_eq!(key, payment_idempotency_key(Address::zero(), &[7; 32], 0.1));
+        assert_ne!(key, payment_idempotency_key(Address::zero(), &[7; 32], 0.2));
+        assert_ne!(key, payment_idempotency_key(Address::zero(), &[8; 32], 0.1));
+    }
+
+    #[tokio::test]
+    async fn test_retried_payment_is_made_once() {
+        let chain = MockChain::default();
+        let sig = Signature::from_bytes([1; 64]).unwrap();
+        let cache_dir = std::env::temp_dir().join("zkcp_test_retried_payment");
+        let _ = fs::remove_dir_all(&cache_dir);
+        let key = payment_idempotency_key(Address::zero(), &[7; 32], 0.1);
+
+        // first attempt gets broadcast, but its response is dropped as if it timed out.
+        let _ = pay_once(&chain, &cache_dir, &key, || chain.sent_signed((), &sig)).await;
+
+        let tx_hash = pay_once(&chain, &cache_dir, &key, || chain.sent_signed((), &sig))
+            .await
+            .unwrap();
+
+        assert_eq!(tx_hash, H256::repeat_byte(1));
+        assert_eq!(chain.broadcasts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_unsettled_payment_is_refunded() {
+        let chain = MockChain::default();
+        let wallet = LocalWallet::new().unwrap();
+        let poll = Duration::from_millis(1);
+        let payment = TxParams {
+            nonce: U256::from(5),
+            gas: U256::from(21_000),
+            gas_price: U256::exp10(9),
+        };
+
+        let tx_hash = settle_or_refund(
+            &chain,
+            &wallet,
+            &payment,
+            async { Ok::<_, anyhow::Error>(H256::zero()) },
+            3,
+            poll,
+        )
+        .await
+        .unwrap();
+        assert_eq!(tx_hash, H256::zero());
+        assert_eq!(chain.broadcasts.load(Ordering::SeqCst), 0);
+
+        let err = settle_or_refund(
+            &chain,
+            &wallet,
+            &payment,
+            std::future::pending::<anyhow::Result<H256>>(),
+            3,
+            poll,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<SettlementTimedOut>(),
+            Some(&SettlementTimedOut {
+                timeout_blocks: 3,
+                refund_tx: H256::repeat_byte(1),
+            })
+        );
+        assert_eq!(chain.broadcasts.load(Ordering::SeqCst), 1);
+        // refund replaces the payment, rather than queueing after it.
+        assert_eq!(*chain.replaced.lock().unwrap(), Some(payment));
+    }
+
+    #[tokio::test]
+    async fn test_locked_payment_is_refunded_after_grace() {
+        let chain = MockChain::default();
+        let wallet = LocalWallet::new().unwrap();
+        let grace = Duration::from_millis(50);
+        let payment = TxParams {
+            nonce: U256::from(5),
+            ..TxParams::default()
+        };
+        let locked_at = Utc::now();
+
+        let err = refund_unsettled(&chain, &wallet, &payment, locked_at, grace)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("grace period"), "{err}");
+        assert_eq!(chain.broadcasts.load(Ordering::SeqCst), 0);
+
+        // seller never reveals the key by broadcasting payment.
+        tokio::time::sleep(grace).await;
+        let refund_tx = refund_unsettled(&chain, &wallet, &payment, locked_at, grace)
+            .await
+            .unwrap();
+        assert_eq!(refund_tx, H256::repeat_byte(1));
+        assert_eq!(*chain.replaced.lock().unwrap(), Some(payment));
+
+        chain.paid.store(true, Ordering::SeqCst);
+        assert!(
+            refund_unsettled(&chain, &wallet, &payment, locked_at, grace)
+                .await
+                .is_err()
+        );
+        assert_eq!(chain.broadcasts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_mined_payment_is_not_refunded() {
+        let chain = MockChain::default();
+        chain.paid.store(true, Ordering::SeqCst);
+        let wallet = LocalWallet::new().unwrap();
+
+        // seller answers only after the timeout, having broadcast payment before it.
+        let tx_hash = settle_or_refund(
+            &chain,
+            &wallet,
+            &TxParams::default(),
+            async {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok::<_, anyhow::Error>(H256::repeat_byte(2))
+            },
+            3,
+            Duration::from_millis(1),
+        )
+        .await
+        .unwrap();
+        assert_eq!(tx_hash, H256::repeat_byte(2));
+        assert_eq!(chain.broadcasts.load(Ordering::SeqCst), 0);
+    }
+}
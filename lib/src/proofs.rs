@@ -0,0 +1,211 @@
+use crate::zk::ProofOfProperty;
+use anyhow::anyhow;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Magic bytes opening every proof set file.
+pub const PROOFS_MAGIC: [u8; 4] = *b"ZKPS";
+
+/// Writes `proofs` into a single file laid out as `magic | u64 count | u64 offset of each proof | proofs`,
+/// each proof encoded as length-prefixed sections, so that any of them can be read without parsing the rest.
+pub fn write_proofs<P: AsRef<Path>>(path: P, proofs: &[ProofOfProperty]) -> anyhow::Result<()> {
+    let entries = proofs.iter().map(encode_proof).collect::<Vec<_>>();
+
+    let mut offset = (PROOFS_MAGIC.len() + 8 + entries.len() * 8) as u64;
+    let mut index = Vec::with_capacity(entries.len() * 8);
+    for entry in &entries {
+        index.extend(offset.to_le_bytes());
+        offset += entry.len() as u64;
+    }
+
+    let file = File::create(path).map_err(|e| anyhow!("error creating proofs file: {e}"))?;
+    let mut writer = BufWriter::new(file);
+    let count = (entries.len() as u64).to_le_bytes();
+    for bytes in [&PROOFS_MAGIC[..], &count, &index]
+        .into_iter()
+        .chain(entries.iter().map(Vec::as_slice))
+    {
+        writer
+            .write_all(bytes)
+            .map_err(|e| anyhow!("error writing proofs file: {e}"))?;
+    }
+
+    writer
+        .flush()
+        .map_err(|e| anyhow!("error writing proofs file: {e}"))
+}
+
+/// Reads all proofs written with [`write_proofs`].
+pub fn read_proofs<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<ProofOfProperty>> {
+    let buf = fs::read(path).map_err(|e| anyhow!("error reading proofs file: {e}"))?;
+    let count = read_count(&mut &buf[..])?;
+
+    (0..count)
+        .map(|i| {
+            let offset = u64_at(&buf, PROOFS_MAGIC.len() + 8 + i * 8)? as usize;
+            let mut entry = buf
+                .get(offset..)
+                .ok_or(anyhow!("proof {i} is out of the file bounds"))?;
+            decode_proof(&mut entry)
+        })
+        .collect()
+}
+
+/// Reads proof at `index` only, seeking to it through the index.
+pub fn read_proof_at<P: AsRef<Path>>(path: P, index: usize) -> anyhow::Result<ProofOfProperty> {
+    let file = File::open(path).map_err(|e| anyhow!("error opening proofs file: {e}"))?;
+    let mut reader = BufReader::new(file);
+
+    let mut header = [0; PROOFS_MAGIC.len() + 8];
+    reader
+        .read_exact(&mut header)
+        .map_err(|_e| anyhow!("proofs file is truncated"))?;
+    let count = read_count(&mut &header[..])?;
+    if index >= count {
+        return Err(anyhow!("proof {index} is out of {count} proofs"));
+    }
+
+    let mut offset = [0; 8];
+    reader
+        .seek(SeekFrom::Start((header.len() + index * 8) as u64))
+        .and_then(|_| reader.read_exact(&mut offset))
+        .map_err(|_e| anyhow!("proofs file is truncated"))?;
+    reader
+        .seek(SeekFrom::Start(u64::from_le_bytes(offset)))
+        .map_err(|e| anyhow!("error seeking proofs file: {e}"))?;
+
+    let mut entry = vec![];
+    reader
+        .read_to_end(&mut entry)
+        .map_err(|e| anyhow!("error reading proofs file: {e}"))?;
+    decode_proof(&mut &entry[..])
+}
+
+fn read_count(buf: &mut &[u8]) -> anyhow::Result<usize> {
+    *buf = buf
+        .strip_prefix(&PROOFS_MAGIC)
+        .ok_or(anyhow!("not a proofs file"))?;
+    Ok(u64_at(buf, 0)? as usize)
+}
+
+fn encode_proof(proof: &ProofOfProperty) -> Vec<u8> {
+    let mut buf = vec![];
+    let mut push = |bytes: &[u8]| {
+        buf.extend((bytes.len() as u64).to_le_bytes());
+        buf.extend(bytes);
+    };
+
+    push(&proof.proof);
+    push(&(proof.arguments.len() as u64).to_le_bytes());
+    for (name, value) in &proof.arguments {
+        push(name.as_bytes());
+        push(value);
+    }
+
+    buf
+}
+
+fn decode_proof(rest: &mut &[u8]) -> anyhow::Result<ProofOfProperty> {
+    let proof = take_section(rest)?.to_vec();
+    let arguments = u64_at(take_section(rest)?, 0)?;
+    let arguments = (0..arguments)
+        .map(|_| {
+            let name = String::from_utf8(take_section(rest)?.to_vec())
+                .map_err(|e| anyhow!("error decoding argument name: {e}"))?;
+            Ok((name, take_section(rest)?.to_vec()))
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    Ok(ProofOfProperty { proof, arguments })
+}
+
+fn take_section<'a>(rest: &mut &'a [u8]) -> anyhow::Result<&'a [u8]> {
+    let len = u64_at(rest, 0)? as usize;
+    let section = rest
+        .get(8..8 + len)
+        .ok_or(anyhow!("proofs file is truncated"))?;
+    *rest = &rest[8 + len..];
+    Ok(section)
+}
+
+fn u64_at(buf: &[u8], at: usize) -> anyhow::Result<u64> {
+    buf.get(at..at + 8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        .ok_or(anyhow!("proofs file is truncated"))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::zk::ProofOfProperty;
+    use crate::{read_proof_at, read_proofs, write_proofs, PairingEngine};
+    use ark_bls12_381::Fr;
+    use ark_groth16::{prepare_verifying_key, verify_proof, Groth16, Proof};
+    use ark_relations::lc;
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+    use ark_snark::{CircuitSpecificSetupSNARK, SNARK};
+    use circuits::{ark_from_bytes, ark_to_bytes};
+
+    struct MulCircuit {
+        a: Fr,
+        b: Fr,
+    }
+
+    impl ConstraintSynthesizer<Fr> for MulCircuit {
+        fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+            let a = cs.new_witness_variable(|| Ok(self.a))?;
+            let b = cs.new_witness_variable(|| Ok(self.b))?;
+            let c = cs.new_input_variable(|| Ok(self.a * self.b))?;
+            cs.enforce_constraint(lc!() + a, lc!() + b, lc!() + c)
+        }
+    }
+
+    #[test]
+    fn test_proofs_round_trip() {
+        let mut rng = rand::thread_rng();
+        let circuit = |i: u64| MulCircuit {
+            a: Fr::from(i),
+            b: Fr::from(7u64),
+        };
+        let (pk, vk) = Groth16::<PairingEngine>::setup(circuit(1), &mut rng).unwrap();
+        let pvk = prepare_verifying_key(&vk);
+
+        // proving is the slow part, so a few real proofs are shared among the synthetic ones.
+        let real = (0..8u64)
+            .map(|i| Groth16::<PairingEngine>::prove(&pk, circuit(i), &mut rng).unwrap())
+            .collect::<Vec<_>>();
+        let proofs = (0..1000usize)
+            .map(|i| ProofOfProperty {
+                proof: ark_to_bytes(real[i % real.len()].clone()).unwrap(),
+                arguments: vec![
+                    ("index".to_string(), (i as u64).to_le_bytes().to_vec()),
+                    (
+                        "c".to_string(),
+                        ark_to_bytes(Fr::from(i as u64 % 8 * 7)).unwrap(),
+                    ),
+                ],
+            })
+            .collect::<Vec<_>>();
+
+        let path = std::env::temp_dir().join("zkcp_test_proofs");
+        write_proofs(&path, &proofs).unwrap();
+        let reloaded = read_proofs(&path).unwrap();
+
+        assert_eq!(reloaded.len(), proofs.len());
+        for (i, (proof, expected)) in reloaded.iter().zip(&proofs).enumerate() {
+            assert_eq!(proof.proof, expected.proof);
+            assert_eq!(proof.arguments, expected.arguments);
+
+            // the rest are byte-equal to these, so they verify just as well.
+            if i < real.len() {
+                let c: Fr = ark_from_bytes(&proof.arguments[1].1).unwrap();
+                let proof: Proof<PairingEngine> = ark_from_bytes(&proof.proof).unwrap();
+                assert!(verify_proof(&pvk, &proof, &[c]).unwrap());
+            }
+        }
+
+        let proof = read_proof_at(&path, 999).unwrap();
+        assert_eq!(proof.arguments, proofs[999].arguments);
+        assert!(read_proof_at(&path, 1000).is_err());
+    }
+}
@@ -0,0 +1,202 @@
+use crate::SaleId;
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use ecdsa_fun::{Signature, ECDSA};
+use ethers::prelude::Address;
+use secp256kfun::marker::{Mark, Normal};
+use secp256kfun::nonce::Deterministic;
+use secp256kfun::{g, Point, Scalar, G};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Domain tag of the quote digest, so that the signature can't be replayed as anything else.
+const QUOTE_TAG: &[u8] = b"zkcp/quote/v1";
+
+/// Time quotes are valid for unless the sale offer expires earlier.
+pub const QUOTE_VALIDITY: Duration = Duration::from_secs(600);
+
+/// Price the seller offers one of its sales at for a limited time. Buyer pays exactly the quoted
+/// price and seller settles only the price it has signed, so neither can switch it mid-swap.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Quote {
+    /// Missing for the default sale.
+    #[serde(default)]
+    pub sale_id: Option<SaleId>,
+    pub price: f64,
+    /// Commitment to the ciphertext and proofs of the sale, see [`crate::zk::VerifiableEncryption::hash`].
+    pub sale_hash: [u8; 32],
+    pub chain_id: u64,
+    /// RFC 3339 time the offer can't be accepted after.
+    pub expires_at: String,
+    /// ERC-20 token the price is paid in, ether if none, see [`crate::Erc20Token`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<Address>,
+}
+
+/// [`Quote`] with the seller's public key and hex-encoded signature over it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SignedQuote {
+    pub quote: Quote,
+    pub seller_pk: String,
+    pub signature: String,
+}
+
+impl Quote {
+    /// Digest of the quoted terms the signature is made over.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(QUOTE_TAG);
+        hasher.update(self.sale_id.as_deref().unwrap_or_default().as_bytes());
+        hasher.update([0]);
+        hasher.update(self.price.to_le_bytes());
+        hasher.update(self.sale_hash);
+        hasher.update(self.chain_id.to_le_bytes());
+        hasher.update(self.expires_at.as_bytes());
+        // quotes in ether keep the digest they had before tokens were supported.
+        if let Some(token) = &self.token {
+            hasher.update(token.as_bytes());
+        }
+        hasher.finalize().into()
+    }
+
+    pub fn sign(self, sk: &Scalar) -> SignedQuote {
+        let ecdsa = ECDSA::new(Deterministic::<Sha256>::default());
+        let signature = hex::encode(ecdsa.sign(sk, &self.digest()).to_bytes());
+        SignedQuote {
+            quote: self,
+            seller_pk: g!(sk * G).mark::<Normal>().to_string(),
+            signature,
+        }
+    }
+
+    /// Fails unless the quote is for the sale `sale_id` with `sale_hash` on chain `chain_id`,
+    /// paid in `token`.
+    pub fn ensure_terms(
+        &self,
+        sale_id: Option<&str>,
+        sale_hash: &[u8; 32],
+        chain_id: u64,
+        token: Option<Address>,
+    ) -> anyhow::Result<()> {
+        if self.sale_id.as_deref() != sale_id || &self.sale_hash != sale_hash {
+            return Err(anyhow!("quote is for another sale"));
+        }
+        if self.chain_id != chain_id {
+            return Err(anyhow!(
+                "quote is for chain {}, not {chain_id}",
+                self.chain_id
+            ));
+        }
+        self.ensure_token(token)
+    }
+
+    /// Fails unless the price is quoted in `token`, ether if none.
+    pub fn ensure_token(&self, token: Option<Address>) -> anyhow::Result<()> {
+        let name = |token: Option<Address>| match token {
+            Some(token) => format!("token {token:?}"),
+            None => "ether".to_string(),
+        };
+        match self.token == token {
+            true => Ok(()),
+            false => Err(anyhow!(
+                "price is quoted in {}, not {}",
+                name(self.token),
+                name(token)
+            )),
+        }
+    }
+
+    /// Fails if the quote has expired by `now`.
+    pub fn ensure_unexpired(&self, now: DateTime<Utc>) -> anyhow::Result<()> {
+        let expires_at = DateTime::parse_from_rfc3339(&self.expires_at)
+            .map_err(|e| anyhow!("error decoding quote expiry: {e}"))?;
+        if now >= expires_at {
+            return Err(anyhow!("quote has expired at {}", self.expires_at));
+        }
+
+        Ok(())
+    }
+}
+
+impl SignedQuote {
+    pub fn seller_pk(&self) -> anyhow::Result<Point> {
+        Point::from_str(&self.seller_pk).map_err(|e| anyhow!("bad seller public key: {e}"))
+    }
+
+    /// Fails unless the quote is signed by `seller_pk`.
+    pub fn verify(&self, seller_pk: &Point) -> anyhow::Result<()> {
+        let signature = hex::decode(&self.signature)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .and_then(Signature::from_bytes)
+            .ok_or(anyhow!("error decoding quote signature"))?;
+
+        match ECDSA::verify_only().verify(seller_pk, &self.quote.digest(), &signature) {
+            true => Ok(()),
+            false => Err(anyhow!("quote signature is invalid")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{keypair_gen, Quote};
+    use chrono::{Duration, Utc};
+    use ethers::prelude::Address;
+
+    #[test]
+    fn test_verify_quote() {
+        let (seller_sk, seller_pk) = keypair_gen();
+        let now = Utc::now();
+        let quote = Quote {
+            sale_id: Some("photos".to_string()),
+            price: 0.1,
+            sale_hash: [7; 32],
+            chain_id: 31337,
+            expires_at: (now + Duration::minutes(10)).to_rfc3339(),
+            token: None,
+        }
+        .sign(&seller_sk);
+        assert_eq!(quote.seller_pk().unwrap(), seller_pk);
+        quote.verify(&seller_pk).unwrap();
+        quote
+            .quote
+            .ensure_terms(Some("photos"), &[7; 32], 31337, None)
+            .unwrap();
+        quote.quote.ensure_unexpired(now).unwrap();
+
+        let mut tampered = quote.clone();
+        tampered.quote.price = 0.01;
+        assert!(tampered.verify(&seller_pk).is_err());
+        let (_, other_pk) = keypair_gen();
+        assert!(quote.verify(&other_pk).is_err());
+
+        let terms = &quote.quote;
+        assert!(terms.ensure_terms(None, &[7; 32], 31337, None).is_err());
+        assert!(terms
+            .ensure_terms(Some("photos"), &[8; 32], 31337, None)
+            .is_err());
+        assert!(terms
+            .ensure_terms(Some("photos"), &[7; 32], 1, None)
+            .is_err());
+        assert!(terms.ensure_unexpired(now + Duration::hours(1)).is_err());
+
+        // quote in a token is signed over it and settles only in that token.
+        let token = Some(Address::repeat_byte(1));
+        let mut in_token = quote.quote.clone();
+        in_token.token = token;
+        assert_ne!(in_token.digest(), quote.quote.digest());
+        let in_token = in_token.sign(&seller_sk);
+        in_token.verify(&seller_pk).unwrap();
+        let terms = &in_token.quote;
+        assert!(terms
+            .ensure_terms(Some("photos"), &[7; 32], 31337, token)
+            .is_ok());
+        assert!(terms
+            .ensure_terms(Some("photos"), &[7; 32], 31337, None)
+            .is_err());
+        assert!(quote.quote.ensure_token(token).is_err());
+    }
+}
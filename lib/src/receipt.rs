@@ -0,0 +1,332 @@
+use crate::zk::VerifiableEncryption;
+use crate::SaleId;
+use anyhow::anyhow;
+use chrono::Utc;
+use ecdsa_fun::{Signature, ECDSA};
+use secp256kfun::nonce::Deterministic;
+use secp256kfun::{Point, Scalar};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Audit log of the completed trades in the cache directory of either party, one [`ReceiptEntry`] per line.
+pub const RECEIPTS_FILE: &str = "receipts.jsonl";
+
+/// Domain tag of the receipt digest, so that the signature can't be replayed as anything else.
+const RECEIPT_TAG: &[u8] = b"zkcp/receipt/v1";
+
+/// Evidence of the completed trade: what was sold, for how much, and the payment the one-time key
+/// was revealed with. All hashes and keys are hex-encoded.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Receipt {
+    /// Missing for the default sale.
+    #[serde(default)]
+    pub sale_id: Option<SaleId>,
+    /// Commitment to the ciphertext and all proofs, see [`VerifiableEncryption::hash`].
+    pub sale_hash: String,
+    /// SHA-256 of the ciphertext.
+    pub ciphertext_hash: String,
+    /// SHA-256 of the proof of encryption.
+    pub proof_hash: String,
+    pub price: f64,
+    pub chain_id: u64,
+    /// Hashes of the payment transactions.
+    pub tx_ids: Vec<String>,
+    /// One-time public key, committing to the secret key the payment revealed.
+    pub one_time_pk: String,
+    /// Missing in receipts buyer recorded alone, see [`ReceiptEntry::seller_signature`].
+    #[serde(default)]
+    pub seller_pk: Option<String>,
+    pub buyer_pk: String,
+    /// RFC 3339 time the trade was settled at, as seen by the party recording it.
+    pub completed_at: String,
+}
+
+/// Line of the audit log: the receipt signed by one or both parties.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReceiptEntry {
+    /// Hex SHA-256 of the previous line of the log, zeros for the first one, so that corrupted,
+    /// dropped or reordered entries break the chain. Nothing anchors its head, so it detects
+    /// accidental damage, not whoever holds the log rebuilding it.
+    pub prev_hash: String,
+    pub receipt: Receipt,
+    #[serde(default)]
+    pub seller_signature: Option<String>,
+    #[serde(default)]
+    pub buyer_signature: Option<String>,
+}
+
+impl Receipt {
+    pub fn new(
+        sale_id: Option<SaleId>,
+        sale: &VerifiableEncryption,
+        price: f64,
+        chain_id: u64,
+        tx_ids: Vec<String>,
+        one_time_pk: &Point,
+        buyer_pk: &Point,
+    ) -> Self {
+        Self {
+            sale_id,
+            sale_hash: hex::encode(sale.hash()),
+            ciphertext_hash: hex::encode(Sha256::digest(&sale.ciphertext)),
+            proof_hash: hex::encode(Sha256::digest(&sale.proof_of_encryption)),
+            price,
+            chain_id,
+            tx_ids,
+            one_time_pk: one_time_pk.to_string(),
+            seller_pk: None,
+            buyer_pk: buyer_pk.to_string(),
+            completed_at: Utc::now().to_rfc3339(),
+        }
+    }
+
+    pub fn with_seller_pk(mut self, seller_pk: &Point) -> Self {
+        self.seller_pk = Some(seller_pk.to_string());
+        self
+    }
+
+    /// Digest of the receipt the signatures are made over.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(RECEIPT_TAG);
+        hasher.update(serde_json::to_vec(self).expect("expected receipt to marshal to json"));
+        hasher.finalize().into()
+    }
+
+    /// Hex-encoded ECDSA signature of `sk` over [`Self::digest`].
+    pub fn sign(&self, sk: &Scalar) -> String {
+        let ecdsa = ECDSA::new(Deterministic::<Sha256>::default());
+        hex::encode(ecdsa.sign(sk, &self.digest()).to_bytes())
+    }
+
+    /// Fails unless `signature` over the receipt is made by hex-encoded `pk`.
+    pub fn verify(&self, pk: &str, signature: &str) -> anyhow::Result<()> {
+        let pk = Point::from_str(pk).map_err(|e| anyhow!("bad public key: {e}"))?;
+        let signature = hex::decode(signature)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .and_then(Signature::from_bytes)
+            .ok_or(anyhow!("error decoding receipt signature"))?;
+
+        match ECDSA::verify_only().verify(&pk, &self.digest(), &signature) {
+            true => Ok(()),
+            false => Err(anyhow!("receipt signature is invalid")),
+        }
+    }
+
+    pub fn buyer_pk(&self) -> anyhow::Result<Point> {
+        Point::from_str(&self.buyer_pk).map_err(|e| anyhow!("bad buyer public key: {e}"))
+    }
+
+    /// Fails unless the receipt is of `sale`, recomputing hashes of its ciphertext and proofs.
+    pub fn ensure_sale(&self, sale: &VerifiableEncryption) -> anyhow::Result<()> {
+        if self.sale_hash != hex::encode(sale.hash()) {
+            return Err(anyhow!("receipt is of another sale"));
+        }
+        if self.ciphertext_hash != hex::encode(Sha256::digest(&sale.ciphertext)) {
+            return Err(anyhow!("receipt doesn't match ciphertext of the sale"));
+        }
+        if self.proof_hash != hex::encode(Sha256::digest(&sale.proof_of_encryption)) {
+            return Err(anyhow!(
+                "receipt doesn't match proof of encryption of the sale"
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Fails unless the receipt is of the same trade as `own`, the one recorded by the other party,
+    /// having every transaction `own` knows of.
+    pub fn ensure_same_trade(&self, own: &Receipt) -> anyhow::Result<()> {
+        let same_terms = self.sale_id == own.sale_id
+            && self.sale_hash == own.sale_hash
+            && self.ciphertext_hash == own.ciphertext_hash
+            && self.proof_hash == own.proof_hash
+            && self.price == own.price
+            && self.chain_id == own.chain_id
+            && self.one_time_pk == own.one_time_pk
+            && self.buyer_pk == own.buyer_pk;
+        if !same_terms {
+            return Err(anyhow!("receipt doesn't match terms of the trade"));
+        }
+        if let Some(missing) = own.tx_ids.iter().find(|tx| !self.tx_ids.contains(tx)) {
+            return Err(anyhow!("receipt is missing transaction {missing}"));
+        }
+
+        Ok(())
+    }
+}
+
+/// Appends `receipt` with its signatures to the audit log at `log`, chained to its last line.
+pub fn append_receipt<P: AsRef<Path>>(
+    log: P,
+    receipt: Receipt,
+    seller_signature: Option<String>,
+    buyer_signature: Option<String>,
+) -> anyhow::Result<ReceiptEntry> {
+    let prev_hash = match fs::read_to_string(&log) {
+        Ok(lines) => match lines.lines().filter(|l| !l.trim().is_empty()).last() {
+            Some(line) => hex::encode(Sha256::digest(line.as_bytes())),
+            None => hex::encode([0u8; 32]),
+        },
+        Err(_) => hex::encode([0u8; 32]),
+    };
+    let entry = ReceiptEntry {
+        prev_hash,
+        receipt,
+        seller_signature,
+        buyer_signature,
+    };
+
+    if let Some(dir) = log.as_ref().parent() {
+        fs::create_dir_all(dir).map_err(|e| anyhow!("error creating receipts dir: {e}"))?;
+    }
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log)
+        .map_err(|e| anyhow!("error opening receipts log: {e}"))?;
+    let line = serde_json::to_string(&entry).expect("expected receipt to marshal to json");
+    writeln!(file, "{line}").map_err(|e| anyhow!("error appending receipt: {e}"))?;
+
+    Ok(entry)
+}
+
+/// Entries of the audit log at `log` as written, none if there's no log yet.
+pub fn read_receipts<P: AsRef<Path>>(log: P) -> anyhow::Result<Vec<ReceiptEntry>> {
+    let lines = match fs::read_to_string(&log) {
+        Ok(lines) => lines,
+        Err(_) => return Ok(vec![]),
+    };
+
+    lines
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .enumerate()
+        .map(|(i, line)| {
+            serde_json::from_str(line).map_err(|e| anyhow!("error decoding receipt #{i}: {e}"))
+        })
+        .collect()
+}
+
+/// Re-checks the audit log at `log` offline: that it's unbroken chain of entries, i.e. not
+/// corrupted or truncated mid-way, each signed by at least one party, all signatures valid.
+/// Returns the entries checked.
+pub fn verify_receipts<P: AsRef<Path>>(log: P) -> anyhow::Result<Vec<ReceiptEntry>> {
+    let lines = fs::read_to_string(&log).map_err(|e| anyhow!("error reading receipts log: {e}"))?;
+
+    let mut prev_hash = hex::encode([0u8; 32]);
+    let mut entries = vec![];
+    for (i, line) in lines.lines().filter(|l| !l.trim().is_empty()).enumerate() {
+        let entry: ReceiptEntry =
+            serde_json::from_str(line).map_err(|e| anyhow!("error decoding receipt #{i}: {e}"))?;
+        if entry.prev_hash != prev_hash {
+            return Err(anyhow!(
+                "receipt #{i} doesn't chain to the one before, log is corrupted"
+            ));
+        }
+
+        let receipt = &entry.receipt;
+        Point::from_str(&receipt.one_time_pk)
+            .map_err(|e| anyhow!("bad one-time public key of receipt #{i}: {e}"))?;
+        match (&receipt.seller_pk, &entry.seller_signature) {
+            (Some(pk), Some(signature)) => receipt
+                .verify(pk, signature)
+                .map_err(|e| anyhow!("seller signature of receipt #{i}: {e}"))?,
+            (None, Some(_)) => return Err(anyhow!("receipt #{i} is signed by unknown seller")),
+            _ => {}
+        }
+        match &entry.buyer_signature {
+            Some(signature) => receipt
+                .verify(&receipt.buyer_pk, signature)
+                .map_err(|e| anyhow!("buyer signature of receipt #{i}: {e}"))?,
+            None if entry.seller_signature.is_none() => {
+                return Err(anyhow!("receipt #{i} is signed by neither party"))
+            }
+            None => {}
+        }
+
+        prev_hash = hex::encode(Sha256::digest(line.as_bytes()));
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::zk::VerifiableEncryption;
+    use crate::{append_receipt, keypair_gen, read_receipts, verify_receipts, Receipt};
+    use std::fs;
+
+    #[test]
+    fn test_verify_receipts() {
+        let log = std::env::temp_dir().join("zkcp_test_receipts.jsonl");
+        let _ = fs::remove_file(&log);
+        let (seller_sk, seller_pk) = keypair_gen();
+        let (buyer_sk, buyer_pk) = keypair_gen();
+        let (_, one_time_pk) = keypair_gen();
+        let sale = VerifiableEncryption {
+            ciphertext: vec![1; 32],
+            proof_of_encryption: vec![2; 192],
+            proofs_of_property: vec![],
+            plaintext_commitment: None,
+        };
+
+        let receipt = |price: f64| {
+            Receipt::new(
+                None,
+                &sale,
+                price,
+                31337,
+                vec!["ab".repeat(32)],
+                &one_time_pk,
+                &buyer_pk,
+            )
+            .with_seller_pk(&seller_pk)
+        };
+        let first = receipt(0.1);
+        let seller_signature = Some(first.sign(&seller_sk));
+        let buyer_signature = Some(first.sign(&buyer_sk));
+        append_receipt(&log, first, seller_signature, buyer_signature).unwrap();
+        let second = receipt(0.2);
+        let seller_signature = Some(second.sign(&seller_sk));
+        append_receipt(&log, second.clone(), seller_signature, None).unwrap();
+
+        assert_eq!(verify_receipts(&log).unwrap().len(), 2);
+        second.ensure_sale(&sale).unwrap();
+        let mut other = sale.clone();
+        other.ciphertext[0] = 0;
+        assert!(second.ensure_sale(&other).is_err());
+        assert!(receipt(0.2).ensure_same_trade(&second).is_ok());
+        assert!(receipt(0.3).ensure_same_trade(&second).is_err());
+
+        // forged signature of the buyer.
+        let mut entries = read_receipts(&log).unwrap();
+        entries[1].buyer_signature = Some(entries[1].receipt.sign(&seller_sk));
+        let forged = entries
+            .iter()
+            .map(|e| serde_json::to_string(e).unwrap() + "\n")
+            .collect::<String>();
+        fs::write(&log, forged).unwrap();
+        assert!(verify_receipts(&log).is_err());
+
+        // rewritten price of the first receipt, re-signed, no longer chains to the second.
+        let mut entries = read_receipts(&log).unwrap();
+        entries[1].buyer_signature = None;
+        entries[0].receipt.price = 0.01;
+        entries[0].seller_signature = Some(entries[0].receipt.sign(&seller_sk));
+        entries[0].buyer_signature = Some(entries[0].receipt.sign(&buyer_sk));
+        let rewritten = entries
+            .iter()
+            .map(|e| serde_json::to_string(e).unwrap() + "\n")
+            .collect::<String>();
+        fs::write(&log, rewritten).unwrap();
+        let err = verify_receipts(&log).unwrap_err();
+        assert!(err.to_string().contains("corrupted"), "{err}");
+    }
+}
@@ -0,0 +1,73 @@
+use crate::artifact::strip_artifact_magic;
+use crate::traits::VkRegistry;
+use crate::ArtifactType;
+use anyhow::anyhow;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+
+/// Hash of the verifying key as anchored on-chain, taken over its encoding without the magic prefix.
+pub fn verifying_key_hash(verifying_key: &[u8]) -> anyhow::Result<[u8; 32]> {
+    let vk = strip_artifact_magic(verifying_key, ArtifactType::VerifyingKey)?;
+    Ok(Sha256::digest(vk).into())
+}
+
+/// Fails unless the verifying key at `vk_path` is the one anchored in `registry` for the sale with `sale_hash`,
+/// since seller handing over both a forged key and proofs made with it would otherwise pass verification.
+pub async fn ensure_anchored_vk<R: VkRegistry, P: AsRef<Path>>(
+    registry: &R,
+    sale_hash: &[u8; 32],
+    vk_path: P,
+) -> anyhow::Result<()> {
+    let vk = fs::read(vk_path).map_err(|e| anyhow!("error reading verifying key: {e}"))?;
+    let vk_hash = verifying_key_hash(&vk)?;
+
+    match registry.anchored_vk_hash(sale_hash).await? {
+        Some(anchored) if anchored == vk_hash => Ok(()),
+        Some(anchored) => Err(anyhow!(
+            "verifying key hash {} doesn't match {} anchored on-chain",
+            hex::encode(vk_hash),
+            hex::encode(anchored)
+        )),
+        None => Err(anyhow!(
+            "no verifying key is anchored on-chain for this sale"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ensure_anchored_vk, verifying_key_hash, VkRegistry, VERIFYING_KEY_MAGIC};
+    use async_trait::async_trait;
+    use std::collections::HashMap;
+    use std::fs;
+
+    struct MockRegistry(HashMap<[u8; 32], [u8; 32]>);
+
+    #[async_trait]
+    impl VkRegistry for MockRegistry {
+        async fn anchored_vk_hash(&self, sale_hash: &[u8; 32]) -> anyhow::Result<Option<[u8; 32]>> {
+            Ok(self.0.get(sale_hash).copied())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ensure_anchored_vk() {
+        let vk = [VERIFYING_KEY_MAGIC.to_vec(), vec![7; 64]].concat();
+        let path = std::env::temp_dir().join("zkcp_test_anchored_vk");
+        fs::write(&path, &vk).unwrap();
+
+        let registry = MockRegistry(HashMap::from([
+            ([1; 32], verifying_key_hash(&vk).unwrap()),
+            ([2; 32], verifying_key_hash(&vk[..60]).unwrap()),
+        ]));
+
+        assert!(ensure_anchored_vk(&registry, &[1; 32], &path).await.is_ok());
+        assert!(ensure_anchored_vk(&registry, &[2; 32], &path)
+            .await
+            .is_err());
+        assert!(ensure_anchored_vk(&registry, &[3; 32], &path)
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,923 @@
+use crate::cipher_host::{decompress_bundle, ZSTD_MAGIC};
+use crate::zk::{MessageEncodingKind, ProofOfProperty, VerifiableEncryption, DATA_CHUNK_SIZE};
+use crate::{
+    split_curve_tag, verifying_key_hash, ChunkOffsets, CurveVar, ProjectiveCurve, TaggedCurve,
+    UTF8_BOM,
+};
+use anyhow::anyhow;
+use ark_ec::PairingEngine;
+use ark_ff::Zero;
+use ark_groth16::Proof;
+use ark_serialize::CanonicalSerialize;
+use circuits::{
+    ark_from_bytes, ark_to_bytes, encryption, plaintext_chunks_to_bytes_sized, EncryptCircuit,
+    Plaintext, PublicKey, SecretKey,
+};
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// Magic bytes opening every `.sale` file.
+pub const SALE_MAGIC: [u8; 4] = *b"ZKCP";
+/// Version 2 prefixes manifest with its [`ManifestFormat`] tag, version 1 manifests are always JSON.
+pub const SALE_VERSION: u8 = 2;
+
+/// Describes the content of the `.sale` file, stored right after its header.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SaleManifest {
+    pub sale_hash: String,
+    pub data_encryption_limit: usize,
+    pub ciphertext_len: usize,
+    pub proofs_of_property: usize,
+    /// Number of plaintext bytes packed into each field element.
+    #[serde(default = "default_chunk_size")]
+    pub chunk_size: usize,
+    /// UTF-8 BOM was stripped from the data, buyer may restore it with [`SaleManifest::restore_bom`].
+    #[serde(default)]
+    pub bom_stripped: bool,
+    /// [`crate::zk::MessageEncoding`] of the ciphertext, field additive for sales predating the choice.
+    #[serde(default)]
+    pub message_encoding: MessageEncodingKind,
+}
+
+fn default_chunk_size() -> usize {
+    DATA_CHUNK_SIZE
+}
+
+impl SaleManifest {
+    /// Casts decrypted plaintext back to bytes using chunk width recorded in the manifest.
+    pub fn decode_plaintext(
+        &self,
+        plaintext: Plaintext<ProjectiveCurve>,
+    ) -> anyhow::Result<Vec<u8>> {
+        plaintext_chunks_to_bytes_sized::<ProjectiveCurve>(plaintext, self.chunk_size)
+            .map_err(|e| anyhow!("error casting plaintext: {e}"))
+    }
+
+    /// Decrypts `ciphertext` with the [`crate::zk::MessageEncoding`] recorded in the manifest and casts it back to bytes.
+    pub fn decrypt(
+        &self,
+        ciphertext: &[u8],
+        sk: SecretKey<ProjectiveCurve>,
+        params: &encryption::Parameters<ProjectiveCurve>,
+    ) -> anyhow::Result<Vec<u8>> {
+        let plaintext = self
+            .message_encoding
+            .encoding()
+            .decrypt(ciphertext, sk, params)?;
+        self.decode_plaintext(plaintext)
+    }
+
+    /// Re-encrypts `ciphertext` to `pk` under `new_chunk_size` wide chunks and records the new layout.
+    /// Requires the secret key of the ciphertext, so only its owner may do it; proofs of the original sale
+    /// don't hold for the result and must be made anew.
+    pub fn rechunk<K: AsRef<[u8]>, R: CryptoRng + RngCore>(
+        &mut self,
+        ciphertext: &[u8],
+        sk: K,
+        new_chunk_size: usize,
+        pk: PublicKey<ProjectiveCurve>,
+        params: &encryption::Parameters<ProjectiveCurve>,
+        rng: &mut R,
+    ) -> anyhow::Result<Vec<u8>> {
+        let sk: SecretKey<ProjectiveCurve> =
+            ark_from_bytes(sk.as_ref()).map_err(|e| anyhow!("error casting secret key: {e}"))?;
+        let ciphertext =
+            ark_from_bytes(ciphertext).map_err(|e| anyhow!("error casting ciphertext: {e}"))?;
+
+        let rechunked = EncryptCircuit::<ProjectiveCurve, CurveVar>::rechunk(
+            ciphertext,
+            sk,
+            self.chunk_size,
+            new_chunk_size,
+            &pk,
+            params,
+            rng,
+        )?;
+        let encoded =
+            ark_to_bytes(rechunked).map_err(|e| anyhow!("error encoding ciphertext: {e}"))?;
+
+        self.chunk_size = new_chunk_size;
+        self.ciphertext_len = encoded.len();
+        Ok(encoded)
+    }
+
+    /// Offsets of the ciphertext chunks, for reading them selectively with [`crate::BlobReader`].
+    pub fn chunk_offsets(&self) -> ChunkOffsets {
+        ChunkOffsets::new(self.ciphertext_len)
+    }
+
+    /// Fails unless `sale` carries every proof of property listed in the manifest,
+    /// so that buyer doesn't pay for a sale some of whose proofs were held back.
+    pub fn ensure_complete_proof_set(
+        &self,
+        sale: &VerifiableEncryption,
+    ) -> Result<(), IncompleteProofSet> {
+        match sale.proofs_of_property.len() {
+            got if got == self.proofs_of_property => Ok(()),
+            got => Err(IncompleteProofSet {
+                expected: self.proofs_of_property,
+                got,
+            }),
+        }
+    }
+
+    /// Prepends UTF-8 BOM to the decrypted `data` if seller has stripped it.
+    pub fn restore_bom(&self, data: Vec<u8>) -> Vec<u8> {
+        match self.bom_stripped {
+            true => [UTF8_BOM.to_vec(), data].concat(),
+            false => data,
+        }
+    }
+}
+
+/// Sale carries other number of proofs of property than its manifest lists.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IncompleteProofSet {
+    pub expected: usize,
+    pub got: usize,
+}
+
+impl Display for IncompleteProofSet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "sale manifest lists {} proofs of property, but {} were delivered",
+            self.expected, self.got
+        )
+    }
+}
+
+impl std::error::Error for IncompleteProofSet {}
+
+/// Serialization format of the [`SaleManifest`], recorded in the leading byte of the manifest section.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ManifestFormat {
+    #[default]
+    Json,
+    #[cfg(feature = "cbor")]
+    Cbor,
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+}
+
+impl ManifestFormat {
+    fn tag(self) -> u8 {
+        match self {
+            ManifestFormat::Json => 0,
+            #[cfg(feature = "cbor")]
+            ManifestFormat::Cbor => 1,
+            #[cfg(feature = "msgpack")]
+            ManifestFormat::MessagePack => 2,
+        }
+    }
+
+    fn from_tag(tag: u8) -> anyhow::Result<Self> {
+        match tag {
+            0 => Ok(ManifestFormat::Json),
+            #[cfg(feature = "cbor")]
+            1 => Ok(ManifestFormat::Cbor),
+            #[cfg(feature = "msgpack")]
+            2 => Ok(ManifestFormat::MessagePack),
+            #[cfg(not(feature = "cbor"))]
+            1 => Err(anyhow!(
+                "manifest is in CBOR, enable `cbor` feature to read it"
+            )),
+            #[cfg(not(feature = "msgpack"))]
+            2 => Err(anyhow!(
+                "manifest is in MessagePack, enable `msgpack` feature to read it"
+            )),
+            tag => Err(anyhow!("unknown manifest format {tag}")),
+        }
+    }
+}
+
+/// Encodes manifest in the given `format`, prefixed with the format tag.
+pub fn encode_manifest(manifest: &SaleManifest, format: ManifestFormat) -> anyhow::Result<Vec<u8>> {
+    let body = match format {
+        ManifestFormat::Json => serde_json::to_vec(manifest).map_err(|e| anyhow!("{e}")),
+        #[cfg(feature = "cbor")]
+        ManifestFormat::Cbor => serde_cbor::to_vec(manifest).map_err(|e| anyhow!("{e}")),
+        #[cfg(feature = "msgpack")]
+        ManifestFormat::MessagePack => {
+            rmp_serde::to_vec_named(manifest).map_err(|e| anyhow!("{e}"))
+        }
+    }
+    .map_err(|e| anyhow!("error encoding manifest: {e}"))?;
+
+    Ok([vec![format.tag()], body].concat())
+}
+
+/// Decodes manifest written by [`encode_manifest`], detecting its format by the leading byte.
+pub fn decode_manifest(bytes: &[u8]) -> anyhow::Result<(SaleManifest, ManifestFormat)> {
+    let (&tag, body) = bytes.split_first().ok_or(anyhow!("manifest is empty"))?;
+    let format = ManifestFormat::from_tag(tag)?;
+
+    let manifest = match format {
+        ManifestFormat::Json => serde_json::from_slice(body).map_err(|e| anyhow!("{e}")),
+        #[cfg(feature = "cbor")]
+        ManifestFormat::Cbor => serde_cbor::from_slice(body).map_err(|e| anyhow!("{e}")),
+        #[cfg(feature = "msgpack")]
+        ManifestFormat::MessagePack => rmp_serde::from_slice(body).map_err(|e| anyhow!("{e}")),
+    }
+    .map_err(|e| anyhow!("error decoding manifest: {e}"))?;
+
+    Ok((manifest, format))
+}
+
+/// Sale as shown to operators and buyers inspecting it, see [`describe_sale`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SaleSummary {
+    /// Version of the `.sale` file, none if summarized from a standalone manifest.
+    pub version: Option<u8>,
+    pub curve: String,
+    pub chunk_size: usize,
+    pub chunk_count: usize,
+    /// Bytes the chunks hold, data is padded up to it so its exact length isn't recorded.
+    pub data_len: usize,
+    pub compression: Option<String>,
+    /// See [`verifying_key_hash`], only known if the verifying key is given with [`SaleSummary::with_verifying_key`].
+    pub vk_hash: Option<String>,
+    pub sale_hash: String,
+}
+
+impl SaleSummary {
+    pub fn with_verifying_key(mut self, verifying_key: &[u8]) -> anyhow::Result<Self> {
+        self.vk_hash = Some(hex::encode(verifying_key_hash(verifying_key)?));
+        Ok(self)
+    }
+}
+
+impl Display for SaleSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let or_none = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+        let rows = [
+            ("version", or_none(self.version.map(|v| v.to_string()))),
+            ("curve", self.curve.clone()),
+            ("chunk size", self.chunk_size.to_string()),
+            ("chunk count", self.chunk_count.to_string()),
+            ("data length", format!("<= {} bytes", self.data_len)),
+            ("compression", or_none(self.compression.clone())),
+            ("vk hash", or_none(self.vk_hash.clone())),
+            ("sale hash", self.sale_hash.clone()),
+        ];
+        for (name, value) in rows {
+            writeln!(f, "{name:<12} {value}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Summarizes sale from the bytes of `.sale` file, possibly zstd compressed, or of its standalone manifest,
+/// either as written by [`encode_manifest`] or plain JSON.
+pub fn describe_sale(bytes: Vec<u8>) -> anyhow::Result<SaleSummary> {
+    let compression = bytes.starts_with(&ZSTD_MAGIC).then(|| "zstd".to_string());
+    let bytes = decompress_bundle(bytes)?;
+
+    let (version, manifest, curve) = match bytes.strip_prefix(&SALE_MAGIC) {
+        Some(rest) => {
+            let (manifest, sale) = read_sale_from(&bytes[..])?;
+            let (curve, _) = split_curve_tag(&sale.proof_of_encryption);
+            (rest.first().copied(), manifest, curve)
+        }
+        None => {
+            let manifest = match decode_manifest(&bytes) {
+                Ok((manifest, _)) => manifest,
+                Err(_) => serde_json::from_slice(&bytes)
+                    .map_err(|e| anyhow!("error decoding manifest: {e}"))?,
+            };
+            (None, manifest, None)
+        }
+    };
+
+    let chunk_count = manifest.chunk_offsets().chunks;
+    Ok(SaleSummary {
+        version,
+        curve: curve
+            .unwrap_or(<crate::PairingEngine as TaggedCurve>::CURVE_TAG)
+            .to_string(),
+        chunk_size: manifest.chunk_size,
+        chunk_count,
+        data_len: chunk_count * manifest.chunk_size,
+        compression,
+        vk_hash: None,
+        sale_hash: manifest.sale_hash,
+    })
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProofSet {
+    proof_of_encryption: Vec<u8>,
+    proofs_of_property: Vec<ProofOfProperty>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    plaintext_commitment: Option<Vec<u8>>,
+}
+
+/// How the sale data was encoded, recorded in the [`SaleManifest`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SaleOptions {
+    pub chunk_size: usize,
+    /// Data had UTF-8 BOM removed before encryption, see [`crate::strip_bom`].
+    pub bom_stripped: bool,
+    pub format: ManifestFormat,
+    pub message_encoding: MessageEncodingKind,
+}
+
+impl Default for SaleOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: DATA_CHUNK_SIZE,
+            bom_stripped: false,
+            format: ManifestFormat::default(),
+            message_encoding: MessageEncodingKind::default(),
+        }
+    }
+}
+
+/// Bytes buyer downloads for the sale of `chunk_count` ciphertext chunks over curve `C`
+/// with `proofs_of_property` Groth16 proofs over `E` besides the proof of encryption.
+/// Counts the ciphertext blob and the proofs only, manifest and section framing add a few hundred bytes on top.
+pub fn sale_wire_size<C: ark_ec::ProjectiveCurve, E: PairingEngine>(
+    chunk_count: usize,
+    proofs_of_property: usize,
+    compressed: bool,
+) -> usize {
+    let blob = encoded_size(&C::zero(), compressed)
+        + 8
+        + chunk_count * encoded_size(&C::BaseField::zero(), compressed);
+    blob + (1 + proofs_of_property) * encoded_size(&Proof::<E>::default(), compressed)
+}
+
+fn encoded_size<T: CanonicalSerialize>(item: &T, compressed: bool) -> usize {
+    match compressed {
+        true => item.serialized_size(),
+        false => item.uncompressed_size(),
+    }
+}
+
+/// Writes sale into a single self-describing file laid out as
+/// `magic | version | manifest | ciphertext | proof set`, each section prefixed with its u64 length.
+/// Manifest is written in JSON, see [`write_sale_with_options`] for other formats.
+pub fn write_sale<P: AsRef<Path>>(
+    path: P,
+    sale: &VerifiableEncryption,
+    data_encryption_limit: usize,
+    chunk_size: usize,
+) -> anyhow::Result<()> {
+    write_sale_with_options(
+        path,
+        sale,
+        data_encryption_limit,
+        SaleOptions {
+            chunk_size,
+            ..Default::default()
+        },
+    )
+}
+
+pub fn write_sale_with_options<P: AsRef<Path>>(
+    path: P,
+    sale: &VerifiableEncryption,
+    data_encryption_limit: usize,
+    options: SaleOptions,
+) -> anyhow::Result<()> {
+    let file = File::create(path).map_err(|e| anyhow!("error writing sale file: {e}"))?;
+    write_sale_to(BufWriter::new(file), sale, data_encryption_limit, options)
+}
+
+/// Streams sale container into `writer` section by section, flushing after each,
+/// so that it can be piped elsewhere as it's produced.
+pub fn write_sale_to<W: Write>(
+    mut writer: W,
+    sale: &VerifiableEncryption,
+    data_encryption_limit: usize,
+    options: SaleOptions,
+) -> anyhow::Result<()> {
+    let manifest = SaleManifest {
+        sale_hash: hex::encode(sale.hash()),
+        data_encryption_limit,
+        ciphertext_len: sale.ciphertext.len(),
+        proofs_of_property: sale.proofs_of_property.len(),
+        chunk_size: options.chunk_size,
+        bom_stripped: options.bom_stripped,
+        message_encoding: options.message_encoding,
+    };
+    let proof_set = ProofSet {
+        proof_of_encryption: sale.proof_of_encryption.clone(),
+        proofs_of_property: sale.proofs_of_property.clone(),
+        plaintext_commitment: sale.plaintext_commitment.clone(),
+    };
+
+    let manifest = encode_manifest(&manifest, options.format)?;
+    let proof_set =
+        serde_json::to_vec(&proof_set).map_err(|e| anyhow!("error encoding proof set: {e}"))?;
+
+    writer
+        .write_all(&SALE_MAGIC)
+        .and_then(|_| writer.write_all(&[SALE_VERSION]))
+        .map_err(|e| anyhow!("error writing sale file: {e}"))?;
+    for section in [&manifest, &sale.ciphertext, &proof_set] {
+        writer
+            .write_all(&(section.len() as u64).to_le_bytes())
+            .and_then(|_| writer.write_all(section))
+            .and_then(|_| writer.flush())
+            .map_err(|e| anyhow!("error writing sale file: {e}"))?;
+    }
+
+    Ok(())
+}
+
+/// Reads sale written with [`write_sale`] and checks it is intact.
+pub fn read_sale<P: AsRef<Path>>(path: P) -> anyhow::Result<(SaleManifest, VerifiableEncryption)> {
+    let file = File::open(path).map_err(|e| anyhow!("error reading sale file: {e}"))?;
+    read_sale_from(BufReader::new(file))
+}
+
+/// Reads sale streamed with [`write_sale_to`] and checks it is intact.
+pub fn read_sale_from<R: Read>(
+    mut reader: R,
+) -> anyhow::Result<(SaleManifest, VerifiableEncryption)> {
+    let mut buf = vec![];
+    reader
+        .read_to_end(&mut buf)
+        .map_err(|e| anyhow!("error reading sale file: {e}"))?;
+    let mut rest = buf
+        .strip_prefix(&SALE_MAGIC)
+        .ok_or(anyhow!("not a sale file"))?;
+
+    let version = match rest.split_first() {
+        Some((&version, tail)) if version == 1 || version == SALE_VERSION => {
+            rest = tail;
+            version
+        }
+        Some((version, _)) => return Err(anyhow!("unsupported sale file version {version}")),
+        None => return Err(anyhow!("sale file is truncated")),
+    };
+
+    let manifest = match (version, take_section(&mut rest)?) {
+        (1, section) => {
+            serde_json::from_slice(section).map_err(|e| anyhow!("error decoding manifest: {e}"))?
+        }
+        (_, section) => decode_manifest(section)?.0,
+    };
+    let ciphertext = take_section(&mut rest)?.to_vec();
+    let ProofSet {
+        proof_of_encryption,
+        proofs_of_property,
+        plaintext_commitment,
+    } = serde_json::from_slice(take_section(&mut rest)?)
+        .map_err(|e| anyhow!("error decoding proof set: {e}"))?;
+
+    let sale = VerifiableEncryption {
+        ciphertext,
+        proof_of_encryption,
+        proofs_of_property,
+        plaintext_commitment,
+    };
+
+    if hex::encode(sale.hash()) != manifest.sale_hash {
+        return Err(anyhow!("sale file content doesn't match its manifest"));
+    }
+
+    Ok((manifest, sale))
+}
+
+fn take_section<'a>(rest: &mut &'a [u8]) -> anyhow::Result<&'a [u8]> {
+    if rest.len() < 8 {
+        return Err(anyhow!("sale file is truncated"));
+    }
+    let (len, tail) = rest.split_at(8);
+    let len = u64::from_le_bytes(len.try_into().unwrap()) as usize;
+    if tail.len() < len {
+        return Err(anyhow!("sale file is truncated"));
+    }
+    let (section, tail) = tail.split_at(len);
+    *rest = tail;
+    Ok(section)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cipher_host::compress_bundle;
+    use crate::zk::{
+        MessageEncodingKind, ProofOfProperty, VerifiableEncryption, ZkSampleEntries,
+        ZkVerifiableEncryption, DATA_CHUNK_SIZE,
+    };
+    use crate::{
+        decode_manifest, describe_sale, encode_manifest, read_proofs, read_sale, read_sale_from,
+        sale_wire_size, strip_bom, write_proofs, write_sale, write_sale_to,
+        write_sale_with_options, ChunkOffsets, CurveVar, IncompleteProofSet, ManifestFormat,
+        PairingEngine, ProjectiveCurve, SaleManifest, SaleOptions,
+    };
+    use ark_groth16::Proof;
+    use ark_std::UniformRand;
+    use circuits::{
+        ark_to_bytes, bytes_to_plaintext_chunks_sized, encryption, EncryptCircuit, Randomness,
+    };
+    use std::fs;
+
+    #[test]
+    fn test_sale_round_trip() {
+        let mut rng = rand::thread_rng();
+        let n = 4;
+        let build_dir = std::env::temp_dir().join("zkcp_test_round_trip");
+        let (enc_dir, prop_dir) = (build_dir.join("encryption"), build_dir.join("property"));
+        let params = || encryption::Parameters::default_multi(n);
+        ZkVerifiableEncryption::new(&enc_dir, ZkSampleEntries::new(&prop_dir, n), params())
+            .compile(&mut rng)
+            .unwrap();
+        let seller =
+            ZkVerifiableEncryption::new(&enc_dir, ZkSampleEntries::new(&prop_dir, n), params());
+        let (sk, pk) = seller.keygen(&mut rng).unwrap();
+        // every byte is a chunk of its own, so the sale spans all `n` of them.
+        let sale = seller
+            .assess_property_and_encrypt([1, 2, 3, 4], sk, pk, &mut rng)
+            .unwrap();
+        let path = std::env::temp_dir().join("zkcp_test_round_trip.sale");
+
+        write_sale(&path, &sale, n, DATA_CHUNK_SIZE).unwrap();
+        let (manifest, restored) = read_sale(&path).unwrap();
+
+        assert_eq!(manifest.data_encryption_limit, n);
+        assert_eq!(manifest.chunk_size, DATA_CHUNK_SIZE);
+        assert_eq!(manifest.ciphertext_len, sale.ciphertext.len());
+        assert_eq!(manifest.proofs_of_property, sale.proofs_of_property.len());
+        assert_eq!(restored.hash(), sale.hash());
+        let buyer = ZkVerifiableEncryption::new_verifier(
+            &enc_dir,
+            ZkSampleEntries::new_verifier(&prop_dir, n),
+            params(),
+        );
+        assert!(buyer.verify_proof(&restored).unwrap());
+
+        let mut tampered = fs::read(&path).unwrap();
+        let last = tampered.len() - 2;
+        tampered[last] ^= 1;
+        fs::write(&path, tampered).unwrap();
+        assert!(read_sale(&path).is_err());
+    }
+
+    #[test]
+    fn test_committed_sale_round_trip() {
+        let mut sale = VerifiableEncryption {
+            ciphertext: vec![3; 32 * 4],
+            proof_of_encryption: vec![4; 192],
+            proofs_of_property: vec![],
+            plaintext_commitment: None,
+        };
+        let uncommitted = sale.hash();
+        sale.plaintext_commitment = Some(vec![9; 32]);
+        assert_ne!(sale.hash(), uncommitted);
+
+        let path = std::env::temp_dir().join("zkcp_test_committed.sale");
+        write_sale(&path, &sale, 4, DATA_CHUNK_SIZE).unwrap();
+        let (_, restored) = read_sale(&path).unwrap();
+        assert_eq!(restored.plaintext_commitment, sale.plaintext_commitment);
+
+        restored
+            .ensure_plaintext_commitment(&hex::encode([9; 32]))
+            .unwrap();
+        assert!(restored
+            .ensure_plaintext_commitment(&hex::encode([8; 32]))
+            .is_err());
+        sale.plaintext_commitment = None;
+        assert!(sale
+            .ensure_plaintext_commitment(&hex::encode([9; 32]))
+            .is_err());
+    }
+
+    #[test]
+    fn test_reject_incomplete_proof_set() {
+        let proof = ProofOfProperty {
+            proof: vec![5; 192],
+            arguments: vec![],
+        };
+        let sale = VerifiableEncryption {
+            ciphertext: vec![1; 32 * 8],
+            proof_of_encryption: vec![4; 192],
+            proofs_of_property: vec![proof.clone(), proof.clone()],
+            plaintext_commitment: None,
+        };
+        let sale_path = std::env::temp_dir().join("zkcp_test_incomplete.sale");
+        write_sale(&sale_path, &sale, 8, DATA_CHUNK_SIZE).unwrap();
+        let (manifest, mut delivered) = read_sale(&sale_path).unwrap();
+        manifest.ensure_complete_proof_set(&delivered).unwrap();
+
+        // proofs handed over apart from the sale file are one short.
+        let proofs_path = std::env::temp_dir().join("zkcp_test_incomplete.proofs");
+        write_proofs(&proofs_path, &[proof]).unwrap();
+        delivered.proofs_of_property = read_proofs(&proofs_path).unwrap();
+        assert_eq!(
+            manifest.ensure_complete_proof_set(&delivered),
+            Err(IncompleteProofSet {
+                expected: 2,
+                got: 1
+            })
+        );
+    }
+
+    /// Pipe end recording how much of the stream was flushed through it.
+    struct Pipe {
+        buf: Vec<u8>,
+        flushed: Vec<usize>,
+    }
+
+    impl std::io::Write for Pipe {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.buf.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.flushed.push(self.buf.len());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_streamed_sale_round_trip() {
+        let sale = VerifiableEncryption {
+            ciphertext: (0..=255).cycle().take(32 * 8).collect(),
+            proof_of_encryption: vec![4; 192],
+            proofs_of_property: vec![ProofOfProperty {
+                proof: vec![5; 192],
+                arguments: vec![("sample_value".to_string(), vec![6; 32])],
+            }],
+            plaintext_commitment: None,
+        };
+
+        let mut pipe = Pipe {
+            buf: vec![],
+            flushed: vec![],
+        };
+        write_sale_to(&mut pipe, &sale, 8, SaleOptions::default()).unwrap();
+        // flushed once per section, the last flush covering the whole container.
+        assert_eq!(pipe.flushed.len(), 3);
+        assert_eq!(pipe.flushed.last(), Some(&pipe.buf.len()));
+
+        let (manifest, restored) = read_sale_from(&pipe.buf[..]).unwrap();
+        assert_eq!(manifest.ciphertext_len, sale.ciphertext.len());
+        assert_eq!(restored.hash(), sale.hash());
+
+        let path = std::env::temp_dir().join("zkcp_test_streamed.sale");
+        write_sale_with_options(&path, &sale, 8, SaleOptions::default()).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), pipe.buf);
+
+        let mut tampered = pipe.buf.clone();
+        let manifest_len = u64::from_le_bytes(tampered[5..13].try_into().unwrap()) as usize;
+        tampered[13 + manifest_len + 8] ^= 1;
+        assert!(read_sale_from(&tampered[..]).is_err());
+        assert!(read_sale_from(&pipe.buf[..pipe.buf.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_decode_with_manifest_chunk_size() {
+        let data = b"zero\0bytes\0\0kept".to_vec();
+        let chunk_size = 5;
+        let plaintext =
+            bytes_to_plaintext_chunks_sized::<ProjectiveCurve, _>(&data, chunk_size).unwrap();
+        let sale = VerifiableEncryption {
+            ciphertext: vec![1; 32 * 4],
+            proof_of_encryption: vec![2; 192],
+            proofs_of_property: vec![],
+            plaintext_commitment: None,
+        };
+        let path = std::env::temp_dir().join("zkcp_test_chunk_size.sale");
+
+        write_sale(&path, &sale, plaintext.len(), chunk_size).unwrap();
+        let (manifest, _) = read_sale(&path).unwrap();
+        assert_eq!(manifest.chunk_size, chunk_size);
+
+        let decoded = manifest.decode_plaintext(plaintext).unwrap();
+        assert_eq!(&decoded[..data.len()], &data[..]);
+        assert!(decoded[data.len()..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_manifest_selects_message_encoding() {
+        let mut rng = rand::thread_rng();
+        let chunk_size = 30;
+        let data = b"encoded either way".repeat(5);
+        let msg = bytes_to_plaintext_chunks_sized::<ProjectiveCurve, _>(&data, chunk_size).unwrap();
+        let params = encryption::Parameters::<ProjectiveCurve>::default_multi(msg.len());
+        let (sk, pk) = EncryptCircuit::<ProjectiveCurve, CurveVar>::keygen(&mut rng).unwrap();
+
+        for (kind, other) in [
+            (
+                MessageEncodingKind::FieldAdditive,
+                MessageEncodingKind::PointEncoded,
+            ),
+            (
+                MessageEncodingKind::PointEncoded,
+                MessageEncodingKind::FieldAdditive,
+            ),
+        ] {
+            let r = Randomness::rand(&mut rng);
+            let sale = VerifiableEncryption {
+                ciphertext: kind.encoding().encrypt(&msg, &pk, &r, &params).unwrap(),
+                proof_of_encryption: vec![4; 192],
+                proofs_of_property: vec![],
+                plaintext_commitment: None,
+            };
+            let path = std::env::temp_dir().join(format!("zkcp_test_{kind:?}.sale"));
+            let options = SaleOptions {
+                chunk_size,
+                message_encoding: kind,
+                ..Default::default()
+            };
+            write_sale_with_options(&path, &sale, msg.len(), options).unwrap();
+
+            let (mut manifest, sale) = read_sale(&path).unwrap();
+            assert_eq!(manifest.message_encoding, kind);
+            let decrypted = manifest.decrypt(&sale.ciphertext, sk, &params).unwrap();
+            assert_eq!(&decrypted[..data.len()], &data[..]);
+
+            manifest.message_encoding = other;
+            assert!(manifest
+                .decrypt(&sale.ciphertext, sk, &params)
+                .map_or(true, |decrypted| !decrypted.starts_with(&data)));
+        }
+    }
+
+    fn manifest() -> SaleManifest {
+        SaleManifest {
+            sale_hash: hex::encode([9; 32]),
+            data_encryption_limit: 8,
+            ciphertext_len: 32 * 9,
+            proofs_of_property: 1,
+            chunk_size: DATA_CHUNK_SIZE,
+            bom_stripped: false,
+            message_encoding: MessageEncodingKind::FieldAdditive,
+        }
+    }
+
+    fn assert_manifest_round_trip(format: ManifestFormat) {
+        let encoded = encode_manifest(&manifest(), format).unwrap();
+        assert_eq!(decode_manifest(&encoded).unwrap(), (manifest(), format));
+
+        let sale = VerifiableEncryption {
+            ciphertext: vec![3; 32 * 9],
+            proof_of_encryption: vec![4; 192],
+            proofs_of_property: vec![],
+            plaintext_commitment: None,
+        };
+        let path = std::env::temp_dir().join(format!("zkcp_test_{format:?}.sale"));
+        let options = SaleOptions {
+            format,
+            ..Default::default()
+        };
+        write_sale_with_options(&path, &sale, 8, options).unwrap();
+        let (restored, _) = read_sale(&path).unwrap();
+        assert_eq!(restored.sale_hash, hex::encode(sale.hash()));
+    }
+
+    #[test]
+    fn test_json_manifest_round_trip() {
+        assert_manifest_round_trip(ManifestFormat::Json);
+        assert!(decode_manifest(&[7, b'{', b'}']).is_err());
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_cbor_manifest_round_trip() {
+        assert_manifest_round_trip(ManifestFormat::Cbor);
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_msgpack_manifest_round_trip() {
+        assert_manifest_round_trip(ManifestFormat::MessagePack);
+    }
+
+    #[test]
+    fn test_bom_stripped_sale() {
+        let file = b"\xef\xbb\xbf[\"1\",\"2\"]".to_vec();
+        let sale = VerifiableEncryption {
+            ciphertext: vec![5; 32 * 3],
+            proof_of_encryption: vec![6; 192],
+            proofs_of_property: vec![],
+            plaintext_commitment: None,
+        };
+        let path = std::env::temp_dir().join("zkcp_test_bom.sale");
+
+        for strip in [false, true] {
+            let (data, bom_stripped) = match strip {
+                true => strip_bom(&file),
+                false => (&file[..], false),
+            };
+            assert_eq!(serde_json::from_slice::<Vec<String>>(data).is_ok(), strip);
+
+            let options = SaleOptions {
+                bom_stripped,
+                ..Default::default()
+            };
+            write_sale_with_options(&path, &sale, 3, options).unwrap();
+            let (manifest, _) = read_sale(&path).unwrap();
+
+            assert_eq!(manifest.bom_stripped, strip);
+            assert_eq!(manifest.restore_bom(data.to_vec()), file);
+        }
+    }
+
+    #[test]
+    fn test_sale_wire_size() {
+        let mut rng = rand::thread_rng();
+        let chunks = 64;
+        let params = encryption::Parameters::<ProjectiveCurve>::default_multi(chunks);
+        let (_, pk) = EncryptCircuit::<ProjectiveCurve, CurveVar>::keygen(&mut rng).unwrap();
+        let msg = (0..chunks as u64).map(Into::into).collect();
+        let ciphertext =
+            EncryptCircuit::<ProjectiveCurve, CurveVar>::new(pk, msg, params, &mut rng)
+                .unwrap()
+                .resulted_ciphertext;
+
+        let proof = ark_to_bytes(Proof::<PairingEngine>::default()).unwrap();
+        let proofs_of_property = (0..3)
+            .map(|_| ProofOfProperty {
+                proof: proof.clone(),
+                arguments: vec![],
+            })
+            .collect::<Vec<_>>();
+        let sale = VerifiableEncryption {
+            ciphertext: ark_to_bytes(ciphertext).unwrap(),
+            proof_of_encryption: proof.clone(),
+            proofs_of_property,
+            plaintext_commitment: None,
+        };
+
+        let size = sale_wire_size::<ProjectiveCurve, PairingEngine>(chunks, 3, true);
+        assert_eq!(
+            size,
+            sale.ciphertext.len()
+                + sale.proof_of_encryption.len()
+                + sale
+                    .proofs_of_property
+                    .iter()
+                    .map(|p| p.proof.len())
+                    .sum::<usize>()
+        );
+        assert!(sale_wire_size::<ProjectiveCurve, PairingEngine>(chunks, 3, false) > size);
+
+        // shipped as blob and proof set file, the rest is framing of the latter.
+        let path = std::env::temp_dir().join("zkcp_test_wire_size_proofs");
+        write_proofs(&path, &sale.proofs_of_property).unwrap();
+        let shipped = sale.ciphertext.len()
+            + sale.proof_of_encryption.len()
+            + fs::metadata(&path).unwrap().len() as usize;
+        assert!(shipped >= size);
+        assert!(shipped - size <= 12 + 3 * 32);
+    }
+
+    #[test]
+    fn test_describe_sale() {
+        let mut rng = rand::thread_rng();
+        let chunks = 10;
+        let params = encryption::Parameters::<ProjectiveCurve>::default_multi(chunks);
+        let (_, pk) = EncryptCircuit::<ProjectiveCurve, CurveVar>::keygen(&mut rng).unwrap();
+        let msg = (0..chunks as u64).map(Into::into).collect();
+        let ciphertext =
+            EncryptCircuit::<ProjectiveCurve, CurveVar>::new(pk, msg, params, &mut rng)
+                .unwrap()
+                .resulted_ciphertext;
+        let sale = VerifiableEncryption {
+            ciphertext: ark_to_bytes(ciphertext).unwrap(),
+            proof_of_encryption: ark_to_bytes(Proof::<PairingEngine>::default()).unwrap(),
+            proofs_of_property: vec![],
+            plaintext_commitment: None,
+        };
+        assert_eq!(ChunkOffsets::new(sale.ciphertext.len()).chunks, chunks);
+
+        let path = std::env::temp_dir().join("zkcp_test_describe.sale");
+        write_sale(&path, &sale, chunks, DATA_CHUNK_SIZE).unwrap();
+        let bytes = fs::read(&path).unwrap();
+
+        let summary = describe_sale(bytes.clone()).unwrap();
+        assert_eq!(summary.version, Some(2));
+        assert_eq!(summary.curve, "BLS12-381");
+        assert_eq!(summary.chunk_count, chunks);
+        assert_eq!(summary.data_len, chunks * DATA_CHUNK_SIZE);
+        assert_eq!(summary.compression, None);
+        assert_eq!(summary.sale_hash, hex::encode(sale.hash()));
+        assert!(summary
+            .to_string()
+            .lines()
+            .any(|line| line == format!("chunk count  {chunks}")));
+
+        let compressed = describe_sale(compress_bundle(&bytes).unwrap()).unwrap();
+        assert_eq!(compressed.compression.as_deref(), Some("zstd"));
+        assert_eq!(compressed.chunk_count, chunks);
+
+        let (manifest, _) = read_sale(&path).unwrap();
+        for standalone in [
+            encode_manifest(&manifest, ManifestFormat::Json).unwrap(),
+            serde_json::to_vec(&manifest).unwrap(),
+        ] {
+            let summary = describe_sale(standalone).unwrap();
+            assert_eq!(summary.version, None);
+            assert_eq!(summary.chunk_count, chunks);
+        }
+    }
+}
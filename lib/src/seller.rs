@@ -1,21 +1,38 @@
 use crate::traits::ChainProvider;
-use crate::zk::{PropertyVerifier, VerifiableEncryption, ZkEncryption, ZkVerifiableEncryption};
-use crate::{CipherHost, ZkConfig};
+use crate::zk::{
+    public_key_from_bytes, trade_key_rng, MemoryWatchdog, PropertyVerifier, ProveBudget,
+    VerifiableEncryption, ZkEncryption, ZkVerifiableEncryption, DATA_CHUNK_SIZE,
+};
+use crate::{
+    append_receipt, find_payment, find_session, payment_idempotency_key, read_receipts,
+    read_sessions, remove_session, seller_session_id, write_pending_payment, write_session,
+    CachedKeyEncryption, CipherHost, DataFingerprint, ProjectiveCurve, Quote, Receipt,
+    ReceiptEntry, SellerEvent, SellerMetrics, SellerSession, SessionPhase, SignedQuote, TxParams,
+    ZkConfig, DATA_FINGERPRINT_FILE, QUOTE_VALIDITY, RECEIPTS_FILE,
+};
 use anyhow::anyhow;
-use circuits::{ark_to_bytes, encryption};
+use ark_ec::ProjectiveCurve as _;
+use chrono::{DateTime, Utc};
+use circuits::{ark_to_bytes, encryption, PublicKey};
 use ecdsa_fun::adaptor::{Adaptor, EncryptedSignature, HashTranscript};
 use ethers::prelude::*;
 use futures::channel::{mpsc, oneshot};
+use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha20Rng;
+use rayon::prelude::*;
 use secp256kfun::marker::{Mark, Normal};
 use secp256kfun::nonce::Deterministic;
 use secp256kfun::{g, Point, Scalar, G};
-use sha2::Sha256;
-use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::io::Read;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::sync::OwnedMutexGuard;
+use tokio::task::spawn_blocking;
 
 pub struct Seller<TChainProvider, TCipherHost, TPropVerifier: PropertyVerifier> {
     cfg: SellerConfig,
@@ -23,32 +40,98 @@ pub struct Seller<TChainProvider, TCipherHost, TPropVerifier: PropertyVerifier>
     chain: TChainProvider,
     cipher_host: TCipherHost,
     wallet: crate::LocalWallet,
-    from_buyers: mpsc::Receiver<SellerMsg>,
-    one_time_keys: HashMap<Address, Scalar>,
+    /// Taken by [`Self::run`], which serves each message in a task of its own.
+    from_buyers: Option<mpsc::Receiver<SellerMsg>>,
+    /// One-time keys of the locked sessions, along with the time each was locked at.
+    one_time_keys: Mutex<HashMap<(Option<SaleId>, Address), (Scalar, DateTime<Utc>)>>,
+    /// Sessions whose Step 3 is being settled, see [`SellerConfig::max_sessions`].
+    settling: Mutex<HashSet<(Option<SaleId>, Address)>>,
+    /// Locks of the sessions being served, so that messages of the same buyer are served in turn.
+    session_locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+    /// Held while the audit log all sessions share is read or appended to, keeping it chained.
+    receipts_lock: Mutex<()>,
     decryption_key: Option<Vec<u8>>,
+    listings: HashMap<SaleId, Listing<TCipherHost>>,
     verifiable_encryption: ZkVerifiableEncryption<TPropVerifier>,
     key_encryption: ZkEncryption,
+    /// Key encryptions proven ahead for each sale, see [`SellerConfig::precomputed_key_encryptions`].
+    key_encryption_pool: Mutex<HashMap<Option<SaleId>, Vec<KeyEncryption>>>,
+    /// Held while the pool is refilled, so that sessions finishing at once don't prove it twice.
+    refilling: Mutex<()>,
+    payout_address: Option<Address>,
+    events: Option<mpsc::UnboundedSender<SellerEvent>>,
+    metrics: Arc<SellerMetrics>,
 }
 
+/// Identifies one of the sales served by the same daemon, see [`Seller::list_sale`].
+pub type SaleId = String;
+
+/// Locks `mutex`, still taking the state a panicked session left behind.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Sale served alongside the one seller was set up with, sharing its circuits and wallet.
+struct Listing<TCipherHost> {
+    cipher_host: TCipherHost,
+    decryption_key: Option<Vec<u8>>,
+    price: f64,
+}
+
+/// Messages are addressed to the sale seller was set up with, unless `sale_id` is given.
 pub enum SellerMsg {
     /// Step 0: Alice encrypts data and generates Proof-of-Encryption (PoE);
     /// Bob requests ciphertext and verifies proof.
     Step0 {
+        sale_id: Option<SaleId>,
         resp_tx: oneshot::Sender<anyhow::Result<VerifiableEncryption>>,
     },
     /// Step 1:Alice generates new key pair, encrypt data decryption key with it, and sends public key and ciphertext to Bob.
     Step1 {
+        sale_id: Option<SaleId>,
         address: Address,
         resp_tx: oneshot::Sender<anyhow::Result<Step1Msg>>,
     },
-    /// Step 3: Alice decrypts this signature and publishes it, ie. get paid
+    /// Step 1 for interactive sales: Bob sends his freshly generated ElGamal public key,
+    /// and Alice encrypts data decryption key to it joined with the one-time key.
+    Handshake {
+        sale_id: Option<SaleId>,
+        address: Address,
+        buyer_pk: Vec<u8>,
+        resp_tx: oneshot::Sender<anyhow::Result<Step1Msg>>,
+    },
+    /// Bob asks for the price Alice signs to sell at for a while, see [`Quote`].
+    Quote {
+        sale_id: Option<SaleId>,
+        resp_tx: oneshot::Sender<anyhow::Result<SignedQuote>>,
+    },
+    /// Step 3: Alice decrypts this signature and publishes it, ie. get paid.
+    /// Payment is for the quoted price if Bob agreed to a quote, for the listed one otherwise,
+    /// pinned to the nonce and gas of `tx_params` Bob signed it with.
     Step3 {
+        sale_id: Option<SaleId>,
         pub_key: Point,
         enc_sig: EncryptedSignature,
+        tx_params: TxParams,
+        quote: Option<SignedQuote>,
         resp_tx: oneshot::Sender<anyhow::Result<H256>>,
     },
+    /// Phase of the swap with buyer at `address` and hash of its payment once made, none if not started.
+    Status {
+        sale_id: Option<SaleId>,
+        address: Address,
+        resp_tx: oneshot::Sender<anyhow::Result<Option<(SessionPhase, Option<H256>)>>>,
+    },
+    /// Latest receipt of the trade with buyer at `address`, signed by Alice, for Bob to countersign.
+    Receipt {
+        sale_id: Option<SaleId>,
+        address: Address,
+        resp_tx: oneshot::Sender<anyhow::Result<Option<ReceiptEntry>>>,
+    },
 }
 
+/// Encrypted one-time key seller offers at Step 1, signed with [`Step1Msg::sign`].
+#[derive(Clone, Debug)]
 pub struct Step1Msg {
     pub ciphertext: Vec<u8>,
     pub proof_of_encryption: Vec<u8>,
@@ -56,11 +139,119 @@ pub struct Step1Msg {
     pub seller_address: Address,
 }
 
+/// Data decryption key encrypted at Step 1 to the one-time key, joined with buyer's key if given.
+struct KeyEncryption {
+    /// Nonce the one-time key is derived with from seller's wallet key, see [`trade_key_rng`].
+    trade_nonce: [u8; 32],
+    one_time_sk: Scalar,
+    one_time_pk: Point,
+    ciphertext: Vec<u8>,
+    proof_of_encryption: Vec<u8>,
+}
+
+impl KeyEncryption {
+    fn prove(
+        key_encryption: &ZkEncryption,
+        plaintext: &[u8],
+        master: &Scalar,
+        buyer_pk: Option<&PublicKey<ProjectiveCurve>>,
+        metrics: &SellerMetrics,
+    ) -> anyhow::Result<Self> {
+        let started = Instant::now();
+        let mut rng = rand::thread_rng();
+        let trade_nonce: [u8; 32] = rng.gen();
+        let mut key_rng = trade_key_rng(master, &trade_nonce);
+        let (elgamal_pk, one_time_sk, one_time_pk) = match buyer_pk {
+            Some(buyer_pk) => key_encryption.keygen_derive_joint(buyer_pk, &mut key_rng),
+            None => key_encryption.keygen_derive(&mut key_rng),
+        }
+        .expect("expected generation to succeed or infinite looped");
+        let (ciphertext, proof_of_encryption) =
+            key_encryption.encrypt(plaintext, elgamal_pk, &mut rng)?;
+        metrics.record_proof(started.elapsed());
+
+        Ok(Self {
+            trade_nonce,
+            one_time_sk,
+            one_time_pk,
+            ciphertext,
+            proof_of_encryption,
+        })
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct SellerConfig {
     pub price: f64,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Wall-clock limit for proving the sale, unlimited if `None`.
+    pub max_prove_time: Option<Duration>,
+    /// Proving pauses between proofs while less than this many bytes of memory are available.
+    pub min_free_memory: Option<u64>,
     pub cache_dir: PathBuf,
     pub zk: ZkConfig,
+    /// Only serves sales, refusing every step that needs the decryption key or wallet's secret,
+    /// so that monitoring instances can run without access to either.
+    pub observer: bool,
+    /// Seeds randomness of the encryption for reproducible tests, see [`encryption_rng`].
+    /// Anyone knowing the seed recovers the decryption key, so real sales must leave it `None`.
+    pub deterministic_seed: Option<u64>,
+    /// Chain the quotes are signed for, see [`Quote::chain_id`].
+    pub chain_id: u64,
+    /// Step 1 key encryptions proven ahead for each sale, on all cores, so that buyers not doing
+    /// the handshake don't wait for the proof. Each is served to a single buyer and replaced after.
+    pub precomputed_key_encryptions: usize,
+    /// Purchases that can be settling at once, from Step 3 until the payment is broadcast, new
+    /// buyers are turned away at Step 1 past it. Locked sessions don't count, buyers have shown
+    /// nothing but an address by then, and are freed after [`Self::lock_ttl`] instead.
+    pub max_sessions: Option<usize>,
+    /// Time one-time key stays locked for buyer who hasn't paid yet, see [`LOCK_TTL`].
+    pub lock_ttl: Duration,
+}
+
+/// Default of [`SellerConfig::lock_ttl`], long enough for buyers to resume interrupted purchases.
+pub const LOCK_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Randomness the sale is encrypted and proven with: derived from `seed` if given,
+/// so that same data, key and seed always encrypt to the same bytes, or from OS entropy otherwise.
+pub fn encryption_rng(seed: Option<u64>) -> ChaCha20Rng {
+    match seed {
+        Some(seed) => ChaCha20Rng::seed_from_u64(seed),
+        None => ChaCha20Rng::from_entropy(),
+    }
+}
+
+/// Fails if sale offer has expired by `now`, offers without expiry never do.
+pub fn check_expiry(expires_at: Option<DateTime<Utc>>, now: DateTime<Utc>) -> anyhow::Result<()> {
+    match expires_at {
+        Some(expires_at) if now >= expires_at => Err(anyhow!(
+            "sale offer has expired at {}",
+            expires_at.to_rfc3339()
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Whether one-time key locked at `locked_at` has outlived `ttl` by `now`.
+pub fn lock_expired(locked_at: DateTime<Utc>, ttl: Duration, now: DateTime<Utc>) -> bool {
+    chrono::Duration::from_std(ttl)
+        .ok()
+        .and_then(|ttl| locked_at.checked_add_signed(ttl))
+        .map_or(false, |expires_at| now >= expires_at)
+}
+
+/// Fails if `in_progress` purchases already reach `max_sessions`, unless buyer is `resuming` one of them.
+pub fn check_sessions_limit(
+    max_sessions: Option<usize>,
+    in_progress: usize,
+    resuming: bool,
+) -> anyhow::Result<()> {
+    match max_sessions {
+        Some(max_sessions) if !resuming && in_progress >= max_sessions => Err(anyhow!(
+            "seller has {in_progress} purchases in progress already, try again later"
+        )),
+        _ => Ok(()),
+    }
 }
 
 impl<TChainProvider: ChainProvider, TCipherHost: CipherHost, TPropVerifier: PropertyVerifier>
@@ -76,124 +267,1194 @@ impl<TChainProvider: ChainProvider, TCipherHost: CipherHost, TPropVerifier: Prop
         let nonce_gen = Deterministic::<Sha256>::default();
         let adaptor = Adaptor::<HashTranscript<Sha256, ChaCha20Rng>, _>::new(nonce_gen);
         let (to_seller, from_buyers) = mpsc::channel(1);
-        let decryption_key =
-            fs::read(cfg.cache_dir.join("decryption_key")).map_or(None, |b| Some(b));
+        let decryption_key = match cfg.observer {
+            true => None,
+            false => fs::read(cfg.cache_dir.join("decryption_key")).map_or(None, |b| Some(b)),
+        };
         let verifiable_encryption = ZkVerifiableEncryption::new(
             &cfg.zk.data_encryption_dir,
             property_verifier,
             encryption::Parameters::default_multi(cfg.zk.data_encryption_limit),
-        );
+        )
+        .with_predicate(cfg.zk.predicate);
         let key_encryption = ZkEncryption::new(&cfg.zk.key_encryption_dir, Default::default());
+        // purchases interrupted by restart resume with one-time keys buyers have already signed to,
+        // unless they've expired meanwhile, or were locked before lock time was recorded.
+        let mut one_time_keys = HashMap::default();
+        let sessions = match cfg.observer {
+            true => vec![],
+            false => read_sessions::<_, SellerSession>(&cfg.cache_dir)?,
+        };
+        let now = Utc::now();
+        for (id, session) in sessions {
+            if session.phase != SessionPhase::Locked {
+                continue;
+            }
+            let locked_at = session
+                .locked_at
+                .as_deref()
+                .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+                .map(|t| t.with_timezone(&Utc));
+            match locked_at {
+                Some(locked_at) if !lock_expired(locked_at, cfg.lock_ttl, now) => {
+                    let sk = Scalar::from_str(&session.one_time_sk)
+                        .map_err(|e| anyhow!("error decoding one-time key of session {id}: {e}"))?;
+                    let _ = one_time_keys.insert((session.sale_id, session.buyer), (sk, locked_at));
+                }
+                _ => remove_session(&cfg.cache_dir, &id)?,
+            }
+        }
         Ok((
             Self {
                 cfg,
                 adaptor,
-                one_time_keys: HashMap::default(),
+                one_time_keys: Mutex::new(one_time_keys),
+                settling: Mutex::default(),
+                session_locks: Mutex::default(),
+                receipts_lock: Mutex::default(),
                 chain,
                 cipher_host,
-                from_buyers,
+                from_buyers: Some(from_buyers),
                 wallet,
                 decryption_key,
+                listings: HashMap::default(),
                 verifiable_encryption,
                 key_encryption,
+                key_encryption_pool: Mutex::default(),
+                refilling: Mutex::default(),
+                payout_address: None,
+                events: None,
+                metrics: Arc::default(),
             },
             to_seller,
         ))
     }
 
+    /// Has buyers pay to `address` instead of the wallet's own, e.g. to account of a hardware wallet.
+    pub fn with_payout_address(mut self, address: Address) -> Self {
+        let _ = self.payout_address.insert(address);
+        self
+    }
+
+    /// Sends [`SellerEvent::Funded`] of every paid trade to `events`, see [`crate::EventWatcher`].
+    pub fn with_events(mut self, events: mpsc::UnboundedSender<SellerEvent>) -> Self {
+        let _ = self.events.insert(events);
+        self
+    }
+
+    /// Counters of proofs and swaps for the server to expose, see [`SellerMetrics`].
+    pub fn metrics(&self) -> Arc<SellerMetrics> {
+        self.metrics.clone()
+    }
+
+    fn emit(&self, event: SellerEvent) {
+        if let Some(events) = &self.events {
+            let _ = events.unbounded_send(event);
+        }
+    }
+
+    fn payout_address(&self) -> Address {
+        self.payout_address
+            .unwrap_or_else(|| self.chain.address_from_pk(self.wallet.pub_key()))
+    }
+
+    /// Fails in observer mode, where no `operation` requiring secrets is allowed.
+    fn ensure_not_observer(&self, operation: &str) -> anyhow::Result<()> {
+        match self.cfg.observer {
+            true => Err(anyhow!(
+                "seller runs in observer mode without access to secrets, refusing to {operation}"
+            )),
+            false => Ok(()),
+        }
+    }
+
+    fn prove_budget(&self) -> ProveBudget {
+        let budget = ProveBudget::new(self.cfg.max_prove_time);
+        match self.cfg.min_free_memory {
+            Some(min_free) => budget.with_memory_watchdog(MemoryWatchdog::new(min_free)),
+            None => budget,
+        }
+    }
+
+    /// Step 0 setup: encrypts `data` and proves it, caching the decryption key along with
+    /// the [`DataFingerprint`] of `data`, so that the cached sale can be told apart from a changed one.
     pub async fn step0_setup(&mut self, data: Vec<u8>) -> anyhow::Result<()> {
-        let (sk, pk) = self.verifiable_encryption.keygen(&mut rand::thread_rng())?;
+        let cache_dir = self.cfg.cache_dir.clone();
+        let (verifiable_encryption, sk_bytes) = self.encrypt(data, &cache_dir)?;
+        let _ = self.decryption_key.insert(sk_bytes);
+
+        let _ = self.cipher_host.write(verifiable_encryption).await;
+
+        Ok(())
+    }
+
+    /// Encrypts and proves `data` as sale of its own listed under `sale_id`, eg. a segment
+    /// of streamed sale, caching its key in `cache_dir` the `cipher_host` keeps the sale in.
+    pub async fn list_new_sale(
+        &mut self,
+        sale_id: SaleId,
+        cipher_host: TCipherHost,
+        cache_dir: &Path,
+        data: Vec<u8>,
+        price: f64,
+    ) -> anyhow::Result<VerifiableEncryption> {
+        let (sale, sk_bytes) = self.encrypt(data, cache_dir)?;
+        self.list_sale(sale_id, cipher_host, sale.clone(), Some(sk_bytes), price)
+            .await?;
+
+        Ok(sale)
+    }
+
+    /// Encrypts `data` to a fresh key, caching the key and fingerprint of the data in `cache_dir`.
+    fn encrypt(
+        &mut self,
+        data: Vec<u8>,
+        cache_dir: &Path,
+    ) -> anyhow::Result<(VerifiableEncryption, Vec<u8>)> {
+        self.ensure_not_observer("encrypt data")?;
+        let fingerprint = DataFingerprint::new(&data, DATA_CHUNK_SIZE);
+        let mut rng = encryption_rng(self.cfg.deterministic_seed);
+        let (sk, pk) = self.verifiable_encryption.keygen(&mut rng)?;
 
         let sk_bytes = ark_to_bytes(sk.clone())
             .map_err(|e| anyhow!("error encoding elgamal secret key: {e}"))?;
 
-        fs::create_dir_all(&self.cfg.cache_dir).expect("expected dir to be created");
-        fs::write(
-            self.cfg.cache_dir.join("decryption_key"),
-            self.decryption_key.insert(sk_bytes),
+        // nothing is cached until proving succeeds, so an aborted run leaves no stale key behind.
+        let started = Instant::now();
+        let verifiable_encryption = self
+            .verifiable_encryption
+            .assess_property_and_encrypt_within(data, sk, pk, &mut self.prove_budget(), &mut rng)?;
+        self.metrics.record_proof(started.elapsed());
+        tracing::info!(elapsed = ?started.elapsed(), "sale encrypted and proven");
+
+        fs::create_dir_all(cache_dir).expect("expected dir to be created");
+        fs::write(cache_dir.join("decryption_key"), &sk_bytes)
+            .map_err(|e| anyhow!("error caching decryption key: {e}"))?;
+        fingerprint.write(cache_dir.join(DATA_FINGERPRINT_FILE))?;
+
+        Ok((verifiable_encryption, sk_bytes))
+    }
+
+    /// Hosts sale encrypted earlier, eg. restored from `.sale` file, whose decryption key is in cache
+    /// unless in observer mode.
+    pub async fn host_sale(&mut self, sale: VerifiableEncryption) -> anyhow::Result<()> {
+        if !self.cfg.observer && self.decryption_key.is_none() {
+            return Err(anyhow!("decryption key for the sale not found in cache"));
+        }
+
+        self.cipher_host.write(sale).await
+    }
+
+    /// Serves another `sale` under `sale_id`, eg. restored from its own cache or `.sale` file.
+    /// Circuits' keys are loaded once and shared across all sales.
+    /// Decryption key is required unless in observer mode, where it's dropped if given.
+    pub async fn list_sale(
+        &mut self,
+        sale_id: SaleId,
+        mut cipher_host: TCipherHost,
+        sale: VerifiableEncryption,
+        decryption_key: Option<Vec<u8>>,
+        price: f64,
+    ) -> anyhow::Result<()> {
+        if self.listings.contains_key(&sale_id) {
+            return Err(anyhow!("sale {sale_id} is already listed"));
+        }
+        let decryption_key = match self.cfg.observer {
+            true => None,
+            false => {
+                Some(decryption_key.ok_or(anyhow!("decryption key for sale {sale_id} not given"))?)
+            }
+        };
+
+        cipher_host.write(sale).await?;
+        let _ = self.listings.insert(
+            sale_id,
+            Listing {
+                cipher_host,
+                decryption_key,
+                price,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Cipher host, decryption key and price of the sale addressed by `sale_id`.
+    fn sale(&self, sale_id: Option<&str>) -> anyhow::Result<(&TCipherHost, Option<&[u8]>, f64)> {
+        match sale_id {
+            None => Ok((
+                &self.cipher_host,
+                self.decryption_key.as_deref(),
+                self.cfg.price,
+            )),
+            Some(id) => self
+                .listings
+                .get(id)
+                .map(|l| (&l.cipher_host, l.decryption_key.as_deref(), l.price))
+                .ok_or(anyhow!("unknown sale {id}")),
+        }
+    }
+
+    /// Signs quote of the listed price of the sale `sale_id`, valid for [`QUOTE_VALIDITY`]
+    /// or until the sale offer expires, whichever comes first.
+    async fn quote(&self, sale_id: Option<SaleId>) -> anyhow::Result<SignedQuote> {
+        self.ensure_not_observer("sign quote")?;
+        let now = Utc::now();
+        check_expiry(self.cfg.expires_at, now)?;
+        let (cipher_host, _, price) = self.sale(sale_id.as_deref())?;
+        let sale_hash = cipher_host.read().await?.hash();
+
+        let validity = chrono::Duration::from_std(QUOTE_VALIDITY)
+            .map_err(|e| anyhow!("error converting quote validity: {e}"))?;
+        let expires_at = match self.cfg.expires_at {
+            Some(offer_expiry) => (now + validity).min(offer_expiry),
+            None => now + validity,
+        };
+        Ok(Quote {
+            sale_id,
+            price,
+            sale_hash,
+            chain_id: self.cfg.chain_id,
+            expires_at: expires_at.to_rfc3339(),
+            token: self.chain.payment_token(),
+        }
+        .sign(self.wallet.sec_key()))
+    }
+
+    /// Price the sale with `sale_hash` is settled at: that of `quote` once it's checked to be signed
+    /// by the seller for this sale and chain, or the `listed` one if buyer hasn't agreed to a quote.
+    fn settled_price(
+        &self,
+        sale_id: Option<&str>,
+        sale_hash: &[u8; 32],
+        listed: f64,
+        quote: Option<&SignedQuote>,
+    ) -> anyhow::Result<f64> {
+        match quote {
+            Some(quote) => {
+                quote.verify(self.wallet.pub_key())?;
+                quote.quote.ensure_terms(
+                    sale_id,
+                    sale_hash,
+                    self.cfg.chain_id,
+                    self.chain.payment_token(),
+                )?;
+                Ok(quote.quote.price)
+            }
+            None => Ok(listed),
+        }
+    }
+
+    /// Appends receipt of the trade settled in `tx_ids`, signed by the seller, to the audit log in cache.
+    fn record_receipt(
+        &self,
+        sale_id: Option<SaleId>,
+        sale: &VerifiableEncryption,
+        price: f64,
+        tx_ids: Vec<String>,
+        one_time_sk: &Scalar,
+        buyer_pk: &Point,
+    ) -> anyhow::Result<ReceiptEntry> {
+        let one_time_pk = g!(one_time_sk * G).mark::<Normal>();
+        let receipt = Receipt::new(
+            sale_id,
+            sale,
+            price,
+            self.cfg.chain_id,
+            tx_ids,
+            &one_time_pk,
+            buyer_pk,
         )
-        .map_err(|e| anyhow!("error caching decryption key: {e}"))?;
+        .with_seller_pk(self.wallet.pub_key());
+        let signature = receipt.sign(self.wallet.sec_key());
 
-        let verifiable_encryption = self.verifiable_encryption.assess_property_and_encrypt(
-            data,
-            sk,
-            pk,
-            &mut rand::thread_rng(),
+        let _appending = lock(&self.receipts_lock);
+        append_receipt(
+            self.cfg.cache_dir.join(RECEIPTS_FILE),
+            receipt,
+            Some(signature),
+            None,
+        )
+    }
+
+    /// Latest receipt in the audit log of the trade of sale `sale_id` with buyer at `address`.
+    fn find_receipt(
+        &self,
+        sale_id: Option<&str>,
+        address: Address,
+    ) -> anyhow::Result<Option<ReceiptEntry>> {
+        let entries = {
+            let _reading = lock(&self.receipts_lock);
+            read_receipts(self.cfg.cache_dir.join(RECEIPTS_FILE))?
+        };
+        Ok(entries.into_iter().rev().find(|entry| {
+            entry.receipt.sale_id.as_deref() == sale_id
+                && entry
+                    .receipt
+                    .buyer_pk()
+                    .map_or(false, |pk| self.chain.address_from_pk(&pk) == address)
+        }))
+    }
+
+    /// Step 1: encrypts data decryption key with a new one-time key, joined with `buyer_pk` if given.
+    /// Encryption is taken from the pool proven ahead if there's one, or from the session
+    /// of the buyer retrying the handshake with the same `buyer_pk`, see [`CachedKeyEncryption`].
+    #[tracing::instrument(
+        name = "session",
+        skip_all,
+        fields(session = %seller_session_id(sale_id.as_deref(), address))
+    )]
+    fn step1(
+        &self,
+        address: Address,
+        sale_id: Option<SaleId>,
+        buyer_pk: Option<PublicKey<ProjectiveCurve>>,
+    ) -> anyhow::Result<Step1Msg> {
+        self.ensure_not_observer("reveal one-time key")?;
+        // buyers can't start purchase after expiry, those started before are honored at Step 3.
+        check_expiry(self.cfg.expires_at, Utc::now())?;
+        self.ensure_session_slot(&sale_id, address, Utc::now())?;
+
+        let plaintext = self
+            .sale(sale_id.as_deref())?
+            .1
+            .ok_or(anyhow!("decryption key for the sale not found in cache"))?
+            .to_vec();
+        let buyer_pk_hex = buyer_pk
+            .map(|pk| ark_to_bytes(pk.into_affine()).map(hex::encode))
+            .transpose()
+            .map_err(|e| anyhow!("error encoding buyer's public key: {e}"))?;
+
+        let encryption = match &buyer_pk_hex {
+            Some(buyer_pk_hex) => self.cached_key_encryption(&sale_id, address, buyer_pk_hex)?,
+            None => lock(&self.key_encryption_pool)
+                .get_mut(&sale_id)
+                .and_then(Vec::pop),
+        };
+        let cached = encryption.is_some();
+        let encryption = match encryption {
+            Some(encryption) => encryption,
+            None => KeyEncryption::prove(
+                &self.key_encryption,
+                &plaintext,
+                self.wallet.sec_key(),
+                buyer_pk.as_ref(),
+                &self.metrics,
+            )?,
+        };
+        let locked_at = Utc::now();
+        write_session(
+            &self.cfg.cache_dir,
+            &seller_session_id(sale_id.as_deref(), address),
+            &SellerSession {
+                phase: SessionPhase::Locked,
+                sale_id: sale_id.clone(),
+                buyer: address,
+                one_time_sk: encryption.one_time_sk.to_string(),
+                trade_nonce: Some(hex::encode(encryption.trade_nonce)),
+                tx_hash: None,
+                locked_at: Some(locked_at.to_rfc3339()),
+                key_encryption: buyer_pk_hex.map(|buyer_pk| CachedKeyEncryption {
+                    buyer_pk,
+                    ciphertext: hex::encode(&encryption.ciphertext),
+                    proof_of_encryption: hex::encode(&encryption.proof_of_encryption),
+                }),
+            },
         )?;
+        let _ = lock(&self.one_time_keys).insert(
+            (sale_id, address),
+            (encryption.one_time_sk.clone(), locked_at),
+        );
+        tracing::info!(
+            cached,
+            handshake = buyer_pk.is_some(),
+            "one-time key locked"
+        );
 
-        let _ = self.cipher_host.write(verifiable_encryption).await;
+        Ok(Step1Msg {
+            ciphertext: encryption.ciphertext,
+            proof_of_encryption: encryption.proof_of_encryption,
+            data_pk: encryption.one_time_pk,
+            seller_address: self.payout_address(),
+        })
+    }
+
+    /// Fails if [`SellerConfig::max_sessions`] are settling already, unless buyer at `address` is
+    /// among them. One-time keys that have outlived [`SellerConfig::lock_ttl`] by `now` are
+    /// dropped, their sessions are removed once the seller restarts.
+    fn ensure_session_slot(
+        &self,
+        sale_id: &Option<SaleId>,
+        address: Address,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        lock(&self.one_time_keys)
+            .retain(|_, (_, locked_at)| !lock_expired(*locked_at, self.cfg.lock_ttl, now));
+        let settling = lock(&self.settling);
+        check_sessions_limit(
+            self.cfg.max_sessions,
+            settling.len(),
+            settling.contains(&(sale_id.clone(), address)),
+        )
+    }
+
+    /// Key encryption of the locked session with buyer at `address`, if it was made to the same `buyer_pk`.
+    fn cached_key_encryption(
+        &self,
+        sale_id: &Option<SaleId>,
+        address: Address,
+        buyer_pk: &str,
+    ) -> anyhow::Result<Option<KeyEncryption>> {
+        let one_time_sk = lock(&self.one_time_keys)
+            .get(&(sale_id.clone(), address))
+            .map(|(sk, _)| sk.clone());
+        let one_time_sk = match one_time_sk {
+            Some(sk) => sk,
+            None => return Ok(None),
+        };
+        let session: Option<SellerSession> = find_session(
+            &self.cfg.cache_dir,
+            &seller_session_id(sale_id.as_deref(), address),
+        )?;
+        let (cached, trade_nonce) = match session {
+            Some(SellerSession {
+                key_encryption: Some(cached),
+                trade_nonce: Some(trade_nonce),
+                ..
+            }) if cached.buyer_pk == buyer_pk => (cached, trade_nonce),
+            _ => return Ok(None),
+        };
+        let decode = |bytes: &str| {
+            hex::decode(bytes).map_err(|e| anyhow!("error decoding cached key encryption: {e}"))
+        };
+
+        Ok(Some(KeyEncryption {
+            trade_nonce: decode(&trade_nonce)?
+                .try_into()
+                .map_err(|_| anyhow!("trade nonce of the session must be 32 bytes"))?,
+            one_time_pk: g!(one_time_sk * G).mark::<Normal>(),
+            one_time_sk,
+            ciphertext: decode(&cached.ciphertext)?,
+            proof_of_encryption: decode(&cached.proof_of_encryption)?,
+        }))
+    }
+
+    /// Proves key encryptions of sale `sale_id` in parallel until there are
+    /// [`SellerConfig::precomputed_key_encryptions`] of them in the pool.
+    fn refill_key_encryptions(&self, sale_id: Option<SaleId>) -> anyhow::Result<()> {
+        // sessions finishing meanwhile find the pool full once this one has refilled it.
+        let _refilling = lock(&self.refilling);
+        let ready = lock(&self.key_encryption_pool)
+            .get(&sale_id)
+            .map_or(0, Vec::len);
+        let missing = self.cfg.precomputed_key_encryptions.saturating_sub(ready);
+        if missing == 0 || self.cfg.observer {
+            return Ok(());
+        }
+        let plaintext = match self.sale(sale_id.as_deref())?.1 {
+            Some(key) => key.to_vec(),
+            None => return Ok(()),
+        };
+
+        let (key_encryption, master, metrics) =
+            (&self.key_encryption, self.wallet.sec_key(), &*self.metrics);
+        let proven = (0..missing)
+            .into_par_iter()
+            .map(|_| KeyEncryption::prove(key_encryption, &plaintext, master, None, metrics))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        tracing::info!(sale_id = ?sale_id, proven = missing, "key encryptions proven ahead");
+        lock(&self.key_encryption_pool)
+            .entry(sale_id)
+            .or_default()
+            .extend(proven);
 
         Ok(())
     }
 
-    pub async fn run(mut self) {
-        loop {
-            if let Some(msg) = self.from_buyers.next().await {
-                match msg {
-                    SellerMsg::Step0 { resp_tx } => {
-                        let _ = resp_tx.send(self.cipher_host.read().await); // todo: DoS defense needed.
-                    }
-                    SellerMsg::Step1 { address, resp_tx } => {
-                        let (elgamal_pk, data_sk, data_pk) = self
-                            .key_encryption
-                            .keygen_derive(&mut rand::thread_rng())
-                            .expect("expected generation to succeed or infinite looped");
-                        let _ = self.one_time_keys.insert(address, data_sk);
-                        let seller_address = self.chain.address_from_pk(self.wallet.pub_key());
-                        let plaintext = self
-                            .decryption_key
-                            .as_ref()
-                            .expect("decryption key was expected");
-                        if let Err(_) = resp_tx.send(
-                            self.key_encryption
-                                .encrypt(plaintext, elgamal_pk, &mut rand::thread_rng())
-                                .map(|(ciphertext, proof_of_encryption)| Step1Msg {
-                                    ciphertext,
-                                    proof_of_encryption,
-                                    data_pk,
-                                    seller_address,
-                                }),
-                        ) {
-                            self.one_time_keys.remove(&address); // todo: DoS defense needed.
-                        }
-                    }
-                    SellerMsg::Step3 {
-                        pub_key,
-                        enc_sig,
-                        resp_tx,
-                    } => {
-                        let local_address = self.chain.address_from_pk(self.wallet.pub_key());
-                        let address = self.chain.address_from_pk(&pub_key);
-                        let decryption_key = match self.one_time_keys.entry(address) {
-                            Entry::Occupied(e) => e.remove(),
-                            Entry::Vacant(_) => {
-                                let _ = resp_tx.send(Err(anyhow!("unknown address")));
-                                continue;
-                            }
-                        };
-
-                        let (pay_tx, tx_hash) = self
-                            .chain
-                            .compose_tx(address, local_address, self.cfg.price)
-                            .unwrap();
-
-                        let one_time_pk = g!(decryption_key * G).mark::<Normal>();
-                        if !self.adaptor.verify_encrypted_signature(
-                            &pub_key,
-                            &one_time_pk,
-                            tx_hash.as_fixed_bytes(),
-                            &enc_sig,
-                        ) {
-                            let _ = resp_tx.send(Err(anyhow!("invalid adaptor signature")));
-                            continue;
-                        }
-                        let decrypted_sig =
-                            self.adaptor.decrypt_signature(&decryption_key, enc_sig);
-
-                        let _ = resp_tx.send(self.chain.sent_signed(pay_tx, &decrypted_sig).await);
-                    }
+    /// Step 3: decrypts adaptor signature of the buyer with `pub_key` with its one-time key and
+    /// broadcasts the payment it signs, once only however many times buyer retries.
+    #[tracing::instrument(name = "session", skip_all, fields(session = tracing::field::Empty))]
+    async fn step3(
+        &self,
+        sale_id: Option<SaleId>,
+        pub_key: Point,
+        enc_sig: EncryptedSignature,
+        tx_params: TxParams,
+        quote: Option<SignedQuote>,
+    ) -> anyhow::Result<H256> {
+        self.ensure_not_observer("sign payment")?;
+        let local_address = self.payout_address();
+        let address = self.chain.address_from_pk(&pub_key);
+        let _ = tracing::Span::current().record(
+            "session",
+            seller_session_id(sale_id.as_deref(), address).as_str(),
+        );
+        let sale = match self.sale(sale_id.as_deref()) {
+            Ok((cipher_host, _, price)) => cipher_host.read().await.map(|cipher| (cipher, price)),
+            Err(e) => Err(e),
+        };
+        let priced = sale.and_then(|(cipher, listed)| {
+            let sale_hash = cipher.hash();
+            self.settled_price(sale_id.as_deref(), &sale_hash, listed, quote.as_ref())
+                .map(|price| {
+                    let payment_key = payment_idempotency_key(address, &sale_hash, price);
+                    (cipher, payment_key, price)
+                })
+        });
+        let (cipher, payment_key, price) = priced?;
+        // buyer retrying after timeout must not make seller broadcast payment twice.
+        if let Ok(Some(tx_hash)) =
+            find_payment(&self.chain, &self.cfg.cache_dir, &payment_key).await
+        {
+            return Ok(tx_hash);
+        }
+        // retries of payments made in time are answered above even once it's expired.
+        if let Some(quote) = &quote {
+            quote.quote.ensure_unexpired(Utc::now())?;
+        }
+
+        let locked = lock(&self.one_time_keys).remove(&(sale_id.clone(), address));
+        let (decryption_key, locked_at) = locked.ok_or(anyhow!("unknown address"))?;
+        if lock_expired(locked_at, self.cfg.lock_ttl, Utc::now()) {
+            return Err(anyhow!("one-time key has expired, start the purchase over"));
+        }
+
+        let (pay_tx, tx_hash) = self
+            .chain
+            .compose_tx(address, local_address, price, &tx_params)?;
+
+        let one_time_pk = g!(decryption_key * G).mark::<Normal>();
+        if !self.adaptor.verify_encrypted_signature(
+            &pub_key,
+            &one_time_pk,
+            tx_hash.as_fixed_bytes(),
+            &enc_sig,
+        ) {
+            return Err(anyhow!("invalid adaptor signature"));
+        }
+        let decrypted_sig = self.adaptor.decrypt_signature(&decryption_key, enc_sig);
+
+        // payment was looked up above already, so it's broadcast and recorded right away.
+        let resp = match self.chain.sent_signed(pay_tx, &decrypted_sig).await {
+            Ok(tx_hash) => {
+                write_pending_payment(&self.cfg.cache_dir, &payment_key, tx_hash).map(|_| tx_hash)
+            }
+            Err(e) => Err(e),
+        };
+        match &resp {
+            Ok(tx_hash) => {
+                self.metrics.record_swap();
+                tracing::info!(tx_hash = ?tx_hash, price, "payment settled");
+                let _ = self.record_receipt(
+                    sale_id.clone(),
+                    &cipher,
+                    price,
+                    vec![hex::encode(tx_hash.as_bytes())],
+                    &decryption_key,
+                    &pub_key,
+                );
+                // transfer is mined once broadcast, funding and claiming the payment at once.
+                self.emit(SellerEvent::Funded {
+                    sale_id: sale_id.clone(),
+                    buyer: address,
+                    price,
+                    funding_tx: hex::encode(tx_hash.as_bytes()),
+                    claim_tx: hex::encode(tx_hash.as_bytes()),
+                });
+                let _ = write_session(
+                    &self.cfg.cache_dir,
+                    &seller_session_id(sale_id.as_deref(), address),
+                    &SellerSession {
+                        phase: SessionPhase::Paid,
+                        sale_id,
+                        buyer: address,
+                        one_time_sk: decryption_key.to_string(),
+                        tx_hash: Some(*tx_hash),
+                        trade_nonce: None,
+                        locked_at: None,
+                        key_encryption: None,
+                    },
+                );
+            }
+            // keep the key, so that buyer can retry settling the same payment.
+            Err(e) => {
+                tracing::warn!("error settling payment: {e}");
+                let _ = lock(&self.one_time_keys)
+                    .insert((sale_id, address), (decryption_key, locked_at));
+            }
+        }
+        resp
+    }
+
+    /// Session of buyer at `address` locked for the task serving its message,
+    /// forgetting the locks no task holds or waits for anymore.
+    async fn lock_session(&self, sale_id: Option<&str>, address: Address) -> OwnedMutexGuard<()> {
+        let session = {
+            let mut session_locks = lock(&self.session_locks);
+            session_locks.retain(|_, session| Arc::strong_count(session) > 1);
+            session_locks
+                .entry(seller_session_id(sale_id, address))
+                .or_default()
+                .clone()
+        };
+        session.lock_owned().await
+    }
+
+    /// Drops the session of buyer at `address` gone before its Step 1 response was sent.
+    fn forget_session(&self, sale_id: Option<SaleId>, address: Address) {
+        let _ = remove_session(
+            &self.cfg.cache_dir,
+            &seller_session_id(sale_id.as_deref(), address),
+        );
+        let _ = lock(&self.one_time_keys).remove(&(sale_id, address)); // todo: DoS defense needed.
+    }
+
+    /// Serves buyers until all senders of [`SellerMsg`] are dropped, each message in a task of its
+    /// own, so that proving Step 1 for one buyer or broadcasting the payment of another holds up
+    /// no one else. Only the messages of the same session wait for each other.
+    pub async fn run(mut self)
+    where
+        TChainProvider: Send + Sync + 'static,
+        TChainProvider::Tx: Send,
+        TCipherHost: Send + Sync + 'static,
+        TPropVerifier: Send + Sync + 'static,
+    {
+        let sale_ids = std::iter::once(None)
+            .chain(self.listings.keys().cloned().map(Some))
+            .collect::<Vec<_>>();
+        for sale_id in sale_ids {
+            if let Err(e) = self.refill_key_encryptions(sale_id) {
+                tracing::error!("error proving key encryptions ahead: {e}");
+            }
+        }
+
+        let mut from_buyers = self
+            .from_buyers
+            .take()
+            .expect("expected seller to run once");
+        let seller = Arc::new(self);
+        while let Some(msg) = from_buyers.next().await {
+            tokio::spawn(seller.clone().serve(msg));
+        }
+    }
+
+    /// Serves `msg` while holding the lock of the session it's addressed to, if any.
+    async fn serve(self: Arc<Self>, msg: SellerMsg)
+    where
+        TChainProvider: Send + Sync + 'static,
+        TChainProvider::Tx: Send,
+        TCipherHost: Send + Sync + 'static,
+        TPropVerifier: Send + Sync + 'static,
+    {
+        match msg {
+            SellerMsg::Step0 { sale_id, resp_tx } => {
+                let resp = match self.sale(sale_id.as_deref()) {
+                    Ok((cipher_host, ..)) => cipher_host.read().await,
+                    Err(e) => Err(e),
+                };
+                let _ = resp_tx.send(resp); // todo: DoS defense needed.
+            }
+            SellerMsg::Step1 {
+                sale_id,
+                address,
+                resp_tx,
+            } => {
+                let session = self.lock_session(sale_id.as_deref(), address).await;
+                // proving holds the thread for a while, so it's kept off the async workers.
+                let (seller, id) = (self.clone(), sale_id.clone());
+                let resp = spawn_blocking(move || seller.step1(address, id, None))
+                    .await
+                    .unwrap_or_else(|e| Err(anyhow!("error serving Step 1: {e}")));
+                if let Err(_) = resp_tx.send(resp) {
+                    self.forget_session(sale_id.clone(), address);
                 }
+                drop(session);
+                // buyer already has its response, so refilling holds up no one.
+                let seller = self.clone();
+                let _ = spawn_blocking(move || seller.refill_key_encryptions(sale_id)).await;
+            }
+            SellerMsg::Handshake {
+                sale_id,
+                address,
+                buyer_pk,
+                resp_tx,
+            } => {
+                let _session = self.lock_session(sale_id.as_deref(), address).await;
+                let (seller, id) = (self.clone(), sale_id.clone());
+                let resp = spawn_blocking(move || {
+                    public_key_from_bytes(buyer_pk)
+                        .and_then(|buyer_pk| seller.step1(address, id, Some(buyer_pk)))
+                })
+                .await
+                .unwrap_or_else(|e| Err(anyhow!("error serving handshake: {e}")));
+                if let Err(_) = resp_tx.send(resp) {
+                    self.forget_session(sale_id, address);
+                }
+            }
+            SellerMsg::Step3 {
+                sale_id,
+                pub_key,
+                enc_sig,
+                tx_params,
+                quote,
+                resp_tx,
+            } => {
+                let address = self.chain.address_from_pk(&pub_key);
+                let _session = self.lock_session(sale_id.as_deref(), address).await;
+                let session = (sale_id.clone(), address);
+                let _ = lock(&self.settling).insert(session.clone());
+                let resp = self
+                    .step3(sale_id, pub_key, enc_sig, tx_params, quote)
+                    .await;
+                let _ = lock(&self.settling).remove(&session);
+                let _ = resp_tx.send(resp);
+            }
+            SellerMsg::Quote { sale_id, resp_tx } => {
+                let _ = resp_tx.send(self.quote(sale_id).await);
+            }
+            SellerMsg::Status {
+                sale_id,
+                address,
+                resp_tx,
+            } => {
+                let _session = self.lock_session(sale_id.as_deref(), address).await;
+                let resp = find_session::<_, SellerSession>(
+                    &self.cfg.cache_dir,
+                    &seller_session_id(sale_id.as_deref(), address),
+                )
+                .map(|session| session.map(|s| (s.phase, s.tx_hash)));
+                let _ = resp_tx.send(resp);
+            }
+            SellerMsg::Receipt {
+                sale_id,
+                address,
+                resp_tx,
+            } => {
+                let _ = resp_tx.send(self.find_receipt(sale_id.as_deref(), address));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::lock;
+    use crate::cipher_host::EphemeralHost;
+    use crate::zk::{CircomParams, ProofBackend, VerifiableEncryption, ZkSampleEntries};
+    use crate::{
+        check_expiry, check_sessions_limit, encryption_rng, find_session, keypair_gen,
+        lock_expired, seller_session_id, verify_receipts, write_session, ChainProvider, CurveVar,
+        LocalWallet, ProjectiveCurve, Quote, Seller, SellerConfig, SellerMsg, SellerSession,
+        SessionPhase, SignedQuote, TxParams, ZkConfig, LOCK_TTL, RECEIPTS_FILE,
+    };
+    use async_trait::async_trait;
+    use chrono::{Duration, Utc};
+    use circuits::{ark_to_bytes, bytes_to_plaintext_chunks, encryption, EncryptCircuit};
+    use ecdsa_fun::Signature;
+    use ethers::prelude::{Address, H256, U256};
+    use futures::channel::oneshot;
+    use futures::SinkExt;
+    use secp256kfun::Point;
+    use std::path::PathBuf;
+
+    struct MockChain;
+
+    #[async_trait]
+    impl ChainProvider for MockChain {
+        type Tx = ();
+
+        async fn tx_params(&self, _: Address, _: Address, _: f64) -> anyhow::Result<TxParams> {
+            Ok(TxParams::default())
+        }
+
+        fn compose_tx(
+            &self,
+            _: Address,
+            _: Address,
+            _: f64,
+            _: &TxParams,
+        ) -> anyhow::Result<((), H256)> {
+            Ok(((), H256::zero()))
+        }
+
+        async fn sent_signed(&self, _tx: (), _sig: &Signature) -> anyhow::Result<H256> {
+            Ok(H256::zero())
+        }
+
+        async fn get_signature(&self, _hash: H256) -> anyhow::Result<Option<Signature>> {
+            Ok(None)
+        }
+
+        async fn block_number(&self) -> anyhow::Result<u64> {
+            Ok(0)
+        }
+
+        fn address_from_pk(&self, _pk: &Point) -> Address {
+            Address::zero()
+        }
+
+        async fn nonce_mined(&self, _: Address, _: U256) -> anyhow::Result<bool> {
+            Ok(false)
+        }
+
+        async fn send_own(
+            &self,
+            _: &LocalWallet,
+            _: Address,
+            _: f64,
+            _: Option<&TxParams>,
+        ) -> anyhow::Result<H256> {
+            Ok(H256::zero())
+        }
+    }
+
+    fn sale(byte: u8) -> VerifiableEncryption {
+        VerifiableEncryption {
+            ciphertext: vec![byte; 32 * 4],
+            proof_of_encryption: vec![byte; 192],
+            proofs_of_property: vec![],
+            plaintext_commitment: None,
+        }
+    }
+
+    fn config(build_dir: PathBuf, observer: bool) -> SellerConfig {
+        SellerConfig {
+            price: 0.1,
+            expires_at: None,
+            max_prove_time: None,
+            min_free_memory: None,
+            cache_dir: build_dir.join("cache"),
+            zk: ZkConfig {
+                prop_verifier_dir: build_dir.clone(),
+                data_encryption_dir: build_dir.clone(),
+                data_encryption_limit: 1,
+                key_encryption_dir: build_dir.clone(),
+                circom_params: CircomParams {
+                    plaintext_field_name: "values".to_string(),
+                    wasm_path: build_dir.join("circuit.wasm"),
+                    r1cs_path: build_dir.join("circuit.r1cs"),
+                },
+                backend: ProofBackend::Groth16,
+                predicate: None,
+            },
+            observer,
+            deterministic_seed: None,
+            chain_id: 31337,
+            precomputed_key_encryptions: 0,
+            max_sessions: None,
+            lock_ttl: LOCK_TTL,
+        }
+    }
+
+    #[test]
+    fn test_deterministic_encryption() {
+        let encrypt = |seed| {
+            let mut rng = encryption_rng(seed);
+            let (_, pk) = EncryptCircuit::<ProjectiveCurve, CurveVar>::keygen(&mut rng).unwrap();
+            let msg = bytes_to_plaintext_chunks::<ProjectiveCurve, _>(b"same input").unwrap();
+            let params = encryption::Parameters::default_multi(msg.len());
+            let circuit =
+                EncryptCircuit::<ProjectiveCurve, CurveVar>::new(pk, msg, params, &mut rng);
+            ark_to_bytes(circuit.unwrap().resulted_ciphertext).unwrap()
+        };
+
+        assert_eq!(encrypt(Some(42)), encrypt(Some(42)));
+        assert_ne!(encrypt(Some(42)), encrypt(Some(43)));
+        assert_ne!(encrypt(None), encrypt(None));
+    }
+
+    #[tokio::test]
+    async fn test_serve_multiple_sales() {
+        let build_dir = std::env::temp_dir().join("zkcp_test_multi_sale");
+        let (mut seller, to_seller) = Seller::new(
+            config(build_dir.clone(), false),
+            MockChain,
+            EphemeralHost::new(),
+            ZkSampleEntries::new(&build_dir, 1),
+            LocalWallet::new().unwrap(),
+        )
+        .unwrap();
+
+        for (id, byte) in [("first", 1), ("second", 2)] {
+            seller
+                .list_sale(
+                    id.to_string(),
+                    EphemeralHost::new(),
+                    sale(byte),
+                    Some(vec![byte]),
+                    0.2,
+                )
+                .await
+                .unwrap();
+        }
+        assert!(seller
+            .list_sale(
+                "first".to_string(),
+                EphemeralHost::new(),
+                sale(3),
+                Some(vec![3]),
+                0.2
+            )
+            .await
+            .is_err());
+        tokio::spawn(seller.run());
+
+        let buyers = [("first", 1), ("second", 2), ("third", 0)].map(|(id, byte)| {
+            let mut to_seller = to_seller.clone();
+            async move {
+                let (resp_tx, resp_rx) = oneshot::channel();
+                let sale_id = Some(id.to_string());
+                to_seller
+                    .send(SellerMsg::Step0 { sale_id, resp_tx })
+                    .await
+                    .unwrap();
+                (byte, resp_rx.await.unwrap())
+            }
+        });
+
+        for (byte, resp) in futures::future::join_all(buyers).await {
+            match byte {
+                0 => assert!(resp.is_err()),
+                _ => assert_eq!(resp.unwrap().hash(), sale(byte).hash()),
             }
         }
     }
+
+    #[tokio::test]
+    async fn test_quoted_price() {
+        let build_dir = std::env::temp_dir().join("zkcp_test_quote");
+        let wallet = LocalWallet::new().unwrap();
+        let seller_pk = wallet.pub_key().clone();
+        let (mut seller, _) = Seller::new(
+            config(build_dir.clone(), false),
+            MockChain,
+            EphemeralHost::new(),
+            ZkSampleEntries::new(&build_dir, 1),
+            wallet,
+        )
+        .unwrap();
+        seller
+            .list_sale(
+                "photos".to_string(),
+                EphemeralHost::new(),
+                sale(1),
+                Some(vec![1]),
+                0.2,
+            )
+            .await
+            .unwrap();
+        let sale_hash = sale(1).hash();
+
+        let quote = seller.quote(Some("photos".to_string())).await.unwrap();
+        quote.verify(&seller_pk).unwrap();
+        assert_eq!(quote.quote.price, 0.2);
+        assert_eq!(quote.quote.sale_hash, sale_hash);
+        assert!(seller.quote(Some("music".to_string())).await.is_err());
+
+        let settled = |quote: Option<&SignedQuote>| {
+            seller.settled_price(Some("photos"), &sale_hash, 0.2, quote)
+        };
+        assert_eq!(settled(None).unwrap(), 0.2);
+        assert_eq!(settled(Some(&quote)).unwrap(), 0.2);
+        // seller settles at any price it has signed, but not at one buyer edited in.
+        let repriced = Quote {
+            price: 0.3,
+            ..quote.quote.clone()
+        }
+        .sign(seller.wallet.sec_key());
+        assert_eq!(settled(Some(&repriced)).unwrap(), 0.3);
+        let mut tampered = quote.clone();
+        tampered.quote.price = 0.01;
+        assert!(settled(Some(&tampered)).is_err());
+        assert!(seller
+            .settled_price(None, &sale_hash, 0.1, Some(&quote))
+            .is_err());
+    }
+
+    #[test]
+    fn test_payout_address() {
+        let build_dir = std::env::temp_dir().join("zkcp_test_payout_address");
+        let (seller, _) = Seller::new(
+            config(build_dir.clone(), false),
+            MockChain,
+            EphemeralHost::new(),
+            ZkSampleEntries::new(&build_dir, 1),
+            LocalWallet::new().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(seller.payout_address(), Address::zero());
+
+        let payout = Address::repeat_byte(7);
+        assert_eq!(seller.with_payout_address(payout).payout_address(), payout);
+    }
+
+    #[test]
+    fn test_receipt_recorded() {
+        let build_dir = std::env::temp_dir().join("zkcp_test_seller_receipt");
+        let _ = std::fs::remove_dir_all(build_dir.join("cache"));
+        let (seller, _) = Seller::new(
+            config(build_dir.clone(), false),
+            MockChain,
+            EphemeralHost::new(),
+            ZkSampleEntries::new(&build_dir, 1),
+            LocalWallet::new().unwrap(),
+        )
+        .unwrap();
+        let (one_time_sk, _) = keypair_gen();
+        let (_, buyer_pk) = keypair_gen();
+        assert!(seller
+            .find_receipt(None, Address::zero())
+            .unwrap()
+            .is_none());
+
+        seller
+            .record_receipt(
+                None,
+                &sale(1),
+                0.1,
+                vec!["00".repeat(32)],
+                &one_time_sk,
+                &buyer_pk,
+            )
+            .unwrap();
+        let entry = seller.find_receipt(None, Address::zero()).unwrap().unwrap();
+        assert_eq!(entry.receipt.sale_hash, hex::encode(sale(1).hash()));
+        assert_eq!(entry.receipt.price, 0.1);
+        assert!(entry.buyer_signature.is_none());
+        assert!(seller
+            .find_receipt(Some("photos"), Address::zero())
+            .unwrap()
+            .is_none());
+        verify_receipts(build_dir.join("cache").join(RECEIPTS_FILE)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_observer_refuses_secrets() {
+        let build_dir = std::env::temp_dir().join("zkcp_test_observer");
+        let (mut seller, mut to_seller) = Seller::new(
+            config(build_dir.clone(), true),
+            MockChain,
+            EphemeralHost::new(),
+            ZkSampleEntries::new(&build_dir, 1),
+            LocalWallet::new().unwrap(),
+        )
+        .unwrap();
+        seller.host_sale(sale(1)).await.unwrap();
+        assert!(seller.step0_setup(vec![1]).await.is_err());
+        tokio::spawn(seller.run());
+
+        let (resp_tx, resp_rx) = oneshot::channel();
+        to_seller
+            .send(SellerMsg::Step0 {
+                sale_id: None,
+                resp_tx,
+            })
+            .await
+            .unwrap();
+        assert_eq!(resp_rx.await.unwrap().unwrap().hash(), sale(1).hash());
+
+        let (resp_tx, resp_rx) = oneshot::channel();
+        to_seller
+            .send(SellerMsg::Step1 {
+                sale_id: None,
+                address: Address::zero(),
+                resp_tx,
+            })
+            .await
+            .unwrap();
+        let err = resp_rx.await.unwrap().err().unwrap();
+        assert!(err.to_string().contains("observer mode"), "{err}");
+    }
+
+    #[test]
+    fn test_purchase_before_expiry() {
+        let now = Utc::now();
+
+        assert!(check_expiry(Some(now + Duration::hours(1)), now).is_ok());
+        assert!(check_expiry(None, now).is_ok());
+    }
+
+    #[test]
+    fn test_purchase_after_expiry() {
+        let now = Utc::now();
+
+        assert!(check_expiry(Some(now - Duration::hours(1)), now).is_err());
+        assert!(check_expiry(Some(now), now).is_err());
+    }
+
+    #[test]
+    fn test_sessions_limit() {
+        assert!(check_sessions_limit(None, 100, false).is_ok());
+        assert!(check_sessions_limit(Some(2), 1, false).is_ok());
+        assert!(check_sessions_limit(Some(2), 2, false).is_err());
+        // buyers resuming their purchase aren't counted twice.
+        assert!(check_sessions_limit(Some(2), 2, true).is_ok());
+    }
+
+    #[test]
+    fn test_lock_expiry() {
+        let now = Utc::now();
+        let ttl = std::time::Duration::from_secs(3600);
+
+        assert!(!lock_expired(now - Duration::minutes(59), ttl, now));
+        assert!(lock_expired(now - Duration::hours(1), ttl, now));
+    }
+
+    #[test]
+    fn test_stale_locked_session_not_counted() {
+        let build_dir = std::env::temp_dir().join("zkcp_test_stale_lock");
+        let cache_dir = build_dir.join("cache");
+        let _ = std::fs::remove_dir_all(&cache_dir);
+        let (one_time_sk, _) = keypair_gen();
+        let now = Utc::now();
+        let lock_session = |byte: u8, locked_at: Option<String>| {
+            let buyer = Address::repeat_byte(byte);
+            let session = SellerSession {
+                phase: SessionPhase::Locked,
+                sale_id: None,
+                buyer,
+                one_time_sk: one_time_sk.to_string(),
+                trade_nonce: None,
+                tx_hash: None,
+                locked_at,
+                key_encryption: None,
+            };
+            let id = seller_session_id(None, buyer);
+            write_session(&cache_dir, &id, &session).unwrap();
+            id
+        };
+        let fresh = lock_session(1, Some(now.to_rfc3339()));
+        let stale = lock_session(2, Some((now - Duration::days(2)).to_rfc3339()));
+        let unstamped = lock_session(3, None);
+
+        let (seller, _) = Seller::new(
+            SellerConfig {
+                max_sessions: Some(1),
+                ..config(build_dir.clone(), false)
+            },
+            MockChain,
+            EphemeralHost::new(),
+            ZkSampleEntries::new(&build_dir, 1),
+            LocalWallet::new().unwrap(),
+        )
+        .unwrap();
+        let session = |id: &str| find_session::<_, SellerSession>(&cache_dir, id).unwrap();
+        assert!(session(&fresh).is_some());
+        assert!(session(&stale).is_none());
+        assert!(session(&unstamped).is_none());
+        assert_eq!(lock(&seller.one_time_keys).len(), 1);
+
+        // locked sessions leave the slot to the next buyer, settling ones take it.
+        let next_buyer = Address::repeat_byte(4);
+        seller.ensure_session_slot(&None, next_buyer, now).unwrap();
+        let _ = lock(&seller.settling).insert((None, Address::repeat_byte(1)));
+        assert!(seller.ensure_session_slot(&None, next_buyer, now).is_err());
+
+        // keys locked past the ttl are dropped while serving too.
+        let later = now + Duration::days(2);
+        assert!(seller
+            .ensure_session_slot(&None, next_buyer, later)
+            .is_err());
+        assert!(lock(&seller.one_time_keys).is_empty());
+    }
 }
@@ -0,0 +1,218 @@
+use crate::zk::VerifiableEncryption;
+use crate::{SaleId, SignedQuote, TxParams};
+use anyhow::anyhow;
+use ethers::prelude::{Address, H256};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SESSIONS_DIR: &str = "sessions";
+
+/// How far the purchase got before it was interrupted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionPhase {
+    /// One-time key was issued and payment is authorized to it with adaptor signature.
+    Locked,
+    /// Payment was broadcast, the data decryption key can be recovered from its signature.
+    Paid,
+    /// Payment is confirmed deep enough to be final, see [`crate::EventWatcher`].
+    Settled,
+}
+
+/// Buyer's side of the purchase, enough to settle it and decrypt the data without starting over.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BuyerSession {
+    pub phase: SessionPhase,
+    pub sale: VerifiableEncryption,
+    pub price: f64,
+    /// Hex-encoded one-time key encrypted by the seller at Step 1.
+    pub encrypted_key: String,
+    pub one_time_pk: String,
+    pub encrypted_sig: String,
+    /// Hex-encoded ElGamal secret key of the handshake, for interactive sales.
+    #[serde(default)]
+    pub handshake_sk: Option<String>,
+    /// Quote the price was agreed on with, if any, sent back to the seller at Step 3.
+    #[serde(default)]
+    pub quote: Option<SignedQuote>,
+    /// Nonce and gas the payment is pinned to, refund is sent at, see [`crate::revoke_payment`].
+    #[serde(default)]
+    pub tx_params: Option<TxParams>,
+    /// RFC 3339 time the payment was locked at, refund is only sent a grace period after.
+    #[serde(default)]
+    pub locked_at: Option<String>,
+    #[serde(default)]
+    pub tx_hash: Option<H256>,
+}
+
+/// Seller's side of the purchase, keeping the one-time key buyer's payment is encrypted to.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SellerSession {
+    pub phase: SessionPhase,
+    #[serde(default)]
+    pub sale_id: Option<SaleId>,
+    pub buyer: Address,
+    pub one_time_sk: String,
+    /// Hex-encoded nonce the one-time key is derived with from seller's wallet key,
+    /// see [`crate::zk::trade_key_rng`], so that it can be recovered without `one_time_sk`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trade_nonce: Option<String>,
+    #[serde(default)]
+    pub tx_hash: Option<H256>,
+    /// RFC 3339 time the one-time key was locked at, see [`crate::SellerConfig::lock_ttl`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locked_at: Option<String>,
+    /// Step 1 response of the handshake, re-served to the buyer retrying it rather than proven again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_encryption: Option<CachedKeyEncryption>,
+}
+
+/// Data decryption key encrypted at Step 1 to `buyer_pk` joined with the one-time key of the session.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedKeyEncryption {
+    /// Hex-encoded ElGamal public key buyer sent with the handshake.
+    pub buyer_pk: String,
+    pub ciphertext: String,
+    pub proof_of_encryption: String,
+}
+
+/// Short id of the session for the purchase identified with `key`, see [`crate::payment_idempotency_key`].
+pub fn session_id(key: &[u8; 32]) -> String {
+    hex::encode(&key[..8])
+}
+
+/// Id of the seller's session with `buyer` for the sale with `sale_id`, the default one if none.
+pub fn seller_session_id(sale_id: Option<&str>, buyer: Address) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(buyer.as_bytes());
+    hasher.update(sale_id.unwrap_or_default());
+    let key: [u8; 32] = hasher.finalize().into();
+    session_id(&key)
+}
+
+fn session_path<P: AsRef<Path>>(cache_dir: P, id: &str) -> PathBuf {
+    cache_dir
+        .as_ref()
+        .join(SESSIONS_DIR)
+        .join(format!("{id}.json"))
+}
+
+pub fn write_session<P: AsRef<Path>, S: Serialize>(
+    cache_dir: P,
+    id: &str,
+    session: &S,
+) -> anyhow::Result<()> {
+    let dir = cache_dir.as_ref().join(SESSIONS_DIR);
+    fs::create_dir_all(&dir).map_err(|e| anyhow!("error creating sessions dir: {e}"))?;
+    let json = serde_json::to_vec(session).expect("expected session to marshal to json");
+    fs::write(session_path(cache_dir, id), json)
+        .map_err(|e| anyhow!("error persisting session: {e}"))
+}
+
+pub fn read_session<P: AsRef<Path>, S: DeserializeOwned>(
+    cache_dir: P,
+    id: &str,
+) -> anyhow::Result<S> {
+    let json = fs::read(session_path(cache_dir, id))
+        .map_err(|e| anyhow!("error reading session {id}: {e}"))?;
+    serde_json::from_slice(&json).map_err(|e| anyhow!("error decoding session {id}: {e}"))
+}
+
+/// Reads session with `id`, none if it was never saved.
+pub fn find_session<P: AsRef<Path>, S: DeserializeOwned>(
+    cache_dir: P,
+    id: &str,
+) -> anyhow::Result<Option<S>> {
+    match session_path(&cache_dir, id).exists() {
+        true => read_session(cache_dir, id).map(Some),
+        false => Ok(None),
+    }
+}
+
+pub fn remove_session<P: AsRef<Path>>(cache_dir: P, id: &str) -> anyhow::Result<()> {
+    fs::remove_file(session_path(cache_dir, id))
+        .map_err(|e| anyhow!("error removing session {id}: {e}"))
+}
+
+/// Reads all sessions persisted in `cache_dir` along with their ids, none if there are no sessions yet.
+pub fn read_sessions<P: AsRef<Path>, S: DeserializeOwned>(
+    cache_dir: P,
+) -> anyhow::Result<Vec<(String, S)>> {
+    let entries = match fs::read_dir(cache_dir.as_ref().join(SESSIONS_DIR)) {
+        Ok(entries) => entries,
+        Err(_) => return Ok(vec![]),
+    };
+
+    let mut sessions = vec![];
+    for entry in entries {
+        let path = entry
+            .map_err(|e| anyhow!("error listing sessions: {e}"))?
+            .path();
+        if let Some(id) = path.file_stem().and_then(|s| s.to_str()) {
+            sessions.push((id.to_string(), read_session(&cache_dir, id)?));
+        }
+    }
+    Ok(sessions)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        find_session, read_session, read_sessions, remove_session, seller_session_id,
+        write_session, CachedKeyEncryption, SellerSession, SessionPhase,
+    };
+    use ethers::prelude::{Address, H256};
+    use std::fs;
+
+    #[test]
+    fn test_sessions_roundtrip() {
+        let cache_dir = std::env::temp_dir().join("zkcp_test_sessions");
+        let _ = fs::remove_dir_all(&cache_dir);
+        assert!(read_sessions::<_, SellerSession>(&cache_dir)
+            .unwrap()
+            .is_empty());
+
+        let id = seller_session_id(Some("photos"), Address::zero());
+        assert_eq!(id.len(), 16);
+        assert_ne!(id, seller_session_id(None, Address::zero()));
+        let mut session = SellerSession {
+            phase: SessionPhase::Locked,
+            sale_id: Some("photos".to_string()),
+            buyer: Address::zero(),
+            one_time_sk: "01".repeat(32),
+            trade_nonce: Some("02".repeat(32)),
+            tx_hash: None,
+            locked_at: None,
+            key_encryption: Some(CachedKeyEncryption {
+                buyer_pk: "02".repeat(32),
+                ciphertext: "03".repeat(64),
+                proof_of_encryption: "04".repeat(192),
+            }),
+        };
+        write_session(&cache_dir, &id, &session).unwrap();
+        let reloaded: SellerSession = read_session(&cache_dir, &id).unwrap();
+        assert_eq!(reloaded.key_encryption, session.key_encryption);
+        assert_eq!(reloaded.trade_nonce, session.trade_nonce);
+
+        session.phase = SessionPhase::Paid;
+        session.tx_hash = Some(H256::repeat_byte(1));
+        write_session(&cache_dir, &id, &session).unwrap();
+        let reloaded: SellerSession = read_session(&cache_dir, &id).unwrap();
+        assert_eq!(reloaded.phase, SessionPhase::Paid);
+        assert_eq!(reloaded.tx_hash, session.tx_hash);
+
+        let sessions = read_sessions::<_, SellerSession>(&cache_dir).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].0, id);
+
+        remove_session(&cache_dir, &id).unwrap();
+        assert!(read_session::<_, SellerSession>(&cache_dir, &id).is_err());
+        assert!(find_session::<_, SellerSession>(&cache_dir, &id)
+            .unwrap()
+            .is_none());
+        let _ = fs::remove_dir_all(cache_dir);
+    }
+}
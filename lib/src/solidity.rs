@@ -0,0 +1,205 @@
+use crate::{CurveTag, PointJson, TaggedCurve, VerifyingKeyJson};
+use anyhow::anyhow;
+use ark_ec::PairingEngine;
+use ark_groth16::VerifyingKey;
+use std::fs;
+use std::path::Path;
+
+/// Groth16 verifier calling into the `ecAdd`, `ecMul` and `ecPairing` precompiles at `0x06..0x08`.
+/// G2 points are laid out as the precompiles expect them, imaginary coefficient first.
+const VERIFIER_TEMPLATE: &str = r#"// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+/// Groth16 verifier generated by `zkcp compile`.
+/// Points of `b` are passed as `[[x.c1, x.c0], [y.c1, y.c0]]`.
+contract Verifier {
+    uint256 constant PRIME_Q = 0x30644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd47;
+    uint256 constant SNARK_SCALAR_FIELD = 0x30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001;
+
+$CONSTANTS
+    function verifyProof(
+        uint256[2] calldata a,
+        uint256[2][2] calldata b,
+        uint256[2] calldata c,
+        uint256[$INPUT_COUNT] calldata input
+    ) external view returns (bool) {
+        uint256 x = GAMMA_ABC_0_X;
+        uint256 y = GAMMA_ABC_0_Y;
+$ACCUMULATE
+        uint256[24] memory p;
+        p[0] = a[0];
+        p[1] = (PRIME_Q - (a[1] % PRIME_Q)) % PRIME_Q;
+        p[2] = b[0][0];
+        p[3] = b[0][1];
+        p[4] = b[1][0];
+        p[5] = b[1][1];
+        p[6] = ALPHA_X;
+        p[7] = ALPHA_Y;
+        p[8] = BETA_X1;
+        p[9] = BETA_X0;
+        p[10] = BETA_Y1;
+        p[11] = BETA_Y0;
+        p[12] = x;
+        p[13] = y;
+        p[14] = GAMMA_X1;
+        p[15] = GAMMA_X0;
+        p[16] = GAMMA_Y1;
+        p[17] = GAMMA_Y0;
+        p[18] = c[0];
+        p[19] = c[1];
+        p[20] = DELTA_X1;
+        p[21] = DELTA_X0;
+        p[22] = DELTA_Y1;
+        p[23] = DELTA_Y0;
+
+        uint256[1] memory out;
+        bool success;
+        assembly {
+            success := staticcall(gas(), 0x08, p, 768, out, 0x20)
+        }
+        return success && out[0] == 1;
+    }
+
+    function accumulate(uint256 x, uint256 y, uint256 px, uint256 py, uint256 s)
+        internal
+        view
+        returns (uint256, uint256)
+    {
+        require(s < SNARK_SCALAR_FIELD, "input is out of the scalar field");
+        uint256[3] memory mulInput;
+        mulInput[0] = px;
+        mulInput[1] = py;
+        mulInput[2] = s;
+        uint256[4] memory addInput;
+        addInput[0] = x;
+        addInput[1] = y;
+        bool success;
+        assembly {
+            success := staticcall(gas(), 0x07, mulInput, 0x60, add(addInput, 0x40), 0x40)
+        }
+        require(success, "ecMul failed");
+        uint256[2] memory sum;
+        assembly {
+            success := staticcall(gas(), 0x06, addInput, 0x80, sum, 0x40)
+        }
+        require(success, "ecAdd failed");
+        return (sum[0], sum[1]);
+    }
+}
+"#;
+
+/// Fails unless `E` is the curve of the EVM pairing precompiles.
+pub fn ensure_evm_pairing<E: TaggedCurve>() -> anyhow::Result<()> {
+    match E::CURVE_TAG {
+        CurveTag::Bn254 => Ok(()),
+        curve => Err(anyhow!(
+            "Solidity verifier needs BN254 curve of the EVM pairing precompiles, but keys are for {curve}"
+        )),
+    }
+}
+
+/// Renders Solidity contract checking proofs against `vk` on-chain.
+pub fn solidity_verifier<E: PairingEngine + TaggedCurve>(
+    vk: &VerifyingKey<E>,
+) -> anyhow::Result<String> {
+    ensure_evm_pairing::<E>()?;
+    let json = VerifyingKeyJson::new(vk)?;
+
+    let mut constants = String::new();
+    g1_constants(&mut constants, "ALPHA", &json.alpha_g1);
+    g2_constants(&mut constants, "BETA", &json.beta_g2);
+    g2_constants(&mut constants, "GAMMA", &json.gamma_g2);
+    g2_constants(&mut constants, "DELTA", &json.delta_g2);
+    for (i, point) in json.gamma_abc_g1.iter().enumerate() {
+        g1_constants(&mut constants, &format!("GAMMA_ABC_{i}"), point);
+    }
+
+    let accumulate = (1..json.gamma_abc_g1.len())
+        .map(|i| {
+            format!(
+                "        (x, y) = accumulate(x, y, GAMMA_ABC_{i}_X, GAMMA_ABC_{i}_Y, input[{}]);\n",
+                i - 1
+            )
+        })
+        .collect::<String>();
+
+    Ok(VERIFIER_TEMPLATE
+        .replace("$CONSTANTS", &constants)
+        .replace("$INPUT_COUNT", &(json.gamma_abc_g1.len() - 1).to_string())
+        .replace("$ACCUMULATE", &accumulate))
+}
+
+pub fn write_solidity_verifier<E: PairingEngine + TaggedCurve, P: AsRef<Path>>(
+    vk: &VerifyingKey<E>,
+    path: P,
+) -> anyhow::Result<()> {
+    fs::write(path, solidity_verifier(vk)?)
+        .map_err(|e| anyhow!("error writing solidity verifier: {e}"))
+}
+
+fn g1_constants(out: &mut String, name: &str, point: &PointJson) {
+    out.push_str(&format!(
+        "    uint256 constant {name}_X = {};\n",
+        point.x[0]
+    ));
+    out.push_str(&format!(
+        "    uint256 constant {name}_Y = {};\n",
+        point.y[0]
+    ));
+}
+
+fn g2_constants(out: &mut String, name: &str, point: &PointJson) {
+    for (coord, values) in [("X", &point.x), ("Y", &point.y)] {
+        out.push_str(&format!(
+            "    uint256 constant {name}_{coord}1 = {};\n",
+            values[1]
+        ));
+        out.push_str(&format!(
+            "    uint256 constant {name}_{coord}0 = {};\n",
+            values[0]
+        ));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ensure_evm_pairing, solidity_verifier, PairingEngine, VerifyingKeyJson};
+    use ark_bn254::{Bn254, Fr};
+    use ark_groth16::Groth16;
+    use ark_relations::lc;
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+    use ark_snark::CircuitSpecificSetupSNARK;
+
+    struct SquareCircuit;
+
+    impl ConstraintSynthesizer<Fr> for SquareCircuit {
+        fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+            let a = cs.new_witness_variable(|| Ok(Fr::from(3u64)))?;
+            let b = cs.new_input_variable(|| Ok(Fr::from(9u64)))?;
+            let c = cs.new_input_variable(|| Ok(Fr::from(1u64)))?;
+            cs.enforce_constraint(lc!() + a, lc!() + a, lc!() + b)?;
+            cs.enforce_constraint(lc!() + c, lc!() + c, lc!() + c)
+        }
+    }
+
+    #[test]
+    fn test_solidity_verifier() {
+        let (_, vk) = Groth16::<Bn254>::setup(SquareCircuit, &mut rand::thread_rng()).unwrap();
+        let source = solidity_verifier(&vk).unwrap();
+
+        let gamma_abc = (0..)
+            .take_while(|i| source.contains(&format!("uint256 constant GAMMA_ABC_{i}_X")))
+            .count();
+        assert_eq!(gamma_abc, 3);
+        assert_eq!(source.matches("= accumulate(").count(), 2);
+        assert!(source.contains("uint256[2] calldata input"));
+        assert!(!source.contains('$'));
+
+        let json = VerifyingKeyJson::new(&vk).unwrap();
+        assert!(source.contains(&format!("ALPHA_X = {};", json.alpha_g1.x[0])));
+        assert!(source.contains(&format!("DELTA_Y1 = {};", json.delta_g2.y[1])));
+
+        let err = ensure_evm_pairing::<PairingEngine>().unwrap_err();
+        assert!(err.to_string().contains("BLS12-381"), "{err}");
+    }
+}
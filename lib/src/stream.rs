@@ -0,0 +1,164 @@
+use crate::SaleId;
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+const STREAMS_DIR: &str = "streams";
+
+/// Id segment `index` of a streamed sale is listed under, counting from 1.
+pub fn segment_id(index: usize) -> SaleId {
+    format!("segment-{index}")
+}
+
+/// Splits `data` into `count` contiguous segments, sold each as sale of its own.
+/// Sizes differ by one entry at most, so that none is left empty.
+pub fn split_segments(data: &[u8], count: usize) -> anyhow::Result<Vec<Vec<u8>>> {
+    if count == 0 || count > data.len() {
+        return Err(anyhow!(
+            "can't split {} entries into {count} segments",
+            data.len()
+        ));
+    }
+
+    let (size, rest) = (data.len() / count, data.len() % count);
+    let mut segments = Vec::with_capacity(count);
+    let mut start = 0;
+    for i in 0..count {
+        let end = start + size + usize::from(i < rest);
+        segments.push(data[start..end].to_vec());
+        start = end;
+    }
+    Ok(segments)
+}
+
+/// Segments buyer pays for, given as `<first>..<last>` including both, or as a single segment.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SegmentRange(pub RangeInclusive<usize>);
+
+impl FromStr for SegmentRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |index: &str| {
+            index
+                .trim()
+                .parse::<usize>()
+                .ok()
+                .filter(|i| *i > 0)
+                .ok_or(anyhow!("segments are counted from 1, got {s}"))
+        };
+        let (first, last) = match s.split_once("..") {
+            Some((first, last)) => (parse(first)?, parse(last)?),
+            None => (parse(s)?, parse(s)?),
+        };
+        if first > last {
+            return Err(anyhow!("segment range {s} is empty"));
+        }
+        Ok(Self(first..=last))
+    }
+}
+
+/// Segments of a streamed sale buyer has paid for and saved, so that buying one range after
+/// another, or again after being interrupted, picks up after the last paid segment.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StreamProgress {
+    /// Hashes of payment transactions of each settled segment.
+    pub settled: BTreeMap<usize, Vec<String>>,
+}
+
+impl StreamProgress {
+    /// Reads progress of the stream sold by `seller`, none made if it was never saved.
+    pub fn read<P: AsRef<Path>>(cache_dir: P, seller: &str) -> anyhow::Result<Self> {
+        let path = progress_path(cache_dir, seller);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let json = fs::read(path).map_err(|e| anyhow!("error reading stream progress: {e}"))?;
+        serde_json::from_slice(&json).map_err(|e| anyhow!("error decoding stream progress: {e}"))
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, cache_dir: P, seller: &str) -> anyhow::Result<()> {
+        let dir = cache_dir.as_ref().join(STREAMS_DIR);
+        fs::create_dir_all(&dir).map_err(|e| anyhow!("error creating streams dir: {e}"))?;
+        let json = serde_json::to_vec(self).expect("expected stream progress to marshal to json");
+        fs::write(progress_path(cache_dir, seller), json)
+            .map_err(|e| anyhow!("error persisting stream progress: {e}"))
+    }
+
+    pub fn is_settled(&self, index: usize) -> bool {
+        self.settled.contains_key(&index)
+    }
+
+    pub fn last_settled(&self) -> Option<usize> {
+        self.settled.keys().next_back().copied()
+    }
+}
+
+fn progress_path<P: AsRef<Path>>(cache_dir: P, seller: &str) -> PathBuf {
+    let digest = Sha256::digest(seller.as_bytes());
+    cache_dir
+        .as_ref()
+        .join(STREAMS_DIR)
+        .join(format!("{}.json", hex::encode(&digest[..8])))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{segment_id, split_segments, SegmentRange, StreamProgress};
+    use std::fs;
+
+    #[test]
+    fn test_split_segments() {
+        let data = (0..10).collect::<Vec<u8>>();
+        let segments = split_segments(&data, 4).unwrap();
+        assert_eq!(
+            segments.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![3, 3, 2, 2]
+        );
+        assert_eq!(segments.concat(), data);
+        // ceil-sized chunks would make 5 segments of 2 entries here.
+        assert_eq!(split_segments(&data, 6).unwrap().len(), 6);
+
+        assert!(split_segments(&data, 0).is_err());
+        assert!(split_segments(&data, 11).is_err());
+        assert_eq!(segment_id(1), "segment-1");
+    }
+
+    #[test]
+    fn test_parse_segment_range() {
+        assert_eq!("1..5".parse::<SegmentRange>().unwrap().0, 1..=5);
+        assert_eq!("3".parse::<SegmentRange>().unwrap().0, 3..=3);
+        for bad in ["0..2", "5..1", "1..", "a..b"] {
+            assert!(bad.parse::<SegmentRange>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_stream_progress_roundtrip() {
+        let cache_dir = std::env::temp_dir().join("zkcp_test_streams");
+        let _ = fs::remove_dir_all(&cache_dir);
+        let seller = "http://localhost:8000";
+        let mut progress = StreamProgress::read(&cache_dir, seller).unwrap();
+        assert_eq!(progress.last_settled(), None);
+
+        let _ = progress.settled.insert(1, vec!["aa".to_string()]);
+        let _ = progress.settled.insert(2, vec!["bb".to_string()]);
+        progress.write(&cache_dir, seller).unwrap();
+
+        let read = StreamProgress::read(&cache_dir, seller).unwrap();
+        assert_eq!(read, progress);
+        assert!(read.is_settled(2) && !read.is_settled(3));
+        assert_eq!(read.last_settled(), Some(2));
+        // progress is kept per seller.
+        assert_eq!(
+            StreamProgress::read(&cache_dir, "http://localhost:8001").unwrap(),
+            StreamProgress::default()
+        );
+        let _ = fs::remove_dir_all(&cache_dir);
+    }
+}
@@ -0,0 +1,84 @@
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+use sysinfo::{ProcessExt, System, SystemExt};
+
+/// Timings and resource stats of a sell or buy run, emitted as a single JSON document at its end.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunSummary {
+    /// Wall-clock seconds spent in each phase of the run.
+    pub phases: BTreeMap<String, f64>,
+    /// Highest resident memory of the process observed at phase boundaries, in bytes.
+    pub peak_mem: u64,
+    pub chunk_count: usize,
+    pub proof_count: usize,
+    pub bytes_transferred: u64,
+}
+
+impl RunSummary {
+    /// Records phase `name` as lasting since `started`, sampling the memory in use on the way.
+    pub fn record_phase<S: Into<String>>(&mut self, name: S, started: Instant) {
+        let elapsed = started.elapsed().as_secs_f64();
+        *self.phases.entry(name.into()).or_default() += elapsed;
+        self.peak_mem = self.peak_mem.max(process_memory());
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let json = serde_json::to_vec_pretty(self).expect("expected summary to marshal to json");
+        fs::write(path, json).map_err(|e| anyhow!("error writing run summary: {e}"))
+    }
+}
+
+/// Resident memory of the current process in bytes, zero where it can't be told.
+fn process_memory() -> u64 {
+    let pid = match sysinfo::get_current_pid() {
+        Ok(pid) => pid,
+        Err(_) => return 0,
+    };
+    let mut system = System::new();
+    system.refresh_process(pid);
+    system.process(pid).map_or(0, |p| p.memory())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::RunSummary;
+    use std::fs;
+    use std::time::Instant;
+
+    #[test]
+    fn test_summary_written() {
+        let mut summary = RunSummary::default();
+        for phase in ["download", "verify", "decrypt"] {
+            let started = Instant::now();
+            let _work = vec![0u8; 1 << 20];
+            summary.record_phase(phase, started);
+        }
+        summary.chunk_count = 16;
+        summary.proof_count = 3;
+        summary.bytes_transferred = 4096;
+
+        let path = std::env::temp_dir().join("zkcp_test_summary.json");
+        summary.write(&path).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+
+        for key in [
+            "phases",
+            "peak_mem",
+            "chunk_count",
+            "proof_count",
+            "bytes_transferred",
+        ] {
+            assert!(json.get(key).is_some(), "summary is missing {key}");
+        }
+        for phase in ["download", "verify", "decrypt"] {
+            assert!(json["phases"][phase].as_f64().unwrap() >= 0.0);
+        }
+        assert_eq!(json["chunk_count"], 16);
+        assert_eq!(json["bytes_transferred"], 4096);
+        assert!(json["peak_mem"].as_u64().unwrap() > 0);
+    }
+}
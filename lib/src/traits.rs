@@ -1,25 +1,70 @@
 use crate::zk::VerifiableEncryption;
+use crate::{LocalWallet, TxParams};
 use async_trait::async_trait;
 use ecdsa_fun::Signature;
-use ethers::prelude::{Address, H256};
-use secp256kfun::Point;
+use ethers::prelude::{Address, H256, U256};
+use secp256kfun::{Point, Scalar};
 
 #[async_trait]
 pub trait ChainProvider {
     type Tx;
 
+    /// Nonce and gas of the transfer of `amount` from `from` to `to`, fetched once by the buyer
+    /// and pinned in the transaction both sides compose with [`Self::compose_tx`].
+    async fn tx_params(&self, from: Address, to: Address, amount: f64) -> anyhow::Result<TxParams>;
+
     fn compose_tx(
         &self,
         from: Address,
         to: Address,
         amount: f64,
+        params: &TxParams,
     ) -> anyhow::Result<(Self::Tx, H256)>;
 
     async fn sent_signed(&self, tx: Self::Tx, sig: &Signature) -> anyhow::Result<H256>;
 
     async fn get_signature(&self, hash: H256) -> anyhow::Result<Option<Signature>>;
 
+    /// Number of the latest block, used to measure timeouts in blocks.
+    async fn block_number(&self) -> anyhow::Result<u64>;
+
     fn address_from_pk(&self, pk: &Point) -> Address;
+
+    /// Whether transaction of `from` with `nonce` is mined, whichever transaction it is.
+    async fn nonce_mined(&self, from: Address, nonce: U256) -> anyhow::Result<bool>;
+
+    /// Sends `amount` to `to` in transaction signed in full with `wallet`, returning its hash once mined.
+    /// Transaction made at the nonce of `replacing`, if given, outbidding its gas price.
+    async fn send_own(
+        &self,
+        wallet: &LocalWallet,
+        to: Address,
+        amount: f64,
+        replacing: Option<&TxParams>,
+    ) -> anyhow::Result<H256>;
+
+    /// Token payments are made in, ether if none, see [`crate::Erc20Token`].
+    fn payment_token(&self) -> Option<Address> {
+        None
+    }
+}
+
+/// Chain whose settlement transactions are watched until they're deep enough to be final,
+/// see [`crate::EventWatcher`].
+#[async_trait]
+pub trait ConfirmationSource {
+    /// Height of the block `tx_id` was included in, none while it's pending or unknown.
+    async fn tx_height(&self, tx_id: &str) -> anyhow::Result<Option<u64>>;
+
+    /// Height of the chain tip.
+    async fn tip_height(&self) -> anyhow::Result<u64>;
+}
+
+/// Contract anchoring hashes of the verifying keys that sales are proven with.
+#[async_trait]
+pub trait VkRegistry {
+    /// Hash of the verifying key anchored for the sale with `sale_hash`, if any.
+    async fn anchored_vk_hash(&self, sale_hash: &[u8; 32]) -> anyhow::Result<Option<[u8; 32]>>;
 }
 
 #[async_trait]
@@ -36,4 +81,11 @@ pub trait CipherDownloader {
     async fn download(&self) -> anyhow::Result<VerifiableEncryption>;
 }
 
+/// Storage of wallet secret keys, each under its name and guarded with its password.
+pub trait Keystore {
+    fn write(&mut self, sk: Scalar, name: &str, password: &[u8]) -> anyhow::Result<()>;
+
+    fn read(&self, name: &str, password: &[u8]) -> anyhow::Result<(Scalar, Point)>;
+}
+
 pub trait DataAdaptor {}
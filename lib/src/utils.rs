@@ -1,17 +1,36 @@
-use crate::{PROVING_KEY_FILE, VERIFYING_KEY_FILE};
+use crate::artifact::{ensure_key_header, strip_artifact_magic, KeyHeader};
+use crate::{
+    ensure_same_circuit, tag_key, verifying_key_hash, ArtifactType, Fr, ProjectiveCurve,
+    TaggedCurve, WrongArtifactType, PROVING_KEY_FILE, PROVING_KEY_MAGIC, VERIFYING_KEY_FILE,
+    VERIFYING_KEY_MAGIC,
+};
 use anyhow::anyhow;
 use ark_ec::PairingEngine;
-use ark_groth16::{ProvingKey, VerifyingKey};
+use ark_ff::PrimeField;
+use ark_groth16::{PreparedVerifyingKey, ProvingKey, VerifyingKey};
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
-use circuits::{ark_from_bytes, ark_to_bytes};
+use circuits::{ark_to_bytes, public_key_from_secret, PublicKey, SecretKey};
 use ethers::prelude::coins_bip39::English;
 use ethers::prelude::MnemonicBuilder;
 use secp256kfun::marker::{Mark, NonZero, Normal};
 use secp256kfun::{g, Point, Scalar, G};
+use sha2::{Digest, Sha256};
 use std::fs;
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
+/// Byte order mark some tools prefix UTF-8 text files with.
+pub const UTF8_BOM: [u8; 3] = [0xef, 0xbb, 0xbf];
+
+/// Strips leading UTF-8 BOM from `bytes`, telling whether there was one.
+pub fn strip_bom(bytes: &[u8]) -> (&[u8], bool) {
+    match bytes.strip_prefix(&UTF8_BOM) {
+        Some(rest) => (rest, true),
+        None => (bytes, false),
+    }
+}
+
 pub fn keypair_gen() -> (Scalar, Point) {
     let sk = Scalar::random(&mut rand::thread_rng());
     let pk = g!(sk * G).mark::<Normal>();
@@ -35,9 +54,23 @@ pub fn keypair_from_bytes<B: AsRef<[u8]>>(buf: B) -> anyhow::Result<(Scalar, Poi
     }
 }
 
+/// BIP-32 path of the key imported from BIP-39 mnemonic unless another is given, the first Ethereum account.
+pub const DEFAULT_DERIVATION_PATH: &str = "m/44'/60'/0'/0/0";
+
 pub fn keypair_from_bip39(phrase: &str) -> anyhow::Result<(Scalar, Point)> {
+    keypair_from_bip39_path(phrase, DEFAULT_DERIVATION_PATH)
+}
+
+/// Imports key at BIP-32 `derivation_path` of BIP-39 mnemonic, e.g. `m/44'/60'/0'/0/1` for the second account,
+/// so that wallets created elsewhere get to be used as they are.
+pub fn keypair_from_bip39_path(
+    phrase: &str,
+    derivation_path: &str,
+) -> anyhow::Result<(Scalar, Point)> {
     let sk_bytes = MnemonicBuilder::<English>::default()
         .phrase(phrase)
+        .derivation_path(derivation_path)
+        .map_err(|e| anyhow!("error parsing derivation path: {e}"))?
         .build()
         .map_err(|e| anyhow!("error parsing mnemonic: {e}"))?
         .signer()
@@ -50,6 +83,28 @@ pub fn keypair_from_bip39(phrase: &str) -> anyhow::Result<(Scalar, Point)> {
     Ok((sk, pk))
 }
 
+/// Deterministically derives ElGamal keypair from BIP-39 mnemonic: key at `account_index`
+/// of the default BIP-32 Ethereum path gets reduced into the JubJub scalar field.
+/// The CLI keeps no long-lived ElGamal key to restore this way: one-time keys are rederived from
+/// the wallet key, see [`crate::zk::trade_key_rng`], and handshake keys are saved with the session.
+pub fn keypair_from_mnemonic(
+    phrase: &str,
+    passphrase: &str,
+    account_index: u32,
+) -> anyhow::Result<(SecretKey<ProjectiveCurve>, PublicKey<ProjectiveCurve>)> {
+    let sk_bytes = MnemonicBuilder::<English>::default()
+        .phrase(phrase)
+        .password(passphrase)
+        .index(account_index)
+        .map_err(|e| anyhow!("error deriving key: {e}"))?
+        .build()
+        .map_err(|e| anyhow!("error parsing mnemonic: {e}"))?
+        .signer()
+        .to_bytes();
+    let sk = Fr::from_le_bytes_mod_order(sk_bytes.as_slice());
+    Ok((sk, public_key_from_secret::<ProjectiveCurve>(&sk)))
+}
+
 pub fn write_to_keystore<D: AsRef<Path>, S: AsRef<str>, P: AsRef<[u8]>>(
     sk: Scalar,
     dir: D,
@@ -57,6 +112,7 @@ pub fn write_to_keystore<D: AsRef<Path>, S: AsRef<str>, P: AsRef<[u8]>>(
     password: P,
 ) -> anyhow::Result<()> {
     let _ = fs::create_dir_all(&dir);
+    create_secret_file(dir.as_ref().join(name.as_ref()))?;
     eth_keystore::encrypt_key(
         dir,
         &mut rand::thread_rng(),
@@ -82,16 +138,176 @@ pub fn read_from_keystore<P: AsRef<Path>, S: AsRef<[u8]>>(
     Ok((sk, pk))
 }
 
-pub fn write_circuit_artifacts<P: AsRef<Path>, E: PairingEngine>(
+/// Permissions of files holding secret key material on Unix.
+pub const SECRET_FILE_MODE: u32 = 0o600;
+
+/// Creates empty file at `path` accessible by its owner only, or narrows permissions of the existing one,
+/// so that secret written into it afterwards is never exposed under broader ones. On Windows the file
+/// inherits ACL of its directory, which is left as it is.
+fn create_secret_file<P: AsRef<Path>>(path: P) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, SECRET_FILE_MODE);
+    options
+        .open(path)
+        .map_err(|e| anyhow!("error creating key file: {e}"))?;
+
+    // mode applies to newly created files only, and umask may have narrowed it further.
+    #[cfg(unix)]
+    fs::set_permissions(
+        path,
+        std::os::unix::fs::PermissionsExt::from_mode(SECRET_FILE_MODE),
+    )
+    .map_err(|e| anyhow!("error setting key file mode: {e}"))?;
+    Ok(())
+}
+
+/// Files in keystore `dir` that other users may access, always none on Windows where ACLs aren't inspected.
+pub fn permissive_key_files<P: AsRef<Path>>(dir: P) -> Vec<PathBuf> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry.metadata().map_or(false, |metadata| {
+                #[cfg(unix)]
+                let permissive =
+                    std::os::unix::fs::PermissionsExt::mode(&metadata.permissions()) & 0o077 != 0;
+                #[cfg(not(unix))]
+                let permissive = false;
+                metadata.is_file() && permissive
+            })
+        })
+        .map(|entry| entry.path())
+        .collect()
+}
+
+/// Re-encrypts keystore at `path` under `new_password`, keeping the previous file as `<name>.bak`.
+/// New keystore is written aside first and then renamed over the original, so the wallet is never left half-written.
+pub fn rotate_keystore_password<P: AsRef<Path>, S: AsRef<[u8]>, N: AsRef<[u8]>>(
+    path: P,
+    old_password: S,
+    new_password: N,
+) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let (sk, _) = read_from_keystore(path, old_password)?;
+
+    let dir = path
+        .parent()
+        .ok_or(anyhow!("keystore path has no parent directory"))?;
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or(anyhow!("keystore path has no valid file name"))?;
+    let rotated = format!("{name}.rotating");
+
+    write_to_keystore(sk, dir, &rotated, new_password)?;
+    fs::copy(path, dir.join(format!("{name}.bak")))
+        .map_err(|e| anyhow!("error backing up keystore: {e}"))?;
+    fs::rename(dir.join(rotated), path).map_err(|e| anyhow!("error replacing keystore: {e}"))
+}
+
+/// Fails unless `path` is an existing regular file that can be opened for reading,
+/// so that a wrong data path is reported before any expensive work is done.
+pub fn ensure_readable_file<P: AsRef<Path>>(path: P) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let metadata = fs::metadata(path)
+        .map_err(|e| anyhow!("data file {} is not accessible: {e}", path.display()))?;
+    if !metadata.is_file() {
+        return Err(anyhow!(
+            "data file {} is not a regular file",
+            path.display()
+        ));
+    }
+
+    fs::File::open(path)
+        .map(|_| ())
+        .map_err(|e| anyhow!("data file {} is not readable: {e}", path.display()))
+}
+
+/// Fails unless SHA-256 of `data` equals hex-encoded `expected`, reporting both digests on mismatch.
+pub fn ensure_content_hash<D: AsRef<[u8]>>(data: D, expected: &str) -> anyhow::Result<()> {
+    let expected = hex::decode(expected.trim_start_matches("0x"))
+        .map_err(|e| anyhow!("bad expected hash: {e}"))?;
+    let actual = Sha256::digest(data.as_ref());
+    if actual.as_slice() != expected.as_slice() {
+        return Err(anyhow!(
+            "content hash mismatch: expected {}, got {}",
+            hex::encode(expected),
+            hex::encode(actual)
+        ));
+    }
+
+    Ok(())
+}
+
+/// Parses octal file mode, like `600` or `0o600`.
+pub fn parse_file_mode(mode: &str) -> anyhow::Result<u32> {
+    let digits = mode.trim_start_matches("0o");
+    u32::from_str_radix(digits, 8)
+        .ok()
+        .filter(|m| *m <= 0o7777)
+        .ok_or(anyhow!("invalid file mode {mode}, expected octal like 600"))
+}
+
+/// Writes `data` to `path`, creating file with permissions `mode` on Unix and ignoring it elsewhere.
+/// File is written aside and renamed over `path`, so it is never readable under broader permissions.
+pub fn write_with_mode<P: AsRef<Path>, D: AsRef<[u8]>>(
+    path: P,
+    data: D,
+    mode: Option<u32>,
+) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let mode = match mode {
+        Some(mode) if cfg!(unix) => mode,
+        _ => return fs::write(path, data).map_err(|e| anyhow!("error writing file: {e}")),
+    };
+
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    let _ = fs::remove_file(&partial);
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, mode);
+    #[cfg(not(unix))]
+    let _ = mode;
+
+    let mut file = options
+        .open(&partial)
+        .map_err(|e| anyhow!("error creating file: {e}"))?;
+    file.write_all(data.as_ref())
+        .and_then(|_| file.sync_all())
+        .map_err(|e| anyhow!("error writing file: {e}"))?;
+    // umask may have narrowed requested mode on creation.
+    #[cfg(unix)]
+    fs::set_permissions(&partial, std::os::unix::fs::PermissionsExt::from_mode(mode))
+        .map_err(|e| anyhow!("error setting file mode: {e}"))?;
+
+    fs::rename(&partial, path).map_err(|e| anyhow!("error replacing file: {e}"))
+}
+
+pub fn write_circuit_artifacts<P: AsRef<Path>, E: PairingEngine + TaggedCurve>(
     path: P,
     pk: &ProvingKey<E>,
     vk: &VerifyingKey<E>,
 ) -> anyhow::Result<()> {
-    let mut pk_buf = vec![];
-    pk.serialize_unchecked(&mut pk_buf)
+    let circuit_hash = circuit_hash(vk)?;
+    let mut pk_buf = PROVING_KEY_MAGIC.to_vec();
+    pk.serialize(&mut pk_buf)
         .map_err(|_e| anyhow!("error encoding proving key"))?;
+    let pk_buf = tag_key(E::CURVE_TAG, &circuit_hash, &pk_buf);
 
-    let vk_buf = ark_to_bytes(vk.clone()).map_err(|_e| anyhow!("error encoding verifying key"))?;
+    let mut vk_buf = VERIFYING_KEY_MAGIC.to_vec();
+    vk.serialize(&mut vk_buf)
+        .map_err(|_e| anyhow!("error encoding verifying key"))?;
+    let vk_buf = tag_key(E::CURVE_TAG, &circuit_hash, &vk_buf);
 
     fs::write(path.as_ref().join(PROVING_KEY_FILE), pk_buf)
         .map_err(|e| anyhow!("error writing proving key: {e}"))?;
@@ -101,25 +317,141 @@ pub fn write_circuit_artifacts<P: AsRef<Path>, E: PairingEngine>(
     Ok(())
 }
 
-pub fn read_proving_key<P: AsRef<Path>, E: PairingEngine>(
+/// Fails if circuit artifacts already exist in `dir`, unless `overwrite` is set,
+/// since overwriting keys in use breaks every proof made with them.
+pub fn ensure_artifacts_writable<P: AsRef<Path>>(dir: P, overwrite: bool) -> anyhow::Result<()> {
+    let existing: Vec<_> = [PROVING_KEY_FILE, VERIFYING_KEY_FILE]
+        .into_iter()
+        .map(|file| dir.as_ref().join(file))
+        .filter(|path| path.exists())
+        .collect();
+
+    if existing.is_empty() || overwrite {
+        return Ok(());
+    }
+
+    Err(anyhow!(
+        "circuit keys already exist at {}, use `--overwrite-keys` to replace them",
+        existing
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    ))
+}
+
+pub fn read_proving_key<P: AsRef<Path>, E: PairingEngine + TaggedCurve>(
     path: P,
 ) -> anyhow::Result<ProvingKey<E>> {
     let buf = fs::read(path.as_ref()).map_err(|e| anyhow!("error reading proving key: {e}"))?;
-    ProvingKey::<E>::deserialize_unchecked(&*buf)
-        .map_err(|e| anyhow!("error decoding proving key: {e}"))
+    let header = ensure_key_header::<E>(&buf)?;
+    let buf = strip_artifact_magic(&buf, ArtifactType::ProvingKey)?;
+    let pk: ProvingKey<E> = decode_artifact(buf, ArtifactType::ProvingKey, header.as_ref())?;
+    ensure_same_circuit(header.as_ref(), circuit_hash(&pk.vk)?)?;
+    Ok(pk)
 }
 
-pub fn read_verifying_key<P: AsRef<Path>, E: PairingEngine>(
+pub fn read_verifying_key<P: AsRef<Path>, E: PairingEngine + TaggedCurve>(
     path: P,
 ) -> anyhow::Result<VerifyingKey<E>> {
-    let pk_buf =
-        fs::read(path.as_ref()).map_err(|e| anyhow!("error reading verifying key: {e}"))?;
-    ark_from_bytes(&*pk_buf).map_err(|e| anyhow!("error decoding verifying key: {e}"))
+    let buf = fs::read(path.as_ref()).map_err(|e| anyhow!("error reading verifying key: {e}"))?;
+    let header = ensure_key_header::<E>(&buf)?;
+    let buf = strip_artifact_magic(&buf, ArtifactType::VerifyingKey)?;
+    let vk: VerifyingKey<E> = decode_artifact(buf, ArtifactType::VerifyingKey, header.as_ref())?;
+    ensure_same_circuit(header.as_ref(), circuit_hash(&vk)?)?;
+    Ok(vk)
+}
+
+/// Caches `pvk` at `path` as the hash of the verifying key it's prepared from,
+/// followed by `e(alpha, beta)` pairing that takes most of the preparation.
+pub fn write_prepared_verifying_key<P: AsRef<Path>, E: PairingEngine>(
+    path: P,
+    pvk: &PreparedVerifyingKey<E>,
+) -> anyhow::Result<()> {
+    let mut buf = circuit_hash(&pvk.vk)?.to_vec();
+    pvk.alpha_g1_beta_g2
+        .serialize(&mut buf)
+        .map_err(|e| anyhow!("error encoding prepared verifying key: {e}"))?;
+    fs::write(path, buf).map_err(|e| anyhow!("error writing prepared verifying key: {e}"))
+}
+
+/// Restores prepared `vk` cached by [`write_prepared_verifying_key`], failing if it was prepared from another key.
+/// G2 points are prepared anew, since arkworks can't decode their prepared form.
+pub fn read_prepared_verifying_key<P: AsRef<Path>, E: PairingEngine>(
+    path: P,
+    vk: &VerifyingKey<E>,
+) -> anyhow::Result<PreparedVerifyingKey<E>> {
+    let buf = fs::read(path.as_ref())
+        .map_err(|e| anyhow!("error reading prepared verifying key: {e}"))?;
+    let (source_hash, mut rest) = buf.split_at(buf.len().min(32));
+    if source_hash != circuit_hash(vk)? {
+        return Err(anyhow!(
+            "cached prepared verifying key is made from another verifying key"
+        ));
+    }
+
+    let alpha_g1_beta_g2 = E::Fqk::deserialize(&mut rest)
+        .map_err(|e| anyhow!("error decoding prepared verifying key: {e}"))?;
+    Ok(PreparedVerifyingKey {
+        vk: vk.clone(),
+        alpha_g1_beta_g2,
+        gamma_g2_neg_pc: (-vk.gamma_g2).into(),
+        delta_g2_neg_pc: (-vk.delta_g2).into(),
+    })
+}
+
+/// Hash of the compressed verifying key identifying the circuit, recorded in headers of both its keys.
+pub fn circuit_hash<E: PairingEngine>(vk: &VerifyingKey<E>) -> anyhow::Result<[u8; 32]> {
+    let vk = ark_to_bytes(vk.clone()).map_err(|e| anyhow!("error encoding verifying key: {e}"))?;
+    verifying_key_hash(&vk)
+}
+
+/// Decodes artifact in the encoding its `header` flags, compressed if there is none, consuming `buf` in full,
+/// so that one written in the other encoding is rejected rather than read into a bogus key.
+fn decode_artifact<O: CanonicalDeserialize>(
+    mut buf: &[u8],
+    expected: ArtifactType,
+    header: Option<&KeyHeader>,
+) -> anyhow::Result<O> {
+    let decoded = match header.map_or(true, |header| header.compressed) {
+        true => O::deserialize(&mut buf),
+        false => O::deserialize_uncompressed(&mut buf),
+    };
+    match decoded {
+        Ok(artifact) if buf.is_empty() => Ok(artifact),
+        _ => Err(WrongArtifactType {
+            expected,
+            found: ArtifactType::Unknown,
+        }
+        .into()),
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::keypair_from_hex;
+    use crate::{
+        circuit_hash, ensure_artifacts_writable, ensure_build_curve, ensure_readable_file,
+        ensure_same_curve, keypair_from_bip39, keypair_from_bip39_path, keypair_from_hex,
+        keypair_from_mnemonic, keypair_gen, parse_file_mode, read_from_keystore,
+        read_prepared_verifying_key, read_proving_key, read_verifying_key,
+        rotate_keystore_password, split_curve_tag, split_key_header, strip_bom, tag_curve, tag_key,
+        write_circuit_artifacts, write_prepared_verifying_key, write_to_keystore, ArtifactType,
+        CircuitMismatch, CurveMismatch, CurveTag, KeyHeader, PairingEngine, TaggedCurve,
+        UnsupportedKeyVersion, WrongArtifactType, WrongKeyCurve, CURVE_TAG_MAGIC,
+        KEY_COMPRESSED_FLAG, KEY_FORMAT_VERSION, PROVING_KEY_FILE, PROVING_KEY_MAGIC,
+        VERIFYING_KEY_FILE,
+    };
+    use ark_bls12_381::Fr;
+    use ark_bn254::Bn254;
+    use ark_groth16::{prepare_verifying_key, verify_proof, Groth16};
+    use ark_relations::lc;
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+    use ark_snark::{CircuitSpecificSetupSNARK, SNARK};
+    use circuits::{ark_to_bytes, ark_to_bytes_uncompressed};
+    use std::fs;
+
+    const MNEMONIC: &str =
+        "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
 
     #[test]
     fn test_keypair_from_hex() {
@@ -130,4 +462,416 @@ mod test {
         println!("sk: {}", hex::encode(sk.to_bytes()));
         println!("pk: {}", hex::encode(pk.to_bytes()));
     }
+
+    #[test]
+    fn test_keypair_from_mnemonic() {
+        let (sk, pk) = keypair_from_mnemonic(MNEMONIC, "", 0).unwrap();
+        let (same_sk, same_pk) = keypair_from_mnemonic(MNEMONIC, "", 0).unwrap();
+        assert_eq!((sk, pk), (same_sk, same_pk));
+
+        let (other_sk, other_pk) = keypair_from_mnemonic(MNEMONIC, "", 1).unwrap();
+        assert_ne!(sk, other_sk);
+        assert_ne!(pk, other_pk);
+
+        let (protected_sk, _) = keypair_from_mnemonic(MNEMONIC, "secret", 0).unwrap();
+        assert_ne!(sk, protected_sk);
+    }
+
+    #[test]
+    fn test_keypair_from_bip39_path() {
+        let (sk, pk) = keypair_from_bip39(MNEMONIC).unwrap();
+        assert_eq!(
+            (sk, pk),
+            keypair_from_bip39_path(MNEMONIC, "m/44'/60'/0'/0/0").unwrap()
+        );
+
+        let (other_sk, _) = keypair_from_bip39_path(MNEMONIC, "m/44'/60'/0'/0/1").unwrap();
+        assert_ne!(sk, other_sk);
+        assert!(keypair_from_bip39_path(MNEMONIC, "not a path").is_err());
+    }
+
+    struct DummyCircuit;
+
+    impl ConstraintSynthesizer<Fr> for DummyCircuit {
+        fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+            let a = cs.new_witness_variable(|| Ok(Fr::from(1u64)))?;
+            cs.enforce_constraint(lc!() + a, lc!() + a, lc!() + a)
+        }
+    }
+
+    #[test]
+    fn test_read_wrong_artifact_type() {
+        let dir = std::env::temp_dir().join("zkcp_test_artifacts");
+        fs::create_dir_all(&dir).unwrap();
+        let (pk, vk) =
+            Groth16::<PairingEngine>::setup(DummyCircuit, &mut rand::thread_rng()).unwrap();
+        write_circuit_artifacts(&dir, &pk, &vk).unwrap();
+
+        assert!(read_proving_key::<_, PairingEngine>(dir.join(PROVING_KEY_FILE)).is_ok());
+        assert!(read_verifying_key::<_, PairingEngine>(dir.join(VERIFYING_KEY_FILE)).is_ok());
+
+        let err = read_proving_key::<_, PairingEngine>(dir.join(VERIFYING_KEY_FILE)).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<WrongArtifactType>(),
+            Some(&WrongArtifactType {
+                expected: ArtifactType::ProvingKey,
+                found: ArtifactType::VerifyingKey,
+            })
+        );
+
+        fs::write(dir.join("random"), [7; 64]).unwrap();
+        let err = read_verifying_key::<_, PairingEngine>(dir.join("random")).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<WrongArtifactType>(),
+            Some(&WrongArtifactType {
+                expected: ArtifactType::VerifyingKey,
+                found: ArtifactType::Unknown,
+            })
+        );
+    }
+
+    #[test]
+    fn test_proving_key_compressed() {
+        let dir = std::env::temp_dir().join("zkcp_test_artifacts_compressed");
+        fs::create_dir_all(&dir).unwrap();
+        let (pk, vk) =
+            Groth16::<PairingEngine>::setup(DummyCircuit, &mut rand::thread_rng()).unwrap();
+        write_circuit_artifacts(&dir, &pk, &vk).unwrap();
+        assert_eq!(
+            read_proving_key::<_, PairingEngine>(dir.join(PROVING_KEY_FILE)).unwrap(),
+            pk
+        );
+
+        let compressed = fs::read(dir.join(PROVING_KEY_FILE)).unwrap();
+        let uncompressed = [
+            &PROVING_KEY_MAGIC[..],
+            &ark_to_bytes_uncompressed(pk).unwrap(),
+        ]
+        .concat();
+        assert!(compressed.len() < uncompressed.len());
+
+        // proving key written in the other encoding is rejected rather than misread.
+        let path = dir.join("uncompressed.pk");
+        fs::write(&path, uncompressed).unwrap();
+        let err = read_proving_key::<_, PairingEngine>(&path).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<WrongArtifactType>(),
+            Some(&WrongArtifactType {
+                expected: ArtifactType::ProvingKey,
+                found: ArtifactType::Unknown,
+            })
+        );
+    }
+
+    #[test]
+    fn test_prepared_verifying_key_cached() {
+        let mut rng = rand::thread_rng();
+        let path = std::env::temp_dir().join("zkcp_test_prepared.vk");
+        let (pk, vk) = Groth16::<PairingEngine>::setup(DummyCircuit, &mut rng).unwrap();
+        let fresh = prepare_verifying_key(&vk);
+        write_prepared_verifying_key(&path, &fresh).unwrap();
+        let cached = read_prepared_verifying_key(&path, &vk).unwrap();
+        assert_eq!(cached, fresh);
+
+        let (other_pk, other_vk) = Groth16::<PairingEngine>::setup(DummyCircuit, &mut rng).unwrap();
+        for proof in [
+            Groth16::prove(&pk, DummyCircuit, &mut rng).unwrap(),
+            Groth16::prove(&other_pk, DummyCircuit, &mut rng).unwrap(),
+        ] {
+            assert_eq!(
+                verify_proof(&cached, &proof, &[]).unwrap(),
+                verify_proof(&fresh, &proof, &[]).unwrap()
+            );
+        }
+
+        let err = read_prepared_verifying_key(&path, &other_vk).unwrap_err();
+        assert!(err.to_string().contains("another verifying key"), "{err}");
+    }
+
+    #[test]
+    fn test_proof_curve_mismatch() {
+        let dir = std::env::temp_dir().join("zkcp_test_artifacts_curve");
+        fs::create_dir_all(&dir).unwrap();
+        let mut rng = rand::thread_rng();
+        let (pk, vk) = Groth16::<PairingEngine>::setup(DummyCircuit, &mut rng).unwrap();
+        write_circuit_artifacts(&dir, &pk, &vk).unwrap();
+        let proof =
+            ark_to_bytes(Groth16::<PairingEngine>::prove(&pk, DummyCircuit, &mut rng).unwrap())
+                .unwrap();
+
+        let vk_bytes = fs::read(dir.join(VERIFYING_KEY_FILE)).unwrap();
+        let (vk_curve, _) = split_curve_tag(&vk_bytes);
+        assert_eq!(vk_curve, Some(CurveTag::Bls12_381));
+
+        let same = tag_curve(CurveTag::Bls12_381, &proof);
+        let (proof_curve, untagged) = split_curve_tag(&same);
+        assert_eq!(untagged, proof);
+        assert!(ensure_same_curve(proof_curve, vk_curve).is_ok());
+        assert!(ensure_same_curve(split_curve_tag(&proof).0, vk_curve).is_ok());
+
+        let bn254 = tag_curve(CurveTag::Bn254, &proof);
+        let err = ensure_same_curve(split_curve_tag(&bn254).0, vk_curve).unwrap_err();
+        assert_eq!(
+            err,
+            CurveMismatch {
+                proof: CurveTag::Bn254,
+                verifying_key: CurveTag::Bls12_381,
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "proof is made for BN254 curve, but verifying key is for BLS12-381"
+        );
+    }
+
+    #[test]
+    fn test_build_curve() {
+        assert_eq!(
+            "BLS12-381".parse::<CurveTag>().unwrap(),
+            CurveTag::Bls12_381
+        );
+        assert_eq!("bn254".parse::<CurveTag>().unwrap(), CurveTag::Bn254);
+        assert!("secp256k1".parse::<CurveTag>().is_err());
+
+        assert!(ensure_build_curve(PairingEngine::CURVE_TAG).is_ok());
+        let other = match PairingEngine::CURVE_TAG {
+            CurveTag::Bls12_381 => CurveTag::Bn254,
+            CurveTag::Bn254 => CurveTag::Bls12_381,
+        };
+        assert!(ensure_build_curve(other).is_err());
+    }
+
+    #[test]
+    fn test_key_header() {
+        let dir = std::env::temp_dir().join("zkcp_test_artifacts_header");
+        fs::create_dir_all(&dir).unwrap();
+        let (pk, vk) =
+            Groth16::<PairingEngine>::setup(DummyCircuit, &mut rand::thread_rng()).unwrap();
+        write_circuit_artifacts(&dir, &pk, &vk).unwrap();
+
+        let header = KeyHeader {
+            curve: CurveTag::Bls12_381,
+            version: KEY_FORMAT_VERSION,
+            compressed: true,
+            circuit_hash: Some(circuit_hash(&vk).unwrap()),
+        };
+        let pk_bytes = fs::read(dir.join(PROVING_KEY_FILE)).unwrap();
+        let (pk_header, rest) = split_key_header(&pk_bytes);
+        assert_eq!(pk_header, Some(header));
+        assert!(rest.starts_with(&PROVING_KEY_MAGIC));
+        let vk_bytes = fs::read(dir.join(VERIFYING_KEY_FILE)).unwrap();
+        assert_eq!(split_key_header(&vk_bytes).0, Some(header));
+
+        assert_eq!(
+            read_proving_key::<_, PairingEngine>(dir.join(PROVING_KEY_FILE)).unwrap(),
+            pk
+        );
+        assert_eq!(
+            read_verifying_key::<_, PairingEngine>(dir.join(VERIFYING_KEY_FILE)).unwrap(),
+            vk
+        );
+
+        let err = read_proving_key::<_, Bn254>(dir.join(PROVING_KEY_FILE)).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<WrongKeyCurve>(),
+            Some(&WrongKeyCurve {
+                expected: CurveTag::Bn254,
+                found: CurveTag::Bls12_381,
+            })
+        );
+        assert_eq!(
+            err.to_string(),
+            "key was built for BLS12-381 curve, expected BN254"
+        );
+        assert!(read_verifying_key::<_, Bn254>(dir.join(VERIFYING_KEY_FILE)).is_err());
+
+        let path = dir.join("future.vk");
+        let mut future = vk_bytes.clone();
+        future[CURVE_TAG_MAGIC.len() + 1] = KEY_FORMAT_VERSION + 1;
+        fs::write(&path, future).unwrap();
+        let err = read_verifying_key::<_, PairingEngine>(&path).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<UnsupportedKeyVersion>(),
+            Some(&UnsupportedKeyVersion {
+                version: KEY_FORMAT_VERSION + 1
+            })
+        );
+
+        // keys tagged before format version was introduced are still read.
+        let (_, untagged) = split_key_header(&vk_bytes);
+        fs::write(&path, tag_curve(CurveTag::Bls12_381, untagged)).unwrap();
+        assert_eq!(read_verifying_key::<_, PairingEngine>(&path).unwrap(), vk);
+    }
+
+    #[test]
+    fn test_key_circuit_hash() {
+        let dir = std::env::temp_dir().join("zkcp_test_artifacts_circuit");
+        fs::create_dir_all(&dir).unwrap();
+        let mut rng = rand::thread_rng();
+        let (pk, vk) = Groth16::<PairingEngine>::setup(DummyCircuit, &mut rng).unwrap();
+        write_circuit_artifacts(&dir, &pk, &vk).unwrap();
+        let pk_bytes = fs::read(dir.join(PROVING_KEY_FILE)).unwrap();
+        let (_, pk_body) = split_key_header(&pk_bytes);
+
+        // key spliced under the header of another circuit's key is rejected.
+        let (_, other_vk) = Groth16::<PairingEngine>::setup(DummyCircuit, &mut rng).unwrap();
+        let other_hash = circuit_hash(&other_vk).unwrap();
+        let path = dir.join("spliced.pk");
+        fs::write(&path, tag_key(CurveTag::Bls12_381, &other_hash, pk_body)).unwrap();
+        let err = read_proving_key::<_, PairingEngine>(&path).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<CircuitMismatch>(),
+            Some(&CircuitMismatch {
+                expected: other_hash,
+                found: circuit_hash(&vk).unwrap(),
+            })
+        );
+
+        // key flagged as uncompressed is decoded as such.
+        let mut uncompressed = pk_bytes[..CURVE_TAG_MAGIC.len() + 3 + 32].to_vec();
+        uncompressed[CURVE_TAG_MAGIC.len() + 2] &= !KEY_COMPRESSED_FLAG;
+        uncompressed.extend(PROVING_KEY_MAGIC);
+        uncompressed.extend(ark_to_bytes_uncompressed(pk.clone()).unwrap());
+        fs::write(&path, &uncompressed).unwrap();
+        let (header, _) = split_key_header(&uncompressed);
+        assert!(!header.unwrap().compressed);
+        assert_eq!(read_proving_key::<_, PairingEngine>(&path).unwrap(), pk);
+    }
+
+    #[test]
+    fn test_ensure_artifacts_writable() {
+        let dir = std::env::temp_dir().join("zkcp_test_overwrite_keys");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        assert!(ensure_artifacts_writable(&dir, false).is_ok());
+
+        let (pk, vk) =
+            Groth16::<PairingEngine>::setup(DummyCircuit, &mut rand::thread_rng()).unwrap();
+        write_circuit_artifacts(&dir, &pk, &vk).unwrap();
+        let err = ensure_artifacts_writable(&dir, false).unwrap_err();
+        assert!(err.to_string().contains(PROVING_KEY_FILE));
+
+        let written = fs::read(dir.join(VERIFYING_KEY_FILE)).unwrap();
+        assert!(ensure_artifacts_writable(&dir, true).is_ok());
+        let (pk, vk) =
+            Groth16::<PairingEngine>::setup(DummyCircuit, &mut rand::thread_rng()).unwrap();
+        write_circuit_artifacts(&dir, &pk, &vk).unwrap();
+        assert_ne!(fs::read(dir.join(VERIFYING_KEY_FILE)).unwrap(), written);
+    }
+
+    #[test]
+    fn test_rotate_keystore_password() {
+        let dir = std::env::temp_dir().join("zkcp_test_rotate_password");
+        let _ = fs::remove_dir_all(&dir);
+        let (sk, pk) = keypair_gen();
+        write_to_keystore(sk, &dir, "wallet", "old").unwrap();
+        let path = dir.join("wallet");
+
+        rotate_keystore_password(&path, "old", "new").unwrap();
+
+        assert_eq!(read_from_keystore(&path, "new").unwrap().1, pk);
+        assert!(read_from_keystore(&path, "old").is_err());
+        assert_eq!(
+            read_from_keystore(dir.join("wallet.bak"), "old").unwrap().1,
+            pk
+        );
+        assert!(!dir.join("wallet.rotating").exists());
+
+        assert!(rotate_keystore_password(&path, "wrong", "other").is_err());
+        assert_eq!(read_from_keystore(&path, "new").unwrap().1, pk);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_key_files_not_world_readable() {
+        use crate::permissive_key_files;
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join("zkcp_test_key_mode");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let mode = |name: &str| fs::metadata(dir.join(name)).unwrap().permissions().mode() & 0o777;
+
+        let (sk, _) = keypair_gen();
+        write_to_keystore(sk, &dir, "fresh", "password").unwrap();
+        assert_eq!(mode("fresh"), 0o600);
+
+        // key written over a file left loose earlier is narrowed too.
+        fs::write(dir.join("existing"), b"").unwrap();
+        fs::set_permissions(dir.join("existing"), fs::Permissions::from_mode(0o644)).unwrap();
+        assert_eq!(permissive_key_files(&dir), vec![dir.join("existing")]);
+        write_to_keystore(sk, &dir, "existing", "password").unwrap();
+        assert_eq!(mode("existing"), 0o600);
+        assert!(permissive_key_files(&dir).is_empty());
+
+        fs::set_permissions(dir.join("fresh"), fs::Permissions::from_mode(0o640)).unwrap();
+        assert_eq!(permissive_key_files(&dir), vec![dir.join("fresh")]);
+    }
+
+    #[test]
+    fn test_strip_bom() {
+        assert_eq!(strip_bom(b"\xef\xbb\xbfdata"), (&b"data"[..], true));
+        assert_eq!(strip_bom(b"data"), (&b"data"[..], false));
+        assert_eq!(strip_bom(b"\xef\xbb"), (&b"\xef\xbb"[..], false));
+    }
+
+    #[test]
+    fn test_parse_file_mode() {
+        assert_eq!(parse_file_mode("600").unwrap(), 0o600);
+        assert_eq!(parse_file_mode("0o644").unwrap(), 0o644);
+        assert!(parse_file_mode("800").is_err());
+        assert!(parse_file_mode("17777").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_with_mode() {
+        use crate::write_with_mode;
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join("zkcp_test_output_mode");
+        fs::write(&path, b"previous").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        write_with_mode(&path, b"data", Some(0o600)).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"data");
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+    }
+
+    #[test]
+    fn test_ensure_readable_file() {
+        let dir = std::env::temp_dir().join("zkcp_test_readable_file");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("data.json");
+        fs::write(&path, b"[]").unwrap();
+
+        assert!(ensure_readable_file(&path).is_ok());
+        assert!(ensure_readable_file(dir.join("missing.json")).is_err());
+        assert!(ensure_readable_file(&dir)
+            .unwrap_err()
+            .to_string()
+            .contains("not a regular file"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_ensure_readable_file_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join("zkcp_test_unreadable_file");
+        fs::write(&path, b"[]").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o000)).unwrap();
+
+        // permissions are not enforced for root.
+        if fs::File::open(&path).is_err() {
+            assert!(ensure_readable_file(&path)
+                .unwrap_err()
+                .to_string()
+                .contains("not readable"));
+        }
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+    }
 }
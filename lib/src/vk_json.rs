@@ -0,0 +1,132 @@
+use anyhow::anyhow;
+use ark_ec::PairingEngine;
+use ark_ff::Zero;
+use ark_groth16::VerifyingKey;
+use ark_serialize::CanonicalSerialize;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Affine point with each coordinate as hex-encoded big-endian base field elements,
+/// one for points over the base field and two, `c0` first, for those over its quadratic extension.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PointJson {
+    pub x: Vec<String>,
+    pub y: Vec<String>,
+}
+
+/// Groth16 verifying key spelled out coordinate by coordinate, for tooling that doesn't link arkworks.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifyingKeyJson {
+    pub alpha_g1: PointJson,
+    pub beta_g2: PointJson,
+    pub gamma_g2: PointJson,
+    pub delta_g2: PointJson,
+    pub gamma_abc_g1: Vec<PointJson>,
+}
+
+impl VerifyingKeyJson {
+    pub fn new<E: PairingEngine>(vk: &VerifyingKey<E>) -> anyhow::Result<Self> {
+        Ok(Self {
+            alpha_g1: point_json::<E, _>(&vk.alpha_g1)?,
+            beta_g2: point_json::<E, _>(&vk.beta_g2)?,
+            gamma_g2: point_json::<E, _>(&vk.gamma_g2)?,
+            delta_g2: point_json::<E, _>(&vk.delta_g2)?,
+            gamma_abc_g1: vk
+                .gamma_abc_g1
+                .iter()
+                .map(point_json::<E, _>)
+                .collect::<anyhow::Result<_>>()?,
+        })
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let json =
+            serde_json::to_vec_pretty(self).expect("expected verifying key to marshal to json");
+        fs::write(path, json).map_err(|e| anyhow!("error writing verifying key json: {e}"))
+    }
+}
+
+/// Splits uncompressed encoding of the point, `x | y` with no flags set for points other than infinity,
+/// into the base field elements of its coordinates.
+fn point_json<E: PairingEngine, P: CanonicalSerialize>(point: &P) -> anyhow::Result<PointJson> {
+    let mut buf = vec![];
+    point
+        .serialize_uncompressed(&mut buf)
+        .map_err(|e| anyhow!("error encoding verifying key point: {e}"))?;
+    let element_len = E::Fq::zero().serialized_size();
+    if buf.len() % (2 * element_len) != 0 {
+        return Err(anyhow!(
+            "point of {} bytes doesn't split into base field elements",
+            buf.len()
+        ));
+    }
+
+    let elements = buf
+        .chunks(element_len)
+        .map(|element| {
+            let be = element.iter().rev().copied().collect::<Vec<_>>();
+            format!("0x{}", hex::encode(be))
+        })
+        .collect::<Vec<_>>();
+    let (x, y) = elements.split_at(elements.len() / 2);
+    Ok(PointJson {
+        x: x.to_vec(),
+        y: y.to_vec(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{PairingEngine, VerifyingKeyJson};
+    use ark_bls12_381::{Fq, Fr};
+    use ark_ff::PrimeField;
+    use ark_groth16::Groth16;
+    use ark_relations::lc;
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+    use ark_snark::CircuitSpecificSetupSNARK;
+    use std::fs;
+
+    struct SquareCircuit;
+
+    impl ConstraintSynthesizer<Fr> for SquareCircuit {
+        fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+            let a = cs.new_witness_variable(|| Ok(Fr::from(3u64)))?;
+            let b = cs.new_input_variable(|| Ok(Fr::from(9u64)))?;
+            let c = cs.new_input_variable(|| Ok(Fr::from(1u64)))?;
+            cs.enforce_constraint(lc!() + a, lc!() + a, lc!() + b)?;
+            cs.enforce_constraint(lc!() + c, lc!() + c, lc!() + c)
+        }
+    }
+
+    fn fq_from_hex(hex: &str) -> Fq {
+        let bytes = hex::decode(hex.strip_prefix("0x").unwrap()).unwrap();
+        Fq::from_be_bytes_mod_order(&bytes)
+    }
+
+    #[test]
+    fn test_verifying_key_json() {
+        let (_, vk) =
+            Groth16::<PairingEngine>::setup(SquareCircuit, &mut rand::thread_rng()).unwrap();
+        let json = VerifyingKeyJson::new(&vk).unwrap();
+
+        assert_eq!(json.gamma_abc_g1.len(), 3);
+        assert_eq!((json.alpha_g1.x.len(), json.alpha_g1.y.len()), (1, 1));
+        assert_eq!((json.beta_g2.x.len(), json.beta_g2.y.len()), (2, 2));
+        assert!(json.alpha_g1.x[0].starts_with("0x") && json.alpha_g1.x[0].len() == 2 + 96);
+
+        assert_eq!(fq_from_hex(&json.alpha_g1.x[0]), vk.alpha_g1.x);
+        assert_eq!(fq_from_hex(&json.alpha_g1.y[0]), vk.alpha_g1.y);
+        assert_eq!(fq_from_hex(&json.delta_g2.x[0]), vk.delta_g2.x.c0);
+        assert_eq!(fq_from_hex(&json.delta_g2.y[1]), vk.delta_g2.y.c1);
+        assert_eq!(
+            fq_from_hex(&json.gamma_abc_g1[2].y[0]),
+            vk.gamma_abc_g1[2].y
+        );
+
+        let path = std::env::temp_dir().join("zkcp_test_vk.json");
+        json.write(&path).unwrap();
+        let reloaded: VerifyingKeyJson = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(reloaded, json);
+    }
+}
@@ -1,6 +1,8 @@
 use crate::utils::keypair_from_hex;
-use crate::{keypair_gen, read_from_keystore};
+use crate::{keypair_gen, read_from_keystore, Keystore};
+use anyhow::anyhow;
 use secp256kfun::{Point, Scalar};
+use sha2::{Digest, Sha256};
 use std::path::Path;
 
 pub struct LocalWallet {
@@ -27,6 +29,16 @@ impl LocalWallet {
         Ok(Self { sk, pk })
     }
 
+    /// Loads wallet `name` from any [`Keystore`], in-memory one included.
+    pub fn from_store<K: Keystore + ?Sized, S: AsRef<[u8]>>(
+        keystore: &K,
+        name: &str,
+        password: S,
+    ) -> anyhow::Result<Self> {
+        let (sk, pk) = keystore.read(name, password.as_ref())?;
+        Ok(Self { sk, pk })
+    }
+
     pub fn pub_key(&self) -> &Point {
         &self.pk
     }
@@ -34,4 +46,57 @@ impl LocalWallet {
     pub fn sec_key(&self) -> &Scalar {
         &self.sk
     }
+
+    pub fn fingerprint(&self) -> String {
+        key_fingerprint(&self.pk)
+    }
+
+    /// Guards against using the wrong wallet, fails unless its fingerprint is `expected`.
+    pub fn ensure_fingerprint(&self, expected: &str) -> anyhow::Result<()> {
+        match self.fingerprint() {
+            actual if actual.eq_ignore_ascii_case(expected.trim()) => Ok(()),
+            actual => Err(anyhow!(
+                "wallet key fingerprint {actual} doesn't match expected {expected}"
+            )),
+        }
+    }
+}
+
+/// Hex of the first 8 bytes of SHA-256 of the compressed public key, short enough to compare by eye.
+pub fn key_fingerprint(pk: &Point) -> String {
+    hex::encode(&Sha256::digest(pk.to_bytes())[..8])
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{key_fingerprint, LocalWallet};
+
+    #[test]
+    fn test_key_fingerprint() {
+        let wallet = LocalWallet::from_hex(
+            "0101010101010101010101010101010101010101010101010101010101010101",
+        )
+        .unwrap();
+        let fingerprint = wallet.fingerprint();
+
+        assert_eq!(fingerprint.len(), 16);
+        assert_eq!(fingerprint, key_fingerprint(wallet.pub_key()));
+        assert_eq!(
+            fingerprint,
+            LocalWallet::from_hex(
+                "0101010101010101010101010101010101010101010101010101010101010101"
+            )
+            .unwrap()
+            .fingerprint()
+        );
+
+        assert!(wallet.ensure_fingerprint(&fingerprint).is_ok());
+        assert!(wallet
+            .ensure_fingerprint(&fingerprint.to_uppercase())
+            .is_ok());
+        assert!(LocalWallet::new()
+            .unwrap()
+            .ensure_fingerprint(&fingerprint)
+            .is_err());
+    }
 }
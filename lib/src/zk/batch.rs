@@ -0,0 +1,166 @@
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::{One, PrimeField, UniformRand, Zero};
+use ark_groth16::{verify_proof, PreparedVerifyingKey, Proof, VerifyingKey};
+use rand::Rng;
+use std::fmt::{Display, Formatter};
+use std::ops::Neg;
+
+/// Proof at `index` of the batch passed to [`verify_batch`] was rejected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidBatchProof {
+    pub index: usize,
+}
+
+impl Display for InvalidBatchProof {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "proof #{} of the batch is invalid", self.index)
+    }
+}
+
+impl std::error::Error for InvalidBatchProof {}
+
+/// Verifies every `(proof, public_inputs)` pair of `batch` against the same prepared `pvk`,
+/// so that verifying key is prepared once for all of them. Stops at the first proof rejected,
+/// proofs whose public inputs don't fit `pvk` included.
+pub fn verify_batch<E: PairingEngine>(
+    pvk: &PreparedVerifyingKey<E>,
+    batch: &[(Proof<E>, Vec<E::Fr>)],
+) -> Result<(), InvalidBatchProof> {
+    for (index, (proof, public_inputs)) in batch.iter().enumerate() {
+        if !verify_proof(pvk, proof, public_inputs).unwrap_or(false) {
+            return Err(InvalidBatchProof { index });
+        }
+    }
+    Ok(())
+}
+
+/// Checks every proof of `batch` against `vk` in one shot: the Groth16 equations are combined
+/// with random weights drawn from `rng` into a single product of `batch.len() + 3` pairings,
+/// instead of the three pairings per proof [`verify_batch`] makes. Tells only whether the batch
+/// as a whole is valid, an invalid proof slips through with negligible probability.
+pub fn verify_batch_combined<E: PairingEngine, R: Rng>(
+    vk: &VerifyingKey<E>,
+    batch: &[(Proof<E>, Vec<E::Fr>)],
+    rng: &mut R,
+) -> bool {
+    let mut pairs = Vec::with_capacity(batch.len() + 3);
+    let (mut alpha, mut inputs, mut c) = (
+        E::Fr::zero(),
+        E::G1Projective::zero(),
+        E::G1Projective::zero(),
+    );
+
+    for (proof, public_inputs) in batch {
+        if public_inputs.len() + 1 != vk.gamma_abc_g1.len() {
+            return false;
+        }
+
+        let r = E::Fr::rand(rng);
+        let prepared = public_inputs.iter().zip(&vk.gamma_abc_g1[1..]).fold(
+            vk.gamma_abc_g1[0].into_projective(),
+            |acc, (input, base)| acc + base.mul(input.into_repr()),
+        );
+        alpha += r;
+        inputs += prepared.mul(r.into_repr());
+        c += proof.c.mul(r.into_repr());
+        pairs.push((
+            proof.a.mul(r.into_repr()).into_affine().into(),
+            proof.b.into(),
+        ));
+    }
+
+    for (g1, g2) in [
+        (vk.alpha_g1.mul(alpha.into_repr()), vk.beta_g2),
+        (inputs, vk.gamma_g2),
+        (c, vk.delta_g2),
+    ] {
+        pairs.push((g1.neg().into_affine().into(), g2.into()));
+    }
+
+    E::product_of_pairings(&pairs).is_one()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::zk::{verify_batch, verify_batch_combined, InvalidBatchProof};
+    use crate::PairingEngine;
+    use ark_bls12_381::Fr;
+    use ark_groth16::{prepare_verifying_key, Groth16};
+    use ark_relations::lc;
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+    use ark_snark::{CircuitSpecificSetupSNARK, SNARK};
+
+    /// Proves knowledge of the square root of the public input.
+    struct SquareCircuit(u64);
+
+    impl ConstraintSynthesizer<Fr> for SquareCircuit {
+        fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+            let a = cs.new_witness_variable(|| Ok(Fr::from(self.0)))?;
+            let b = cs.new_input_variable(|| Ok(Fr::from(self.0 * self.0)))?;
+            cs.enforce_constraint(lc!() + a, lc!() + a, lc!() + b)
+        }
+    }
+
+    #[test]
+    fn test_verify_batch() {
+        let mut rng = rand::thread_rng();
+        let (pk, vk) = Groth16::<PairingEngine>::setup(SquareCircuit(1), &mut rng).unwrap();
+        let pvk = prepare_verifying_key(&vk);
+
+        let mut batch = (1..6u64)
+            .map(|a| {
+                let proof = Groth16::<PairingEngine>::prove(&pk, SquareCircuit(a), &mut rng);
+                (proof.unwrap(), vec![Fr::from(a * a)])
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(verify_batch(&pvk, &batch), Ok(()));
+
+        // out of two tampered proofs the first one is reported.
+        batch[3].1 = vec![Fr::from(5u64 * 5)];
+        batch[1].1 = vec![Fr::from(3u64 * 3)];
+        assert_eq!(
+            verify_batch(&pvk, &batch),
+            Err(InvalidBatchProof { index: 1 })
+        );
+
+        batch[1].1 = vec![Fr::from(2u64 * 2)];
+        assert_eq!(
+            verify_batch(&pvk, &batch),
+            Err(InvalidBatchProof { index: 3 })
+        );
+
+        batch[3].1 = vec![];
+        assert_eq!(
+            verify_batch(&pvk, &batch),
+            Err(InvalidBatchProof { index: 3 })
+        );
+    }
+
+    #[test]
+    fn test_verify_batch_combined() {
+        let mut rng = rand::thread_rng();
+        let (pk, vk) = Groth16::<PairingEngine>::setup(SquareCircuit(1), &mut rng).unwrap();
+
+        let mut batch = (1..9u64)
+            .map(|a| {
+                let proof = Groth16::<PairingEngine>::prove(&pk, SquareCircuit(a), &mut rng);
+                (proof.unwrap(), vec![Fr::from(a * a)])
+            })
+            .collect::<Vec<_>>();
+        assert!(verify_batch_combined(&vk, &batch, &mut rng));
+        assert!(verify_batch_combined(&vk, &[], &mut rng));
+
+        // swapped inputs keep every factor of the equations, but not their pairing.
+        let swap_inputs = |batch: &mut Vec<(_, Vec<Fr>)>| {
+            let inputs = std::mem::take(&mut batch[2].1);
+            batch[2].1 = std::mem::replace(&mut batch[5].1, inputs);
+        };
+        swap_inputs(&mut batch);
+        assert!(!verify_batch_combined(&vk, &batch, &mut rng));
+        swap_inputs(&mut batch);
+        assert!(verify_batch_combined(&vk, &batch, &mut rng));
+
+        batch[7].1 = vec![];
+        assert!(!verify_batch_combined(&vk, &batch, &mut rng));
+    }
+}
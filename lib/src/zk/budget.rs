@@ -0,0 +1,214 @@
+use std::fmt::{Display, Formatter};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use sysinfo::{System, SystemExt};
+
+/// Proving was aborted for exceeding the wall-clock limit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProveTimeExceeded {
+    /// Number of proving steps finished before the limit was hit.
+    pub completed: usize,
+    pub elapsed: Duration,
+    pub limit: Duration,
+}
+
+impl Display for ProveTimeExceeded {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "proving exceeded {}s limit after {} completed steps ({}s elapsed)",
+            self.limit.as_secs_f64(),
+            self.completed,
+            self.elapsed.as_secs_f64()
+        )
+    }
+}
+
+impl std::error::Error for ProveTimeExceeded {}
+
+/// Source of the amount of memory available to the process, in bytes.
+pub trait MemorySource {
+    fn available_memory(&self) -> u64;
+}
+
+/// Memory available on the machine, as reported by the OS.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemMemory;
+
+impl MemorySource for SystemMemory {
+    fn available_memory(&self) -> u64 {
+        let mut system = System::new();
+        system.refresh_memory();
+        system.available_memory()
+    }
+}
+
+/// Holds off further proving while available memory is below `min_free` bytes,
+/// since running out of it gets the prover killed and the work done so far lost.
+pub struct MemoryWatchdog {
+    source: Box<dyn MemorySource + Send + Sync>,
+    min_free: u64,
+    poll_interval: Duration,
+}
+
+impl MemoryWatchdog {
+    pub fn new(min_free: u64) -> Self {
+        Self::with_source(SystemMemory, min_free)
+    }
+
+    pub fn with_source<S: MemorySource + Send + Sync + 'static>(source: S, min_free: u64) -> Self {
+        Self {
+            source: Box::new(source),
+            min_free,
+            poll_interval: Duration::from_secs(1),
+        }
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn is_throttled(&self) -> bool {
+        self.source.available_memory() < self.min_free
+    }
+
+    /// Blocks until enough memory is available, returning how many times it had to wait.
+    pub fn wait_for_memory(&self) -> usize {
+        let mut waits = 0;
+        while self.is_throttled() {
+            sleep(self.poll_interval);
+            waits += 1;
+        }
+        waits
+    }
+}
+
+/// Wall-clock budget for the prove step, checked between the proofs since those can't be interrupted.
+pub struct ProveBudget {
+    started: Instant,
+    limit: Option<Duration>,
+    completed: usize,
+    watchdog: Option<MemoryWatchdog>,
+}
+
+impl ProveBudget {
+    pub fn new(limit: Option<Duration>) -> Self {
+        Self {
+            started: Instant::now(),
+            limit,
+            completed: 0,
+            watchdog: None,
+        }
+    }
+
+    /// Pauses at every checkpoint until `watchdog` reports enough free memory for the next proof.
+    pub fn with_memory_watchdog(mut self, watchdog: MemoryWatchdog) -> Self {
+        self.watchdog = Some(watchdog);
+        self
+    }
+
+    pub fn unlimited() -> Self {
+        Self::new(None)
+    }
+
+    /// Marks proving step as completed, failing if the budget is spent by now,
+    /// otherwise waits for the memory to free up before the next step if watchdog is set.
+    pub fn checkpoint(&mut self) -> Result<(), ProveTimeExceeded> {
+        self.completed += 1;
+        let elapsed = self.started.elapsed();
+        match self.limit {
+            Some(limit) if elapsed > limit => {
+                return Err(ProveTimeExceeded {
+                    completed: self.completed,
+                    elapsed,
+                    limit,
+                })
+            }
+            _ => {}
+        }
+
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.wait_for_memory();
+        }
+        Ok(())
+    }
+
+    pub fn completed(&self) -> usize {
+        self.completed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::zk::{MemorySource, MemoryWatchdog, ProveBudget};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    /// Reports available memory from `readings` in turn, repeating the last one.
+    struct MockMemory {
+        readings: Vec<u64>,
+        polled: Arc<AtomicUsize>,
+    }
+
+    impl MemorySource for MockMemory {
+        fn available_memory(&self) -> u64 {
+            let i = self.polled.fetch_add(1, Ordering::SeqCst);
+            self.readings[i.min(self.readings.len() - 1)]
+        }
+    }
+
+    #[test]
+    fn test_prove_time_exceeded() {
+        let slow_prove = || sleep(Duration::from_millis(40));
+        let mut budget = ProveBudget::new(Some(Duration::from_millis(100)));
+
+        let mut result = Ok(());
+        for _ in 0..5 {
+            slow_prove();
+            result = budget.checkpoint();
+            if result.is_err() {
+                break;
+            }
+        }
+
+        let err = result.unwrap_err();
+        assert_eq!(err.completed, 3);
+        assert_eq!(budget.completed(), 3);
+        assert!(err.elapsed > err.limit);
+    }
+
+    #[test]
+    fn test_unlimited_budget() {
+        let mut budget = ProveBudget::unlimited();
+
+        assert!((0..3).all(|_| budget.checkpoint().is_ok()));
+        assert_eq!(budget.completed(), 3);
+    }
+
+    #[test]
+    fn test_throttled_under_memory_pressure() {
+        let polled = Arc::new(AtomicUsize::new(0));
+        let watchdog = MemoryWatchdog::with_source(
+            MockMemory {
+                readings: vec![100, 10, 20, 200],
+                polled: polled.clone(),
+            },
+            50,
+        )
+        .with_poll_interval(Duration::from_millis(1));
+        assert!(!watchdog.is_throttled());
+        assert!(watchdog.is_throttled());
+
+        let mut budget = ProveBudget::unlimited().with_memory_watchdog(watchdog);
+        // one more low reading is left, then memory frees up.
+        assert!(budget.checkpoint().is_ok());
+        assert_eq!(polled.load(Ordering::SeqCst), 4);
+
+        assert!(budget.checkpoint().is_ok());
+        assert_eq!(polled.load(Ordering::SeqCst), 5);
+        assert_eq!(budget.completed(), 2);
+    }
+}
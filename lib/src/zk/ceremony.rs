@@ -0,0 +1,403 @@
+use crate::{read_proving_key, write_circuit_artifacts, TaggedCurve, PROVING_KEY_FILE};
+use anyhow::anyhow;
+use ark_ec::msm::VariableBaseMSM;
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::{Field, PrimeField, UniformRand, Zero};
+use ark_groth16::ProvingKey;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
+use rand::{CryptoRng, Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::Path;
+
+/// File the transcript of the ceremony is kept in, next to the circuit keys.
+pub const CEREMONY_TRANSCRIPT_FILE: &str = "ceremony.transcript";
+/// Magic bytes prefixing ceremony transcripts.
+pub const CEREMONY_TRANSCRIPT_MAGIC: &[u8; 4] = b"ZKCR";
+
+/// Phase-2 contribution multiplying `delta` of the proving key by the contributor's secret factor,
+/// with the proof of knowing it: `s_delta = s * factor` and `r_delta = r * factor`,
+/// where `r` is derived from the transcript so far and `s`, so proofs can't be replayed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Contribution<E: PairingEngine> {
+    pub delta_after: E::G1Affine,
+    pub s: E::G1Affine,
+    pub s_delta: E::G1Affine,
+    pub r_delta: E::G2Affine,
+}
+
+impl<E: PairingEngine> Contribution<E> {
+    /// Hash contributors check their contribution is included in the transcript by.
+    pub fn hash(&self) -> [u8; 32] {
+        let mut buf = vec![];
+        self.serialize(&mut buf)
+            .expect("expected contribution to serialize");
+        Sha256::digest(&buf).into()
+    }
+}
+
+impl<E: PairingEngine> CanonicalSerialize for Contribution<E> {
+    fn serialize<W: Write>(&self, mut writer: W) -> Result<(), SerializationError> {
+        self.delta_after.serialize(&mut writer)?;
+        self.s.serialize(&mut writer)?;
+        self.s_delta.serialize(&mut writer)?;
+        self.r_delta.serialize(&mut writer)
+    }
+
+    fn serialized_size(&self) -> usize {
+        self.delta_after.serialized_size()
+            + self.s.serialized_size()
+            + self.s_delta.serialized_size()
+            + self.r_delta.serialized_size()
+    }
+}
+
+impl<E: PairingEngine> CanonicalDeserialize for Contribution<E> {
+    fn deserialize<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
+        Ok(Self {
+            delta_after: CanonicalDeserialize::deserialize(&mut reader)?,
+            s: CanonicalDeserialize::deserialize(&mut reader)?,
+            s_delta: CanonicalDeserialize::deserialize(&mut reader)?,
+            r_delta: CanonicalDeserialize::deserialize(&mut reader)?,
+        })
+    }
+}
+
+/// Ceremony transcript doesn't lead to the proving key it's checked against.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvalidTranscript {
+    /// Contribution at the index doesn't prove knowledge of its factor or isn't applied to the previous `delta`.
+    Contribution(usize),
+    /// Proving key differs from the one the contributions lead to.
+    KeyMismatch(&'static str),
+}
+
+impl Display for InvalidTranscript {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvalidTranscript::Contribution(index) => {
+                write!(f, "ceremony contribution #{index} is invalid")
+            }
+            InvalidTranscript::KeyMismatch(reason) => {
+                write!(f, "proving key doesn't match ceremony transcript: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvalidTranscript {}
+
+/// Proving key the ceremony started from, followed by the contributions made to it in order.
+/// Only `delta` is randomized, so the key is as trustworthy as the setup it started from for the rest.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CeremonyTranscript<E: PairingEngine> {
+    pub initial: ProvingKey<E>,
+    pub contributions: Vec<Contribution<E>>,
+}
+
+impl<E: PairingEngine> CeremonyTranscript<E> {
+    pub fn new(initial: ProvingKey<E>) -> Self {
+        Self {
+            initial,
+            contributions: vec![],
+        }
+    }
+
+    /// Multiplies `delta` of `pk`, the key the transcript currently ends at, by a fresh secret factor
+    /// and records the contribution. Factor is dropped right after, which is what contributors are trusted with.
+    pub fn contribute<R: Rng + CryptoRng>(
+        &mut self,
+        pk: &mut ProvingKey<E>,
+        rng: &mut R,
+    ) -> Contribution<E> {
+        let factor = loop {
+            let factor = E::Fr::rand(rng);
+            if !factor.is_zero() {
+                break factor;
+            }
+        };
+        let inverse = factor.inverse().expect("expected factor to be non-zero");
+
+        let s = E::G1Projective::rand(rng).into_affine();
+        let s_delta = s.mul(factor.into_repr()).into_affine();
+        let r = challenge::<E>(&self.digest(), &s, &s_delta);
+
+        pk.delta_g1 = pk.delta_g1.mul(factor.into_repr()).into_affine();
+        pk.vk.delta_g2 = pk.vk.delta_g2.mul(factor.into_repr()).into_affine();
+        scale::<E>(&mut pk.h_query, inverse);
+        scale::<E>(&mut pk.l_query, inverse);
+
+        let contribution = Contribution {
+            delta_after: pk.delta_g1,
+            s,
+            s_delta,
+            r_delta: r.mul(factor.into_repr()).into_affine(),
+        };
+        self.contributions.push(contribution.clone());
+        contribution
+    }
+
+    /// Contribution with factor derived from public `beacon`, e.g. a future block hash,
+    /// so the last contributor can't pick the final key alone.
+    pub fn contribute_beacon(&mut self, pk: &mut ProvingKey<E>, beacon: &[u8]) -> Contribution<E> {
+        let mut rng = ChaCha20Rng::from_seed(Sha256::digest(beacon).into());
+        self.contribute(pk, &mut rng)
+    }
+
+    /// Checks every contribution and that `pk` is the key they lead to from the initial one.
+    pub fn verify<R: Rng>(&self, pk: &ProvingKey<E>, rng: &mut R) -> Result<(), InvalidTranscript> {
+        let mut digest = Sha256::digest(&ark_bytes(&self.initial));
+        let mut delta = self.initial.delta_g1;
+        for (index, contribution) in self.contributions.iter().enumerate() {
+            let r = challenge::<E>(&digest, &contribution.s, &contribution.s_delta);
+            if contribution.s.is_zero()
+                || !same_ratio::<E>(
+                    (contribution.s, contribution.s_delta),
+                    (r, contribution.r_delta),
+                )
+                || !same_ratio::<E>((delta, contribution.delta_after), (r, contribution.r_delta))
+            {
+                return Err(InvalidTranscript::Contribution(index));
+            }
+            delta = contribution.delta_after;
+            digest = Sha256::digest(&[&digest[..], &ark_bytes(contribution)].concat());
+        }
+
+        let initial = &self.initial;
+        if pk.delta_g1 != delta {
+            return Err(InvalidTranscript::KeyMismatch(
+                "delta isn't the one of the last contribution",
+            ));
+        }
+        if !same_ratio::<E>(
+            (initial.delta_g1, pk.delta_g1),
+            (initial.vk.delta_g2, pk.vk.delta_g2),
+        ) {
+            return Err(InvalidTranscript::KeyMismatch(
+                "delta isn't scaled alike in G1 and G2",
+            ));
+        }
+        if pk.vk.alpha_g1 != initial.vk.alpha_g1
+            || pk.vk.beta_g2 != initial.vk.beta_g2
+            || pk.vk.gamma_g2 != initial.vk.gamma_g2
+            || pk.vk.gamma_abc_g1 != initial.vk.gamma_abc_g1
+            || pk.beta_g1 != initial.beta_g1
+            || pk.a_query != initial.a_query
+            || pk.b_g1_query != initial.b_g1_query
+            || pk.b_g2_query != initial.b_g2_query
+        {
+            return Err(InvalidTranscript::KeyMismatch(
+                "key is changed other than by contributions",
+            ));
+        }
+
+        for (before, after) in [
+            (&initial.h_query, &pk.h_query),
+            (&initial.l_query, &pk.l_query),
+        ] {
+            if before.len() != after.len() {
+                return Err(InvalidTranscript::KeyMismatch("query sizes differ"));
+            }
+            // queries must be divided by the same factor delta is multiplied by, checked at once for random combination of them.
+            let weights = (0..before.len())
+                .map(|_| E::Fr::rand(rng).into_repr())
+                .collect::<Vec<_>>();
+            let before = VariableBaseMSM::multi_scalar_mul(before, &weights).into_affine();
+            let after = VariableBaseMSM::multi_scalar_mul(after, &weights).into_affine();
+            if !same_ratio::<E>((after, before), (initial.vk.delta_g2, pk.vk.delta_g2)) {
+                return Err(InvalidTranscript::KeyMismatch(
+                    "queries aren't scaled along with delta",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Digest of the transcript so far, which the next contribution's challenge is derived from.
+    fn digest(&self) -> Vec<u8> {
+        self.contributions
+            .iter()
+            .fold(Sha256::digest(&ark_bytes(&self.initial)), |digest, c| {
+                Sha256::digest(&[&digest[..], &ark_bytes(c)].concat())
+            })
+            .to_vec()
+    }
+}
+
+impl<E: PairingEngine> CanonicalSerialize for CeremonyTranscript<E> {
+    fn serialize<W: Write>(&self, mut writer: W) -> Result<(), SerializationError> {
+        self.initial.serialize(&mut writer)?;
+        self.contributions.serialize(&mut writer)
+    }
+
+    fn serialized_size(&self) -> usize {
+        self.initial.serialized_size() + self.contributions.serialized_size()
+    }
+}
+
+impl<E: PairingEngine> CanonicalDeserialize for CeremonyTranscript<E> {
+    fn deserialize<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
+        Ok(Self {
+            initial: CanonicalDeserialize::deserialize(&mut reader)?,
+            contributions: CanonicalDeserialize::deserialize(&mut reader)?,
+        })
+    }
+}
+
+pub fn write_ceremony_transcript<P: AsRef<Path>, E: PairingEngine>(
+    path: P,
+    transcript: &CeremonyTranscript<E>,
+) -> anyhow::Result<()> {
+    let mut buf = CEREMONY_TRANSCRIPT_MAGIC.to_vec();
+    transcript
+        .serialize(&mut buf)
+        .map_err(|_e| anyhow!("error encoding ceremony transcript"))?;
+    fs::write(path, buf).map_err(|e| anyhow!("error writing ceremony transcript: {e}"))
+}
+
+pub fn read_ceremony_transcript<P: AsRef<Path>, E: PairingEngine>(
+    path: P,
+) -> anyhow::Result<CeremonyTranscript<E>> {
+    let buf = fs::read(path).map_err(|e| anyhow!("error reading ceremony transcript: {e}"))?;
+    let buf = buf
+        .strip_prefix(CEREMONY_TRANSCRIPT_MAGIC)
+        .ok_or_else(|| anyhow!("error decoding ceremony transcript: missing magic bytes"))?;
+    CeremonyTranscript::deserialize(buf)
+        .map_err(|e| anyhow!("error decoding ceremony transcript: {e}"))
+}
+
+/// Reads the transcript of the ceremony over circuit keys in `dir` along with its current proving key,
+/// starting the transcript from that key if there is none yet.
+pub fn read_ceremony<P: AsRef<Path>, E: PairingEngine + TaggedCurve>(
+    dir: P,
+) -> anyhow::Result<(CeremonyTranscript<E>, ProvingKey<E>)> {
+    let pk = read_proving_key::<_, E>(dir.as_ref().join(PROVING_KEY_FILE))?;
+    let transcript_path = dir.as_ref().join(CEREMONY_TRANSCRIPT_FILE);
+    let transcript = match transcript_path.exists() {
+        true => read_ceremony_transcript(transcript_path)?,
+        false => CeremonyTranscript::new(pk.clone()),
+    };
+    Ok((transcript, pk))
+}
+
+/// Replaces circuit keys in `dir` with contributed `pk`, saving `transcript` leading to it alongside.
+pub fn write_ceremony<P: AsRef<Path>, E: PairingEngine + TaggedCurve>(
+    dir: P,
+    transcript: &CeremonyTranscript<E>,
+    pk: &ProvingKey<E>,
+) -> anyhow::Result<()> {
+    write_circuit_artifacts(dir.as_ref(), pk, &pk.vk)?;
+    write_ceremony_transcript(dir.as_ref().join(CEREMONY_TRANSCRIPT_FILE), transcript)
+}
+
+/// Point `r` of the contribution's proof of knowledge, bound to the transcript `digest` and `s`.
+fn challenge<E: PairingEngine>(
+    digest: &[u8],
+    s: &E::G1Affine,
+    s_delta: &E::G1Affine,
+) -> E::G2Affine {
+    let seed = Sha256::digest(&[digest, &ark_bytes(s), &ark_bytes(s_delta)].concat());
+    E::G2Projective::rand(&mut ChaCha20Rng::from_seed(seed.into())).into_affine()
+}
+
+/// Checks `e(a.0, b.1) == e(a.1, b.0)`, i.e. that both pairs differ by the same factor.
+fn same_ratio<E: PairingEngine>(
+    a: (E::G1Affine, E::G1Affine),
+    b: (E::G2Affine, E::G2Affine),
+) -> bool {
+    E::pairing(a.0, b.1) == E::pairing(a.1, b.0)
+}
+
+fn scale<E: PairingEngine>(points: &mut Vec<E::G1Affine>, factor: E::Fr) {
+    let scaled = points
+        .par_iter()
+        .map(|p| p.mul(factor.into_repr()))
+        .collect::<Vec<_>>();
+    *points = E::G1Projective::batch_normalization_into_affine(&scaled);
+}
+
+fn ark_bytes<T: CanonicalSerialize>(value: &T) -> Vec<u8> {
+    let mut buf = vec![];
+    value
+        .serialize(&mut buf)
+        .expect("expected ceremony value to serialize");
+    buf
+}
+
+#[cfg(test)]
+mod test {
+    use crate::zk::{CeremonyTranscript, InvalidTranscript};
+    use crate::PairingEngine;
+    use ark_ec::{AffineCurve, ProjectiveCurve};
+    use ark_ff::{PrimeField, UniformRand};
+    use ark_groth16::Groth16;
+    use ark_relations::lc;
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+    use ark_snark::{CircuitSpecificSetupSNARK, SNARK};
+
+    type Fr = <PairingEngine as ark_ec::PairingEngine>::Fr;
+
+    /// Proves knowledge of `a` and `b` with the public input being their product.
+    struct MulCircuit(u64, u64);
+
+    impl ConstraintSynthesizer<Fr> for MulCircuit {
+        fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+            let a = cs.new_witness_variable(|| Ok(Fr::from(self.0)))?;
+            let b = cs.new_witness_variable(|| Ok(Fr::from(self.1)))?;
+            let c = cs.new_input_variable(|| Ok(Fr::from(self.0 * self.1)))?;
+            cs.enforce_constraint(lc!() + a, lc!() + b, lc!() + c)
+        }
+    }
+
+    #[test]
+    fn test_ceremony_contributions() {
+        let mut rng = rand::thread_rng();
+        let (mut pk, _) = Groth16::<PairingEngine>::setup(MulCircuit(1, 1), &mut rng).unwrap();
+        let mut transcript = CeremonyTranscript::new(pk.clone());
+        assert!(transcript.verify(&pk, &mut rng).is_ok());
+
+        let first = transcript.contribute(&mut pk, &mut rng);
+        let second = transcript.contribute(&mut pk, &mut rng);
+        transcript.contribute_beacon(&mut pk, b"block 1000000");
+        assert_ne!(first.hash(), second.hash());
+        assert!(transcript.verify(&pk, &mut rng).is_ok());
+
+        let proof = Groth16::prove(&pk, MulCircuit(3, 5), &mut rng).unwrap();
+        assert!(Groth16::verify(&pk.vk, &[Fr::from(15u64)], &proof).unwrap());
+
+        let mut buf = vec![];
+        transcript.serialize(&mut buf).unwrap();
+        let decoded = CeremonyTranscript::<PairingEngine>::deserialize(&buf[..]).unwrap();
+        assert_eq!(decoded, transcript);
+
+        let mut forged = transcript.clone();
+        forged.contributions[1].s_delta = forged.contributions[1]
+            .s
+            .mul(Fr::rand(&mut rng).into_repr())
+            .into_affine();
+        assert_eq!(
+            forged.verify(&pk, &mut rng),
+            Err(InvalidTranscript::Contribution(1))
+        );
+
+        let mut tampered = pk.clone();
+        tampered.h_query[0] = tampered.h_query[0].mul(2u64).into_affine();
+        assert_eq!(
+            transcript.verify(&tampered, &mut rng),
+            Err(InvalidTranscript::KeyMismatch(
+                "queries aren't scaled along with delta"
+            ))
+        );
+
+        let previous = CeremonyTranscript::new(transcript.initial.clone());
+        assert!(matches!(
+            previous.verify(&pk, &mut rng),
+            Err(InvalidTranscript::KeyMismatch(_))
+        ));
+    }
+}
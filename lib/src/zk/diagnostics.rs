@@ -0,0 +1,352 @@
+use crate::{strip_artifact_magic, ArtifactType};
+use anyhow::anyhow;
+use ark_ec::PairingEngine;
+use ark_ff::Zero;
+use ark_groth16::{Groth16, Proof, VerifyingKey};
+use ark_serialize::CanonicalDeserialize;
+use ark_snark::SNARK;
+use sha2::{Digest, Sha256};
+use std::fmt::{Display, Formatter};
+
+/// Most likely reason for Groth'16 proof to be rejected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VerificationFailure {
+    VerifyingKeyMismatch,
+    PublicInputCount { expected: usize, actual: usize },
+    MalformedProof,
+    FieldMismatch { index: usize },
+    PairingCheckFailed,
+}
+
+impl Display for VerificationFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerificationFailure::VerifyingKeyMismatch => {
+                write!(f, "verifying key does not match the expected circuit")
+            }
+            VerificationFailure::PublicInputCount { expected, actual } => write!(
+                f,
+                "wrong number of public inputs: expected {expected}, got {actual}"
+            ),
+            VerificationFailure::MalformedProof => {
+                write!(f, "proof is not made of valid curve points")
+            }
+            VerificationFailure::FieldMismatch { index } => {
+                write!(
+                    f,
+                    "public input #{index} is not an element of the scalar field"
+                )
+            }
+            VerificationFailure::PairingCheckFailed => write!(f, "pairing check failed"),
+        }
+    }
+}
+
+/// Public inputs vector doesn't match the number of inputs verifying key was made for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PublicInputCountMismatch {
+    pub expected: usize,
+    pub got: usize,
+}
+
+impl Display for PublicInputCountMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "wrong number of public inputs: expected {}, got {}",
+            self.expected, self.got
+        )
+    }
+}
+
+impl std::error::Error for PublicInputCountMismatch {}
+
+/// Checks that `inputs` fit `vk`, since `Groth16::verify` fails cryptically otherwise.
+pub fn validate_public_inputs_len<E: PairingEngine>(
+    vk: &VerifyingKey<E>,
+    inputs: &[E::Fr],
+) -> Result<(), PublicInputCountMismatch> {
+    let expected = vk.gamma_abc_g1.len().saturating_sub(1);
+    match inputs.len() {
+        got if got != expected => Err(PublicInputCountMismatch { expected, got }),
+        _ => Ok(()),
+    }
+}
+
+/// Structural properties of a verifying key, known before any proof is at hand.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VkSummary {
+    pub public_inputs: usize,
+    pub curve: &'static str,
+}
+
+/// Cheap sanity check of the `verifying_key` without verifying any proof:
+/// it must be made of valid non-identity points on the curve of `E` and have no trailing data.
+pub fn validate_verifying_key<E: PairingEngine>(verifying_key: &[u8]) -> anyhow::Result<VkSummary> {
+    let mut reader = strip_artifact_magic(verifying_key, ArtifactType::VerifyingKey)
+        .map_err(|e| anyhow!("error reading verifying key: {e}"))?;
+    let vk = VerifyingKey::<E>::deserialize(&mut reader)
+        .map_err(|e| anyhow!("error decoding verifying key: {e}"))?;
+
+    if !reader.is_empty() {
+        return Err(anyhow!("verifying key has {} trailing bytes", reader.len()));
+    }
+    if vk.gamma_abc_g1.is_empty() {
+        return Err(anyhow!("verifying key has no input commitments"));
+    }
+    if vk.alpha_g1.is_zero()
+        || vk.beta_g2.is_zero()
+        || vk.gamma_g2.is_zero()
+        || vk.delta_g2.is_zero()
+    {
+        return Err(anyhow!("verifying key contains point at infinity"));
+    }
+
+    Ok(VkSummary {
+        public_inputs: vk.gamma_abc_g1.len() - 1,
+        curve: std::any::type_name::<E>(),
+    })
+}
+
+/// Verifies `proof` against the `declared_inputs` and returns them decoded into scalars,
+/// so that caller can read values the proof commits to, eg. the content hash.
+pub fn verify_and_extract<E: PairingEngine>(
+    verifying_key: &[u8],
+    proof: &[u8],
+    declared_inputs: &[Vec<u8>],
+) -> anyhow::Result<Vec<E::Fr>> {
+    let vk = strip_artifact_magic(verifying_key, ArtifactType::VerifyingKey)
+        .map_err(|e| anyhow!("error reading verifying key: {e}"))?;
+    let vk = VerifyingKey::<E>::deserialize(vk)
+        .map_err(|e| anyhow!("error decoding verifying key: {e}"))?;
+    let proof = Proof::<E>::deserialize(proof).map_err(|e| anyhow!("error decoding proof: {e}"))?;
+
+    let inputs = declared_inputs
+        .iter()
+        .enumerate()
+        .map(|(index, input)| {
+            let mut reader = &input[..];
+            match E::Fr::deserialize(&mut reader) {
+                Ok(fr) if reader.is_empty() => Ok(fr),
+                _ => Err(anyhow!(
+                    "public input #{index} is not an element of the scalar field"
+                )),
+            }
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    validate_public_inputs_len(&vk, &inputs)?;
+
+    match Groth16::<E>::verify(&vk, &inputs, &proof) {
+        Ok(true) => Ok(inputs),
+        Ok(false) => Err(anyhow!("proof doesn't hold for the declared public inputs")),
+        Err(e) => Err(anyhow!("error verifying proof: {e}")),
+    }
+}
+
+/// Checks common causes of verification failure in order and reports the first one found,
+/// falling back to [`VerificationFailure::PairingCheckFailed`] when proof is structurally sound.
+/// Returns `None` if proof is in fact valid.
+pub fn explain_verification_failure<E: PairingEngine>(
+    verifying_key: &[u8],
+    expected_vk_hash: Option<&[u8; 32]>,
+    proof: &[u8],
+    public_inputs: &[Vec<u8>],
+) -> Option<VerificationFailure> {
+    if expected_vk_hash.map_or(false, |h| h[..] != Sha256::digest(verifying_key)[..]) {
+        return Some(VerificationFailure::VerifyingKeyMismatch);
+    }
+
+    let vk = match VerifyingKey::<E>::deserialize(verifying_key) {
+        Ok(vk) => vk,
+        Err(_) => return Some(VerificationFailure::VerifyingKeyMismatch),
+    };
+
+    let expected = vk.gamma_abc_g1.len().saturating_sub(1);
+    if public_inputs.len() != expected {
+        return Some(VerificationFailure::PublicInputCount {
+            expected,
+            actual: public_inputs.len(),
+        });
+    }
+
+    let proof = match Proof::<E>::deserialize(proof) {
+        Ok(proof) => proof,
+        Err(_) => return Some(VerificationFailure::MalformedProof),
+    };
+
+    let mut inputs = vec![];
+    for (index, input) in public_inputs.iter().enumerate() {
+        let mut reader = &input[..];
+        match E::Fr::deserialize(&mut reader) {
+            Ok(fr) if reader.is_empty() => inputs.push(fr),
+            _ => return Some(VerificationFailure::FieldMismatch { index }),
+        }
+    }
+
+    match Groth16::<E>::verify(&vk, &inputs, &proof) {
+        Ok(true) => None,
+        _ => Some(VerificationFailure::PairingCheckFailed),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::zk::{
+        explain_verification_failure, validate_public_inputs_len, validate_verifying_key,
+        verify_and_extract, PublicInputCountMismatch, VerificationFailure,
+    };
+    use crate::PairingEngine;
+    use ark_bls12_381::Fr;
+    use ark_ff::One;
+    use ark_groth16::Groth16;
+    use ark_relations::lc;
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+    use ark_snark::{CircuitSpecificSetupSNARK, SNARK};
+    use circuits::ark_to_bytes;
+    use sha2::{Digest, Sha256};
+
+    /// Proves knowledge of `a` and `b` such that `a * b = c` for public `c`.
+    struct MulCircuit {
+        a: Fr,
+        b: Fr,
+    }
+
+    impl ConstraintSynthesizer<Fr> for MulCircuit {
+        fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+            let a = cs.new_witness_variable(|| Ok(self.a))?;
+            let b = cs.new_witness_variable(|| Ok(self.b))?;
+            let c = cs.new_input_variable(|| Ok(self.a * self.b))?;
+            cs.enforce_constraint(lc!() + a, lc!() + b, lc!() + c)
+        }
+    }
+
+    fn setup() -> (Vec<u8>, Vec<u8>, Vec<Vec<u8>>) {
+        let mut rng = rand::thread_rng();
+        let (a, b) = (Fr::from(3u64), Fr::from(5u64));
+        let (pk, vk) = Groth16::<PairingEngine>::setup(MulCircuit { a, b }, &mut rng).unwrap();
+        let proof = Groth16::<PairingEngine>::prove(&pk, MulCircuit { a, b }, &mut rng).unwrap();
+
+        (
+            ark_to_bytes(vk).unwrap(),
+            ark_to_bytes(proof).unwrap(),
+            vec![ark_to_bytes(a * b).unwrap()],
+        )
+    }
+
+    #[test]
+    fn test_validate_public_inputs_len() {
+        let mut rng = rand::thread_rng();
+        let (a, b) = (Fr::from(3u64), Fr::from(5u64));
+        let (_, vk) = Groth16::<PairingEngine>::setup(MulCircuit { a, b }, &mut rng).unwrap();
+
+        assert_eq!(validate_public_inputs_len(&vk, &[a * b]), Ok(()));
+        assert_eq!(
+            validate_public_inputs_len(&vk, &[]),
+            Err(PublicInputCountMismatch {
+                expected: 1,
+                got: 0
+            })
+        );
+        assert_eq!(
+            validate_public_inputs_len(&vk, &[a, b, a * b]),
+            Err(PublicInputCountMismatch {
+                expected: 1,
+                got: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_verifying_key() {
+        let (vk, _, _) = setup();
+
+        let summary = validate_verifying_key::<PairingEngine>(&vk).unwrap();
+        assert_eq!(summary.public_inputs, 1);
+        assert_eq!(summary.curve, std::any::type_name::<PairingEngine>());
+
+        let mut corrupted = vk.clone();
+        corrupted[8] ^= 0xff;
+        assert!(validate_verifying_key::<PairingEngine>(&corrupted).is_err());
+        assert!(validate_verifying_key::<PairingEngine>(&vk[..vk.len() - 1]).is_err());
+        assert!(validate_verifying_key::<PairingEngine>(&[vk.clone(), vec![0]].concat()).is_err());
+    }
+
+    #[test]
+    fn test_valid_proof_has_no_failure() {
+        let (vk, proof, inputs) = setup();
+        let vk_hash: [u8; 32] = Sha256::digest(&vk).into();
+
+        let failure =
+            explain_verification_failure::<PairingEngine>(&vk, Some(&vk_hash), &proof, &inputs);
+        assert_eq!(failure, None);
+    }
+
+    #[test]
+    fn test_explain_vk_mismatch() {
+        let (vk, proof, inputs) = setup();
+
+        let failure =
+            explain_verification_failure::<PairingEngine>(&vk, Some(&[0; 32]), &proof, &inputs);
+        assert_eq!(failure, Some(VerificationFailure::VerifyingKeyMismatch));
+    }
+
+    #[test]
+    fn test_explain_public_input_count() {
+        let (vk, proof, mut inputs) = setup();
+        inputs.push(ark_to_bytes(Fr::one()).unwrap());
+
+        let failure = explain_verification_failure::<PairingEngine>(&vk, None, &proof, &inputs);
+        assert_eq!(
+            failure,
+            Some(VerificationFailure::PublicInputCount {
+                expected: 1,
+                actual: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_explain_malformed_proof() {
+        let (vk, proof, inputs) = setup();
+
+        let failure =
+            explain_verification_failure::<PairingEngine>(&vk, None, &proof[1..], &inputs);
+        assert_eq!(failure, Some(VerificationFailure::MalformedProof));
+    }
+
+    #[test]
+    fn test_explain_field_mismatch() {
+        let (vk, proof, _) = setup();
+
+        let failure =
+            explain_verification_failure::<PairingEngine>(&vk, None, &proof, &[vec![0xff; 32]]);
+        assert_eq!(
+            failure,
+            Some(VerificationFailure::FieldMismatch { index: 0 })
+        );
+    }
+
+    #[test]
+    fn test_explain_pairing_check_failed() {
+        let (vk, proof, _) = setup();
+        let wrong_inputs = vec![ark_to_bytes(Fr::one()).unwrap()];
+
+        let failure =
+            explain_verification_failure::<PairingEngine>(&vk, None, &proof, &wrong_inputs);
+        assert_eq!(failure, Some(VerificationFailure::PairingCheckFailed));
+    }
+
+    #[test]
+    fn test_verify_and_extract() {
+        let (vk, proof, inputs) = setup();
+
+        let extracted = verify_and_extract::<PairingEngine>(&vk, &proof, &inputs).unwrap();
+        assert_eq!(extracted, vec![Fr::from(15u64)]);
+        assert_eq!(ark_to_bytes(extracted[0]).unwrap(), inputs[0]);
+
+        let wrong_inputs = vec![ark_to_bytes(Fr::one()).unwrap()];
+        assert!(verify_and_extract::<PairingEngine>(&vk, &proof, &wrong_inputs).is_err());
+        let err = verify_and_extract::<PairingEngine>(&vk, &proof, &[]).unwrap_err();
+        assert!(err.is::<PublicInputCountMismatch>());
+    }
+}
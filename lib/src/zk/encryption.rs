@@ -1,26 +1,202 @@
+use crate::zk::{
+    explain_verification_failure, validate_public_inputs_len, Predicate, VerificationFailure,
+};
 use crate::{
-    keypair_from_bytes, read_proving_key, read_verifying_key, write_circuit_artifacts, CurveVar,
-    Fq, PairingEngine, ProjectiveCurve, PROVING_KEY_FILE, VERIFYING_KEY_FILE,
+    ensure_same_curve, keypair_from_bytes, read_proving_key, read_verifying_key, split_curve_tag,
+    write_circuit_artifacts, CurveAffine, CurveParameters, CurveVar, Fq, Fr, PairingEngine,
+    ProjectiveCurve, TaggedCurve, PROVING_KEY_FILE, VERIFYING_KEY_FILE,
 };
 use anyhow::anyhow;
-use ark_ff::Field;
+use ark_ec::twisted_edwards_extended::{GroupAffine, GroupProjective};
+use ark_ec::{AffineCurve, ProjectiveCurve as _, TEModelParameters};
+use ark_ff::{Field, PrimeField, Zero};
 use ark_groth16::{Groth16, ProvingKey, VerifyingKey};
+use ark_serialize::{CanonicalDeserialize, CanonicalDeserializeWithFlags, EdwardsFlags};
 use ark_snark::{CircuitSpecificSetupSNARK, SNARK};
 use ark_std::UniformRand;
 use circuits::{
-    ark_from_bytes, ark_to_bytes, bytes_to_plaintext_chunks, encryption, plaintext_chunks_to_bytes,
-    EncryptCircuit, Plaintext, PublicKey, SecretKey,
+    ark_from_bytes, ark_to_bytes, bytes_to_plaintext_chunks, bytes_to_plaintext_chunks_with_width,
+    encryption, plaintext_chunks_to_bytes, plaintext_chunks_to_bytes_with_width, Ciphertext,
+    EncryptCircuit, Plaintext, PlaintextChunks, PublicKey, SecretKey,
 };
-use rand::{CryptoRng, Rng, RngCore};
+use rand::{CryptoRng, Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
 use secp256kfun::{Point, Scalar};
+use sha2::{Digest, Sha256};
+use std::fmt::{Display, Formatter};
 use std::fs;
+use std::io::Read;
 use std::path::{Path, PathBuf};
 
+/// Decodes ElGamal public key received from the counterparty,
+/// rejecting points that are off the curve, outside of the prime order subgroup, or at infinity.
+pub fn public_key_from_bytes<B: AsRef<[u8]>>(
+    bytes: B,
+) -> anyhow::Result<PublicKey<ProjectiveCurve>> {
+    let pk: CurveAffine =
+        ark_from_bytes(bytes.as_ref()).map_err(|e| anyhow!("error decoding public key: {e}"))?;
+    if pk.is_zero() || !pk.is_on_curve() || !pk.is_in_correct_subgroup_assuming_on_curve() {
+        return Err(anyhow!("public key is not a valid subgroup point"));
+    }
+
+    Ok(pk.into_projective())
+}
+
+/// Parses hex-encoded ElGamal public key, see [`public_key_from_bytes`].
+pub fn public_key_from_hex(hex: &str) -> anyhow::Result<PublicKey<ProjectiveCurve>> {
+    public_key_from_bytes(hex::decode(hex).map_err(|e| anyhow!("bad public key hex: {e}"))?)
+}
+
+/// Domain tag of the randomness one-time keys are derived from, see [`trade_key_rng`].
+const TRADE_KEY_TAG: &[u8] = b"contangle/trade-key/v1";
+
+/// Randomness the one-time key of a trade is generated from with [`ZkEncryption::keygen_derive`],
+/// derived from seller's `master` secret and the `trade_nonce` of the trade. The key is recovered
+/// from both alone, while revealing it at settlement exposes neither the master nor other trades.
+pub fn trade_key_rng(master: &Scalar, trade_nonce: &[u8]) -> ChaCha20Rng {
+    let mut hasher = Sha256::new();
+    hasher.update(TRADE_KEY_TAG);
+    hasher.update(master.to_bytes());
+    hasher.update(trade_nonce);
+    ChaCha20Rng::from_seed(hasher.finalize().into())
+}
+
+/// Reason ciphertext received from the counterparty was rejected by [`ciphertext_from_bytes`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MalformedCiphertext {
+    Encoding(String),
+    OffCurve,
+    OutsideSubgroup,
+    UnreducedChunk { index: usize },
+    Corrupted,
+}
+
+impl Display for MalformedCiphertext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MalformedCiphertext::Encoding(e) => write!(f, "error decoding ciphertext: {e}"),
+            MalformedCiphertext::OffCurve => write!(f, "ciphertext c1 is not a point on the curve"),
+            MalformedCiphertext::OutsideSubgroup => {
+                write!(f, "ciphertext c1 is outside of the prime order subgroup")
+            }
+            MalformedCiphertext::UnreducedChunk { index } => write!(
+                f,
+                "ciphertext c2 #{index} is not a reduced base field element"
+            ),
+            MalformedCiphertext::Corrupted => write!(f, "ciphertext corrupted in transit"),
+        }
+    }
+}
+
+impl std::error::Error for MalformedCiphertext {}
+
+/// Prefix of ciphertext framed by [`frame_ciphertext`].
+pub const CIPHERTEXT_FRAME_MAGIC: &[u8; 4] = b"ZKCF";
+
+/// Frames encoded `c1 || c2` with [`CIPHERTEXT_FRAME_MAGIC`] and its SHA-256 digest appended,
+/// so that bytes flipped in transit are caught before the ciphertext is decoded.
+pub fn frame_ciphertext<B: AsRef<[u8]>>(bytes: B) -> Vec<u8> {
+    let bytes = bytes.as_ref();
+    [&CIPHERTEXT_FRAME_MAGIC[..], bytes, &Sha256::digest(bytes)].concat()
+}
+
+/// Strips the frame of [`frame_ciphertext`] once its digest is checked, unframed bytes are passed as is.
+fn unframe_ciphertext(bytes: &[u8]) -> Result<&[u8], MalformedCiphertext> {
+    let framed = match bytes.strip_prefix(&CIPHERTEXT_FRAME_MAGIC[..]) {
+        Some(framed) if framed.len() >= 32 => framed,
+        _ => return Ok(bytes),
+    };
+    let (bytes, digest) = framed.split_at(framed.len() - 32);
+    match Sha256::digest(bytes).as_slice() == digest {
+        true => Ok(bytes),
+        false => Err(MalformedCiphertext::Corrupted),
+    }
+}
+
+/// Decodes ciphertext received from the counterparty, checking explicitly that `c1` is a point
+/// of the prime order subgroup and every `c2` chunk is below the base field modulus.
+/// Digest of ciphertext framed by [`frame_ciphertext`] is verified before anything is decoded.
+pub fn ciphertext_from_bytes<B: AsRef<[u8]>>(
+    bytes: B,
+) -> Result<Ciphertext<ProjectiveCurve>, MalformedCiphertext> {
+    ciphertext_from_bytes_on::<CurveParameters, _>(bytes)
+}
+
+/// [`ciphertext_from_bytes`] for counterparties always framing ciphertext, rejecting raw one.
+pub fn ciphertext_from_bytes_framed<B: AsRef<[u8]>>(
+    bytes: B,
+) -> Result<Ciphertext<ProjectiveCurve>, MalformedCiphertext> {
+    if !bytes.as_ref().starts_with(CIPHERTEXT_FRAME_MAGIC) {
+        return Err(MalformedCiphertext::Encoding(
+            "ciphertext is not framed".to_string(),
+        ));
+    }
+    ciphertext_from_bytes(bytes)
+}
+
+/// [`ciphertext_from_bytes`] over any twisted Edwards curve, the width of `c1` and of each `c2` chunk
+/// following from the encoding of the curve's base field rather than being fixed.
+pub fn ciphertext_from_bytes_on<P: TEModelParameters, B: AsRef<[u8]>>(
+    bytes: B,
+) -> Result<Ciphertext<GroupProjective<P>>, MalformedCiphertext>
+where
+    P::BaseField: PrimeField,
+{
+    let encoding =
+        |e: ark_serialize::SerializationError| MalformedCiphertext::Encoding(e.to_string());
+    // every field is read in full, so truncated input fails rather than decodes short.
+    let mut reader = unframe_ciphertext(bytes.as_ref())?;
+
+    let (x, flags): (P::BaseField, EdwardsFlags) =
+        CanonicalDeserializeWithFlags::deserialize_with_flags(&mut reader).map_err(encoding)?;
+    let c1 = match x.is_zero() {
+        true => GroupAffine::<P>::zero(),
+        false => GroupAffine::<P>::get_point_from_x(x, flags.is_positive())
+            .ok_or(MalformedCiphertext::OffCurve)?,
+    };
+    if !c1.is_in_correct_subgroup_assuming_on_curve() {
+        return Err(MalformedCiphertext::OutsideSubgroup);
+    }
+
+    let len = u64::deserialize(&mut reader).map_err(encoding)?;
+    let c2 = (0..len as usize)
+        .map(|index| {
+            let repr =
+                <P::BaseField as PrimeField>::BigInt::deserialize(&mut reader).map_err(encoding)?;
+            P::BaseField::from_repr(repr).ok_or(MalformedCiphertext::UnreducedChunk { index })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if !reader.is_empty() {
+        return Err(MalformedCiphertext::Encoding(format!(
+            "{} trailing bytes",
+            reader.len()
+        )));
+    }
+
+    Ok((c1.into_projective(), c2))
+}
+
+/// Encoded secret key of the joint key from [`ZkEncryption::keygen_derive_joint`],
+/// combined from the revealed one-time secret and the buyer's own secret.
+pub fn joint_secret_key(
+    one_time_sk: &Scalar,
+    buyer_sk: &SecretKey<ProjectiveCurve>,
+) -> anyhow::Result<Vec<u8>> {
+    let one_time_sk: Fr = ark_from_bytes(one_time_sk.to_bytes())
+        .map_err(|e| anyhow!("error casting one-time secret key: {e}"))?;
+    ark_to_bytes(one_time_sk + buyer_sk).map_err(|e| anyhow!("error encoding secret key: {e}"))
+}
+
 pub struct ZkEncryption {
     build_dir: PathBuf,
     pub(crate) params: encryption::Parameters<ProjectiveCurve>,
     proving_key: Option<ProvingKey<PairingEngine>>,
     verifying_key: Option<VerifyingKey<PairingEngine>>,
+    /// Width recorded in the plaintext header, see [`Self::with_chunk_size`].
+    chunk_size: Option<usize>,
+    /// Proofs also show plaintext hashes to the commitment, see [`Self::with_plaintext_commitment`].
+    commit_plaintext: bool,
 }
 
 impl ZkEncryption {
@@ -36,6 +212,8 @@ impl ZkEncryption {
             params,
             proving_key,
             verifying_key,
+            chunk_size: None,
+            commit_plaintext: false,
         }
     }
 
@@ -50,15 +228,60 @@ impl ZkEncryption {
             params,
             proving_key: None,
             verifying_key: Some(verifying_key),
+            chunk_size: None,
+            commit_plaintext: false,
+        }
+    }
+
+    /// Verifier of proofs against `verifying_key` obtained apart from the build directory.
+    pub fn from_verifying_key(
+        verifying_key: VerifyingKey<PairingEngine>,
+        params: encryption::Parameters<ProjectiveCurve>,
+    ) -> Self {
+        Self {
+            build_dir: PathBuf::new(),
+            params,
+            proving_key: None,
+            verifying_key: Some(verifying_key),
+            chunk_size: None,
+            commit_plaintext: false,
         }
     }
 
+    /// Decryptor needing no circuit keys, e.g. to recover bought data outside of the exchange.
+    pub fn new_decryptor(params: encryption::Parameters<ProjectiveCurve>) -> Self {
+        Self::new_inner(params)
+    }
+
     pub(crate) fn new_inner(params: encryption::Parameters<ProjectiveCurve>) -> Self {
         Self {
             build_dir: PathBuf::new(),
             params,
             proving_key: None,
             verifying_key: None,
+            chunk_size: None,
+            commit_plaintext: false,
+        }
+    }
+
+    /// Chunks plaintext `chunk_size` wide with the width recorded in its header instead of
+    /// [`circuits::PLAINTEXT_CHUNK_SIZE`], decrypting fails for plaintext chunked any other width.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+
+    /// Proves plaintext also hashes to the commitment from [`Self::plaintext_commitment`],
+    /// checked with [`Self::verify_committed_proof`]. Circuit has to be compiled in the same mode.
+    pub fn with_plaintext_commitment(mut self) -> Self {
+        self.commit_plaintext = true;
+        self
+    }
+
+    /// Proves `predicate` over the plaintext, see [`Predicate`].
+    pub fn with_predicate(self, predicate: Predicate) -> Self {
+        match predicate {
+            Predicate::Digest => self.with_plaintext_commitment(),
         }
     }
 
@@ -68,8 +291,17 @@ impl ZkEncryption {
         pk: PublicKey<ProjectiveCurve>,
         mut rng: &mut R,
     ) -> anyhow::Result<(Vec<u8>, Vec<u8>)> {
-        let msg = bytes_to_plaintext_chunks::<ProjectiveCurve, _>(msg.as_ref())
-            .map_err(|e| anyhow!("error casting plaintext: {e}"))?;
+        let msg = self.plaintext_chunks(msg.as_ref())?;
+        self.prove_chunks(msg, pk, &mut rng)
+    }
+
+    /// Encrypts already chunked `msg` of at most `params.n` chunks, returning encoded ciphertext and proof.
+    pub(crate) fn prove_chunks<R: CryptoRng + RngCore>(
+        &self,
+        msg: Plaintext<ProjectiveCurve>,
+        pk: PublicKey<ProjectiveCurve>,
+        mut rng: &mut R,
+    ) -> anyhow::Result<(Vec<u8>, Vec<u8>)> {
         let circuit = self.build_circuit(msg, pk, &mut rng)?;
         let ciphertext = circuit.resulted_ciphertext.clone();
         let proving_key = self.proving_key.as_ref().expect("proving key expected");
@@ -84,6 +316,36 @@ impl ZkEncryption {
         Ok((ciphertext_encoded, proof_encoded))
     }
 
+    /// Encoded Poseidon digest of `msg` the proofs of [`Self::with_plaintext_commitment`] are bound to.
+    pub fn plaintext_commitment<M: AsRef<[u8]>>(&self, msg: M) -> anyhow::Result<Vec<u8>> {
+        let msg = self.plaintext_chunks(msg.as_ref())?;
+        let commitment =
+            EncryptCircuit::<ProjectiveCurve, CurveVar>::plaintext_commitment(&msg, &self.params);
+        ark_to_bytes(commitment).map_err(|e| anyhow!("error encoding commitment: {e}"))
+    }
+
+    /// Chunks of `len` bytes read from `reader`, framed as [`Self::encrypt`] frames them.
+    pub(crate) fn plaintext_stream<R: Read>(
+        &self,
+        reader: R,
+        len: u64,
+    ) -> anyhow::Result<PlaintextChunks<ProjectiveCurve, R>> {
+        match self.chunk_size {
+            Some(chunk_size) => PlaintextChunks::with_width(reader, len, chunk_size),
+            None => PlaintextChunks::new(reader, len),
+        }
+    }
+
+    fn plaintext_chunks(&self, msg: &[u8]) -> anyhow::Result<Plaintext<ProjectiveCurve>> {
+        match self.chunk_size {
+            Some(chunk_size) => {
+                bytes_to_plaintext_chunks_with_width::<ProjectiveCurve, _>(msg, chunk_size)
+            }
+            None => bytes_to_plaintext_chunks::<ProjectiveCurve, _>(msg),
+        }
+        .map_err(|e| anyhow!("error casting plaintext: {e}"))
+    }
+
     pub(crate) fn build_circuit<R: CryptoRng + RngCore>(
         &self,
         msg: Plaintext<ProjectiveCurve>,
@@ -91,6 +353,17 @@ impl ZkEncryption {
         mut rng: &mut R,
     ) -> anyhow::Result<EncryptCircuit<ProjectiveCurve, CurveVar>> {
         EncryptCircuit::<ProjectiveCurve, CurveVar>::new(pk, msg, self.params.clone(), &mut rng)
+            .map(|circuit| self.configure(circuit))
+    }
+
+    fn configure(
+        &self,
+        circuit: EncryptCircuit<ProjectiveCurve, CurveVar>,
+    ) -> EncryptCircuit<ProjectiveCurve, CurveVar> {
+        match self.commit_plaintext {
+            true => circuit.with_plaintext_commitment(),
+            false => circuit,
+        }
     }
 
     pub fn decrypt<K: AsRef<[u8]>, B: AsRef<[u8]>>(
@@ -100,12 +373,61 @@ impl ZkEncryption {
     ) -> anyhow::Result<Vec<u8>> {
         let sk: SecretKey<ProjectiveCurve> =
             ark_from_bytes(sk.as_ref()).map_err(|e| anyhow!("error casting secret key: {e}"))?;
-        let ciphertext = ark_from_bytes(ciphertext.as_ref())
-            .map_err(|e| anyhow!("error casting ciphertext: {e}"))?;
+        let ciphertext = ciphertext_from_bytes(ciphertext)?;
+        let plaintext =
+            EncryptCircuit::<ProjectiveCurve, CurveVar>::decrypt(ciphertext, sk, &self.params)?;
+        self.plaintext_bytes(plaintext)
+    }
+
+    /// Decrypts `ciphertext` along with the encoded Poseidon digest of its plaintext, which matches
+    /// the commitment proofs of [`Self::with_plaintext_commitment`] are bound to for the right key.
+    pub fn decrypt_committed<K: AsRef<[u8]>, B: AsRef<[u8]>>(
+        &self,
+        sk: K,
+        ciphertext: B,
+    ) -> anyhow::Result<(Vec<u8>, Vec<u8>)> {
+        let sk: SecretKey<ProjectiveCurve> =
+            ark_from_bytes(sk.as_ref()).map_err(|e| anyhow!("error casting secret key: {e}"))?;
+        let ciphertext = ciphertext_from_bytes(ciphertext)?;
         let plaintext =
             EncryptCircuit::<ProjectiveCurve, CurveVar>::decrypt(ciphertext, sk, &self.params)?;
-        plaintext_chunks_to_bytes::<ProjectiveCurve>(plaintext)
-            .map_err(|e| anyhow!("error casting plaintext: {e}"))
+        let commitment = EncryptCircuit::<ProjectiveCurve, CurveVar>::plaintext_commitment(
+            &plaintext,
+            &self.params,
+        );
+        let commitment =
+            ark_to_bytes(commitment).map_err(|e| anyhow!("error encoding commitment: {e}"))?;
+        Ok((self.plaintext_bytes(plaintext)?, commitment))
+    }
+
+    /// Decrypts ciphertexts of the consecutive windows proved by [`crate::zk::StreamingProver`].
+    pub fn decrypt_windows<K: AsRef<[u8]>, B: AsRef<[u8]>>(
+        &self,
+        sk: K,
+        windows: &[B],
+    ) -> anyhow::Result<Vec<u8>> {
+        let sk: SecretKey<ProjectiveCurve> =
+            ark_from_bytes(sk.as_ref()).map_err(|e| anyhow!("error casting secret key: {e}"))?;
+        let mut plaintext = vec![];
+        for ciphertext in windows {
+            let ciphertext = ciphertext_from_bytes(ciphertext)?;
+            plaintext.extend(EncryptCircuit::<ProjectiveCurve, CurveVar>::decrypt(
+                ciphertext,
+                sk,
+                &self.params,
+            )?);
+        }
+        self.plaintext_bytes(plaintext)
+    }
+
+    fn plaintext_bytes(&self, plaintext: Plaintext<ProjectiveCurve>) -> anyhow::Result<Vec<u8>> {
+        match self.chunk_size {
+            Some(chunk_size) => {
+                plaintext_chunks_to_bytes_with_width::<ProjectiveCurve>(plaintext, chunk_size)
+            }
+            None => plaintext_chunks_to_bytes::<ProjectiveCurve>(plaintext),
+        }
+        .map_err(|e| anyhow!("error casting plaintext: {e}"))
     }
 
     pub fn keygen<R: CryptoRng + RngCore>(
@@ -132,40 +454,109 @@ impl ZkEncryption {
         }
     }
 
+    /// Like [`Self::keygen_derive`], but the resulting ElGamal key is combined with `buyer_pk`,
+    /// so ciphertext can only be decrypted with the buyer's secret once the one-time key is revealed.
+    pub fn keygen_derive_joint<R: CryptoRng + RngCore>(
+        &self,
+        buyer_pk: &PublicKey<ProjectiveCurve>,
+        mut rng: &mut R,
+    ) -> anyhow::Result<(PublicKey<ProjectiveCurve>, Scalar, Point)> {
+        let (native_pk, secp_sk, secp_pk) = self.keygen_derive(&mut rng)?;
+        Ok((native_pk + buyer_pk, secp_sk, secp_pk))
+    }
+
     pub fn verify_proof<PB: AsRef<[u8]>, CB: AsRef<[u8]>>(
         &self,
         proof: PB,
         ciphertext: CB,
     ) -> anyhow::Result<bool> {
+        let public_inputs = self.public_inputs(ciphertext)?;
+        self.verify_with_inputs(proof.as_ref(), &public_inputs)
+    }
+
+    /// Verifies proof of [`Self::with_plaintext_commitment`] against the encoded plaintext `commitment`.
+    pub fn verify_committed_proof<PB: AsRef<[u8]>, CB: AsRef<[u8]>, MB: AsRef<[u8]>>(
+        &self,
+        proof: PB,
+        ciphertext: CB,
+        commitment: MB,
+    ) -> anyhow::Result<bool> {
+        let commitment: Fq = ark_from_bytes(commitment.as_ref())
+            .map_err(|e| anyhow!("error decoding commitment: {e}"))?;
+        let ciphertext = ciphertext_from_bytes(ciphertext)?;
+        let public_inputs =
+            EncryptCircuit::<ProjectiveCurve, CurveVar>::get_public_inputs_committed::<PairingEngine>(
+                &ciphertext,
+                &commitment,
+                &self.params,
+            );
+        self.verify_with_inputs(proof.as_ref(), &public_inputs)
+    }
+
+    fn verify_with_inputs(&self, proof: &[u8], public_inputs: &[Fq]) -> anyhow::Result<bool> {
+        let (curve, proof) = split_curve_tag(proof);
+        ensure_same_curve(curve, Some(PairingEngine::CURVE_TAG))?;
         let proof = ark_from_bytes(proof)?;
-        let ciphertext = ark_from_bytes(ciphertext.as_ref())
-            .map_err(|_e| anyhow!("error casting ciphertext"))?;
         let verifying_key = self
             .verifying_key
             .as_ref()
             .expect("verifying key was expected");
 
-        let public_inputs = EncryptCircuit::<ProjectiveCurve, CurveVar>::get_public_inputs::<
-            PairingEngine,
-        >(&ciphertext, &self.params);
+        validate_public_inputs_len(verifying_key, public_inputs)?;
 
-        Groth16::verify(&verifying_key, &public_inputs, &proof)
+        Groth16::verify(&verifying_key, public_inputs, &proof)
             .map_err(|_e| anyhow!("error verifying Groth'16 proof"))
     }
 
+    /// Reports the most likely reason for the proof of encryption to be rejected, if any.
+    /// With `expected_vk_hash` the seller announced, a swapped verifying key is told apart too.
+    pub fn explain_proof<PB: AsRef<[u8]>, CB: AsRef<[u8]>>(
+        &self,
+        proof: PB,
+        ciphertext: CB,
+        expected_vk_hash: Option<&[u8; 32]>,
+    ) -> anyhow::Result<Option<VerificationFailure>> {
+        let verifying_key = ark_to_bytes(
+            self.verifying_key
+                .clone()
+                .expect("verifying key was expected"),
+        )
+        .map_err(|_e| anyhow!("error encoding verifying key"))?;
+
+        let public_inputs = self
+            .public_inputs(ciphertext)?
+            .into_iter()
+            .map(ark_to_bytes)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| anyhow!("error encoding public inputs: {e}"))?;
+
+        Ok(explain_verification_failure::<PairingEngine>(
+            &verifying_key,
+            expected_vk_hash,
+            proof.as_ref(),
+            &public_inputs,
+        ))
+    }
+
+    /// Public inputs of the proof of encryption derived from the encoded `ciphertext`.
+    pub(crate) fn public_inputs<CB: AsRef<[u8]>>(&self, ciphertext: CB) -> anyhow::Result<Vec<Fq>> {
+        let ciphertext = ciphertext_from_bytes(ciphertext)?;
+
+        Ok(
+            EncryptCircuit::<ProjectiveCurve, CurveVar>::get_public_inputs::<PairingEngine>(
+                &ciphertext,
+                &self.params,
+            ),
+        )
+    }
+
     pub fn compile<R: Rng + CryptoRng>(
         &self,
         mut rng: &mut R,
     ) -> anyhow::Result<(ProvingKey<PairingEngine>, VerifyingKey<PairingEngine>)> {
         let pk = ProjectiveCurve::rand(&mut rng);
         let msg = vec![Fq::from_random_bytes(&*vec![]).unwrap()];
-        let c = EncryptCircuit::<ProjectiveCurve, CurveVar>::new(
-            pk,
-            msg,
-            self.params.clone(),
-            &mut rng,
-        )
-        .unwrap();
+        let c = self.build_circuit(msg, pk, &mut rng).unwrap();
         let (pk, vk) = Groth16::<PairingEngine>::setup(c, &mut rng)
             .map_err(|e| anyhow!("error compiling circuit: {e}"))?;
 
@@ -179,7 +570,251 @@ impl ZkEncryption {
         let mut rng = rand::thread_rng();
         let pk = ProjectiveCurve::rand(&mut rng);
         let msg = vec![Fq::from_random_bytes(&*vec![]).unwrap()];
-        EncryptCircuit::<ProjectiveCurve, CurveVar>::new(pk, msg, self.params.clone(), &mut rng)
-            .unwrap()
+        self.build_circuit(msg, pk, &mut rng).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::zk::{
+        ciphertext_from_bytes, ciphertext_from_bytes_framed, ciphertext_from_bytes_on,
+        frame_ciphertext, joint_secret_key, public_key_from_bytes, public_key_from_hex,
+        trade_key_rng, MalformedCiphertext, VerificationFailure, ZkEncryption,
+    };
+    use crate::{
+        read_verifying_key, verifying_key_hash, Fq, Fr, ProjectiveCurve, VERIFYING_KEY_FILE,
+    };
+    use ark_ec::ProjectiveCurve as _;
+    use ark_ed_on_bls12_381::EdwardsAffine;
+    use ark_ff::{PrimeField, UniformRand, Zero};
+    use ark_serialize::{CanonicalSerializeWithFlags, EdwardsFlags};
+    use circuits::ark_to_bytes;
+    use proptest::prelude::*;
+    use secp256kfun::Scalar;
+    use std::fs;
+
+    #[test]
+    fn test_trade_keys_derive_from_master() {
+        let enc = ZkEncryption::new(
+            std::env::temp_dir().join("zkcp_test_trade_keys"),
+            Default::default(),
+        );
+        let master = Scalar::random(&mut rand::thread_rng());
+        let derive = |master: &Scalar, nonce: &[u8]| {
+            let (elgamal_pk, sk, pk) = enc
+                .keygen_derive(&mut trade_key_rng(master, nonce))
+                .unwrap();
+            (elgamal_pk, sk.to_bytes(), pk)
+        };
+
+        let first = derive(&master, b"trade 1");
+        assert_eq!(first, derive(&master, b"trade 1"));
+        assert_ne!(first.1, derive(&master, b"trade 2").1);
+        let other_master = Scalar::random(&mut rand::thread_rng());
+        assert_ne!(first.1, derive(&other_master, b"trade 1").1);
+    }
+
+    #[test]
+    fn test_handshake_encrypts_to_buyer() {
+        let mut rng = rand::thread_rng();
+        let build_dir = std::env::temp_dir().join("zkcp_test_handshake");
+        ZkEncryption::new(&build_dir, Default::default())
+            .compile(&mut rng)
+            .unwrap();
+        let seller = ZkEncryption::new(&build_dir, Default::default());
+        let buyer = ZkEncryption::new_verifier(&build_dir, Default::default());
+
+        // buyer sends freshly generated key over the handshake.
+        let (buyer_sk, buyer_pk) = buyer.keygen(&mut rng).unwrap();
+        let buyer_pk =
+            public_key_from_bytes(ark_to_bytes(buyer_pk.into_affine()).unwrap()).unwrap();
+
+        let msg = vec![42; 16];
+        let (joint_pk, one_time_sk, _) = seller.keygen_derive_joint(&buyer_pk, &mut rng).unwrap();
+        let (ciphertext, proof) = seller.encrypt(&msg, joint_pk, &mut rng).unwrap();
+        assert!(buyer.verify_proof(&proof, &ciphertext).unwrap());
+        let auditor = ZkEncryption::from_verifying_key(
+            read_verifying_key(build_dir.join(VERIFYING_KEY_FILE)).unwrap(),
+            Default::default(),
+        );
+        assert!(auditor.verify_proof(&proof, &ciphertext).unwrap());
+
+        let sk = joint_secret_key(&one_time_sk, &buyer_sk).unwrap();
+        assert_eq!(buyer.decrypt(sk, &ciphertext).unwrap(), msg);
+        // revealed one-time key alone is not enough.
+        assert_ne!(
+            buyer.decrypt(one_time_sk.to_bytes(), &ciphertext).unwrap(),
+            msg
+        );
+    }
+
+    #[test]
+    fn test_explain_swapped_verifying_key() {
+        let mut rng = rand::thread_rng();
+        let build_dir = std::env::temp_dir().join("zkcp_test_explain_vk");
+        ZkEncryption::new(&build_dir, Default::default())
+            .compile(&mut rng)
+            .unwrap();
+        let seller = ZkEncryption::new(&build_dir, Default::default());
+        let (_, pk) = seller.keygen(&mut rng).unwrap();
+        let (ciphertext, proof) = seller.encrypt(&[42; 16], pk, &mut rng).unwrap();
+        // hash seller announces is taken over the key file, as it's anchored on-chain.
+        let vk = fs::read(build_dir.join(VERIFYING_KEY_FILE)).unwrap();
+        let announced = verifying_key_hash(&vk).unwrap();
+
+        let explain = |vk_hash| seller.explain_proof(&proof, &ciphertext, vk_hash).unwrap();
+        assert_eq!(explain(Some(&announced)), None);
+        assert_eq!(
+            explain(Some(&[0; 32])),
+            Some(VerificationFailure::VerifyingKeyMismatch)
+        );
+    }
+
+    #[test]
+    fn test_reject_invalid_public_key() {
+        let identity = ark_to_bytes(ProjectiveCurve::zero().into_affine()).unwrap();
+
+        assert!(public_key_from_bytes(identity).is_err());
+        assert!(public_key_from_bytes([0xff; 32]).is_err());
+        assert!(public_key_from_bytes([1; 7]).is_err());
+        assert!(public_key_from_hex("not hex").is_err());
+    }
+
+    /// Encodes `c1` with given x coordinate followed by `c2` chunks of valid ciphertext.
+    fn with_c1_x(x: Fq, ciphertext: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![];
+        x.serialize_with_flags(&mut bytes, EdwardsFlags::default())
+            .unwrap();
+        bytes.extend(&ciphertext[bytes.len()..]);
+        bytes
+    }
+
+    #[test]
+    fn test_ciphertext_decode_other_curve() {
+        use ark_ed_on_bn254::{EdwardsParameters, EdwardsProjective, Fq};
+
+        let mut rng = rand::thread_rng();
+        let (c1, c2) = (
+            EdwardsProjective::rand(&mut rng),
+            (0..4u64).map(Fq::from).collect::<Vec<_>>(),
+        );
+        let ciphertext = ark_to_bytes((c1, c2.clone())).unwrap();
+        assert_eq!(
+            ciphertext_from_bytes_on::<EdwardsParameters, _>(&ciphertext).unwrap(),
+            (c1, c2)
+        );
+
+        let truncated = &ciphertext[..ciphertext.len() - 1];
+        assert!(matches!(
+            ciphertext_from_bytes_on::<EdwardsParameters, _>(truncated),
+            Err(MalformedCiphertext::Encoding(_))
+        ));
+    }
+
+    #[test]
+    fn test_reject_malformed_ciphertext() {
+        let mut rng = rand::thread_rng();
+        let (c1, c2) = (
+            ProjectiveCurve::rand(&mut rng),
+            (0..4u64).map(Fq::from).collect::<Vec<_>>(),
+        );
+        let ciphertext = ark_to_bytes((c1, c2.clone())).unwrap();
+        assert_eq!(ciphertext_from_bytes(&ciphertext).unwrap(), (c1, c2));
+
+        let off_curve = std::iter::repeat_with(|| Fq::rand(&mut rng))
+            .find(|x| EdwardsAffine::get_point_from_x(*x, false).is_none())
+            .unwrap();
+        assert_eq!(
+            ciphertext_from_bytes(with_c1_x(off_curve, &ciphertext)),
+            Err(MalformedCiphertext::OffCurve)
+        );
+
+        // cofactor is 8, so most of the curve points are outside of the prime order subgroup.
+        let outside_subgroup = std::iter::repeat_with(|| Fq::rand(&mut rng))
+            .find(|x| {
+                EdwardsAffine::get_point_from_x(*x, false)
+                    .map_or(false, |p| !p.is_in_correct_subgroup_assuming_on_curve())
+            })
+            .unwrap();
+        assert_eq!(
+            ciphertext_from_bytes(with_c1_x(outside_subgroup, &ciphertext)),
+            Err(MalformedCiphertext::OutsideSubgroup)
+        );
+
+        let mut over_range = ciphertext.clone();
+        let chunk = over_range.len() - 32 * 2;
+        over_range[chunk..chunk + 32].copy_from_slice(&[0xff; 32]);
+        assert_eq!(
+            ciphertext_from_bytes(over_range),
+            Err(MalformedCiphertext::UnreducedChunk { index: 2 })
+        );
+
+        assert!(matches!(
+            ciphertext_from_bytes(&ciphertext[..ciphertext.len() - 1]),
+            Err(MalformedCiphertext::Encoding(_))
+        ));
+    }
+
+    #[test]
+    fn test_framed_ciphertext_integrity() {
+        let mut rng = rand::thread_rng();
+        let (c1, c2) = (
+            ProjectiveCurve::rand(&mut rng),
+            (0..4u64).map(Fq::from).collect::<Vec<_>>(),
+        );
+        let ciphertext = ark_to_bytes((c1, c2.clone())).unwrap();
+        let framed = frame_ciphertext(&ciphertext);
+        assert_eq!(ciphertext_from_bytes(&framed).unwrap(), (c1, c2.clone()));
+        assert_eq!(ciphertext_from_bytes_framed(&framed).unwrap(), (c1, c2));
+        assert!(matches!(
+            ciphertext_from_bytes_framed(&ciphertext),
+            Err(MalformedCiphertext::Encoding(_))
+        ));
+
+        // flipped c1 byte is caught by the digest before c1 is decoded.
+        let mut flipped = framed.clone();
+        flipped[4] ^= 1;
+        assert_eq!(
+            ciphertext_from_bytes(&flipped),
+            Err(MalformedCiphertext::Corrupted)
+        );
+
+        let mut flipped_digest = framed.clone();
+        *flipped_digest.last_mut().unwrap() ^= 1;
+        assert_eq!(
+            ciphertext_from_bytes_framed(flipped_digest),
+            Err(MalformedCiphertext::Corrupted)
+        );
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(256))]
+
+        #[test]
+        fn prop_arbitrary_ciphertext_rejected(
+            bytes in prop::collection::vec(any::<u8>(), 0..512),
+        ) {
+            // bytes off the wire are rejected or decoded, never a panic or a huge allocation.
+            let _ = ciphertext_from_bytes(&bytes);
+            let _ = ciphertext_from_bytes_framed(frame_ciphertext(&bytes));
+        }
+
+        #[test]
+        fn prop_framed_ciphertext_round_trip(
+            seed in any::<[u8; 32]>(),
+            c2 in prop::collection::vec(any::<[u8; 32]>(), 0..16),
+            flip in any::<prop::sample::Index>(),
+        ) {
+            let c1 = ProjectiveCurve::prime_subgroup_generator()
+                .mul(Fr::from_le_bytes_mod_order(&seed).into_repr());
+            let c2 = c2.iter().map(|b| Fq::from_le_bytes_mod_order(b)).collect::<Vec<_>>();
+            let framed = frame_ciphertext(ark_to_bytes((c1, c2.clone())).unwrap());
+            prop_assert_eq!(ciphertext_from_bytes_framed(&framed).unwrap(), (c1, c2));
+
+            // any byte flipped past the magic breaks either the ciphertext or its digest.
+            let mut flipped = framed.clone();
+            flipped[4 + flip.index(framed.len() - 4)] ^= 1;
+            prop_assert_eq!(ciphertext_from_bytes(&flipped), Err(MalformedCiphertext::Corrupted));
+        }
     }
 }
@@ -0,0 +1,182 @@
+use crate::zk::ciphertext_from_bytes;
+use crate::{CurveAffine, CurveVar, Fq, ProjectiveCurve};
+use anyhow::anyhow;
+use ark_ec::{AffineCurve, ProjectiveCurve as _};
+use ark_ff::{BigInteger, FpParameters, PrimeField};
+use circuits::{
+    ark_from_bytes, ark_to_bytes, encryption, EncryptCircuit, Plaintext, PublicKey, Randomness,
+    SecretKey,
+};
+use serde::{Deserialize, Serialize};
+use std::ops::MulAssign;
+
+/// Way plaintext chunks are embedded into the ElGamal ciphertext.
+pub trait MessageEncoding {
+    /// Encrypts `msg` to `pk` under randomness `r`, returning the encoded ciphertext.
+    fn encrypt(
+        &self,
+        msg: &Plaintext<ProjectiveCurve>,
+        pk: &PublicKey<ProjectiveCurve>,
+        r: &Randomness<ProjectiveCurve>,
+        params: &encryption::Parameters<ProjectiveCurve>,
+    ) -> anyhow::Result<Vec<u8>>;
+
+    fn decrypt(
+        &self,
+        ciphertext: &[u8],
+        sk: SecretKey<ProjectiveCurve>,
+        params: &encryption::Parameters<ProjectiveCurve>,
+    ) -> anyhow::Result<Plaintext<ProjectiveCurve>>;
+}
+
+/// Chunks are added to the base field element hashed from the shared point, as the encryption circuit proves.
+pub struct FieldAdditive;
+
+impl MessageEncoding for FieldAdditive {
+    fn encrypt(
+        &self,
+        msg: &Plaintext<ProjectiveCurve>,
+        pk: &PublicKey<ProjectiveCurve>,
+        r: &Randomness<ProjectiveCurve>,
+        params: &encryption::Parameters<ProjectiveCurve>,
+    ) -> anyhow::Result<Vec<u8>> {
+        let ciphertext = EncryptCircuit::<ProjectiveCurve, CurveVar>::encrypt(pk, msg, r, params)
+            .map_err(|e| anyhow!("error encrypting message: {e}"))?;
+        ark_to_bytes(ciphertext).map_err(|e| anyhow!("error encoding ciphertext: {e}"))
+    }
+
+    fn decrypt(
+        &self,
+        ciphertext: &[u8],
+        sk: SecretKey<ProjectiveCurve>,
+        params: &encryption::Parameters<ProjectiveCurve>,
+    ) -> anyhow::Result<Plaintext<ProjectiveCurve>> {
+        let ciphertext = ciphertext_from_bytes(ciphertext).map_err(|e| anyhow!("{e}"))?;
+        EncryptCircuit::<ProjectiveCurve, CurveVar>::decrypt(ciphertext, sk, params)
+    }
+}
+
+/// Chunks are mapped onto curve points and added to the shared point, as in textbook ElGamal,
+/// so that ciphertexts stay homomorphic under the group operation.
+/// Point is the first one of the prime order subgroup at the x coordinate of `chunk * 256 + j`,
+/// hence chunks must leave the top byte of the field element free. Not covered by the encryption circuit.
+pub struct PointEncoded;
+
+impl PointEncoded {
+    /// Shift making room for the counter probing x coordinates.
+    const PROBE_BITS: u32 = 8;
+
+    fn encode_point(chunk: &Fq, index: usize) -> anyhow::Result<CurveAffine> {
+        let mut limit = <Fq as PrimeField>::Params::MODULUS;
+        limit.divn(Self::PROBE_BITS);
+        if chunk.into_repr() >= limit {
+            return Err(anyhow!("chunk {index} is too wide for point encoding"));
+        }
+
+        let base = *chunk * Fq::from(1u64 << Self::PROBE_BITS);
+        (0..1u64 << Self::PROBE_BITS)
+            .filter_map(|j| CurveAffine::get_point_from_x(base + Fq::from(j), false))
+            .find(|point| point.is_in_correct_subgroup_assuming_on_curve())
+            .ok_or(anyhow!("no curve point found for chunk {index}"))
+    }
+
+    fn decode_point(point: &CurveAffine) -> Fq {
+        let mut repr = point.x.into_repr();
+        repr.divn(Self::PROBE_BITS);
+        Fq::from_repr(repr).expect("expected shifted coordinate to be below the modulus")
+    }
+}
+
+impl MessageEncoding for PointEncoded {
+    fn encrypt(
+        &self,
+        msg: &Plaintext<ProjectiveCurve>,
+        pk: &PublicKey<ProjectiveCurve>,
+        r: &Randomness<ProjectiveCurve>,
+        _params: &encryption::Parameters<ProjectiveCurve>,
+    ) -> anyhow::Result<Vec<u8>> {
+        let mut c1 = ProjectiveCurve::prime_subgroup_generator();
+        c1.mul_assign(r.0);
+        let mut shared = *pk;
+        shared.mul_assign(r.0);
+
+        let c2 = msg
+            .iter()
+            .enumerate()
+            .map(|(i, m)| Ok(Self::encode_point(m, i)?.into_projective() + shared))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        ark_to_bytes((c1, c2)).map_err(|e| anyhow!("error encoding ciphertext: {e}"))
+    }
+
+    fn decrypt(
+        &self,
+        ciphertext: &[u8],
+        sk: SecretKey<ProjectiveCurve>,
+        _params: &encryption::Parameters<ProjectiveCurve>,
+    ) -> anyhow::Result<Plaintext<ProjectiveCurve>> {
+        let (c1, c2): (ProjectiveCurve, Vec<ProjectiveCurve>) =
+            ark_from_bytes(ciphertext).map_err(|e| anyhow!("error decoding ciphertext: {e}"))?;
+        let mut shared = c1;
+        shared.mul_assign(sk);
+
+        Ok(c2
+            .into_iter()
+            .map(|c2i| Self::decode_point(&(c2i - shared).into_affine()))
+            .collect())
+    }
+}
+
+/// [`MessageEncoding`] of the sale, recorded in the [`crate::SaleManifest`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageEncodingKind {
+    #[default]
+    FieldAdditive,
+    PointEncoded,
+}
+
+impl MessageEncodingKind {
+    pub fn encoding(self) -> &'static dyn MessageEncoding {
+        match self {
+            MessageEncodingKind::FieldAdditive => &FieldAdditive,
+            MessageEncodingKind::PointEncoded => &PointEncoded,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::zk::MessageEncodingKind;
+    use crate::{CurveAffine, CurveVar, Fq, ProjectiveCurve};
+    use ark_ff::One;
+    use ark_std::UniformRand;
+    use circuits::{bytes_to_plaintext_chunks_sized, encryption, EncryptCircuit, Randomness};
+
+    #[test]
+    fn test_message_encodings_round_trip() {
+        let mut rng = rand::thread_rng();
+        let params = encryption::Parameters::<ProjectiveCurve>::default_multi(4);
+        let (sk, pk) = EncryptCircuit::<ProjectiveCurve, CurveVar>::keygen(&mut rng).unwrap();
+        let msg = bytes_to_plaintext_chunks_sized::<ProjectiveCurve, _>([0xff; 120], 30).unwrap();
+
+        for kind in [
+            MessageEncodingKind::FieldAdditive,
+            MessageEncodingKind::PointEncoded,
+        ] {
+            let r = Randomness::rand(&mut rng);
+            let ciphertext = kind.encoding().encrypt(&msg, &pk, &r, &params).unwrap();
+            assert_eq!(
+                kind.encoding().decrypt(&ciphertext, sk, &params).unwrap(),
+                msg,
+                "{kind:?}"
+            );
+        }
+
+        let wide = vec![-Fq::one()];
+        let r = Randomness::rand(&mut rng);
+        assert!(MessageEncodingKind::PointEncoded
+            .encoding()
+            .encrypt(&wide, &pk, &r, &params)
+            .is_err());
+    }
+}
@@ -1,11 +1,29 @@
+mod batch;
+mod budget;
+mod ceremony;
+mod diagnostics;
 mod encryption;
+mod message_encoding;
+mod pool;
+mod predicate;
+mod proof_system;
 mod property;
 mod sample_entries;
+mod streaming;
 mod traits;
 mod verifiable_encryption;
 
+pub use batch::*;
+pub use budget::*;
+pub use ceremony::*;
+pub use diagnostics::*;
 pub use encryption::*;
+pub use message_encoding::*;
+pub use pool::*;
+pub use predicate::*;
+pub use proof_system::*;
 pub use property::*;
 pub use sample_entries::*;
+pub use streaming::*;
 pub use traits::*;
 pub use verifiable_encryption::*;
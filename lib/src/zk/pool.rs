@@ -0,0 +1,70 @@
+use anyhow::anyhow;
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+/// Pool of `threads` threads to verify proofs on, so that verification can't take over the whole machine.
+/// Zero means as many threads as there are CPUs.
+pub fn verification_pool(threads: usize) -> anyhow::Result<ThreadPool> {
+    ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .thread_name(|i| format!("zkcp-verify-{i}"))
+        .build()
+        .map_err(|e| anyhow!("error starting verification pool: {e}"))
+}
+
+/// Checks `items` with `verify` concurrently on `pool`, returning results in the order of `items`.
+pub fn verify_concurrently<T, F>(
+    pool: &ThreadPool,
+    items: &[T],
+    verify: F,
+) -> anyhow::Result<Vec<bool>>
+where
+    T: Sync,
+    F: Fn(&T) -> anyhow::Result<bool> + Send + Sync,
+{
+    pool.install(|| items.par_iter().map(&verify).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::zk::{verification_pool, verify_concurrently};
+    use sha2::{Digest, Sha256};
+
+    #[test]
+    fn test_results_independent_of_thread_count() {
+        // proof is valid if it's the digest of the ciphertext.
+        let items = (0..64u8)
+            .map(|i| {
+                let ciphertext = vec![i; 1024];
+                let mut proof = Sha256::digest(&ciphertext).to_vec();
+                if i == 37 {
+                    proof[0] ^= 1;
+                }
+                (ciphertext, proof)
+            })
+            .collect::<Vec<_>>();
+        let verify = |(ciphertext, proof): &(Vec<u8>, Vec<u8>)| {
+            Ok(Sha256::digest(ciphertext).as_slice() == &proof[..])
+        };
+
+        let sequential = items
+            .iter()
+            .map(verify)
+            .collect::<anyhow::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(sequential.iter().position(|valid| !valid), Some(37));
+        assert_eq!(sequential.iter().filter(|valid| !**valid).count(), 1);
+
+        for threads in [0, 1, 2, 4, 8] {
+            let pool = verification_pool(threads).unwrap();
+            if threads > 0 {
+                assert_eq!(pool.current_num_threads(), threads);
+            }
+            assert_eq!(
+                verify_concurrently(&pool, &items, verify).unwrap(),
+                sequential,
+                "{threads} threads"
+            );
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Statement over the plaintext the data encryption circuit is compiled to prove besides the encryption,
+/// recorded in [`crate::ZkConfig`]. Custom ones plug into the circuit with [`circuits::PlaintextPredicate`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Predicate {
+    /// Plaintext hashes with Poseidon to the commitment published along with the ciphertext.
+    Digest,
+}
+
+impl FromStr for Predicate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "digest" | "poseidon" => Ok(Predicate::Digest),
+            "sha256" => Err(anyhow!(
+                "sha256 digest predicate isn't available yet, use `digest` for the Poseidon one"
+            )),
+            name => Err(anyhow!("unknown predicate '{name}', expected digest")),
+        }
+    }
+}
+
+impl Display for Predicate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Predicate::Digest => write!(f, "digest"),
+        }
+    }
+}
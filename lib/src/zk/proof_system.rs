@@ -0,0 +1,140 @@
+use crate::{Fq, PairingEngine};
+use anyhow::anyhow;
+use ark_groth16::{Groth16, Proof, ProvingKey, VerifyingKey};
+use ark_relations::r1cs::ConstraintSynthesizer;
+use ark_snark::{CircuitSpecificSetupSNARK, SNARK};
+use rand::{CryptoRng, Rng};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Proof system circuits are set up, proved and verified with.
+pub trait ProofSystem {
+    type ProvingKey;
+    type VerifyingKey;
+    type Proof;
+
+    /// Setup has to be redone, and trusted anew, on every change of the circuit.
+    const CIRCUIT_SPECIFIC_SETUP: bool;
+
+    fn setup<C: ConstraintSynthesizer<Fq>, R: Rng + CryptoRng>(
+        circuit: C,
+        rng: &mut R,
+    ) -> anyhow::Result<(Self::ProvingKey, Self::VerifyingKey)>;
+
+    fn prove<C: ConstraintSynthesizer<Fq>, R: Rng + CryptoRng>(
+        pk: &Self::ProvingKey,
+        circuit: C,
+        rng: &mut R,
+    ) -> anyhow::Result<Self::Proof>;
+
+    fn verify(
+        vk: &Self::VerifyingKey,
+        public_inputs: &[Fq],
+        proof: &Self::Proof,
+    ) -> anyhow::Result<bool>;
+}
+
+/// Groth16 over [`PairingEngine`], the backend artifacts of `compile` are made for.
+pub struct Groth16Backend;
+
+impl ProofSystem for Groth16Backend {
+    type ProvingKey = ProvingKey<PairingEngine>;
+    type VerifyingKey = VerifyingKey<PairingEngine>;
+    type Proof = Proof<PairingEngine>;
+
+    const CIRCUIT_SPECIFIC_SETUP: bool = true;
+
+    fn setup<C: ConstraintSynthesizer<Fq>, R: Rng + CryptoRng>(
+        circuit: C,
+        rng: &mut R,
+    ) -> anyhow::Result<(Self::ProvingKey, Self::VerifyingKey)> {
+        Groth16::<PairingEngine>::setup(circuit, rng)
+            .map_err(|e| anyhow!("error compiling circuit: {e}"))
+    }
+
+    fn prove<C: ConstraintSynthesizer<Fq>, R: Rng + CryptoRng>(
+        pk: &Self::ProvingKey,
+        circuit: C,
+        rng: &mut R,
+    ) -> anyhow::Result<Self::Proof> {
+        Groth16::<PairingEngine>::prove(pk, circuit, rng)
+            .map_err(|e| anyhow!("error proving circuit: {e}"))
+    }
+
+    fn verify(
+        vk: &Self::VerifyingKey,
+        public_inputs: &[Fq],
+        proof: &Self::Proof,
+    ) -> anyhow::Result<bool> {
+        Groth16::<PairingEngine>::verify(vk, public_inputs, proof)
+            .map_err(|_e| anyhow!("error verifying Groth'16 proof"))
+    }
+}
+
+/// [`ProofSystem`] circuits are compiled with, recorded in [`crate::ZkConfig`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProofBackend {
+    #[default]
+    Groth16,
+}
+
+impl FromStr for ProofBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "groth16" => Ok(ProofBackend::Groth16),
+            name @ ("marlin" | "plonk") => Err(anyhow!(
+                "{name} backend with universal setup isn't available yet, only groth16 is"
+            )),
+            name => Err(anyhow!("unknown proof backend '{name}'")),
+        }
+    }
+}
+
+impl Display for ProofBackend {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProofBackend::Groth16 => write!(f, "groth16"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::zk::{Groth16Backend, ProofBackend, ProofSystem};
+    use crate::Fq;
+    use ark_relations::lc;
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+
+    /// Proves knowledge of the square root of the public input.
+    struct SquareCircuit(u64);
+
+    impl ConstraintSynthesizer<Fq> for SquareCircuit {
+        fn generate_constraints(self, cs: ConstraintSystemRef<Fq>) -> Result<(), SynthesisError> {
+            let a = cs.new_witness_variable(|| Ok(Fq::from(self.0)))?;
+            let b = cs.new_input_variable(|| Ok(Fq::from(self.0 * self.0)))?;
+            cs.enforce_constraint(lc!() + a, lc!() + a, lc!() + b)
+        }
+    }
+
+    #[test]
+    fn test_groth16_backend() {
+        let mut rng = rand::thread_rng();
+        let (pk, vk) = Groth16Backend::setup(SquareCircuit(1), &mut rng).unwrap();
+        let proof = Groth16Backend::prove(&pk, SquareCircuit(3), &mut rng).unwrap();
+        assert!(Groth16Backend::verify(&vk, &[Fq::from(9u64)], &proof).unwrap());
+        assert!(!Groth16Backend::verify(&vk, &[Fq::from(4u64)], &proof).unwrap());
+
+        assert_eq!(
+            " Groth16".parse::<ProofBackend>().unwrap(),
+            ProofBackend::Groth16
+        );
+        assert_eq!(ProofBackend::Groth16.to_string(), "groth16");
+        let err = "marlin".parse::<ProofBackend>().unwrap_err().to_string();
+        assert!(err.contains("universal setup"), "{err}");
+        assert!("stark".parse::<ProofBackend>().is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::zk::ZkEncryption;
+use crate::zk::{validate_public_inputs_len, ZkEncryption};
 use crate::{
     read_proving_key, read_verifying_key, write_circuit_artifacts, CurveVar, PairingEngine,
     ProjectiveCurve, PROVING_KEY_FILE, VERIFYING_KEY_FILE,
@@ -173,6 +173,7 @@ impl ZkPropertyVerifier {
                 &ciphertext,
                 &self.encryption.params,
             );
+        validate_public_inputs_len(verifying_key, &public_inputs)?;
 
         Groth16::verify(&verifying_key, &public_inputs, &proof)
             .map_err(|_e| anyhow!("error verifying Groth'16 proof"))
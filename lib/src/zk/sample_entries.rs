@@ -1,13 +1,14 @@
-use crate::zk::{ProofOfProperty, PropertyVerifier};
+use crate::zk::{
+    validate_public_inputs_len, verify_batch, InvalidBatchProof, ProofOfProperty, PropertyVerifier,
+};
 use crate::{
-    read_proving_key, read_verifying_key, write_circuit_artifacts, CurveVar, Fq, PairingEngine,
+    read_proving_key, read_verifying_key, write_circuit_artifacts, CurveVar, Fq, Fr, PairingEngine,
     ProjectiveCurve as Curve, PROVING_KEY_FILE, VERIFYING_KEY_FILE,
 };
 use anyhow::anyhow;
 use ark_ec::ProjectiveCurve;
-use ark_ed_on_bls12_381::Fr;
 use ark_ff::{ToConstraintField, Zero};
-use ark_groth16::{Groth16, ProvingKey, VerifyingKey};
+use ark_groth16::{prepare_verifying_key, Groth16, Proof, ProvingKey, VerifyingKey};
 use ark_serialize::SerializationError;
 use ark_snark::{CircuitSpecificSetupSNARK, SNARK};
 use circuits::poseidon::get_poseidon_params;
@@ -44,6 +45,18 @@ impl ZkSampleEntries {
         }
     }
 
+    pub(crate) fn new_inner(n: usize) -> Self {
+        Self {
+            build_dir: PathBuf::new(),
+            params: encryption::Parameters::<Curve> {
+                n,
+                poseidon: get_poseidon_params::<Curve>(2),
+            },
+            proving_key: None,
+            verifying_key: None,
+        }
+    }
+
     pub fn new_verifier<P: AsRef<Path>>(build_dir: P, n: usize) -> Self {
         let verifying_key = read_verifying_key(build_dir.as_ref().join(VERIFYING_KEY_FILE))
             .expect("verification key missing");
@@ -57,6 +70,34 @@ impl ZkSampleEntries {
             verifying_key: Some(verifying_key),
         }
     }
+
+    fn verifying_key(&self) -> &VerifyingKey<PairingEngine> {
+        self.verifying_key
+            .as_ref()
+            .expect("verifying key was expected")
+    }
+
+    /// Decodes proof of property along with its arguments prepended to `public_inputs`.
+    fn decode_proof(
+        &self,
+        args: ProofOfProperty,
+        public_inputs: Vec<Fq>,
+    ) -> anyhow::Result<(Proof<PairingEngine>, Vec<Fq>)> {
+        let proof = ark_from_bytes(args.proof)?;
+        let sample_value = args
+            .arguments
+            .into_iter()
+            .map(|(_, a)| ark_from_bytes(a))
+            .collect::<Result<Vec<_>, SerializationError>>()
+            .map_err(|_e| anyhow!("error decoding proof argument to scalar"))?;
+        let public_inputs = sample_value
+            .into_iter()
+            .chain(public_inputs)
+            .collect::<Vec<_>>();
+        validate_public_inputs_len(self.verifying_key(), &public_inputs)?;
+
+        Ok((proof, public_inputs))
+    }
 }
 
 impl PropertyVerifier for ZkSampleEntries {
@@ -107,26 +148,28 @@ impl PropertyVerifier for ZkSampleEntries {
     }
 
     fn verify_proof(&self, args: ProofOfProperty, public_inputs: Vec<Fq>) -> anyhow::Result<bool> {
-        let proof = ark_from_bytes(args.proof)?;
-        let mut sample_value = args
-            .arguments
-            .into_iter()
-            .map(|(_, a)| ark_from_bytes(a))
-            .collect::<Result<Vec<_>, SerializationError>>()
-            .map_err(|_e| anyhow!("error decoding proof argument to scalar"))?;
-        let public_inputs = sample_value
-            .into_iter()
-            .chain(public_inputs)
-            .collect::<Vec<_>>();
-        let verifying_key = self
-            .verifying_key
-            .as_ref()
-            .expect("verifying key was expected");
+        let (proof, public_inputs) = self.decode_proof(args, public_inputs)?;
 
-        Groth16::verify(&verifying_key, &public_inputs, &proof)
+        Groth16::verify(self.verifying_key(), &public_inputs, &proof)
             .map_err(|e| anyhow!("error verifying proof of property: {e}"))
     }
 
+    fn find_invalid_proof(
+        &self,
+        proofs: &[ProofOfProperty],
+        public_inputs: Vec<Fq>,
+    ) -> anyhow::Result<Option<usize>> {
+        let batch = proofs
+            .iter()
+            .map(|args| self.decode_proof(args.clone(), public_inputs.clone()))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        match verify_batch(&prepare_verifying_key(self.verifying_key()), &batch) {
+            Ok(()) => Ok(None),
+            Err(InvalidBatchProof { index }) => Ok(Some(index)),
+        }
+    }
+
     fn compile<R: Rng + CryptoRng>(
         &self,
         mut rng: &mut R,
@@ -0,0 +1,247 @@
+use crate::zk::ZkEncryption;
+use crate::ProjectiveCurve;
+use anyhow::anyhow;
+use circuits::{PlaintextChunks, PublicKey};
+use rayon::prelude::*;
+use rayon::ThreadPool;
+use std::collections::VecDeque;
+use std::io::Read;
+
+/// Verifies `(index, ciphertext, proof)` items one by one as they arrive,
+/// so that the stream can be aborted on the first invalid proof rather than after buffering all of them.
+pub struct StreamingVerifier<F> {
+    verify: F,
+    verified: usize,
+    failed_at: Option<usize>,
+}
+
+impl<F: FnMut(&[u8], &[u8]) -> anyhow::Result<bool>> StreamingVerifier<F> {
+    /// Creates verifier from `verify(ciphertext, proof)`, eg. [`crate::zk::ZkEncryption::verify_proof`].
+    pub fn new(verify: F) -> Self {
+        Self {
+            verify,
+            verified: 0,
+            failed_at: None,
+        }
+    }
+
+    /// Verifies next item, returning index of the failed one if stream must be aborted.
+    pub fn feed(&mut self, index: usize, ciphertext: &[u8], proof: &[u8]) -> Result<(), usize> {
+        if let Some(failed) = self.failed_at {
+            return Err(failed);
+        }
+
+        match (self.verify)(ciphertext, proof) {
+            Ok(true) => {
+                self.verified += 1;
+                Ok(())
+            }
+            _ => {
+                let _ = self.failed_at.insert(index);
+                Err(index)
+            }
+        }
+    }
+
+    /// Consumes items until the stream ends or the first invalid proof.
+    pub fn verify_stream<C, P, I>(&mut self, stream: I) -> anyhow::Result<usize>
+    where
+        C: AsRef<[u8]>,
+        P: AsRef<[u8]>,
+        I: IntoIterator<Item = (usize, C, P)>,
+    {
+        for (index, ciphertext, proof) in stream {
+            self.feed(index, ciphertext.as_ref(), proof.as_ref())
+                .map_err(|i| anyhow!("invalid proof at index {i}, stream aborted"))?;
+        }
+
+        Ok(self.verified)
+    }
+
+    pub fn verified(&self) -> usize {
+        self.verified
+    }
+
+    pub fn failed_at(&self) -> Option<usize> {
+        self.failed_at
+    }
+}
+
+/// Ciphertext and proof of encryption of the window at `index` of the stream.
+#[derive(Clone, Debug)]
+pub struct WindowProof {
+    pub index: usize,
+    pub ciphertext: Vec<u8>,
+    pub proof: Vec<u8>,
+}
+
+/// Encrypts data read from `reader` window by window, each window being as many chunks as the circuit
+/// takes, and proves as many windows at once as `pool` has threads. Proofs are yielded as soon as their
+/// batch is done, so that only that batch is ever held in memory.
+pub struct StreamingProver<'a, R: Read> {
+    encryption: &'a ZkEncryption,
+    chunks: PlaintextChunks<ProjectiveCurve, R>,
+    pk: PublicKey<ProjectiveCurve>,
+    pool: &'a ThreadPool,
+    proved: VecDeque<WindowProof>,
+    next_index: usize,
+    done: bool,
+}
+
+impl<'a, R: Read> StreamingProver<'a, R> {
+    /// Prover of `len` bytes read from `reader`, framed as [`ZkEncryption::encrypt`] frames them,
+    /// so that [`ZkEncryption::decrypt_windows`] restores the data from the window ciphertexts in order.
+    pub fn new(
+        encryption: &'a ZkEncryption,
+        reader: R,
+        len: u64,
+        pk: PublicKey<ProjectiveCurve>,
+        pool: &'a ThreadPool,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            encryption,
+            chunks: encryption.plaintext_stream(reader, len)?,
+            pk,
+            pool,
+            proved: VecDeque::new(),
+            next_index: 0,
+            done: false,
+        })
+    }
+
+    fn prove_batch(&mut self) -> anyhow::Result<()> {
+        let window = self.encryption.params.n;
+        let mut windows = vec![];
+        while windows.len() < self.pool.current_num_threads() && !self.done {
+            let msg = self
+                .chunks
+                .by_ref()
+                .take(window)
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            self.done = msg.len() < window;
+            if !msg.is_empty() {
+                windows.push(msg);
+            }
+        }
+
+        let (encryption, pk, first) = (self.encryption, self.pk, self.next_index);
+        let proved = self.pool.install(|| {
+            windows
+                .into_par_iter()
+                .enumerate()
+                .map(|(i, msg)| {
+                    let (ciphertext, proof) =
+                        encryption.prove_chunks(msg, pk, &mut rand::thread_rng())?;
+                    Ok(WindowProof {
+                        index: first + i,
+                        ciphertext,
+                        proof,
+                    })
+                })
+                .collect::<anyhow::Result<Vec<_>>>()
+        })?;
+
+        self.next_index += proved.len();
+        self.proved.extend(proved);
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for StreamingProver<'_, R> {
+    type Item = anyhow::Result<WindowProof>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.proved.is_empty() && !self.done {
+            if let Err(e) = self.prove_batch() {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+
+        self.proved.pop_front().map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::zk::{verification_pool, StreamingProver, StreamingVerifier, ZkEncryption};
+    use circuits::{ark_to_bytes, encryption};
+    use std::io::Read;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_stream_aborts_on_bad_proof() {
+        let mut received = 0;
+        let stream = (0..10)
+            .map(|i| (i, vec![i as u8], vec![if i == 4 { 0 } else { i as u8 }]))
+            .inspect(|_| received += 1);
+
+        // proof is valid if it matches the ciphertext.
+        let mut verifier = StreamingVerifier::new(|ciphertext, proof| Ok(ciphertext == proof));
+
+        assert!(verifier.verify_stream(stream).is_err());
+        assert_eq!(verifier.failed_at(), Some(4));
+        assert_eq!(verifier.verified(), 4);
+        assert_eq!(received, 5);
+        assert_eq!(verifier.feed(5, &[5], &[5]), Err(4));
+    }
+
+    /// Counts bytes read through it.
+    struct CountingReader<'a> {
+        inner: &'a [u8],
+        read: &'a AtomicUsize,
+    }
+
+    impl Read for CountingReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.inner.read(buf)?;
+            self.read.fetch_add(n, Ordering::SeqCst);
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_streaming_prover() {
+        let mut rng = rand::thread_rng();
+        let build_dir = std::env::temp_dir().join("zkcp_test_streaming_prover");
+        let params = encryption::Parameters::default_multi(4);
+        ZkEncryption::new(&build_dir, params.clone())
+            .compile(&mut rng)
+            .unwrap();
+        let encryption = ZkEncryption::new(&build_dir, params);
+        let (sk, pk) = encryption.keygen(&mut rng).unwrap();
+
+        // 8 bytes of length prefix and data make 22 chunks, that is 6 windows of 4.
+        let data = (0..=255).cycle().take(31 * 21 + 5).collect::<Vec<u8>>();
+        let threads = 2;
+        let pool = verification_pool(threads).unwrap();
+        let read = AtomicUsize::new(0);
+        let reader = CountingReader {
+            inner: &data,
+            read: &read,
+        };
+        let mut prover =
+            StreamingProver::new(&encryption, reader, data.len() as u64, pk, &pool).unwrap();
+
+        // first batch is proved before the rest of the data is read.
+        let first = prover.next().unwrap().unwrap();
+        assert_eq!(first.index, 0);
+        assert!(read.load(Ordering::SeqCst) <= threads * 4 * 31);
+
+        let windows = std::iter::once(Ok(first))
+            .chain(prover)
+            .collect::<anyhow::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            windows.iter().map(|w| w.index).collect::<Vec<_>>(),
+            (0..6).collect::<Vec<_>>()
+        );
+        assert!(windows
+            .iter()
+            .all(|w| encryption.verify_proof(&w.proof, &w.ciphertext).unwrap()));
+
+        let ciphertexts = windows.iter().map(|w| &w.ciphertext).collect::<Vec<_>>();
+        let sk = ark_to_bytes(sk).unwrap();
+        assert_eq!(encryption.decrypt_windows(&sk, &ciphertexts).unwrap(), data);
+    }
+}
@@ -22,6 +22,21 @@ pub trait PropertyVerifier {
 
     fn verify_proof(&self, proof: ProofOfProperty, public_inputs: Vec<Fq>) -> anyhow::Result<bool>;
 
+    /// Index of the first of `proofs` rejected against the same `public_inputs`, if any.
+    /// Verifiers backed by a single verifying key may override it to verify them in a batch.
+    fn find_invalid_proof(
+        &self,
+        proofs: &[ProofOfProperty],
+        public_inputs: Vec<Fq>,
+    ) -> anyhow::Result<Option<usize>> {
+        for (index, proof) in proofs.iter().enumerate() {
+            if !self.verify_proof(proof.clone(), public_inputs.clone())? {
+                return Ok(Some(index));
+            }
+        }
+        Ok(None)
+    }
+
     fn compile<R: Rng + CryptoRng>(
         &self,
         rng: &mut R,
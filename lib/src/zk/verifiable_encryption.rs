@@ -1,23 +1,33 @@
 use crate::zk::traits::PropertyVerifier;
-use crate::zk::ZkEncryption;
+use crate::zk::{Predicate, ProveBudget, VerificationFailure, ZkEncryption};
 use crate::{
-    read_proving_key, read_verifying_key, PairingEngine, ProjectiveCurve, PROVING_KEY_FILE,
-    VERIFYING_KEY_FILE,
+    read_proving_key, read_verifying_key, CurveVar, PairingEngine, ProjectiveCurve,
+    PROVING_KEY_FILE, VERIFYING_KEY_FILE,
 };
 use anyhow::anyhow;
 use ark_groth16::{Groth16, ProvingKey, VerifyingKey};
 use ark_snark::SNARK;
-use circuits::{ark_to_bytes, bytes_to_plaintext_chunks_direct, encryption, PublicKey, SecretKey};
+use circuits::{
+    ark_to_bytes, bytes_to_plaintext_chunks_direct, encryption, EncryptCircuit, PublicKey,
+    SecretKey,
+};
 use rand::{CryptoRng, Rng, RngCore};
+use rayon::ThreadPool;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::path::{Path, PathBuf};
 
+/// Plaintext is cast into field elements byte by byte, see [`bytes_to_plaintext_chunks_direct`].
+pub const DATA_CHUNK_SIZE: usize = 1;
+
 pub struct ZkVerifiableEncryption<PV: PropertyVerifier> {
     build_dir: PathBuf,
     encryption: ZkEncryption,
     verifier: PV,
     proving_key: Option<ProvingKey<PairingEngine>>,
     verifying_key: Option<VerifyingKey<PairingEngine>>,
+    /// Statement proof of encryption also shows over the plaintext, see [`Self::with_predicate`].
+    predicate: Option<Predicate>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -25,6 +35,10 @@ pub struct VerifiableEncryption {
     pub ciphertext: Vec<u8>,
     pub proof_of_encryption: Vec<u8>,
     pub proofs_of_property: Vec<ProofOfProperty>,
+    /// Encoded Poseidon digest of the plaintext proof of encryption is bound to,
+    /// for circuits compiled with [`Predicate::Digest`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plaintext_commitment: Option<Vec<u8>>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -33,6 +47,48 @@ pub struct ProofOfProperty {
     pub arguments: Vec<(String, Vec<u8>)>,
 }
 
+impl VerifiableEncryption {
+    /// SHA-256 digest binding the ciphertext together with all of its proofs.
+    pub fn hash(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        let mut absorb = |bytes: &[u8]| {
+            hasher.update((bytes.len() as u64).to_le_bytes());
+            hasher.update(bytes);
+        };
+
+        absorb(&self.ciphertext);
+        absorb(&self.proof_of_encryption);
+        for proof in &self.proofs_of_property {
+            absorb(&proof.proof);
+            for (name, value) in &proof.arguments {
+                absorb(name.as_bytes());
+                absorb(value);
+            }
+        }
+        // left out when missing, so that hashes of sales predating predicates stay the same.
+        if let Some(commitment) = &self.plaintext_commitment {
+            absorb(commitment);
+        }
+
+        hasher.finalize().into()
+    }
+
+    /// Fails unless the sale is bound to the hex-encoded plaintext commitment the seller has advertised.
+    /// Only meaningful once the proof of encryption was verified with [`Predicate::Digest`].
+    pub fn ensure_plaintext_commitment(&self, expected: &str) -> anyhow::Result<()> {
+        let expected = hex::decode(expected.trim_start_matches("0x"))
+            .map_err(|e| anyhow!("error decoding expected commitment: {e}"))?;
+        match &self.plaintext_commitment {
+            Some(commitment) if commitment == &expected => Ok(()),
+            Some(commitment) => Err(anyhow!(
+                "sale is bound to plaintext commitment {}, not the expected one",
+                hex::encode(commitment)
+            )),
+            None => Err(anyhow!("sale carries no plaintext commitment")),
+        }
+    }
+}
+
 impl<PV: PropertyVerifier> ZkVerifiableEncryption<PV> {
     pub fn new<P: AsRef<Path>>(
         build_dir: P,
@@ -49,6 +105,7 @@ impl<PV: PropertyVerifier> ZkVerifiableEncryption<PV> {
             verifier,
             proving_key,
             verifying_key,
+            predicate: None,
         }
     }
 
@@ -66,7 +123,18 @@ impl<PV: PropertyVerifier> ZkVerifiableEncryption<PV> {
             verifier,
             proving_key: None,
             verifying_key: Some(verifying_key),
+            predicate: None,
+        }
+    }
+
+    /// Proves and checks `predicate` over the plaintext along with its encryption,
+    /// circuit has to be compiled with the same one. Nothing changes for `None`.
+    pub fn with_predicate(mut self, predicate: Option<Predicate>) -> Self {
+        if let Some(predicate) = predicate {
+            self.encryption = self.encryption.with_predicate(predicate);
         }
+        self.predicate = predicate;
+        self
     }
 
     pub fn assess_property_and_encrypt<M: AsRef<[u8]>, R: CryptoRng + RngCore>(
@@ -74,6 +142,19 @@ impl<PV: PropertyVerifier> ZkVerifiableEncryption<PV> {
         msg: M,
         sk: SecretKey<ProjectiveCurve>,
         pk: PublicKey<ProjectiveCurve>,
+        rng: &mut R,
+    ) -> anyhow::Result<VerifiableEncryption> {
+        self.assess_property_and_encrypt_within(msg, sk, pk, &mut ProveBudget::unlimited(), rng)
+    }
+
+    /// Same as [`Self::assess_property_and_encrypt`], but aborts with [`crate::zk::ProveTimeExceeded`]
+    /// once `budget` is spent, checking it after each proof.
+    pub fn assess_property_and_encrypt_within<M: AsRef<[u8]>, R: CryptoRng + RngCore>(
+        &self,
+        msg: M,
+        sk: SecretKey<ProjectiveCurve>,
+        pk: PublicKey<ProjectiveCurve>,
+        budget: &mut ProveBudget,
         mut rng: &mut R,
     ) -> anyhow::Result<VerifiableEncryption> {
         let msg = bytes_to_plaintext_chunks_direct::<ProjectiveCurve, _>(
@@ -83,6 +164,21 @@ impl<PV: PropertyVerifier> ZkVerifiableEncryption<PV> {
         .map_err(|e| anyhow!("error casting plaintext: {e}"))?;
 
         let mut proofs_of_property = self.verifier.assess_plaintext(msg.clone(), &mut rng)?;
+        budget.checkpoint()?;
+
+        let plaintext_commitment = match self.predicate {
+            Some(Predicate::Digest) => {
+                let commitment = EncryptCircuit::<ProjectiveCurve, CurveVar>::plaintext_commitment(
+                    &msg,
+                    &self.encryption.params,
+                );
+                Some(
+                    ark_to_bytes(commitment)
+                        .map_err(|e| anyhow!("error encoding commitment: {e}"))?,
+                )
+            }
+            None => None,
+        };
 
         let enc_circuit = self.encryption.build_circuit(msg, pk, &mut rng)?;
 
@@ -91,6 +187,7 @@ impl<PV: PropertyVerifier> ZkVerifiableEncryption<PV> {
 
         let proof = Groth16::<PairingEngine>::prove(proving_key, enc_circuit, &mut rng)
             .map_err(|e| anyhow!("error proving encryption: {e}"))?;
+        budget.checkpoint()?;
 
         let ciphertext_encoded = ark_to_bytes(ciphertext.clone())
             .map_err(|e| anyhow!("error encoding ciphertext: {e}"))?;
@@ -102,31 +199,75 @@ impl<PV: PropertyVerifier> ZkVerifiableEncryption<PV> {
             sk,
             &mut rng,
         )?);
+        budget.checkpoint()?;
 
         Ok(VerifiableEncryption {
             ciphertext: ciphertext_encoded,
             proof_of_encryption: proof_encoded,
             proofs_of_property,
+            plaintext_commitment,
         })
     }
 
     pub fn verify_proof(&self, proof: &VerifiableEncryption) -> anyhow::Result<bool> {
         let public_inputs = self.verifier.prepare_public_inputs(&proof.ciphertext)?;
-        let is_valid_enc = self
-            .encryption
-            .verify_proof(&proof.proof_of_encryption, &proof.ciphertext)?;
-        let is_valid_property = proof
-            .proofs_of_property
-            .iter()
-            .map(|proof| {
+        let is_valid_enc = self.verify_encryption(proof)?;
+        let invalid_property = self
+            .verifier
+            .find_invalid_proof(&proof.proofs_of_property, public_inputs)?;
+
+        Ok(is_valid_enc && invalid_property.is_none())
+    }
+
+    /// Same as [`Self::verify_proof`], but checks both kinds of proofs concurrently on `pool`.
+    pub fn verify_proof_on(
+        &self,
+        proof: &VerifiableEncryption,
+        pool: &ThreadPool,
+    ) -> anyhow::Result<bool>
+    where
+        PV: Sync,
+    {
+        let public_inputs = self.verifier.prepare_public_inputs(&proof.ciphertext)?;
+        let (is_valid_enc, invalid_property) = pool.join(
+            || self.verify_encryption(proof),
+            || {
                 self.verifier
-                    .verify_proof(proof.clone(), public_inputs.clone())
-            })
-            .collect::<anyhow::Result<Vec<_>>>()?
-            .into_iter()
-            .all(|c| c);
+                    .find_invalid_proof(&proof.proofs_of_property, public_inputs)
+            },
+        );
+
+        Ok(is_valid_enc? && invalid_property?.is_none())
+    }
 
-        Ok(is_valid_enc && is_valid_property)
+    fn verify_encryption(&self, proof: &VerifiableEncryption) -> anyhow::Result<bool> {
+        match (self.predicate, &proof.plaintext_commitment) {
+            (Some(Predicate::Digest), Some(commitment)) => self.encryption.verify_committed_proof(
+                &proof.proof_of_encryption,
+                &proof.ciphertext,
+                commitment,
+            ),
+            (Some(predicate), None) => Err(anyhow!(
+                "sale is missing plaintext commitment of {predicate} predicate"
+            )),
+            (None, _) => self
+                .encryption
+                .verify_proof(&proof.proof_of_encryption, &proof.ciphertext),
+        }
+    }
+
+    /// Reports the most likely reason for the proof of encryption to be rejected, if any,
+    /// see [`ZkEncryption::explain_proof`].
+    pub fn explain_proof(
+        &self,
+        proof: &VerifiableEncryption,
+        expected_vk_hash: Option<&[u8; 32]>,
+    ) -> anyhow::Result<Option<VerificationFailure>> {
+        self.encryption.explain_proof(
+            &proof.proof_of_encryption,
+            &proof.ciphertext,
+            expected_vk_hash,
+        )
     }
 
     pub fn compile<R: Rng + CryptoRng>(&self, mut rng: &mut R) -> anyhow::Result<()> {
@@ -143,6 +284,15 @@ impl<PV: PropertyVerifier> ZkVerifiableEncryption<PV> {
         self.encryption.decrypt(sk, ciphertext)
     }
 
+    /// Same as [`Self::decrypt`], but also returns the commitment to the plaintext, see [`ZkEncryption::decrypt_committed`].
+    pub fn decrypt_committed<K: AsRef<[u8]>, B: AsRef<[u8]>>(
+        &self,
+        sk: K,
+        ciphertext: B,
+    ) -> anyhow::Result<(Vec<u8>, Vec<u8>)> {
+        self.encryption.decrypt_committed(sk, ciphertext)
+    }
+
     pub fn keygen<R: CryptoRng + RngCore>(
         &self,
         mut rng: &mut R,
@@ -1,49 +1,403 @@
-use crate::{InfoResponse, Step0Response, Step1Response};
+use crate::{
+    CatalogEntry, InfoResponse, QuoteResponse, SaleMetadata, Step0Response, Step1Response,
+    SwapStatus, ANNOUNCEMENT_SCHEMA_VERSION,
+};
 use anyhow::anyhow;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use ecdsa_fun::adaptor::EncryptedSignature;
 use ethers::prelude::Address;
 use ethers::types::H256;
 use scriptless_zkcp::zk::VerifiableEncryption;
-use scriptless_zkcp::{CipherDownloader, Step1Msg};
+use scriptless_zkcp::{
+    eth_address, CipherDownloader, ReceiptEntry, SaleAnnouncement, SignedQuote, Step1Msg, TxParams,
+};
 use secp256kfun::Point;
 use serde_json::json;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
 use surf::Url;
 
+/// How [`SellerClient::connect`] tolerates a seller that's momentarily unreachable, eg. restarting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConnectPolicy {
+    /// Limit on each attempt to reach the seller, none to wait as long as it takes.
+    pub timeout: Option<Duration>,
+    /// Attempts made after the first one fails.
+    pub retries: u32,
+    /// Delay before the first retry, doubled before each next one.
+    pub backoff: Duration,
+}
+
+impl Default for ConnectPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: None,
+            retries: 0,
+            backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Client for the seller daemon, which may be reachable through several endpoints.
+///
+/// Endpoints are tried in order: the first one to serve the sale info becomes active
+/// and all further requests go to it, unless it fails to serve the ciphertext,
+/// in which case the next endpoint serving a ciphertext with the announced hash is used.
+/// Steps of the swap itself only go to the active endpoint and fail rather than fail over,
+/// so that no step seller has already acted on, payment above all, is repeated elsewhere.
 pub struct SellerClient {
-    client: surf::Client,
+    endpoints: Vec<(Url, surf::Client)>,
+    active: AtomicUsize,
+    sale_hash: Mutex<Option<[u8; 32]>>,
+    wire_size: Mutex<Option<usize>>,
+    plaintext_commitment: Mutex<Option<Vec<u8>>>,
+    expires_at: Mutex<Option<DateTime<Utc>>>,
+    seller_pk: Mutex<Option<Point>>,
+    sale_id: Option<String>,
+    expected_seller: Option<Address>,
 }
 
 impl SellerClient {
-    pub fn new<S: AsRef<str>>(server_url: S) -> anyhow::Result<Self> {
-        let url = Url::parse(server_url.as_ref())
-            .map_err(|e| anyhow!("error parsing server url: {e}"))?;
-        let config = surf::Config::new().set_base_url(url).set_timeout(None);
+    /// Creates client from the comma-separated list of seller server URLs.
+    pub fn new<S: AsRef<str>>(server_urls: S) -> anyhow::Result<Self> {
+        let endpoints = server_urls
+            .as_ref()
+            .split(',')
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .map(|url| -> anyhow::Result<(Url, surf::Client)> {
+                let url = Url::parse(url).map_err(|e| anyhow!("error parsing server url: {e}"))?;
+                let config = surf::Config::new()
+                    .set_base_url(url.clone())
+                    .set_timeout(None);
+                Ok((url, config.try_into()?))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        if endpoints.is_empty() {
+            return Err(anyhow!("at least one seller address is required"));
+        }
+
         Ok(Self {
-            client: config.try_into()?,
+            endpoints,
+            active: AtomicUsize::new(0),
+            sale_hash: Mutex::new(None),
+            wire_size: Mutex::new(None),
+            plaintext_commitment: Mutex::new(None),
+            expires_at: Mutex::new(None),
+            seller_pk: Mutex::new(None),
+            sale_id: None,
+            expected_seller: None,
         })
     }
 
+    /// Addresses all requests to the sale `sale_id` of the daemon serving several.
+    pub fn with_sale<S: Into<String>>(mut self, sale_id: S) -> Self {
+        self.sale_id = Some(sale_id.into());
+        self
+    }
+
+    /// Refuses announcements unless signed by the wallet of `seller`, rather than trusting whichever daemon answers.
+    pub fn with_expected_seller(mut self, seller: Address) -> Self {
+        self.expected_seller = Some(seller);
+        self
+    }
+
     pub async fn price(&self) -> anyhow::Result<f64> {
-        let InfoResponse { price } = self
-            .client
-            .get("info")
-            .recv_json::<InfoResponse>()
+        let mut last_err = anyhow!("no seller endpoint available");
+        for (i, (url, client)) in self.endpoints.iter().enumerate() {
+            match client
+                .get(self.route("info"))
+                .recv_json::<InfoResponse>()
+                .await
+            {
+                Ok(InfoResponse {
+                    schema_version,
+                    price,
+                    sale_hash,
+                    wire_size,
+                    expires_at,
+                    plaintext_commitment,
+                    seller_pk,
+                    signature,
+                }) => {
+                    ensure_schema_version(schema_version)?;
+                    let sale_hash = decode_hash(&sale_hash)?;
+                    let plaintext_commitment = plaintext_commitment
+                        .map(hex::decode)
+                        .transpose()
+                        .map_err(|e| anyhow!("error decoding plaintext commitment: {e}"))?;
+                    if let Some(expected) = self.expected_seller {
+                        let announcement = SaleAnnouncement {
+                            schema_version,
+                            price,
+                            sale_hash,
+                            wire_size,
+                            expires_at: expires_at.clone(),
+                            plaintext_commitment: plaintext_commitment.clone(),
+                        };
+                        match (&seller_pk, &signature) {
+                            (Some(seller_pk), Some(signature)) => {
+                                announcement.verify(seller_pk, signature, expected)?
+                            }
+                            _ => return Err(anyhow!("{url} announces sale unsigned")),
+                        }
+                    }
+                    let expires_at = expires_at
+                        .map(|t| DateTime::parse_from_rfc3339(&t))
+                        .transpose()
+                        .map_err(|e| anyhow!("error decoding sale expiry: {e}"))?;
+                    let seller_pk = seller_pk
+                        .as_deref()
+                        .map(Point::from_str)
+                        .transpose()
+                        .map_err(|e| anyhow!("bad seller public key: {e}"))?;
+                    let _ = self.sale_hash.lock().unwrap().insert(sale_hash);
+                    *self.seller_pk.lock().unwrap() = seller_pk;
+                    *self.wire_size.lock().unwrap() = wire_size;
+                    *self.plaintext_commitment.lock().unwrap() = plaintext_commitment;
+                    *self.expires_at.lock().unwrap() = expires_at.map(|t| t.with_timezone(&Utc));
+                    self.active.store(i, Ordering::SeqCst);
+                    return Ok(price);
+                }
+                Err(e) => last_err = anyhow!("error requesting price from {url}: {e}"),
+            }
+        }
+
+        Err(last_err)
+    }
+
+    /// Fetches the price as [`Self::price`] does, retrying with exponential backoff as `policy` allows.
+    /// `on_retry` is called with the failed attempt number, its error and the delay before the next one.
+    /// Only covers reaching the seller, so that later steps, payment included, are never repeated.
+    pub async fn connect<F: FnMut(u32, &anyhow::Error, Duration)>(
+        &self,
+        policy: ConnectPolicy,
+        mut on_retry: F,
+    ) -> anyhow::Result<f64> {
+        let mut delay = policy.backoff;
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let res = match policy.timeout {
+                Some(timeout) => async_std::future::timeout(timeout, self.price())
+                    .await
+                    .unwrap_or_else(|_| {
+                        Err(anyhow!(
+                            "timed out reaching seller after {}s",
+                            timeout.as_secs_f64()
+                        ))
+                    }),
+                None => self.price().await,
+            };
+
+            match res {
+                Err(e) if attempt <= policy.retries => {
+                    on_retry(attempt, &e, delay);
+                    async_std::task::sleep(delay).await;
+                    delay *= 2;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// Lists sales the daemon serves alongside the default one, from the first endpoint answering.
+    pub async fn catalog(&self) -> anyhow::Result<Vec<CatalogEntry>> {
+        let mut last_err = anyhow!("no seller endpoint available");
+        for (url, client) in &self.endpoints {
+            match client.get("catalog").recv_json::<Vec<CatalogEntry>>().await {
+                Ok(entries) => return Ok(entries),
+                Err(e) => last_err = anyhow!("error requesting catalog from {url}: {e}"),
+            }
+        }
+
+        Err(last_err)
+    }
+
+    /// Fetches details of the sale the client is addressed to, from the first endpoint answering.
+    pub async fn inspect(&self) -> anyhow::Result<SaleMetadata> {
+        let mut last_err = anyhow!("no seller endpoint available");
+        for (url, client) in &self.endpoints {
+            let mut resp = match client.get(self.route("inspect")).await {
+                Ok(resp) => resp,
+                Err(e) => {
+                    last_err = anyhow!("error inspecting sale at {url}: {e}");
+                    continue;
+                }
+            };
+            if resp.status() != 200 {
+                return Err(anyhow!("{}", resp.body_string().await.unwrap()));
+            }
+            return resp
+                .body_json::<SaleMetadata>()
+                .await
+                .map_err(|e| anyhow!("error decoding sale details: {e}"));
+        }
+
+        Err(last_err)
+    }
+
+    /// Expiry of the sale offer announced by the seller, available after [`Self::price`] call.
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        *self.expires_at.lock().unwrap()
+    }
+
+    /// Bytes to download for the sale as announced by the seller, available after [`Self::price`] call.
+    pub fn wire_size(&self) -> Option<usize> {
+        *self.wire_size.lock().unwrap()
+    }
+
+    /// Commitment to the plaintext announced by the seller, if the sale is bound to one,
+    /// available after [`Self::price`] call.
+    pub fn plaintext_commitment(&self) -> Option<Vec<u8>> {
+        self.plaintext_commitment.lock().unwrap().clone()
+    }
+
+    /// Hash of the sale announced by the seller, available after [`Self::price`] call.
+    pub fn sale_hash(&self) -> Option<[u8; 32]> {
+        *self.sale_hash.lock().unwrap()
+    }
+
+    /// Key the seller signed its announcement with, if it did, available after [`Self::price`]
+    /// call. Checked to be the expected seller's if one is set.
+    pub fn seller_pk(&self) -> Option<Point> {
+        self.seller_pk.lock().unwrap().clone()
+    }
+
+    /// Asks seller for a signed quote of the sale, checked to be signed by the expected seller if set
+    /// and to be for the sale announced at [`Self::price`] call.
+    pub async fn quote(&self) -> anyhow::Result<SignedQuote> {
+        let mut resp = self
+            .active_client()
+            .get(self.route("quote"))
+            .await
+            .map_err(|e| anyhow!("error requesting quote: {e}"))?;
+        if resp.status() != 200 {
+            return Err(anyhow!("{}", resp.body_string().await.unwrap()));
+        }
+        let quote = resp
+            .body_json::<QuoteResponse>()
+            .await
+            .map_err(|e| anyhow!("error decoding quote: {e}"))?
+            .into_quote()?;
+
+        let seller_pk = quote.seller_pk()?;
+        quote.verify(&seller_pk)?;
+        if let Some(expected) = self.expected_seller {
+            let seller = eth_address(&seller_pk);
+            if seller != expected {
+                return Err(anyhow!(
+                    "quote is signed by {seller:?}, not expected seller {expected:?}"
+                ));
+            }
+        }
+        if self
+            .sale_hash()
+            .map_or(false, |h| h != quote.quote.sale_hash)
+        {
+            return Err(anyhow!("quote is for another sale than the announced one"));
+        }
+
+        Ok(quote)
+    }
+
+    /// Asks seller how far the swap with buyer at `address` got, eg. to resume it after a crash.
+    pub async fn status(&self, address: Address) -> anyhow::Result<SwapStatus> {
+        let address = hex::encode(address.to_fixed_bytes());
+        let mut resp = self
+            .active_client()
+            .get(self.route(&format!("status/{address}")))
             .await
-            .map_err(|e| anyhow!("error requesting price: {e}"))?;
+            .map_err(|e| anyhow!("error requesting swap status: {e}"))?;
+        if resp.status() != 200 {
+            return Err(anyhow!("{}", resp.body_string().await.unwrap()));
+        }
 
-        Ok(price)
+        resp.body_json::<SwapStatus>()
+            .await
+            .map_err(|e| anyhow!("error decoding swap status: {e}"))
     }
 
-    pub async fn step1(&self, address: Address) -> anyhow::Result<Step1Msg> {
+    /// Asks seller for its receipt of the trade completed with buyer at `address`, none if it has none.
+    /// Receipt is checked to be signed by the seller, and by the expected seller if set.
+    pub async fn receipt(&self, address: Address) -> anyhow::Result<Option<ReceiptEntry>> {
         let address = hex::encode(address.to_fixed_bytes());
         let mut resp = self
-            .client
-            .get(format!("step1/{address}"))
+            .active_client()
+            .get(self.route(&format!("receipt/{address}")))
+            .await
+            .map_err(|e| anyhow!("error requesting receipt: {e}"))?;
+        if resp.status() == 404 {
+            return Ok(None);
+        }
+        if resp.status() != 200 {
+            return Err(anyhow!("{}", resp.body_string().await.unwrap()));
+        }
+        let entry = resp
+            .body_json::<ReceiptEntry>()
+            .await
+            .map_err(|e| anyhow!("error decoding receipt: {e}"))?;
+
+        let (seller_pk, signature) = match (&entry.receipt.seller_pk, &entry.seller_signature) {
+            (Some(pk), Some(signature)) => (pk, signature),
+            _ => return Err(anyhow!("seller's receipt isn't signed")),
+        };
+        entry.receipt.verify(seller_pk, signature)?;
+        if let Some(expected) = self.expected_seller {
+            let seller_pk =
+                Point::from_str(seller_pk).map_err(|e| anyhow!("bad seller public key: {e}"))?;
+            let seller = eth_address(&seller_pk);
+            if seller != expected {
+                return Err(anyhow!(
+                    "receipt is signed by {seller:?}, not expected seller {expected:?}"
+                ));
+            }
+        }
+
+        Ok(Some(entry))
+    }
+
+    /// Sale of the daemon all requests are addressed to, none for the default one.
+    pub fn sale_id(&self) -> Option<&str> {
+        self.sale_id.as_deref()
+    }
+
+    pub async fn step1(&self, address: Address) -> anyhow::Result<Step1Msg> {
+        let resp = self
+            .active_client()
+            .get(self.route(&format!("step1/{}", hex::encode(address.to_fixed_bytes()))))
             .await
             .map_err(|e| anyhow!("error requesting step1: {e}"))?;
 
+        self.decode_step1(resp, address).await
+    }
+
+    /// Sends buyer's ElGamal public key, so that seller encrypts to it live.
+    pub async fn handshake(&self, address: Address, buyer_pk: &[u8]) -> anyhow::Result<Step1Msg> {
+        let resp = self
+            .active_client()
+            .post(self.route(&format!(
+                "handshake/{}",
+                hex::encode(address.to_fixed_bytes())
+            )))
+            .body(hex::encode(buyer_pk))
+            .await
+            .map_err(|e| anyhow!("error requesting handshake: {e}"))?;
+
+        self.decode_step1(resp, address).await
+    }
+
+    /// Decodes message of Step 1 made to `buyer`, failing unless it's signed by the expected seller
+    /// if there's one, before buyer signs any payment to the address it gives.
+    async fn decode_step1(
+        &self,
+        mut resp: surf::Response,
+        buyer: Address,
+    ) -> anyhow::Result<Step1Msg> {
         if resp.status() != 200 {
             return Err(anyhow!("{}", resp.body_string().await.unwrap()));
         }
@@ -53,6 +407,8 @@ impl SellerClient {
             proof_of_encryption,
             data_pk,
             address,
+            signer_pk,
+            signature,
         } = resp
             .body_json::<Step1Response>()
             .await
@@ -62,24 +418,45 @@ impl SellerClient {
         let seller_address =
             Address::from_str(&address).map_err(|e| anyhow!("bad address: {e}"))?;
 
-        Ok(Step1Msg {
+        let msg = Step1Msg {
             ciphertext,
             proof_of_encryption,
             data_pk,
             seller_address,
-        })
+        };
+        if let Some(expected) = self.expected_seller {
+            match (signer_pk, signature) {
+                (Some(signer_pk), Some(signature)) => {
+                    msg.verify(self.sale_id(), buyer, &signer_pk, &signature, expected)?
+                }
+                _ => return Err(anyhow!("seller answered step1 unsigned")),
+            }
+        }
+
+        Ok(msg)
     }
 
-    pub async fn step3(&self, pub_key: Point, enc_sig: EncryptedSignature) -> anyhow::Result<H256> {
+    /// Sends buyer's encrypted signature of the payment pinned to `tx_params`,
+    /// paying the price of `quote` if one was agreed to.
+    /// Isn't retried on another endpoint, as seller may have broadcast the payment already.
+    pub async fn step3(
+        &self,
+        pub_key: Point,
+        enc_sig: EncryptedSignature,
+        tx_params: &TxParams,
+        quote: Option<&SignedQuote>,
+    ) -> anyhow::Result<H256> {
         let pub_key = hex::encode(pub_key.to_bytes());
         let enc_sig = enc_sig.to_string();
 
         let mut resp = self
-            .client
-            .post("step3")
+            .active_client()
+            .post(self.route("step3"))
             .body(json!({
                 "pub_key": pub_key,
-                "enc_sig": enc_sig
+                "enc_sig": enc_sig,
+                "tx_params": tx_params,
+                "quote": quote.cloned().map(QuoteResponse::from)
             }))
             .await
             .map_err(|e| anyhow!("error requesting step3: {e}"))?;
@@ -95,14 +472,24 @@ impl SellerClient {
 
         H256::from_str(&tx_hash).map_err(|_e| anyhow!("error decoding hash"))
     }
-}
 
-#[async_trait]
-impl CipherDownloader for SellerClient {
-    async fn download(&self) -> anyhow::Result<VerifiableEncryption> {
-        let mut resp = self
-            .client
-            .get(format!("step0"))
+    fn route(&self, path: &str) -> String {
+        match &self.sale_id {
+            Some(id) => format!("{path}?sale={id}"),
+            None => path.to_string(),
+        }
+    }
+
+    fn active_client(&self) -> &surf::Client {
+        &self.endpoints[self.active.load(Ordering::SeqCst)].1
+    }
+
+    async fn download_from(
+        client: &surf::Client,
+        route: &str,
+    ) -> anyhow::Result<VerifiableEncryption> {
+        let mut resp = client
+            .get(route)
             .await
             .map_err(|e| anyhow!("error requesting step0: {e}"))?;
 
@@ -114,6 +501,7 @@ impl CipherDownloader for SellerClient {
             ciphertext,
             proof_of_encryption,
             proofs_of_property,
+            plaintext_commitment,
         } = resp
             .body_json::<Step0Response>()
             .await
@@ -123,6 +511,595 @@ impl CipherDownloader for SellerClient {
             ciphertext,
             proof_of_encryption,
             proofs_of_property,
+            plaintext_commitment,
         });
     }
 }
+
+#[async_trait]
+impl CipherDownloader for SellerClient {
+    async fn download(&self) -> anyhow::Result<VerifiableEncryption> {
+        let announced_hash = *self.sale_hash.lock().unwrap();
+        let mut last_err = anyhow!("no seller endpoint available");
+        let from = self.active.load(Ordering::SeqCst);
+        for (i, (url, client)) in self.endpoints.iter().enumerate().skip(from) {
+            match Self::download_from(client, &self.route("step0")).await {
+                Ok(cipher) if announced_hash.map_or(true, |h| h == cipher.hash()) => {
+                    self.active.store(i, Ordering::SeqCst);
+                    return Ok(cipher);
+                }
+                Ok(_) => last_err = anyhow!("{url} served ciphertext not matching announced sale"),
+                Err(e) => last_err = anyhow!("error downloading from {url}: {e}"),
+            }
+        }
+
+        Err(last_err)
+    }
+}
+
+/// Refuses announcements of the schema newer than this client supports, rather than misreading them.
+fn ensure_schema_version(version: u32) -> anyhow::Result<()> {
+    if version > ANNOUNCEMENT_SCHEMA_VERSION {
+        return Err(anyhow!(
+            "seller announces sale in schema version {version}, but this client supports up to {ANNOUNCEMENT_SCHEMA_VERSION}, please upgrade"
+        ));
+    }
+
+    Ok(())
+}
+
+fn decode_hash(hash: &str) -> anyhow::Result<[u8; 32]> {
+    hex::decode(hash)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(anyhow!("error decoding sale hash"))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::client::{ensure_schema_version, ConnectPolicy, SellerClient};
+    use crate::{
+        build, InfoResponse, ListingInfo, RateLimiter, ServeConfig, ANNOUNCEMENT_SCHEMA_VERSION,
+        OPENAPI_SCHEMA,
+    };
+    use chrono::{Duration, Utc};
+    use ethers::prelude::{Address, H256};
+    use futures::channel::{mpsc, oneshot};
+    use futures::StreamExt;
+    use rocket::fairing::AdHoc;
+    use rocket::{Build, Rocket};
+    use scriptless_zkcp::zk::VerifiableEncryption;
+    use scriptless_zkcp::{
+        append_receipt, eth_address, keypair_gen, read_receipts, CipherDownloader, Quote, Receipt,
+        SellerMetrics, SellerMsg, SessionPhase, Step1Msg,
+    };
+    use std::collections::HashMap;
+    use std::net::TcpListener;
+    use std::sync::Arc;
+
+    /// Launches `server` on a port picked by the OS, returning its url once it's up.
+    async fn launch(server: Rocket<Build>) -> String {
+        let (ready_tx, ready_rx) = oneshot::channel();
+        let server = server
+            .configure(rocket::Config {
+                port: 0,
+                ..rocket::Config::debug_default()
+            })
+            .attach(AdHoc::on_liftoff("ready", |rocket| {
+                let port = rocket.config().port;
+                Box::pin(async move {
+                    let _ = ready_tx.send(port);
+                })
+            }));
+        rocket::tokio::spawn(server.launch());
+        format!("http://127.0.0.1:{}", ready_rx.await.unwrap())
+    }
+
+    /// Port that was free a moment ago, nothing listens on until it's launched on.
+    fn free_port() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().port()
+    }
+
+    #[rocket::async_test]
+    async fn test_failover_to_second_endpoint() {
+        let sale = VerifiableEncryption {
+            ciphertext: vec![1, 2, 3],
+            proof_of_encryption: vec![4, 5, 6],
+            proofs_of_property: vec![],
+            plaintext_commitment: None,
+        };
+
+        let (to_runtime, mut from_server) = mpsc::channel(1);
+        let served = sale.clone();
+        rocket::tokio::spawn(async move {
+            while let Some(msg) = from_server.next().await {
+                if let SellerMsg::Step0 { resp_tx, .. } = msg {
+                    let _ = resp_tx.send(Ok(served.clone()));
+                }
+            }
+        });
+
+        let expires_at = Utc::now() + Duration::hours(1);
+        let server = build(
+            to_runtime,
+            ServeConfig {
+                price: 0.1,
+                sale_hash: sale.hash(),
+                wire_size: Some(4096),
+                expires_at: Some(expires_at),
+                ..Default::default()
+            },
+        );
+        let url = launch(server).await;
+
+        // nothing listens on the first endpoint
+        let dead = format!("http://127.0.0.1:{}", free_port());
+        let client = SellerClient::new(format!("{dead}, {url}")).unwrap();
+
+        assert_eq!(client.price().await.unwrap(), 0.1);
+        assert_eq!(
+            client.expires_at().map(|t| t.timestamp()),
+            Some(expires_at.timestamp())
+        );
+        assert_eq!(client.wire_size(), Some(4096));
+        assert_eq!(client.download().await.unwrap().hash(), sale.hash());
+    }
+
+    #[rocket::async_test]
+    async fn test_connect_retries_until_seller_is_up() {
+        let sale = VerifiableEncryption {
+            ciphertext: vec![1, 2, 3],
+            proof_of_encryption: vec![4, 5, 6],
+            proofs_of_property: vec![],
+            plaintext_commitment: None,
+        };
+        let (to_runtime, _from_server) = mpsc::channel(1);
+        let port = free_port();
+        let server = build(
+            to_runtime,
+            ServeConfig {
+                price: 0.2,
+                sale_hash: sale.hash(),
+                ..Default::default()
+            },
+        )
+        .configure(rocket::Config {
+            port,
+            ..rocket::Config::debug_default()
+        });
+
+        let client = SellerClient::new(format!("http://127.0.0.1:{port}")).unwrap();
+        let policy = ConnectPolicy {
+            timeout: Some(std::time::Duration::from_secs(5)),
+            retries: 5,
+            backoff: std::time::Duration::from_millis(100),
+        };
+        let no_retries = ConnectPolicy {
+            retries: 0,
+            ..policy
+        };
+        assert!(client.connect(no_retries, |_, _, _| {}).await.is_err());
+
+        // seller comes up only after the first attempt failed.
+        let mut delays = vec![];
+        let mut server = Some(server);
+        let price = client
+            .connect(policy, |_, _, delay| {
+                delays.push(delay);
+                if let Some(server) = server.take() {
+                    rocket::tokio::spawn(server.launch());
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!(price, 0.2);
+
+        assert!(!delays.is_empty());
+        assert!(delays.windows(2).all(|d| d[1] == d[0] * 2));
+    }
+
+    #[rocket::async_test]
+    async fn test_expected_seller_signature() {
+        let (seller_sk, seller_pk) = keypair_gen();
+        let (to_runtime, _from_server) = mpsc::channel(1);
+        let server = build(
+            to_runtime,
+            ServeConfig {
+                price: 0.3,
+                sale_hash: [7; 32],
+                wire_size: Some(1024),
+                plaintext_commitment: Some(vec![9; 32]),
+                signing_key: Some(seller_sk),
+                ..Default::default()
+            },
+        );
+        let url = launch(server).await;
+
+        let client = SellerClient::new(&url)
+            .unwrap()
+            .with_expected_seller(eth_address(&seller_pk));
+        assert_eq!(client.price().await.unwrap(), 0.3);
+        assert_eq!(client.plaintext_commitment(), Some(vec![9; 32]));
+
+        // daemon answering is signing with another key than the expected seller's.
+        let (_, other_pk) = keypair_gen();
+        let client = SellerClient::new(&url)
+            .unwrap()
+            .with_expected_seller(eth_address(&other_pk));
+        let err = client.price().await.unwrap_err();
+        assert!(err.to_string().contains("not expected seller"), "{err}");
+    }
+
+    #[rocket::async_test]
+    async fn test_signed_quote() {
+        let (seller_sk, seller_pk) = keypair_gen();
+        let (to_runtime, mut from_server) = mpsc::channel(1);
+        let signing_key = seller_sk.clone();
+        rocket::tokio::spawn(async move {
+            while let Some(msg) = from_server.next().await {
+                if let SellerMsg::Quote { sale_id, resp_tx } = msg {
+                    let quote = Quote {
+                        sale_id,
+                        price: 0.25,
+                        sale_hash: [7; 32],
+                        chain_id: 31337,
+                        expires_at: (Utc::now() + Duration::minutes(10)).to_rfc3339(),
+                        token: None,
+                    };
+                    let _ = resp_tx.send(Ok(quote.sign(&signing_key)));
+                }
+            }
+        });
+
+        let server = build(
+            to_runtime,
+            ServeConfig {
+                price: 0.3,
+                sale_hash: [7; 32],
+                signing_key: Some(seller_sk),
+                ..Default::default()
+            },
+        );
+        let url = launch(server).await;
+
+        let client = SellerClient::new(&url)
+            .unwrap()
+            .with_expected_seller(eth_address(&seller_pk));
+        assert_eq!(client.price().await.unwrap(), 0.3);
+        assert_eq!(client.seller_pk(), Some(seller_pk));
+        let quote = client.quote().await.unwrap();
+        assert_eq!(quote.quote.price, 0.25);
+        quote.verify(&seller_pk).unwrap();
+
+        let (_, other_pk) = keypair_gen();
+        let client = SellerClient::new(&url)
+            .unwrap()
+            .with_expected_seller(eth_address(&other_pk));
+        let err = client.quote().await.unwrap_err();
+        assert!(err.to_string().contains("not expected seller"), "{err}");
+    }
+
+    #[rocket::async_test]
+    async fn test_seller_receipt() {
+        let (seller_sk, seller_pk) = keypair_gen();
+        let (_, one_time_pk) = keypair_gen();
+        let (_, buyer_pk) = keypair_gen();
+        let log = std::env::temp_dir().join("zkcp_test_client_receipts.jsonl");
+        let _ = std::fs::remove_file(&log);
+        let sale = VerifiableEncryption {
+            ciphertext: vec![1; 32],
+            proof_of_encryption: vec![2; 192],
+            proofs_of_property: vec![],
+            plaintext_commitment: None,
+        };
+        let receipt = Receipt::new(
+            None,
+            &sale,
+            0.3,
+            31337,
+            vec!["00".repeat(32)],
+            &one_time_pk,
+            &buyer_pk,
+        )
+        .with_seller_pk(&seller_pk);
+        let signature = receipt.sign(&seller_sk);
+        append_receipt(&log, receipt, Some(signature), None).unwrap();
+
+        let (to_runtime, mut from_server) = mpsc::channel(1);
+        rocket::tokio::spawn(async move {
+            while let Some(msg) = from_server.next().await {
+                if let SellerMsg::Receipt {
+                    address, resp_tx, ..
+                } = msg
+                {
+                    let entry = match address == Address::zero() {
+                        true => read_receipts(&log).unwrap().pop(),
+                        false => None,
+                    };
+                    let _ = resp_tx.send(Ok(entry));
+                }
+            }
+        });
+
+        let server = build(
+            to_runtime,
+            ServeConfig {
+                price: 0.3,
+                sale_hash: [7; 32],
+                ..Default::default()
+            },
+        );
+        let url = launch(server).await;
+
+        let client = SellerClient::new(&url)
+            .unwrap()
+            .with_expected_seller(eth_address(&seller_pk));
+        let entry = client.receipt(Address::zero()).await.unwrap().unwrap();
+        assert_eq!(entry.receipt.price, 0.3);
+        assert_eq!(entry.receipt.seller_pk, Some(seller_pk.to_string()));
+        assert!(client
+            .receipt(Address::repeat_byte(1))
+            .await
+            .unwrap()
+            .is_none());
+
+        let (_, other_pk) = keypair_gen();
+        let client = SellerClient::new(&url)
+            .unwrap()
+            .with_expected_seller(eth_address(&other_pk));
+        let err = client.receipt(Address::zero()).await.unwrap_err();
+        assert!(err.to_string().contains("not expected seller"), "{err}");
+    }
+
+    #[rocket::async_test]
+    async fn test_inspect_and_catalog() {
+        let (seller_sk, seller_pk) = keypair_gen();
+        let (to_runtime, _from_server) = mpsc::channel(1);
+        let listings = HashMap::from([(
+            "photos".to_string(),
+            ListingInfo {
+                price: 2.5,
+                sale_hash: [8; 32],
+                wire_size: Some(2048),
+                plaintext_commitment: None,
+            },
+        )]);
+        let server = build(
+            to_runtime,
+            ServeConfig {
+                price: 0.4,
+                sale_hash: [7; 32],
+                wire_size: Some(1024),
+                listings,
+                signing_key: Some(seller_sk),
+                verifying_key_hash: Some([9; 32]),
+                ..Default::default()
+            },
+        );
+        let url = launch(server).await;
+
+        let client = SellerClient::new(&url).unwrap();
+        let catalog = client.catalog().await.unwrap();
+        assert_eq!(catalog.len(), 1);
+        assert_eq!(catalog[0].id, "photos");
+
+        let default = client.inspect().await.unwrap();
+        assert_eq!(default.id, None);
+        assert_eq!(default.price, 0.4);
+        assert_eq!(default.verifying_key_hash, Some(hex::encode([9; 32])));
+        assert_eq!(
+            default.seller_address,
+            Some(hex::encode(eth_address(&seller_pk).to_fixed_bytes()))
+        );
+
+        let photos = client.with_sale("photos").inspect().await.unwrap();
+        assert_eq!(photos.id.as_deref(), Some("photos"));
+        assert_eq!(photos.price, 2.5);
+        assert_eq!(photos.sale_hash, hex::encode([8; 32]));
+        assert_eq!(photos.wire_size, Some(2048));
+
+        let unknown = SellerClient::new(&url).unwrap().with_sale("music");
+        let err = unknown.inspect().await.unwrap_err();
+        assert!(err.to_string().contains("unknown sale music"), "{err}");
+    }
+
+    #[rocket::async_test]
+    async fn test_swap_status_and_schema() {
+        let paid = Address::repeat_byte(1);
+        let (to_runtime, mut from_server) = mpsc::channel(1);
+        rocket::tokio::spawn(async move {
+            while let Some(msg) = from_server.next().await {
+                if let SellerMsg::Status {
+                    address, resp_tx, ..
+                } = msg
+                {
+                    let status =
+                        (address == paid).then(|| (SessionPhase::Paid, Some(H256::repeat_byte(2))));
+                    let _ = resp_tx.send(Ok(status));
+                }
+            }
+        });
+
+        let server = build(
+            to_runtime,
+            ServeConfig {
+                price: 0.1,
+                sale_hash: [7; 32],
+                ..Default::default()
+            },
+        );
+        let routes = server
+            .routes()
+            .map(|route| route.uri.to_string())
+            .collect::<Vec<_>>();
+        let url = launch(server).await;
+
+        let client = SellerClient::new(&url).unwrap();
+        let status = client.status(paid).await.unwrap();
+        assert_eq!(status.phase, Some(SessionPhase::Paid));
+        assert_eq!(status.tx_hash, Some(hex::encode([2; 32])));
+        let status = client.status(Address::zero()).await.unwrap();
+        assert_eq!(status.phase, None);
+        assert_eq!(status.tx_hash, None);
+
+        // every mounted route is documented.
+        let schema: serde_json::Value = surf::get(format!("{url}/openapi.json"))
+            .recv_json()
+            .await
+            .unwrap();
+        assert_eq!(
+            schema,
+            serde_json::from_str::<serde_json::Value>(OPENAPI_SCHEMA).unwrap()
+        );
+        for route in routes {
+            let path = route
+                .split('?')
+                .next()
+                .unwrap()
+                .replace('<', "{")
+                .replace('>', "}");
+            assert!(
+                schema["paths"].get(&path).is_some(),
+                "{path} is undocumented"
+            );
+        }
+    }
+
+    #[rocket::async_test]
+    async fn test_rate_limited_steps() {
+        let sale = VerifiableEncryption {
+            ciphertext: vec![1, 2, 3],
+            proof_of_encryption: vec![4, 5, 6],
+            proofs_of_property: vec![],
+            plaintext_commitment: None,
+        };
+        let (to_runtime, mut from_server) = mpsc::channel(1);
+        let served = sale.clone();
+        rocket::tokio::spawn(async move {
+            while let Some(msg) = from_server.next().await {
+                if let SellerMsg::Step0 { resp_tx, .. } = msg {
+                    let _ = resp_tx.send(Ok(served.clone()));
+                }
+            }
+        });
+
+        let server = build(
+            to_runtime,
+            ServeConfig {
+                price: 0.1,
+                sale_hash: sale.hash(),
+                rate_limit: Some(RateLimiter::new(1, std::time::Duration::from_secs(60))),
+                ..Default::default()
+            },
+        );
+        let url = launch(server).await;
+
+        let step0 = || surf::get(format!("{url}/step0"));
+        assert_eq!(step0().await.unwrap().status(), surf::StatusCode::Ok);
+        assert_eq!(
+            step0().await.unwrap().status(),
+            surf::StatusCode::TooManyRequests
+        );
+        // announcement stays available to throttled clients.
+        let info = surf::get(format!("{url}/info")).await.unwrap();
+        assert_eq!(info.status(), surf::StatusCode::Ok);
+    }
+
+    #[rocket::async_test]
+    async fn test_metrics() {
+        let metrics = Arc::new(SellerMetrics::default());
+        metrics.record_proof(std::time::Duration::from_secs(2));
+        let (to_runtime, _from_server) = mpsc::channel(1);
+
+        let server = build(
+            to_runtime,
+            ServeConfig {
+                price: 0.1,
+                sale_hash: [7; 32],
+                metrics: Some(metrics.clone()),
+                ..Default::default()
+            },
+        );
+        let url = launch(server).await;
+
+        // counters are read at scrape time, so swaps completed after launch show up.
+        metrics.record_swap();
+        let scraped = surf::get(format!("{url}/metrics"))
+            .recv_string()
+            .await
+            .unwrap();
+        assert!(scraped.contains("contangle_proofs_generated_total 1\n"));
+        assert!(scraped.contains("contangle_swaps_completed_total 1\n"));
+        assert!(scraped.contains("contangle_proving_seconds_bucket{le=\"2.5\"} 1\n"));
+    }
+
+    #[rocket::async_test]
+    async fn test_signed_step1() {
+        let (seller_sk, seller_pk) = keypair_gen();
+        let (_, data_pk) = keypair_gen();
+        let (to_runtime, mut from_server) = mpsc::channel(1);
+        rocket::tokio::spawn(async move {
+            while let Some(msg) = from_server.next().await {
+                if let SellerMsg::Step1 { resp_tx, .. } = msg {
+                    let _ = resp_tx.send(Ok(Step1Msg {
+                        ciphertext: vec![1, 2, 3],
+                        proof_of_encryption: vec![4, 5, 6],
+                        data_pk,
+                        seller_address: Address::repeat_byte(2),
+                    }));
+                }
+            }
+        });
+
+        let server = build(
+            to_runtime,
+            ServeConfig {
+                price: 0.3,
+                sale_hash: [7; 32],
+                signing_key: Some(seller_sk),
+                ..Default::default()
+            },
+        );
+        let url = launch(server).await;
+
+        let buyer = Address::repeat_byte(1);
+        let client = SellerClient::new(&url)
+            .unwrap()
+            .with_expected_seller(eth_address(&seller_pk));
+        let msg = client.step1(buyer).await.unwrap();
+        assert_eq!(msg.seller_address, Address::repeat_byte(2));
+
+        // daemon answering is signing with another key than the expected seller's.
+        let (_, other_pk) = keypair_gen();
+        let client = SellerClient::new(&url)
+            .unwrap()
+            .with_expected_seller(eth_address(&other_pk));
+        let err = client.step1(buyer).await.unwrap_err();
+        assert!(err.to_string().contains("not expected seller"), "{err}");
+    }
+
+    #[test]
+    fn test_announcement_schema_version() {
+        let announcement = |version: u32| -> InfoResponse {
+            serde_json::from_value(serde_json::json!({
+                "schema_version": version,
+                "price": 0.1,
+                "sale_hash": hex::encode([0; 32]),
+            }))
+            .unwrap()
+        };
+
+        let same = announcement(ANNOUNCEMENT_SCHEMA_VERSION);
+        assert!(ensure_schema_version(same.schema_version).is_ok());
+
+        let newer = announcement(ANNOUNCEMENT_SCHEMA_VERSION + 1);
+        let err = ensure_schema_version(newer.schema_version).unwrap_err();
+        assert!(err.to_string().contains("please upgrade"));
+
+        // announcements predating versioning stay readable.
+        let legacy: InfoResponse =
+            serde_json::from_str(r#"{"price": 0.1, "sale_hash": ""}"#).unwrap();
+        assert!(ensure_schema_version(legacy.schema_version).is_ok());
+    }
+}
@@ -1,30 +1,195 @@
 pub mod client;
+mod rate_limit;
+
+pub use rate_limit::*;
 
 #[macro_use]
 extern crate rocket;
 
+use chrono::{DateTime, Utc};
 use ecdsa_fun::adaptor::EncryptedSignature;
 use ethers::prelude::*;
 use futures::channel::{mpsc, oneshot};
 use futures_util::{SinkExt, TryFutureExt};
-use rocket::http::Status;
+use rocket::http::{ContentType, Status};
 use rocket::response::status;
 use rocket::serde::{json::Json, Deserialize, Serialize};
 use rocket::State;
 use scriptless_zkcp::zk::{ProofOfProperty, VerifiableEncryption};
-use scriptless_zkcp::{SellerMsg, Step1Msg};
-use secp256kfun::Point;
+use scriptless_zkcp::{
+    eth_address, Quote, ReceiptEntry, SaleAnnouncement, SaleId, SellerMetrics, SellerMsg,
+    SessionPhase, SignedQuote, Step1Msg, TxParams,
+};
+use secp256kfun::marker::{Mark, Normal};
+use secp256kfun::{g, Point, Scalar, G};
+use std::collections::HashMap;
 use std::str::FromStr;
+use std::sync::Arc;
 
 struct Runtime {
     tx: mpsc::Sender<SellerMsg>,
     price: f64,
+    sale_hash: [u8; 32],
+    wire_size: Option<usize>,
+    plaintext_commitment: Option<Vec<u8>>,
+    expires_at: Option<DateTime<Utc>>,
+    listings: HashMap<SaleId, ListingInfo>,
+    /// Seller's wallet key announcements are signed with, unsigned if none, eg. in observer mode.
+    signing_key: Option<Scalar>,
+    /// Hash of the data encryption verifying key all sales are proven with, see [`scriptless_zkcp::verifying_key_hash`].
+    verifying_key_hash: Option<[u8; 32]>,
+}
+
+/// Announced details of the sale listed alongside the default one, see [`scriptless_zkcp::Seller::list_sale`].
+#[derive(Clone, Debug)]
+pub struct ListingInfo {
+    pub price: f64,
+    pub sale_hash: [u8; 32],
+    /// Bytes buyer downloads for the sale, see [`scriptless_zkcp::sale_wire_size`].
+    pub wire_size: Option<usize>,
+    /// See [`scriptless_zkcp::VerifiableEncryption::plaintext_commitment`].
+    pub plaintext_commitment: Option<Vec<u8>>,
+}
+
+/// PEM certificate chain and private key the daemon serves HTTPS with.
+#[derive(Clone, Debug)]
+pub struct TlsPaths {
+    pub certs: String,
+    pub key: String,
 }
 
+/// OpenAPI document describing every endpoint of the daemon, served at `/openapi.json`.
+pub const OPENAPI_SCHEMA: &str = include_str!("../openapi.json");
+
+/// Version of the sale announcement served at `/info`, bumped whenever its meaning changes,
+/// so that older buyers refuse announcements they can't fully understand.
+pub const ANNOUNCEMENT_SCHEMA_VERSION: u32 = 2;
+
 #[derive(Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
 struct InfoResponse {
+    /// Missing in announcements predating versioning, those are understood as they are.
+    #[serde(default)]
+    schema_version: u32,
+    price: f64,
+    sale_hash: String,
+    #[serde(default)]
+    wire_size: Option<usize>,
+    #[serde(default)]
+    expires_at: Option<String>,
+    /// Hex-encoded commitment to the plaintext, missing unless the sale is bound to one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    plaintext_commitment: Option<String>,
+    /// Seller's public key and its signature over the [`SaleAnnouncement`], missing if unsigned.
+    #[serde(default)]
+    seller_pk: Option<String>,
+    #[serde(default)]
+    signature: Option<String>,
+}
+
+/// Details of the sale buyer checks before starting the exchange, served at `/inspect`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SaleMetadata {
+    /// Missing for the default sale.
+    #[serde(default)]
+    pub id: Option<SaleId>,
+    pub price: f64,
+    /// Commitment to the ciphertext and proofs buyer downloads, see [`scriptless_zkcp::VerifiableEncryption::hash`].
+    pub sale_hash: String,
+    #[serde(default)]
+    pub wire_size: Option<usize>,
+    #[serde(default)]
+    pub expires_at: Option<String>,
+    #[serde(default)]
+    pub verifying_key_hash: Option<String>,
+    /// Address of the wallet seller signs announcements and gets paid with, unknown for observers.
+    #[serde(default)]
+    pub seller_address: Option<String>,
+}
+
+/// Sale of the daemon's catalog, see [`ListingInfo`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CatalogEntry {
+    pub id: SaleId,
+    pub price: f64,
+    pub sale_hash: String,
+    #[serde(default)]
+    pub wire_size: Option<usize>,
+}
+
+/// Progress of the swap with a buyer, served at `/status`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SwapStatus {
+    /// Missing if the swap wasn't started.
+    #[serde(default)]
+    pub phase: Option<SessionPhase>,
+    /// Hex-encoded hash of the payment once made.
+    #[serde(default)]
+    pub tx_hash: Option<String>,
+}
+
+/// Signed price offer served at `/quote`, which buyer sends back with its payment, see [`Quote`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct QuoteResponse {
+    #[serde(default)]
+    sale_id: Option<SaleId>,
     price: f64,
+    sale_hash: String,
+    chain_id: u64,
+    expires_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+    seller_pk: String,
+    signature: String,
+}
+
+impl From<SignedQuote> for QuoteResponse {
+    fn from(signed: SignedQuote) -> Self {
+        Self {
+            sale_id: signed.quote.sale_id,
+            price: signed.quote.price,
+            sale_hash: hex::encode(signed.quote.sale_hash),
+            chain_id: signed.quote.chain_id,
+            expires_at: signed.quote.expires_at,
+            token: signed
+                .quote
+                .token
+                .map(|token| hex::encode(token.to_fixed_bytes())),
+            seller_pk: signed.seller_pk,
+            signature: signed.signature,
+        }
+    }
+}
+
+impl QuoteResponse {
+    fn into_quote(self) -> anyhow::Result<SignedQuote> {
+        let sale_hash = hex::decode(&self.sale_hash)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or(anyhow::anyhow!("error decoding quoted sale hash"))?;
+        let token = self
+            .token
+            .as_deref()
+            .map(Address::from_str)
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("error decoding quoted token: {e}"))?;
+        Ok(SignedQuote {
+            quote: Quote {
+                sale_id: self.sale_id,
+                price: self.price,
+                sale_hash,
+                chain_id: self.chain_id,
+                expires_at: self.expires_at,
+                token,
+            },
+            seller_pk: self.seller_pk,
+            signature: self.signature,
+        })
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -33,6 +198,8 @@ struct Step0Response {
     ciphertext: Vec<u8>,
     proof_of_encryption: Vec<u8>,
     proofs_of_property: Vec<ProofOfProperty>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    plaintext_commitment: Option<Vec<u8>>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -42,6 +209,12 @@ struct Step1Response {
     proof_of_encryption: Vec<u8>,
     data_pk: String,
     address: String,
+    /// Seller's wallet key and its signature over the message, see [`Step1Msg::sign`], missing
+    /// if unsigned.
+    #[serde(default)]
+    signer_pk: Option<String>,
+    #[serde(default)]
+    signature: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -49,20 +222,155 @@ struct Step1Response {
 struct Step3Request<'r> {
     pub_key: &'r str,
     enc_sig: &'r str,
+    /// Nonce and gas the signed payment is pinned to.
+    tx_params: TxParams,
+    /// Quote the buyer pays the price of, listed price is paid without it.
+    #[serde(default)]
+    quote: Option<QuoteResponse>,
 }
 
-#[get("/info")]
-async fn info(state: &State<Runtime>) -> Json<InfoResponse> {
-    Json(InfoResponse { price: state.price })
+#[get("/info?<sale>")]
+async fn info(
+    state: &State<Runtime>,
+    sale: Option<&str>,
+) -> Result<Json<InfoResponse>, status::Custom<String>> {
+    let (price, sale_hash, wire_size, plaintext_commitment) = match sale {
+        None => (
+            state.price,
+            state.sale_hash,
+            state.wire_size,
+            state.plaintext_commitment.clone(),
+        ),
+        Some(id) => state
+            .listings
+            .get(id)
+            .map(|l| {
+                (
+                    l.price,
+                    l.sale_hash,
+                    l.wire_size,
+                    l.plaintext_commitment.clone(),
+                )
+            })
+            .ok_or(status::Custom(
+                Status::NotFound,
+                format!("unknown sale {id}"),
+            ))?,
+    };
+
+    let announcement = SaleAnnouncement {
+        schema_version: ANNOUNCEMENT_SCHEMA_VERSION,
+        price,
+        sale_hash,
+        wire_size,
+        expires_at: state.expires_at.map(|t| t.to_rfc3339()),
+        plaintext_commitment,
+    };
+    let (seller_pk, signature) = match &state.signing_key {
+        Some(sk) => (
+            Some(g!(sk * G).mark::<Normal>().to_string()),
+            Some(announcement.sign(sk)),
+        ),
+        None => (None, None),
+    };
+
+    Ok(Json(InfoResponse {
+        schema_version: announcement.schema_version,
+        price,
+        sale_hash: hex::encode(sale_hash),
+        wire_size,
+        expires_at: announcement.expires_at,
+        plaintext_commitment: announcement.plaintext_commitment.map(hex::encode),
+        seller_pk,
+        signature,
+    }))
+}
+
+#[get("/inspect?<sale>")]
+async fn inspect(
+    state: &State<Runtime>,
+    sale: Option<&str>,
+) -> Result<Json<SaleMetadata>, status::Custom<String>> {
+    let (price, sale_hash, wire_size) = match sale {
+        None => (state.price, state.sale_hash, state.wire_size),
+        Some(id) => state
+            .listings
+            .get(id)
+            .map(|l| (l.price, l.sale_hash, l.wire_size))
+            .ok_or(status::Custom(
+                Status::NotFound,
+                format!("unknown sale {id}"),
+            ))?,
+    };
+
+    Ok(Json(SaleMetadata {
+        id: sale.map(Into::into),
+        price,
+        sale_hash: hex::encode(sale_hash),
+        wire_size,
+        expires_at: state.expires_at.map(|t| t.to_rfc3339()),
+        verifying_key_hash: state.verifying_key_hash.map(hex::encode),
+        seller_address: state
+            .signing_key
+            .as_ref()
+            .map(|sk| hex::encode(eth_address(&g!(sk * G).mark::<Normal>()).to_fixed_bytes())),
+    }))
 }
 
-#[get("/step0")]
-async fn step0(state: &State<Runtime>) -> Result<Json<Step0Response>, status::Custom<String>> {
+/// Lists sales served alongside the default one, for buyers to pick by id.
+#[get("/catalog")]
+async fn catalog(state: &State<Runtime>) -> Json<Vec<CatalogEntry>> {
+    let mut entries = state
+        .listings
+        .iter()
+        .map(|(id, listing)| CatalogEntry {
+            id: id.clone(),
+            price: listing.price,
+            sale_hash: hex::encode(listing.sale_hash),
+            wire_size: listing.wire_size,
+        })
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| a.id.cmp(&b.id));
+    Json(entries)
+}
+
+#[get("/quote?<sale>")]
+async fn quote(
+    state: &State<Runtime>,
+    _throttled: Throttled,
+    sale: Option<&str>,
+) -> Result<Json<QuoteResponse>, status::Custom<String>> {
     let (tx, rx) = oneshot::channel();
     state
         .tx
         .clone()
-        .send(SellerMsg::Step0 { resp_tx: tx })
+        .send(SellerMsg::Quote {
+            sale_id: sale.map(Into::into),
+            resp_tx: tx,
+        })
+        .await
+        .map_err(|e| status::Custom(Status::ServiceUnavailable, e.to_string()))?;
+
+    rx.await
+        .map_err(|e| status::Custom(Status::ServiceUnavailable, e.to_string()))?
+        .map(|quote| Json(quote.into()))
+        .map_err(|e| status::Custom(Status::InternalServerError, e.to_string()))
+}
+
+#[get("/step0?<sale>")]
+async fn step0(
+    state: &State<Runtime>,
+    _throttled: Throttled,
+    sale: Option<&str>,
+) -> Result<Json<Step0Response>, status::Custom<String>> {
+    let (tx, rx) = oneshot::channel();
+    state
+        .tx
+        .clone()
+        .send(SellerMsg::Step0 {
+            sale_id: sale.map(Into::into),
+            resp_tx: tx,
+        })
         .await
         .map_err(|e| status::Custom(Status::ServiceUnavailable, e.to_string()))?;
 
@@ -70,6 +378,7 @@ async fn step0(state: &State<Runtime>) -> Result<Json<Step0Response>, status::Cu
         ciphertext,
         proof_of_encryption,
         proofs_of_property,
+        plaintext_commitment,
     } = rx
         .await
         .map_err(|e| status::Custom(Status::ServiceUnavailable, e.to_string()))?
@@ -79,13 +388,16 @@ async fn step0(state: &State<Runtime>) -> Result<Json<Step0Response>, status::Cu
         ciphertext,
         proof_of_encryption,
         proofs_of_property,
+        plaintext_commitment,
     }))
 }
 
-#[get("/step1/<address>")]
+#[get("/step1/<address>?<sale>")]
 async fn step1(
     state: &State<Runtime>,
+    _throttled: Throttled,
     address: &str,
+    sale: Option<&str>,
 ) -> Result<Json<Step1Response>, status::Custom<String>> {
     let (tx, rx) = oneshot::channel();
     let address = Address::from_str(&address)
@@ -94,33 +406,84 @@ async fn step1(
         .tx
         .clone()
         .send(SellerMsg::Step1 {
+            sale_id: sale.map(Into::into),
             address,
             resp_tx: tx,
         })
         .await
         .map_err(|e| status::Custom(Status::ServiceUnavailable, e.to_string()))?;
 
+    step1_response(state, sale, address, rx).await
+}
+
+#[post("/handshake/<address>?<sale>", data = "<buyer_pk>")]
+async fn handshake(
+    state: &State<Runtime>,
+    _throttled: Throttled,
+    address: &str,
+    sale: Option<&str>,
+    buyer_pk: &str,
+) -> Result<Json<Step1Response>, status::Custom<String>> {
+    let (tx, rx) = oneshot::channel();
+    let address = Address::from_str(&address)
+        .map_err(|e| status::Custom(Status::BadRequest, e.to_string()))?;
+    let buyer_pk = hex::decode(buyer_pk.trim())
+        .map_err(|e| status::Custom(Status::BadRequest, format!("bad public key: {e}")))?;
+    state
+        .tx
+        .clone()
+        .send(SellerMsg::Handshake {
+            sale_id: sale.map(Into::into),
+            address,
+            buyer_pk,
+            resp_tx: tx,
+        })
+        .await
+        .map_err(|e| status::Custom(Status::ServiceUnavailable, e.to_string()))?;
+
+    step1_response(state, sale, address, rx).await
+}
+
+/// Answers with the message of Step 1 made to `buyer`, signed if the seller signs announcements.
+async fn step1_response(
+    state: &State<Runtime>,
+    sale: Option<&str>,
+    buyer: Address,
+    rx: oneshot::Receiver<anyhow::Result<Step1Msg>>,
+) -> Result<Json<Step1Response>, status::Custom<String>> {
+    let msg = rx
+        .await
+        .map_err(|e| status::Custom(Status::ServiceUnavailable, e.to_string()))?
+        .map_err(|e| status::Custom(Status::InternalServerError, e.to_string()))?;
+    let (signer_pk, signature) = match &state.signing_key {
+        Some(sk) => (
+            Some(g!(sk * G).mark::<Normal>().to_string()),
+            Some(msg.sign(sale, buyer, sk)),
+        ),
+        None => (None, None),
+    };
     let Step1Msg {
         ciphertext,
         proof_of_encryption,
         data_pk,
         seller_address,
-    } = rx
-        .await
-        .map_err(|e| status::Custom(Status::ServiceUnavailable, e.to_string()))?
-        .map_err(|e| status::Custom(Status::InternalServerError, e.to_string()))?;
+    } = msg;
 
     Ok(Json(Step1Response {
         ciphertext,
         proof_of_encryption,
         data_pk: hex::encode(data_pk.to_bytes()),
         address: hex::encode(seller_address.to_fixed_bytes()),
+        signer_pk,
+        signature,
     }))
 }
 
-#[post("/step3", data = "<req>")]
+#[post("/step3?<sale>", data = "<req>")]
 async fn step3(
     state: &State<Runtime>,
+    _throttled: Throttled,
+    sale: Option<&str>,
     req: Json<Step3Request<'_>>,
 ) -> Result<String, status::Custom<String>> {
     let (tx, rx) = oneshot::channel();
@@ -128,12 +491,21 @@ async fn step3(
         .map_err(|e| status::Custom(Status::BadRequest, format!("bad public key: {e}")))?;
     let enc_sig = EncryptedSignature::from_str(req.enc_sig)
         .map_err(|e| status::Custom(Status::BadRequest, format!("bad public key: {e}")))?;
+    let quote = req
+        .quote
+        .clone()
+        .map(QuoteResponse::into_quote)
+        .transpose()
+        .map_err(|e| status::Custom(Status::BadRequest, e.to_string()))?;
     state
         .tx
         .clone()
         .send(SellerMsg::Step3 {
+            sale_id: sale.map(Into::into),
             pub_key,
             enc_sig,
+            tx_params: req.tx_params,
+            quote,
             resp_tx: tx,
         })
         .await
@@ -148,14 +520,152 @@ async fn step3(
     Ok(hex::encode(tx_hash))
 }
 
-#[allow(unused_must_use)]
-pub async fn serve(to_runtime: mpsc::Sender<SellerMsg>, price: f64) {
-    rocket::build()
+#[get("/status/<address>?<sale>")]
+async fn status(
+    state: &State<Runtime>,
+    address: &str,
+    sale: Option<&str>,
+) -> Result<Json<SwapStatus>, status::Custom<String>> {
+    let (tx, rx) = oneshot::channel();
+    let address = Address::from_str(&address)
+        .map_err(|e| status::Custom(Status::BadRequest, e.to_string()))?;
+    state
+        .tx
+        .clone()
+        .send(SellerMsg::Status {
+            sale_id: sale.map(Into::into),
+            address,
+            resp_tx: tx,
+        })
+        .await
+        .map_err(|e| status::Custom(Status::ServiceUnavailable, e.to_string()))?;
+
+    let session = rx
+        .await
+        .map_err(|e| status::Custom(Status::ServiceUnavailable, e.to_string()))?
+        .map_err(|e| status::Custom(Status::InternalServerError, e.to_string()))?;
+
+    Ok(Json(match session {
+        Some((phase, tx_hash)) => SwapStatus {
+            phase: Some(phase),
+            tx_hash: tx_hash.map(|h| hex::encode(h.to_fixed_bytes())),
+        },
+        None => SwapStatus {
+            phase: None,
+            tx_hash: None,
+        },
+    }))
+}
+
+/// Seller-signed receipt of the completed trade with buyer at `address`, for it to countersign.
+#[get("/receipt/<address>?<sale>")]
+async fn receipt(
+    state: &State<Runtime>,
+    address: &str,
+    sale: Option<&str>,
+) -> Result<Json<ReceiptEntry>, status::Custom<String>> {
+    let (tx, rx) = oneshot::channel();
+    let address = Address::from_str(&address)
+        .map_err(|e| status::Custom(Status::BadRequest, e.to_string()))?;
+    state
+        .tx
+        .clone()
+        .send(SellerMsg::Receipt {
+            sale_id: sale.map(Into::into),
+            address,
+            resp_tx: tx,
+        })
+        .await
+        .map_err(|e| status::Custom(Status::ServiceUnavailable, e.to_string()))?;
+
+    rx.await
+        .map_err(|e| status::Custom(Status::ServiceUnavailable, e.to_string()))?
+        .map_err(|e| status::Custom(Status::InternalServerError, e.to_string()))?
+        .map(Json)
+        .ok_or(status::Custom(
+            Status::NotFound,
+            "no receipt of the trade with this buyer".to_string(),
+        ))
+}
+
+/// Counters of the seller in Prometheus text format, if the daemon collects them.
+#[get("/metrics")]
+fn metrics(
+    metrics: Option<&State<Arc<SellerMetrics>>>,
+) -> Result<(ContentType, String), status::Custom<String>> {
+    metrics
+        .map(|metrics| (ContentType::Plain, metrics.render()))
+        .ok_or(status::Custom(
+            Status::NotFound,
+            "seller doesn't collect metrics".to_string(),
+        ))
+}
+
+#[get("/openapi.json")]
+fn openapi() -> (ContentType, &'static str) {
+    (ContentType::JSON, OPENAPI_SCHEMA)
+}
+
+/// What the seller daemon serves, and how.
+#[derive(Default)]
+pub struct ServeConfig {
+    pub price: f64,
+    pub sale_hash: [u8; 32],
+    pub wire_size: Option<usize>,
+    pub plaintext_commitment: Option<Vec<u8>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub listings: HashMap<SaleId, ListingInfo>,
+    pub signing_key: Option<Scalar>,
+    pub verifying_key_hash: Option<[u8; 32]>,
+    pub rate_limit: Option<RateLimiter>,
+    pub metrics: Option<Arc<SellerMetrics>>,
+    pub tls: Option<TlsPaths>,
+}
+
+fn build(to_runtime: mpsc::Sender<SellerMsg>, cfg: ServeConfig) -> rocket::Rocket<rocket::Build> {
+    let server = rocket::build()
         .manage(Runtime {
             tx: to_runtime,
-            price,
+            price: cfg.price,
+            sale_hash: cfg.sale_hash,
+            wire_size: cfg.wire_size,
+            plaintext_commitment: cfg.plaintext_commitment,
+            expires_at: cfg.expires_at,
+            listings: cfg.listings,
+            signing_key: cfg.signing_key,
+            verifying_key_hash: cfg.verifying_key_hash,
         })
-        .mount("/", routes![info, step0, step1, step3])
+        .mount(
+            "/",
+            routes![
+                info, catalog, inspect, quote, step0, step1, handshake, step3, status, receipt,
+                metrics, openapi
+            ],
+        );
+    let server = match cfg.rate_limit {
+        Some(rate_limit) => server.manage(rate_limit),
+        None => server,
+    };
+    let server = match cfg.metrics {
+        Some(metrics) => server.manage(metrics),
+        None => server,
+    };
+    match cfg.tls {
+        Some(TlsPaths { certs, key }) => {
+            let figment = server
+                .figment()
+                .clone()
+                .merge(("tls.certs", certs))
+                .merge(("tls.key", key));
+            server.configure(figment)
+        }
+        None => server,
+    }
+}
+
+#[allow(unused_must_use)]
+pub async fn serve(to_runtime: mpsc::Sender<SellerMsg>, cfg: ServeConfig) {
+    build(to_runtime, cfg)
         .launch()
         .await
         .expect("expect server to run");
@@ -0,0 +1,121 @@
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Number of requests each client IP may make to the endpoints driving purchases within `window`.
+#[derive(Debug)]
+pub struct RateLimiter {
+    max_requests: u32,
+    window: Duration,
+    /// Peers whose `X-Real-IP` header names the client, see [`Self::with_trusted_proxies`].
+    trusted_proxies: Vec<IpAddr>,
+    clients: Mutex<HashMap<IpAddr, (Instant, u32)>>,
+}
+
+impl RateLimiter {
+    pub fn new(max_requests: u32, window: Duration) -> Self {
+        Self {
+            max_requests,
+            window,
+            trusted_proxies: vec![],
+            clients: Mutex::default(),
+        }
+    }
+
+    /// Counts requests forwarded by reverse proxies at `proxies` for the client named in their
+    /// `X-Real-IP` header. Anyone can set the header, so it's ignored coming from other peers.
+    pub fn with_trusted_proxies(mut self, proxies: Vec<IpAddr>) -> Self {
+        self.trusted_proxies = proxies;
+        self
+    }
+
+    /// Client the request from peer at `remote` is counted for, given `real_ip` it forwards.
+    pub fn client(&self, remote: IpAddr, real_ip: Option<IpAddr>) -> IpAddr {
+        match real_ip {
+            Some(ip) if self.trusted_proxies.contains(&remote) => ip,
+            _ => remote,
+        }
+    }
+
+    /// Counts request of `ip` made at `now`, false once it's over the limit of the current window.
+    pub fn allow(&self, ip: IpAddr, now: Instant) -> bool {
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        clients.retain(|_, (started, _)| now.saturating_duration_since(*started) < self.window);
+        let (_, count) = clients.entry(ip).or_insert((now, 0));
+        *count = count.saturating_add(1);
+        *count <= self.max_requests
+    }
+}
+
+/// Guard of the endpoints driving purchases, rejecting clients over the limit
+/// of the [`RateLimiter`] managed by the server, if there's one.
+pub struct Throttled;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Throttled {
+    type Error = String;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let limiter = match req.rocket().state::<RateLimiter>() {
+            Some(limiter) => limiter,
+            None => return Outcome::Success(Throttled),
+        };
+        let client = req
+            .remote()
+            .map(|remote| limiter.client(remote.ip(), req.real_ip()));
+        match client {
+            Some(ip) if !limiter.allow(ip, Instant::now()) => Outcome::Failure((
+                Status::TooManyRequests,
+                format!("too many requests from {ip}, try again later"),
+            )),
+            _ => Outcome::Success(Throttled),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::RateLimiter;
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_rate_limit_per_ip() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        let (first, second) = (
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)),
+        );
+        let now = Instant::now();
+
+        assert!(limiter.allow(first, now));
+        assert!(limiter.allow(first, now));
+        assert!(!limiter.allow(first, now));
+        // other clients are counted on their own.
+        assert!(limiter.allow(second, now));
+
+        // limit is lifted once the window has passed.
+        let later = now + Duration::from_secs(61);
+        assert!(limiter.allow(first, later));
+    }
+
+    #[test]
+    fn test_forwarded_ip_from_trusted_proxy_only() {
+        let (proxy, client) = (
+            IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+        );
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        // header is forged by the peer itself unless it's a configured proxy.
+        assert_eq!(limiter.client(client, Some(proxy)), client);
+        assert_eq!(limiter.client(proxy, Some(client)), proxy);
+
+        let limiter = limiter.with_trusted_proxies(vec![proxy]);
+        assert_eq!(limiter.client(proxy, Some(client)), client);
+        assert_eq!(limiter.client(proxy, None), proxy);
+        assert_eq!(limiter.client(client, Some(proxy)), client);
+    }
+}
@@ -0,0 +1,61 @@
+use crate::poseidon::poseidon_params;
+use alloc::vec::Vec;
+use anyhow::anyhow;
+use ark_ec::ProjectiveCurve;
+use ark_ed_on_bls12_381::{EdwardsProjective, Fq, Fr};
+use ark_ff::{BigInteger, PrimeField};
+use ark_serialize::CanonicalDeserialize;
+use ark_sponge::poseidon::PoseidonSponge;
+use ark_sponge::CryptographicSponge;
+
+/// Bytes packed into each plaintext chunk, same as `circuits::PLAINTEXT_CHUNK_SIZE`.
+pub const PLAINTEXT_CHUNK_SIZE: usize = 31;
+
+/// Decrypts `ciphertext` with the data decryption key `sk`, both in arkworks canonical encoding,
+/// and unpacks plaintext framed with its length, as `decrypt` command does.
+pub fn decrypt(ciphertext: &[u8], sk: &[u8]) -> anyhow::Result<Vec<u8>> {
+    plaintext_chunks_to_bytes(&decrypt_chunks(ciphertext, sk)?)
+}
+
+/// Decrypts `ciphertext` into plaintext chunks, as field elements.
+pub fn decrypt_chunks(ciphertext: &[u8], sk: &[u8]) -> anyhow::Result<Vec<Fq>> {
+    let (c1, c2) = <(EdwardsProjective, Vec<Fq>)>::deserialize(ciphertext)
+        .map_err(|e| anyhow!("error decoding ciphertext: {e}"))?;
+    let sk = Fr::deserialize(sk).map_err(|e| anyhow!("error decoding secret key: {e}"))?;
+
+    // shared secret is Poseidon hash of c1^sk, see `circuits::EncryptCircuit::decrypt`.
+    let mut s = c1;
+    s *= sk;
+    let mut sponge = PoseidonSponge::new(&poseidon_params());
+    sponge.absorb(&s.into_affine());
+    let dh = sponge.squeeze_field_elements::<Fq>(1).remove(0);
+
+    Ok(c2.into_iter().map(|c2i| c2i - dh).collect())
+}
+
+/// Unpacks [`PLAINTEXT_CHUNK_SIZE`] bytes of each chunk, truncated to the length recorded in their u64 little-endian prefix.
+pub fn plaintext_chunks_to_bytes(chunks: &[Fq]) -> anyhow::Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(chunks.len() * PLAINTEXT_CHUNK_SIZE);
+    for (i, chunk) in chunks.iter().enumerate() {
+        let bytes = chunk.into_repr().to_bytes_le();
+        if bytes[PLAINTEXT_CHUNK_SIZE..].iter().any(|&b| b != 0) {
+            return Err(anyhow!("chunk {i} exceeds {PLAINTEXT_CHUNK_SIZE} bytes"));
+        }
+        buf.extend_from_slice(&bytes[..PLAINTEXT_CHUNK_SIZE]);
+    }
+
+    let len = buf
+        .get(..8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        .ok_or(anyhow!("plaintext is missing its length prefix"))?;
+    // wrapping the length around on wasm32 would silently yield a shorter plaintext.
+    let len = usize::try_from(len)
+        .map_err(|_| anyhow!("plaintext length {len} doesn't fit into memory"))?;
+    buf.get(8..)
+        .filter(|data| data.len() >= len)
+        .map(|data| data[..len].to_vec())
+        .ok_or(anyhow!(
+            "plaintext length {len} exceeds {} unpacked bytes",
+            buf.len() - 8
+        ))
+}
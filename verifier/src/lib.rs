@@ -0,0 +1,157 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+//! Groth'16 proof verification, also usable without `std` given `alloc`, eg. inside enclaves,
+//! when built with `--no-default-features`. Decryption of the purchased data comes with `decrypt` feature.
+
+extern crate alloc;
+
+#[cfg(feature = "decrypt")]
+mod decrypt;
+#[cfg(feature = "decrypt")]
+mod poseidon;
+
+#[cfg(feature = "decrypt")]
+pub use decrypt::*;
+#[cfg(feature = "decrypt")]
+pub use poseidon::*;
+
+use alloc::vec::Vec;
+use anyhow::anyhow;
+use ark_bls12_381::{Bls12_381 as PairingEngine, Fr};
+use ark_ec::ProjectiveCurve;
+use ark_ed_on_bls12_381::EdwardsProjective;
+use ark_ff::Zero;
+use ark_groth16::{Groth16, Proof, VerifyingKey};
+use ark_serialize::CanonicalDeserialize;
+use ark_snark::SNARK;
+
+/// Magic bytes prefixing verifying key files written by `compile` command.
+const VERIFYING_KEY_MAGIC: &[u8] = b"ZKVK";
+/// Header tagging verifying key with the curve it's made for, here BLS12-381, written before its magic.
+const BLS12_381_CURVE_TAG: &[u8] = b"ZKCT\x01";
+/// Version of the key format, written after the curve tag by `compile` command.
+/// Since version 2 it's followed by encoding flags and 32-byte circuit hash.
+const KEY_FORMAT_VERSION: u8 = 2;
+/// Flag of the key written with curve points compressed.
+const KEY_COMPRESSED_FLAG: u8 = 1;
+
+/// Verifies Groth'16 proof against verifying key,
+/// both in arkworks canonical encoding, ie. same as seller sends proofs.
+/// Verifying key may also be passed as `compile` command writes it, ie. prefixed with magic bytes.
+/// Public inputs are encoded as a vector of BLS12-381 scalars (length-prefixed).
+pub fn verify_proof(
+    vk_bytes: &[u8],
+    proof_bytes: &[u8],
+    public_inputs: &[u8],
+) -> anyhow::Result<bool> {
+    let public_inputs = Vec::<Fr>::deserialize(public_inputs)
+        .map_err(|e| anyhow!("error decoding public inputs: {e}"))?;
+    verify_proof_with_inputs(vk_bytes, proof_bytes, &public_inputs)
+}
+
+/// Verifies proof of encryption of `ciphertext`, encoded as the seller sends it,
+/// in the circuit padded to `n` chunks.
+pub fn verify_encryption_proof(
+    vk_bytes: &[u8],
+    proof_bytes: &[u8],
+    ciphertext: &[u8],
+    n: usize,
+) -> anyhow::Result<bool> {
+    let public_inputs = encryption_public_inputs(ciphertext, n)?;
+    verify_proof_with_inputs(vk_bytes, proof_bytes, &public_inputs)
+}
+
+/// Verifies proof of encryption of `ciphertext` sized as the circuit of `vk_bytes` is,
+/// bound to the plaintext `commitment` for circuits compiled with `--predicate digest`.
+pub fn verify_committed_encryption_proof(
+    vk_bytes: &[u8],
+    proof_bytes: &[u8],
+    ciphertext: &[u8],
+    commitment: Option<&[u8]>,
+) -> anyhow::Result<bool> {
+    let verifying_key = decode_verifying_key(vk_bytes)?;
+    let commitment = commitment
+        .map(Fr::deserialize)
+        .transpose()
+        .map_err(|e| anyhow!("error decoding plaintext commitment: {e}"))?;
+
+    // c1 coordinates come first, commitment follows the padded c2 chunks.
+    let n = (verifying_key.gamma_abc_g1.len() - 1)
+        .checked_sub(2 + usize::from(commitment.is_some()))
+        .ok_or(anyhow!("verifying key isn't of the encryption circuit"))?;
+    let mut public_inputs = encryption_public_inputs(ciphertext, n)?;
+    public_inputs.extend(commitment);
+    verify_with_key(&verifying_key, proof_bytes, &public_inputs)
+}
+
+/// Public inputs of the encryption circuit:
+/// coordinates of `c1`, then `c2` chunks padded with zeros to `n`.
+pub fn encryption_public_inputs(ciphertext: &[u8], n: usize) -> anyhow::Result<Vec<Fr>> {
+    let (c1, c2) = <(EdwardsProjective, Vec<Fr>)>::deserialize(ciphertext)
+        .map_err(|e| anyhow!("error decoding ciphertext: {e}"))?;
+    if c2.len() > n {
+        return Err(anyhow!(
+            "ciphertext of {} chunks exceeds circuit limit of {n}",
+            c2.len()
+        ));
+    }
+
+    let c1 = c1.into_affine();
+    Ok([c1.x, c1.y]
+        .into_iter()
+        .chain(c2.iter().copied())
+        .chain((c2.len()..n).map(|_| Fr::zero()))
+        .collect())
+}
+
+fn verify_proof_with_inputs(
+    vk_bytes: &[u8],
+    proof_bytes: &[u8],
+    public_inputs: &[Fr],
+) -> anyhow::Result<bool> {
+    verify_with_key(&decode_verifying_key(vk_bytes)?, proof_bytes, public_inputs)
+}
+
+fn decode_verifying_key(vk_bytes: &[u8]) -> anyhow::Result<VerifyingKey<PairingEngine>> {
+    let vk_bytes = match vk_bytes.strip_prefix(BLS12_381_CURVE_TAG) {
+        Some(rest) if rest.starts_with(VERIFYING_KEY_MAGIC) => rest,
+        Some([version, ..]) if *version > KEY_FORMAT_VERSION => {
+            return Err(anyhow!(
+                "key format version {version} is not supported, expected at most {KEY_FORMAT_VERSION}"
+            ));
+        }
+        Some([1, rest @ ..]) => rest,
+        Some([_, flags, rest @ ..]) => {
+            if flags & KEY_COMPRESSED_FLAG == 0 {
+                return Err(anyhow!("uncompressed verifying keys are not supported"));
+            }
+            rest.get(32..)
+                .ok_or(anyhow!("verifying key header is truncated"))?
+        }
+        _ => vk_bytes,
+    };
+    let vk_bytes = vk_bytes
+        .strip_prefix(VERIFYING_KEY_MAGIC)
+        .unwrap_or(vk_bytes);
+    VerifyingKey::<PairingEngine>::deserialize(vk_bytes)
+        .map_err(|e| anyhow!("error decoding verifying key: {e}"))
+}
+
+fn verify_with_key(
+    verifying_key: &VerifyingKey<PairingEngine>,
+    proof_bytes: &[u8],
+    public_inputs: &[Fr],
+) -> anyhow::Result<bool> {
+    let proof = Proof::<PairingEngine>::deserialize(proof_bytes)
+        .map_err(|e| anyhow!("error decoding proof: {e}"))?;
+
+    let expected = verifying_key.gamma_abc_g1.len() - 1;
+    if public_inputs.len() != expected {
+        return Err(anyhow!(
+            "wrong number of public inputs: expected {expected}, got {}",
+            public_inputs.len()
+        ));
+    }
+
+    Groth16::<PairingEngine>::verify(verifying_key, public_inputs, &proof)
+        .map_err(|e| anyhow!("error verifying Groth'16 proof: {e}"))
+}
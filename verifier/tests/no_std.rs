@@ -0,0 +1,91 @@
+//! Checks the verify path as built without `std`: `cargo test -p verifier --no-default-features`.
+
+use ark_bls12_381::{Bls12_381 as E, Fr};
+use ark_ec::ProjectiveCurve;
+use ark_ed_on_bls12_381::EdwardsProjective;
+use ark_ff::Zero;
+use ark_groth16::Groth16;
+use ark_relations::lc;
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError, Variable};
+use ark_serialize::CanonicalSerialize;
+use ark_snark::{CircuitSpecificSetupSNARK, SNARK};
+use ark_std::{test_rng, UniformRand};
+use verifier::{
+    encryption_public_inputs, verify_committed_encryption_proof, verify_encryption_proof,
+};
+
+/// Exposes `inputs` as public inputs, as the encryption circuit does with the ciphertext.
+struct PublicInputsCircuit(Vec<Fr>);
+
+impl ConstraintSynthesizer<Fr> for PublicInputsCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        for input in self.0 {
+            let v = cs.new_input_variable(|| Ok(input))?;
+            cs.enforce_constraint(lc!() + v, lc!() + Variable::One, lc!() + v)?;
+        }
+        Ok(())
+    }
+}
+
+fn to_bytes<I: CanonicalSerialize>(i: I) -> Vec<u8> {
+    let mut buf = vec![];
+    i.serialize(&mut buf).unwrap();
+    buf
+}
+
+#[test]
+fn test_verify_encryption_proof() {
+    let mut rng = test_rng();
+    let n = 4;
+    let c1 = EdwardsProjective::rand(&mut rng);
+    let c2 = vec![Fr::rand(&mut rng), Fr::rand(&mut rng)];
+    let ciphertext = to_bytes((c1, c2.clone()));
+
+    let public_inputs = encryption_public_inputs(&ciphertext, n).unwrap();
+    let c1 = c1.into_affine();
+    assert_eq!(
+        public_inputs,
+        vec![c1.x, c1.y, c2[0], c2[1], Fr::zero(), Fr::zero()]
+    );
+    assert!(encryption_public_inputs(&ciphertext, 1).is_err());
+
+    let circuit = || PublicInputsCircuit(public_inputs.clone());
+    let (pk, vk) = Groth16::<E>::setup(circuit(), &mut rng).unwrap();
+    let proof = Groth16::<E>::prove(&pk, circuit(), &mut rng).unwrap();
+
+    let (vk_bytes, proof_bytes) = (to_bytes(vk), to_bytes(proof));
+    assert!(verify_encryption_proof(&vk_bytes, &proof_bytes, &ciphertext, n).unwrap());
+
+    let other = to_bytes((EdwardsProjective::rand(&mut rng), c2));
+    assert!(!verify_encryption_proof(&vk_bytes, &proof_bytes, &other, n).unwrap());
+}
+
+#[test]
+fn test_verify_committed_encryption_proof() {
+    let mut rng = test_rng();
+    let n = 3;
+    let c1 = EdwardsProjective::rand(&mut rng);
+    let c2 = vec![Fr::rand(&mut rng)];
+    let ciphertext = to_bytes((c1, c2));
+    let commitment = Fr::rand(&mut rng);
+
+    let mut public_inputs = encryption_public_inputs(&ciphertext, n).unwrap();
+    public_inputs.push(commitment);
+    let circuit = || PublicInputsCircuit(public_inputs.clone());
+    let (pk, vk) = Groth16::<E>::setup(circuit(), &mut rng).unwrap();
+    let proof = Groth16::<E>::prove(&pk, circuit(), &mut rng).unwrap();
+
+    let (vk_bytes, proof_bytes) = (to_bytes(vk), to_bytes(proof));
+    let verify = |commitment: Option<Fr>| {
+        verify_committed_encryption_proof(
+            &vk_bytes,
+            &proof_bytes,
+            &ciphertext,
+            commitment.map(to_bytes).as_deref(),
+        )
+    };
+    assert!(verify(Some(commitment)).unwrap());
+    assert!(!verify(Some(Fr::rand(&mut rng))).unwrap());
+    // without commitment extra public input is taken for the last c2 chunk, which doesn't match.
+    assert!(!verify(None).unwrap());
+}
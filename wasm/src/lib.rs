@@ -0,0 +1,170 @@
+pub use verifier::{
+    decrypt, encryption_public_inputs, verify_committed_encryption_proof, verify_encryption_proof,
+    verify_proof,
+};
+use wasm_bindgen::prelude::*;
+
+/// Verifies Groth'16 proof in browser environment.
+///
+/// All arguments are expected in arkworks canonical encoding, ie. same as seller sends proofs.
+/// Verifying key may also be passed as `compile` command writes it, ie. prefixed with magic bytes.
+/// Public inputs are encoded as a vector of BLS12-381 scalars (length-prefixed).
+#[wasm_bindgen]
+pub fn verify_proof_wasm(
+    vk_bytes: &[u8],
+    proof_bytes: &[u8],
+    public_inputs: &[u8],
+) -> Result<bool, JsValue> {
+    verify_proof(vk_bytes, proof_bytes, public_inputs)
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Verifies proof of encryption of `ciphertext` in browser environment, sizing the circuit after `vk_bytes`.
+///
+/// Public key data is encrypted to is a witness of the circuit, bound to the sale by the adaptor signature
+/// instead, so `commitment` to the plaintext is the only other statement, empty unless circuits are
+/// compiled with `--predicate digest`.
+#[wasm_bindgen(js_name = verify_encryption_proof)]
+pub fn verify_encryption_proof_wasm(
+    vk_bytes: &[u8],
+    proof_bytes: &[u8],
+    ciphertext: &[u8],
+    commitment: &[u8],
+) -> Result<bool, JsValue> {
+    let commitment = Some(commitment).filter(|c| !c.is_empty());
+    verify_committed_encryption_proof(vk_bytes, proof_bytes, ciphertext, commitment)
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Decrypts purchased data in browser environment, given data decryption key `sk` recovered after payment.
+#[wasm_bindgen(js_name = decrypt)]
+pub fn decrypt_wasm(ciphertext: &[u8], sk: &[u8]) -> Result<Vec<u8>, JsValue> {
+    decrypt(ciphertext, sk).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        decrypt, decrypt_wasm, encryption_public_inputs, verify_encryption_proof_wasm,
+        verify_proof_wasm,
+    };
+    use ark_bls12_381::{Bls12_381 as E, Fr};
+    use ark_ec::ProjectiveCurve;
+    use ark_ed_on_bls12_381::{EdwardsProjective, Fr as JubJubFr};
+    use ark_ff::PrimeField;
+    use ark_groth16::Groth16;
+    use ark_relations::lc;
+    use ark_relations::r1cs::{
+        ConstraintSynthesizer, ConstraintSystemRef, SynthesisError, Variable,
+    };
+    use ark_serialize::CanonicalSerialize;
+    use ark_snark::{CircuitSpecificSetupSNARK, SNARK};
+    use ark_sponge::poseidon::PoseidonSponge;
+    use ark_sponge::CryptographicSponge;
+    use ark_std::{test_rng, UniformRand};
+    use verifier::{poseidon_params, PLAINTEXT_CHUNK_SIZE};
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    struct MulCircuit {
+        a: Option<Fr>,
+        b: Option<Fr>,
+    }
+
+    impl ConstraintSynthesizer<Fr> for MulCircuit {
+        fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+            let a = cs.new_witness_variable(|| self.a.ok_or(SynthesisError::AssignmentMissing))?;
+            let b = cs.new_witness_variable(|| self.b.ok_or(SynthesisError::AssignmentMissing))?;
+            let c = cs.new_input_variable(|| {
+                let a = self.a.ok_or(SynthesisError::AssignmentMissing)?;
+                let b = self.b.ok_or(SynthesisError::AssignmentMissing)?;
+                Ok(a * b)
+            })?;
+
+            cs.enforce_constraint(lc!() + a, lc!() + b, lc!() + c)
+        }
+    }
+
+    /// Exposes `inputs` as public inputs, as the encryption circuit does with the ciphertext.
+    struct PublicInputsCircuit(Vec<Fr>);
+
+    impl ConstraintSynthesizer<Fr> for PublicInputsCircuit {
+        fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+            for input in self.0 {
+                let v = cs.new_input_variable(|| Ok(input))?;
+                cs.enforce_constraint(lc!() + v, lc!() + Variable::One, lc!() + v)?;
+            }
+            Ok(())
+        }
+    }
+
+    fn to_bytes<I: CanonicalSerialize>(i: I) -> Vec<u8> {
+        let mut buf = vec![];
+        i.serialize(&mut buf).unwrap();
+        buf
+    }
+
+    #[wasm_bindgen_test]
+    fn test_verify_known_proof() {
+        let mut rng = test_rng();
+        let (pk, vk) = Groth16::<E>::setup(MulCircuit { a: None, b: None }, &mut rng).unwrap();
+
+        let a = Fr::rand(&mut rng);
+        let b = Fr::rand(&mut rng);
+        let circuit = MulCircuit {
+            a: Some(a),
+            b: Some(b),
+        };
+        let proof = Groth16::<E>::prove(&pk, circuit, &mut rng).unwrap();
+
+        let vk_bytes = to_bytes(vk);
+        let proof_bytes = to_bytes(proof);
+
+        assert!(verify_proof_wasm(&vk_bytes, &proof_bytes, &to_bytes(vec![a * b])).unwrap());
+        assert!(!verify_proof_wasm(&vk_bytes, &proof_bytes, &to_bytes(vec![a + b])).unwrap());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_verify_encryption_proof() {
+        let mut rng = test_rng();
+        let c1 = EdwardsProjective::rand(&mut rng);
+        let c2 = vec![Fr::rand(&mut rng)];
+        let ciphertext = to_bytes((c1, c2.clone()));
+
+        let circuit = || PublicInputsCircuit(encryption_public_inputs(&ciphertext, 2).unwrap());
+        let (pk, vk) = Groth16::<E>::setup(circuit(), &mut rng).unwrap();
+        let proof = Groth16::<E>::prove(&pk, circuit(), &mut rng).unwrap();
+
+        // verifying key goes first, as in `verify_proof_wasm`.
+        let (vk_bytes, proof_bytes) = (to_bytes(vk), to_bytes(proof));
+        assert!(verify_encryption_proof_wasm(&vk_bytes, &proof_bytes, &ciphertext, &[]).unwrap());
+        let other = to_bytes((EdwardsProjective::rand(&mut rng), c2));
+        assert!(!verify_encryption_proof_wasm(&vk_bytes, &proof_bytes, &other, &[]).unwrap());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_decrypt() {
+        let mut rng = test_rng();
+        let data = b"purchased data spanning more than a single chunk of plaintext".to_vec();
+        let sk = JubJubFr::rand(&mut rng);
+        let pk = EdwardsProjective::prime_subgroup_generator().mul(sk.into_repr());
+
+        // encrypted as `circuits::EncryptCircuit::encrypt` does, with plaintext framed by its length.
+        let r = JubJubFr::rand(&mut rng);
+        let c1 = EdwardsProjective::prime_subgroup_generator().mul(r.into_repr());
+        let mut sponge = PoseidonSponge::new(&poseidon_params());
+        sponge.absorb(&pk.mul(r.into_repr()).into_affine());
+        let dh = sponge.squeeze_field_elements::<Fr>(1).remove(0);
+        let framed = [&(data.len() as u64).to_le_bytes()[..], &data].concat();
+        let c2 = framed
+            .chunks(PLAINTEXT_CHUNK_SIZE)
+            .map(|chunk| Fr::from_le_bytes_mod_order(chunk) + dh)
+            .collect::<Vec<_>>();
+        let ciphertext = to_bytes((c1, c2));
+
+        assert_eq!(decrypt_wasm(&ciphertext, &to_bytes(sk)).unwrap(), data);
+        assert_ne!(
+            decrypt(&ciphertext, &to_bytes(JubJubFr::rand(&mut rng))).ok(),
+            Some(data)
+        );
+    }
+}